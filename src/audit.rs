@@ -0,0 +1,694 @@
+use anyhow::{Context, Result};
+use semver::{Op, Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::insights::Insights;
+use crate::inventory::compare_versions;
+use crate::logger::Logger;
+use crate::plugins::{InstalledPackage, PluginRegistry};
+use crate::report::format_table;
+use crate::runner::{ActionResult, ActionStatus, record_action};
+
+/// Action name suffix of the per-plugin results of a `--security-only` run
+const SECURITY_SUFFIX: &str = "-security";
+
+/// Upgrades looked at when a fixed version is itself affected by another
+/// advisory
+const MAX_FIX_STEPS: usize = 16;
+
+/// OSV ecosystem of the packages of `manager`, for the managers advisories
+/// are published for
+pub fn ecosystem(manager: &str) -> Option<&'static str> {
+    match manager {
+        "cargo" => Some("crates.io"),
+        "npm" => Some("npm"),
+        "pip" | "uv" => Some("PyPI"),
+        "gem" => Some("RubyGems"),
+        _ => None,
+    }
+}
+
+/// Ecosystem `package` is audited in; cargo packages installed from git or
+/// a path have no advisories
+fn audited_ecosystem(package: &InstalledPackage) -> Option<&'static str> {
+    if package.manager == "cargo" && package.source != "crates.io" {
+        return None;
+    }
+    ecosystem(&package.manager)
+}
+
+/// Package name as advisories of `ecosystem` are matched on. PyPI names are
+/// case-insensitive with `_`, `.` and `-` equivalent.
+fn normalize(ecosystem: &str, name: &str) -> String {
+    match ecosystem {
+        "PyPI" => name.to_lowercase().replace(['_', '.'], "-"),
+        _ => name.to_string(),
+    }
+}
+
+/// Order two versions as semver when both parse as one, otherwise with
+/// [`compare_versions`]
+fn order(a: &str, b: &str) -> Ordering {
+    match (Version::parse(a), Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => compare_versions(a, b),
+    }
+}
+
+/// One event of an OSV range
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RangeEvent {
+    Introduced(String),
+    Fixed(String),
+    LastAffected(String),
+    Limit(String),
+}
+
+impl RangeEvent {
+    fn version(&self) -> &str {
+        match self {
+            Self::Introduced(v) | Self::Fixed(v) | Self::LastAffected(v) | Self::Limit(v) => v,
+        }
+    }
+}
+
+/// Whether `version` falls in the range described by `events`, evaluated in
+/// version order as the OSV schema describes
+fn in_range(events: &[RangeEvent], version: &str) -> bool {
+    let mut events: Vec<&RangeEvent> = events.iter().collect();
+    events.sort_by(|a, b| match (a.version(), b.version()) {
+        ("0", "0") => Ordering::Equal,
+        ("0", _) => Ordering::Less,
+        (_, "0") => Ordering::Greater,
+        (a, b) => order(a, b),
+    });
+    let mut affected = false;
+    for event in events {
+        match event {
+            RangeEvent::Introduced(v) if v == "0" || order(version, v) != Ordering::Less => {
+                affected = true
+            }
+            RangeEvent::Fixed(v) | RangeEvent::Limit(v) if order(version, v) != Ordering::Less => {
+                affected = false
+            }
+            RangeEvent::LastAffected(v) if order(version, v) == Ordering::Greater => {
+                affected = false
+            }
+            _ => {}
+        }
+    }
+    affected
+}
+
+/// Lowest version a semver requirement allows, when it has a lower bound
+fn lower_bound(req: &VersionReq) -> Option<Version> {
+    req.comparators
+        .iter()
+        .filter(|c| matches!(c.op, Op::GreaterEq | Op::Caret | Op::Tilde | Op::Exact))
+        .map(|c| Version {
+            major: c.major,
+            minor: c.minor.unwrap_or(0),
+            patch: c.patch.unwrap_or(0),
+            pre: c.pre.clone(),
+            build: semver::BuildMetadata::EMPTY,
+        })
+        .max()
+}
+
+/// Versions of a package an advisory affects
+#[derive(Clone, Debug)]
+pub enum AffectedVersions {
+    /// OSV: `SEMVER` and `ECOSYSTEM` ranges, and versions listed one by one
+    Ranges {
+        ranges: Vec<Vec<RangeEvent>>,
+        versions: Vec<String>,
+    },
+    /// RustSec: every version matching neither a patched nor an unaffected
+    /// requirement
+    Unpatched {
+        patched: Vec<VersionReq>,
+        unaffected: Vec<VersionReq>,
+    },
+}
+
+impl AffectedVersions {
+    pub fn contains(&self, version: &str) -> bool {
+        match self {
+            Self::Ranges { ranges, versions } => {
+                versions.iter().any(|v| v == version) || ranges.iter().any(|r| in_range(r, version))
+            }
+            Self::Unpatched {
+                patched,
+                unaffected,
+            } => match Version::parse(version) {
+                Ok(v) => !patched.iter().chain(unaffected).any(|req| req.matches(&v)),
+                Err(_) => false,
+            },
+        }
+    }
+
+    /// Lowest version above `version` in which the advisory is fixed
+    pub fn fixed_after(&self, version: &str) -> Option<String> {
+        match self {
+            Self::Ranges { ranges, .. } => ranges
+                .iter()
+                .filter(|r| in_range(r, version))
+                .flatten()
+                .filter_map(|e| match e {
+                    RangeEvent::Fixed(v) if order(v, version) == Ordering::Greater => Some(v),
+                    _ => None,
+                })
+                .min_by(|a, b| order(a, b))
+                .cloned(),
+            Self::Unpatched { patched, .. } => {
+                let current = Version::parse(version).ok()?;
+                patched
+                    .iter()
+                    .filter_map(lower_bound)
+                    .filter(|v| *v > current)
+                    .min()
+                    .map(|v| v.to_string())
+            }
+        }
+    }
+}
+
+/// A package an advisory names, by ecosystem (`crates.io`, `npm`, `PyPI`,
+/// `RubyGems`, ...)
+#[derive(Clone, Debug)]
+pub struct AffectedPackage {
+    pub ecosystem: String,
+    pub name: String,
+    pub versions: AffectedVersions,
+}
+
+/// A published vulnerability
+#[derive(Clone, Debug)]
+pub struct Advisory {
+    pub id: String,
+    /// Other ids of the same vulnerability, such as its CVE
+    pub aliases: Vec<String>,
+    pub summary: String,
+    pub affected: Vec<AffectedPackage>,
+}
+
+#[derive(Deserialize)]
+struct OsvRecord {
+    id: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    summary: Option<String>,
+    details: Option<String>,
+    withdrawn: Option<String>,
+    #[serde(default)]
+    affected: Vec<OsvAffected>,
+}
+
+#[derive(Deserialize)]
+struct OsvAffected {
+    package: Option<OsvPackage>,
+    #[serde(default)]
+    ranges: Vec<OsvRange>,
+    #[serde(default)]
+    versions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct OsvPackage {
+    ecosystem: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct OsvRange {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    events: Vec<RangeEvent>,
+}
+
+/// Parse one advisory in OSV format. Withdrawn advisories are `None`; `GIT`
+/// ranges, which name commits rather than versions, are left out.
+pub fn parse_osv(json: &str) -> Result<Option<Advisory>> {
+    let record: OsvRecord = serde_json::from_str(json)?;
+    if record.withdrawn.is_some() {
+        return Ok(None);
+    }
+    let summary = record
+        .summary
+        .or_else(|| {
+            record
+                .details
+                .and_then(|d| d.lines().next().map(str::to_string))
+        })
+        .unwrap_or_default();
+    let affected = record
+        .affected
+        .into_iter()
+        .filter_map(|a| {
+            let package = a.package?;
+            let ranges = a
+                .ranges
+                .into_iter()
+                .filter(|r| r.kind != "GIT")
+                .map(|r| r.events)
+                .collect();
+            Some(AffectedPackage {
+                ecosystem: package.ecosystem,
+                name: package.name,
+                versions: AffectedVersions::Ranges {
+                    ranges,
+                    versions: a.versions,
+                },
+            })
+        })
+        .collect();
+    Ok(Some(Advisory {
+        id: record.id,
+        aliases: record.aliases,
+        summary,
+        affected,
+    }))
+}
+
+#[derive(Deserialize)]
+struct RustSecFrontMatter {
+    advisory: RustSecAdvisory,
+    #[serde(default)]
+    versions: RustSecVersions,
+}
+
+#[derive(Deserialize)]
+struct RustSecAdvisory {
+    id: String,
+    package: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    informational: Option<String>,
+    withdrawn: Option<toml::Value>,
+}
+
+#[derive(Default, Deserialize)]
+struct RustSecVersions {
+    #[serde(default)]
+    patched: Vec<String>,
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
+
+/// Parse one advisory of a RustSec advisory-db checkout: Markdown starting
+/// with a fenced TOML block, followed by a `# title`. Withdrawn and
+/// informational advisories (unmaintained crates and the like) are `None`.
+pub fn parse_rustsec(markdown: &str) -> Result<Option<Advisory>> {
+    let rest = markdown
+        .strip_prefix("```toml")
+        .context("missing TOML front matter")?;
+    let (front, body) = rest
+        .split_once("\n```")
+        .context("unterminated TOML front matter")?;
+    let front: RustSecFrontMatter = toml::from_str(front)?;
+    let advisory = front.advisory;
+    if advisory.withdrawn.is_some() || advisory.informational.is_some() {
+        return Ok(None);
+    }
+    let requirements = |reqs: &[String]| -> Result<Vec<VersionReq>> {
+        reqs.iter()
+            .map(|r| VersionReq::parse(r).with_context(|| format!("invalid requirement {:?}", r)))
+            .collect()
+    };
+    let summary = body
+        .lines()
+        .find_map(|l| l.strip_prefix("# "))
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    Ok(Some(Advisory {
+        id: advisory.id,
+        aliases: advisory.aliases,
+        summary,
+        affected: vec![AffectedPackage {
+            ecosystem: "crates.io".to_string(),
+            name: advisory.package,
+            versions: AffectedVersions::Unpatched {
+                patched: requirements(&front.versions.patched)?,
+                unaffected: requirements(&front.versions.unaffected)?,
+            },
+        }],
+    }))
+}
+
+/// An installed package an advisory affects
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub plugin: String,
+    pub name: String,
+    pub version: String,
+    pub id: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    pub summary: String,
+    /// Lowest version that fixes this advisory, when one is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fixed: Option<String>,
+}
+
+/// Advisories indexed by ecosystem and package name
+#[derive(Debug, Default)]
+pub struct AdvisoryDb {
+    advisories: Vec<Advisory>,
+    index: HashMap<(String, String), Vec<usize>>,
+    /// Files that could not be parsed, with the reason
+    pub unreadable: Vec<(String, String)>,
+}
+
+impl AdvisoryDb {
+    pub fn new(advisories: Vec<Advisory>) -> Self {
+        let mut db = Self::default();
+        for advisory in advisories {
+            db.add(advisory);
+        }
+        db
+    }
+
+    fn add(&mut self, advisory: Advisory) {
+        let i = self.advisories.len();
+        for affected in &advisory.affected {
+            let key = (
+                affected.ecosystem.clone(),
+                normalize(&affected.ecosystem, &affected.name),
+            );
+            let entries = self.index.entry(key).or_default();
+            if !entries.contains(&i) {
+                entries.push(i);
+            }
+        }
+        self.advisories.push(advisory);
+    }
+
+    /// Load every advisory under `path`: a directory of OSV `.json` files
+    /// (such as an extracted OSV export), a RustSec advisory-db checkout,
+    /// or an OSV `.zip` export like `all.zip`. Directories are searched
+    /// recursively and may hold several zip files.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut db = Self::default();
+        if path.is_dir() {
+            db.load_dir(path)?;
+        } else if path.is_file() {
+            db.load_file(path);
+        } else {
+            anyhow::bail!("Advisory database not found: {}", path.display());
+        }
+        if db.advisories.is_empty() && db.unreadable.is_empty() {
+            anyhow::bail!("No advisories found in {}", path.display());
+        }
+        Ok(db)
+    }
+
+    fn load_dir(&mut self, dir: &Path) -> Result<()> {
+        let mut entries: Vec<_> = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read {}", dir.display()))?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .collect();
+        entries.sort();
+        for path in entries {
+            let hidden = path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if hidden {
+                continue;
+            }
+            if path.is_dir() {
+                self.load_dir(&path)?;
+            } else {
+                self.load_file(&path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Load an advisory or zip archive, recording why it could not be read.
+    /// Files of other kinds, and Markdown without front matter such as a
+    /// README, are ignored.
+    fn load_file(&mut self, path: &Path) {
+        let parsed = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => std::fs::read_to_string(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| parse_osv(&json)),
+            Some("md") => match std::fs::read_to_string(path) {
+                Ok(markdown) if !markdown.starts_with("```toml") => return,
+                Ok(markdown) => parse_rustsec(&markdown),
+                Err(e) => Err(e.into()),
+            },
+            Some("zip") => {
+                if let Err(e) = self.load_zip(path) {
+                    self.unreadable
+                        .push((path.display().to_string(), format!("{:#}", e)));
+                }
+                return;
+            }
+            _ => return,
+        };
+        match parsed {
+            Ok(Some(advisory)) => self.add(advisory),
+            Ok(None) => {}
+            Err(e) => self
+                .unreadable
+                .push((path.display().to_string(), format!("{:#}", e))),
+        }
+    }
+
+    fn load_zip(&mut self, path: &Path) -> Result<()> {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            if !file.is_file() || !file.name().ends_with(".json") {
+                continue;
+            }
+            let name = format!("{}:{}", path.display(), file.name());
+            let mut json = String::new();
+            let parsed = file
+                .read_to_string(&mut json)
+                .map_err(anyhow::Error::from)
+                .and_then(|_| parse_osv(&json));
+            match parsed {
+                Ok(Some(advisory)) => self.add(advisory),
+                Ok(None) => {}
+                Err(e) => self.unreadable.push((name, format!("{:#}", e))),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.advisories.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.advisories.is_empty()
+    }
+
+    /// Advisories affecting `version` of package `name`, each with the
+    /// lowest version that fixes it
+    fn matching<'a>(
+        &'a self,
+        ecosystem: &str,
+        name: &str,
+        version: &'a str,
+    ) -> impl Iterator<Item = (&'a Advisory, Option<String>)> + 'a {
+        let key = (ecosystem.to_string(), normalize(ecosystem, name));
+        let name = key.1.clone();
+        let ecosystem = key.0.clone();
+        self.index
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .map(|&i| &self.advisories[i])
+            .filter_map(move |advisory| {
+                let affected: Vec<&AffectedVersions> = advisory
+                    .affected
+                    .iter()
+                    .filter(|a| a.ecosystem == ecosystem && normalize(&ecosystem, &a.name) == name)
+                    .map(|a| &a.versions)
+                    .filter(|v| v.contains(version))
+                    .collect();
+                if affected.is_empty() {
+                    return None;
+                }
+                let fixed = affected
+                    .iter()
+                    .filter_map(|v| v.fixed_after(version))
+                    .min_by(|a, b| order(a, b));
+                Some((advisory, fixed))
+            })
+    }
+
+    /// Every advisory affecting one of `packages`, in the order of
+    /// `packages`. Packages of managers without advisories are skipped.
+    pub fn audit(&self, packages: &[InstalledPackage]) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for package in packages {
+            let Some(ecosystem) = audited_ecosystem(package) else {
+                continue;
+            };
+            for (advisory, fixed) in self.matching(ecosystem, &package.name, &package.version) {
+                findings.push(Finding {
+                    plugin: package.manager.clone(),
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                    id: advisory.id.clone(),
+                    aliases: advisory.aliases.clone(),
+                    summary: advisory.summary.clone(),
+                    fixed,
+                });
+            }
+        }
+        findings
+    }
+
+    /// Version to upgrade `package` to so that no advisory affects it: the
+    /// highest fixed version of the advisories affecting it, again while
+    /// that version is itself affected. `None` when no advisory affects the
+    /// package or one of them has no fixed version.
+    pub fn secure_version(&self, package: &InstalledPackage) -> Option<String> {
+        let ecosystem = audited_ecosystem(package)?;
+        let mut version = package.version.clone();
+        for _ in 0..MAX_FIX_STEPS {
+            let fixed: Vec<Option<String>> = self
+                .matching(ecosystem, &package.name, &version)
+                .map(|(_, fixed)| fixed)
+                .collect();
+            if fixed.is_empty() {
+                return (version != package.version).then_some(version);
+            }
+            version = fixed
+                .into_iter()
+                .collect::<Option<Vec<String>>>()?
+                .into_iter()
+                .max_by(|a, b| order(a, b))?;
+        }
+        None
+    }
+
+    /// The affected `packages` at their [secure
+    /// versions](Self::secure_version), grouped by plugin, and the affected
+    /// packages no fixed version is known for
+    pub fn upgrades<'a>(
+        &self,
+        packages: &'a [InstalledPackage],
+    ) -> (
+        Vec<(String, Vec<InstalledPackage>)>,
+        Vec<&'a InstalledPackage>,
+    ) {
+        let mut upgrades: Vec<(String, Vec<InstalledPackage>)> = Vec::new();
+        let mut unfixed = Vec::new();
+        for package in packages {
+            let Some(ecosystem) = audited_ecosystem(package) else {
+                continue;
+            };
+            if self
+                .matching(ecosystem, &package.name, &package.version)
+                .next()
+                .is_none()
+            {
+                continue;
+            }
+            let Some(version) = self.secure_version(package) else {
+                unfixed.push(package);
+                continue;
+            };
+            let mut package = package.clone();
+            package.version = version;
+            match upgrades.iter_mut().find(|(p, _)| *p == package.manager) {
+                Some((_, packages)) => packages.push(package),
+                None => upgrades.push((package.manager.clone(), vec![package])),
+            }
+        }
+        (upgrades, unfixed)
+    }
+}
+
+/// One aligned table of every finding, or a note that `packages` packages
+/// were checked without any
+pub fn render_findings(findings: &[Finding], packages: usize) -> String {
+    if findings.is_empty() {
+        return format!("No known vulnerabilities in {} packages\n", packages);
+    }
+    let rows: Vec<[&str; 6]> = findings
+        .iter()
+        .map(|f| {
+            [
+                f.plugin.as_str(),
+                &f.name,
+                &f.version,
+                f.fixed.as_deref().unwrap_or("-"),
+                &f.id,
+                &f.summary,
+            ]
+        })
+        .collect();
+    format_table(
+        [
+            "Plugin",
+            "Package",
+            "Installed",
+            "Fixed",
+            "Advisory",
+            "Summary",
+        ],
+        &rows,
+    )
+}
+
+/// Install the secure versions of a `--security-only` run, one plugin at a
+/// time. Each plugin is reported as a `<plugin>-security` action, which
+/// fails for plugins that cannot install a given version.
+pub async fn apply_upgrades(
+    registry: &PluginRegistry<'_>,
+    config: &Config,
+    insights: &Insights,
+    logger: &Logger,
+    upgrades: &[(String, Vec<InstalledPackage>)],
+) -> Vec<ActionResult> {
+    let mut results = Vec::new();
+    for (plugin_name, packages) in upgrades {
+        let action = format!("{}{}", plugin_name, SECURITY_SUFFIX);
+        if crate::plugins::cancellation().is_some() {
+            results.push(ActionResult::new(&action, ActionStatus::Cancelled));
+            continue;
+        }
+        let mut logger = logger.with_prefix(plugin_name);
+        let result = record_action(plugin_name, &action, async {
+            let plugin = registry
+                .get_plugin(plugin_name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown plugin: {}", plugin_name))?;
+            if !plugin
+                .install_versions(config, insights, &mut logger, packages)
+                .await?
+            {
+                let specs: Vec<String> = packages
+                    .iter()
+                    .map(|p| format!("{} {}", p.name, p.version))
+                    .collect();
+                anyhow::bail!(
+                    "{} cannot install given versions; upgrade to {} by hand",
+                    plugin_name,
+                    specs.join(", ")
+                );
+            }
+            Ok(())
+        })
+        .await;
+        if let Some(ref e) = result.error {
+            logger.error(e);
+        }
+        results.push(result);
+    }
+    results
+}
@@ -3,6 +3,9 @@ use colored::Colorize;
 use serde::Deserialize;
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::translate::PackageMap;
 
 pub fn generate_sample_yaml() -> String {
     r#"# Updatehauler Configuration File
@@ -42,6 +45,31 @@ dry_run: false
 # Maximum lines for logfile before rotation
 max_log_lines: 10000
 
+# Number of runs kept in the run history (0 = don't record runs)
+# max_history_runs: 1000
+
+# Maximum number of plugins updated in parallel (1 = run sequentially)
+jobs: 4
+
+# Command timeouts in seconds (0 = no limit). When a timeout fires the command
+# is killed along with the processes it started (processes run as another
+# user, e.g. through sudo, may survive) and the action is reported as TIMEOUT.
+# timeouts:
+#   default: 3600
+#   plugins:
+#     nvim: 300
+#   commands:
+#     "snap refresh": 1800
+
+# Retry commands that fail for a transient reason (network down, package
+# manager lock held). Omit plugins/commands to retry everything.
+# retry:
+#   max_attempts: 3
+#   backoff: 10        # seconds before the first retry, doubled each time
+#   max_backoff: 300
+#   plugins: [os, brew]
+#   commands: ["apt-get update"]
+
 # Logfile path (default: ~/.local/updates.log)
 # logfile: ~/.local/updates.log
 
@@ -51,10 +79,47 @@ max_log_lines: 10000
 # Completion files directory (default: ~/.local/share)
 # completionsdir: ~/.local/share
 
+# State directory holding the run history (default: $XDG_STATE_HOME/updatehauler
+# or ~/.local/state/updatehauler)
+# statedir: ~/.local/state/updatehauler
+
 # Custom save file locations
 # brew_save_file: ~/.config/brew/Darwin-Brewfile
 # cargo_save_file: ~/.config/cargo/Darwin-arm64-cargo-backup.json
 
+# Keep every save file in a git repository, committed after each run that
+# saves packages ("save_repo: {}" uses the defaults)
+# save_repo:
+#   path: ~/.config/updatehauler/saves
+#   push: false
+#   remote: origin
+
+# Alternatives used by "brew restore --translate" when a Brewfile from
+# another platform lists formulas, casks or App Store apps that can't be
+# installed here; added to the table shipped with updatehauler
+# package_map:
+#   brew:
+#     fd: {apt: fd-find, dnf: fd-find}
+#   cask:
+#     firefox: {flatpak: org.mozilla.firefox}
+
+# Vulnerability advisories checked by "updatehauler audit" and
+# --security-only: a directory of OSV JSON files, an OSV all.zip export
+# or a RustSec advisory-db checkout
+# advisory_db: ~/.local/share/osv
+
+# Which OS updates the os plugin installs: full (default), security (only
+# security updates) or minimal (security and bug fix updates). security and
+# minimal are supported with apt and dnf.
+# os:
+#   mode: full
+
+# Reboot after the os plugin's update when it needs one (new kernel, core
+# libraries or services): never (default), if_required (after the grace
+# period, in minutes) or "at HH:MM"
+# auto_reboot: never
+# reboot_grace: 5
+
 # Schedule configuration for automated updates
 schedule:
   # Minute (0-59, default: 0)
@@ -69,6 +134,72 @@ schedule:
   # 0 and 7 are Sunday
   day_of_week: "*"
 
+# Custom plugins for package managers without a built-in plugin. Commands
+# are split like a shell would but run directly; {save_file} is replaced
+# with save_file. Enable/disable them under plugins: like built-ins.
+# custom_plugins:
+#   - name: acme
+#     description: Update ACME internal tools
+#     detect: acmectl
+#     run_after: [os]
+#     save_file: ~/.config/acme/tools.txt
+#     update:
+#       - acmectl self-update
+#       - acmectl upgrade --all
+#     save:
+#       - acmectl list --installed
+#     restore:
+#       - acmectl install --from-file {save_file}
+#     actions:
+#       doctor:
+#         - acmectl doctor
+
+# Shell commands run around plugins and the whole run. Hooks get
+# UPDATEHAULER_HOOK, UPDATEHAULER_PLUGIN, UPDATEHAULER_ACTIONS,
+# UPDATEHAULER_STATUS and UPDATEHAULER_FAILED in their environment.
+# A failing pre-hook skips its plugin (pre_run: the whole run) unless
+# pre_failure is set to continue.
+# hooks:
+#   pre_run:
+#     - tmutil localsnapshot
+#   pre_os:
+#     - multipass stop devvm
+#   post_brew:
+#     - ~/bin/relink-dotfiles
+#   on_failure:
+#     - notify-send "updates failed: $UPDATEHAULER_FAILED"
+#   post_run:
+#     - curl -fsS "https://status.example.com/ping?status=$UPDATEHAULER_STATUS"
+#   pre_failure: skip
+
+# Smoke tests run with sh -c after a plugin's update. A failing command
+# marks the update VERIFY_FAIL; with rollback: true the packages the update
+# changed are reinstalled at their previous versions.
+# verify:
+#   nvim:
+#     - nvim --headless +qa
+#   npm:
+#     - node -e 1
+#   cargo:
+#     - cargo --version
+#   rollback: false
+
+# Discover updatehauler-plugin-<name> executables on PATH (default: true)
+# external_plugins: true
+
+# Named run profiles, selected with --profile <name>. A profile is an
+# action list or a map with actions plus jobs, timeout, debug, brew_sudo
+# and schedule overrides. "schedule enable --profile weekend" installs a
+# separate scheduled entry for the profile.
+# profiles:
+#   quick: [brew, npm]
+#   weekend:
+#     actions: [os, brew, brew-save, docker, nvim]
+#     jobs: 2
+#     schedule:
+#       hour: "9"
+#       day_of_week: "6"
+
 # Plugin configuration
 plugins:
   # Enable or disable Homebrew plugin
@@ -123,6 +254,26 @@ pub fn validate_schedule_value(value: &str, field: &str) -> Result<()> {
     Ok(())
 }
 
+/// `$XDG_STATE_HOME/updatehauler`, or `~/.local/state/updatehauler` when
+/// XDG_STATE_HOME is unset or not absolute
+pub fn default_state_dir(home: &Path) -> PathBuf {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or_else(|| home.join(".local/state"))
+        .join("updatehauler")
+}
+
+/// `path` with a leading `~` replaced by `home`
+fn expand_home(home: &str, path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(format!("{}{}", home, rest))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
 pub fn has_path_traversal(path: &Path) -> bool {
     path.components()
         .any(|c| c == std::path::Component::ParentDir)
@@ -141,13 +292,29 @@ pub struct ConfigFile {
     pub brew_sudo: Option<bool>,
     pub confirm_run: Option<bool>,
     pub max_log_lines: Option<usize>,
+    pub max_history_runs: Option<usize>,
+    pub jobs: Option<usize>,
     pub logfile: Option<String>,
     pub installdir: Option<String>,
     pub brew_save_file: Option<String>,
     pub cargo_save_file: Option<String>,
     pub completionsdir: Option<String>,
+    pub statedir: Option<String>,
     pub schedule: Option<ScheduleConfig>,
+    pub timeouts: Option<TimeoutConfig>,
+    pub retry: Option<RetryConfig>,
     pub plugins: Option<PluginConfig>,
+    pub custom_plugins: Option<Vec<CustomPluginConfig>>,
+    pub external_plugins: Option<bool>,
+    pub hooks: Option<HooksConfig>,
+    pub verify: Option<VerifyConfig>,
+    pub profiles: Option<BTreeMap<String, ProfileConfig>>,
+    pub save_repo: Option<SaveRepoConfig>,
+    pub package_map: Option<PackageMap>,
+    pub advisory_db: Option<String>,
+    pub os: Option<OsConfig>,
+    pub auto_reboot: Option<String>,
+    pub reboot_grace: Option<u64>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -159,6 +326,490 @@ pub struct ScheduleConfig {
     pub day_of_week: Option<String>,
 }
 
+impl ScheduleConfig {
+    pub fn validate(&self) -> Result<()> {
+        for (value, field) in [
+            (&self.minute, "minute"),
+            (&self.hour, "hour"),
+            (&self.day_of_month, "day_of_month"),
+            (&self.month, "month"),
+            (&self.day_of_week, "day_of_week"),
+        ] {
+            if let Some(value) = value {
+                validate_schedule_value(value, field)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A profile is either a bare action list or a map with option overrides
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ProfileConfig {
+    Actions(Vec<String>),
+    Options(Profile),
+}
+
+/// A named run selected with `--profile`: the actions to run plus options
+/// that override the top-level config (command-line flags still win)
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Profile {
+    pub actions: Vec<String>,
+    pub jobs: Option<usize>,
+    /// Default command timeout in seconds
+    pub timeout: Option<u64>,
+    pub debug: Option<bool>,
+    pub brew_sudo: Option<bool>,
+    /// When `schedule enable --profile` runs this profile
+    pub schedule: Option<ScheduleConfig>,
+}
+
+impl From<ProfileConfig> for Profile {
+    fn from(file: ProfileConfig) -> Self {
+        match file {
+            ProfileConfig::Actions(actions) => Self {
+                actions,
+                ..Self::default()
+            },
+            ProfileConfig::Options(profile) => profile,
+        }
+    }
+}
+
+/// Profile names end up in cron entries and launchd labels
+pub fn is_valid_profile_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TimeoutConfig {
+    pub default: Option<u64>,
+    pub plugins: Option<BTreeMap<String, u64>>,
+    pub commands: Option<BTreeMap<String, u64>>,
+}
+
+/// Command deadlines in seconds, where 0 means "no limit".
+///
+/// The most specific setting wins: a matching `commands` entry, then the
+/// plugin's entry, then `default`.
+#[derive(Clone, Debug, Default)]
+pub struct Timeouts {
+    pub default: u64,
+    pub plugins: BTreeMap<String, u64>,
+    /// Keyed by command prefix, e.g. `"snap refresh"` or `"nvim --headless"`
+    pub commands: BTreeMap<String, u64>,
+}
+
+impl Timeouts {
+    /// Deadline for `command_line` (program basename followed by its arguments)
+    /// run on behalf of `plugin`
+    pub fn resolve(&self, plugin: Option<&str>, command_line: &str) -> Option<Duration> {
+        let secs = self
+            .commands
+            .iter()
+            .filter(|(prefix, _)| matches_command_prefix(command_line, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, &secs)| secs)
+            .or_else(|| plugin.and_then(|p| self.plugins.get(p).copied()))
+            .unwrap_or(self.default);
+
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RetryConfig {
+    pub max_attempts: Option<u32>,
+    pub backoff: Option<u64>,
+    pub max_backoff: Option<u64>,
+    pub plugins: Option<Vec<String>>,
+    pub commands: Option<Vec<String>>,
+}
+
+/// How often a command that failed for a transient reason (network, lock
+/// contention) is started again. Other failures are never retried.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Total number of attempts; 1 disables retries
+    pub max_attempts: u32,
+    /// Seconds before the first retry, doubled for every further attempt
+    pub backoff: u64,
+    pub max_backoff: u64,
+    /// Plugins the policy applies to; together with `commands`, empty means all
+    pub plugins: Vec<String>,
+    /// Command prefixes the policy applies to, e.g. `"apt-get update"`
+    pub commands: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            backoff: 10,
+            max_backoff: 300,
+            plugins: Vec::new(),
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn applies_to(&self, plugin: Option<&str>, command_line: &str) -> bool {
+        if self.plugins.is_empty() && self.commands.is_empty() {
+            return true;
+        }
+        plugin.is_some_and(|p| self.plugins.iter().any(|name| name == p))
+            || self
+                .commands
+                .iter()
+                .any(|prefix| matches_command_prefix(command_line, prefix))
+    }
+
+    /// Delay before starting attempt `attempt + 1`
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_secs(self.backoff.saturating_mul(factor).min(self.max_backoff))
+    }
+}
+
+/// Whether `command_line` is `prefix` or starts with `prefix` followed by more arguments
+pub fn matches_command_prefix(command_line: &str, prefix: &str) -> bool {
+    command_line == prefix
+        || command_line
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with(' '))
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SaveRepoConfig {
+    pub path: Option<String>,
+    pub push: Option<bool>,
+    pub remote: Option<String>,
+}
+
+/// Git repository that every save file is copied into and committed after
+/// a run that saved packages
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SaveRepo {
+    pub path: PathBuf,
+    /// Push to `remote` after each commit
+    pub push: bool,
+    pub remote: String,
+}
+
+impl SaveRepo {
+    pub fn new(home: &Path) -> Self {
+        Self {
+            path: home.join(".config/updatehauler/saves"),
+            push: false,
+            remote: "origin".to_string(),
+        }
+    }
+}
+
+/// What happens to the guarded plugin (or run) when a pre-hook fails
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PreHookFailure {
+    #[default]
+    Skip,
+    Continue,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct HooksConfig {
+    pub pre_failure: Option<PreHookFailure>,
+    /// `pre_run`, `post_run`, `on_failure`, `pre_<plugin>` and `post_<plugin>`
+    #[serde(flatten)]
+    pub commands: BTreeMap<String, Vec<String>>,
+}
+
+/// Shell commands run around plugins and around the whole run. Every command
+/// is run with `sh -c`; a hook stops at its first failing command.
+#[derive(Clone, Debug, Default)]
+pub struct Hooks {
+    pub pre_run: Vec<String>,
+    pub post_run: Vec<String>,
+    /// Run after the run when any action failed, before `post_run`
+    pub on_failure: Vec<String>,
+    pub pre_plugin: BTreeMap<String, Vec<String>>,
+    pub post_plugin: BTreeMap<String, Vec<String>>,
+    pub pre_failure: PreHookFailure,
+}
+
+impl Hooks {
+    pub fn from_config(file: HooksConfig) -> Result<Self> {
+        let mut hooks = Self {
+            pre_failure: file.pre_failure.unwrap_or_default(),
+            ..Self::default()
+        };
+        for (key, commands) in file.commands {
+            match key.as_str() {
+                "pre_run" => hooks.pre_run = commands,
+                "post_run" => hooks.post_run = commands,
+                "on_failure" => hooks.on_failure = commands,
+                _ => {
+                    let (map, plugin) = match (key.strip_prefix("pre_"), key.strip_prefix("post_"))
+                    {
+                        (Some(plugin), _) => (&mut hooks.pre_plugin, plugin),
+                        (_, Some(plugin)) => (&mut hooks.post_plugin, plugin),
+                        _ => anyhow::bail!(
+                            "unknown hook '{}' (expected pre_run, post_run, on_failure, pre_<plugin> or post_<plugin>)",
+                            key
+                        ),
+                    };
+                    if !is_valid_plugin_name(plugin) {
+                        anyhow::bail!("hook '{}' does not name a valid plugin", key);
+                    }
+                    map.insert(plugin.to_string(), commands);
+                }
+            }
+        }
+        Ok(hooks)
+    }
+
+    pub fn pre(&self, plugin: &str) -> &[String] {
+        self.pre_plugin.get(plugin).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn post(&self, plugin: &str) -> &[String] {
+        self.post_plugin.get(plugin).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Which updates the os plugin installs
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OsUpdateMode {
+    #[default]
+    Full,
+    /// Only updates that fix security issues
+    Security,
+    /// Security and bug fix updates
+    Minimal,
+}
+
+impl OsUpdateMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Security => "security",
+            Self::Minimal => "minimal",
+        }
+    }
+}
+
+/// When the machine is rebooted after an OS update that needs one
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum AutoReboot {
+    #[default]
+    Never,
+    /// Once the grace period has passed
+    IfRequired,
+    /// At the next `HH:MM`
+    At(String),
+}
+
+impl AutoReboot {
+    /// Parse `never`, `if_required` or `at HH:MM`
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        match value {
+            "never" => return Ok(Self::Never),
+            "if_required" => return Ok(Self::IfRequired),
+            _ => {}
+        }
+        let valid_time = |time: &str| {
+            let Some((hour, minute)) = time.split_once(':') else {
+                return false;
+            };
+            hour.len() == 2
+                && minute.len() == 2
+                && hour.parse::<u8>().is_ok_and(|h| h < 24)
+                && minute.parse::<u8>().is_ok_and(|m| m < 60)
+        };
+        match value.strip_prefix("at ").map(str::trim) {
+            Some(time) if valid_time(time) => Ok(Self::At(time.to_string())),
+            _ => anyhow::bail!(
+                "auto_reboot must be never, if_required or 'at HH:MM' (got '{}')",
+                value
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OsConfig {
+    pub mode: Option<OsUpdateMode>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct VerifyConfig {
+    pub rollback: Option<bool>,
+    /// Smoke test commands keyed by plugin
+    #[serde(flatten)]
+    pub commands: BTreeMap<String, Vec<String>>,
+}
+
+/// Shell commands that check a plugin still works after its update. Every
+/// command is run with `sh -c`; verification stops at the first failing one.
+#[derive(Clone, Debug, Default)]
+pub struct Verify {
+    pub plugins: BTreeMap<String, Vec<String>>,
+    /// Reinstall the previous versions of the packages a plugin changed when
+    /// its verification fails
+    pub rollback: bool,
+}
+
+impl Verify {
+    pub fn from_config(file: VerifyConfig) -> Result<Self> {
+        for plugin in file.commands.keys() {
+            if !is_valid_plugin_name(plugin) {
+                anyhow::bail!("verify key '{}' does not name a valid plugin", plugin);
+            }
+        }
+        Ok(Self {
+            plugins: file.commands,
+            rollback: file.rollback.unwrap_or(false),
+        })
+    }
+
+    pub fn commands(&self, plugin: &str) -> &[String] {
+        self.plugins.get(plugin).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Names that cannot be used for custom plugins because they are CLI subcommands
+pub const RESERVED_PLUGIN_NAMES: &[&str] = &[
+    "audit",
+    "config",
+    "converge",
+    "help",
+    "history",
+    "install",
+    "install-completions",
+    "inventory",
+    "outdated",
+    "remove",
+    "rollback",
+    "saves",
+    "sbom",
+    "schedule",
+    "snapshot",
+    "trim-logfile",
+    "update",
+];
+
+/// A package manager declared under `custom_plugins:`; every command is a
+/// program followed by shell-style quoted arguments (no shell is involved).
+/// `{save_file}` in a command is replaced with the plugin's save file.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CustomPluginConfig {
+    pub name: String,
+    pub description: Option<String>,
+    /// Binary that must be on PATH for the plugin to be available
+    /// (default: the program of the first update command)
+    pub detect: Option<String>,
+    pub enabled: Option<bool>,
+    #[serde(default)]
+    pub run_after: Vec<String>,
+    /// File the stdout of the save commands is written to
+    pub save_file: Option<String>,
+    #[serde(default)]
+    pub update: Vec<String>,
+    #[serde(default)]
+    pub save: Vec<String>,
+    #[serde(default)]
+    pub restore: Vec<String>,
+    /// Extra actions, run as `updatehauler <name> <action>`
+    #[serde(default)]
+    pub actions: BTreeMap<String, Vec<String>>,
+}
+
+/// Plugin names may not contain '-', which separates a plugin from its action
+pub fn is_valid_plugin_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+impl CustomPluginConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !is_valid_plugin_name(&self.name) {
+            anyhow::bail!(
+                "custom plugin name '{}' must only contain lowercase letters, digits and '_'",
+                self.name
+            );
+        }
+        if RESERVED_PLUGIN_NAMES.contains(&self.name.as_str()) {
+            anyhow::bail!(
+                "custom plugin name '{}' is reserved for a built-in command",
+                self.name
+            );
+        }
+        if self.update.is_empty() {
+            anyhow::bail!(
+                "custom plugin '{}' needs at least one update command",
+                self.name
+            );
+        }
+        for action in self.actions.keys() {
+            if action.is_empty()
+                || !action
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_-".contains(c))
+            {
+                anyhow::bail!(
+                    "custom plugin '{}' action '{}' must only contain lowercase letters, digits, '-' and '_'",
+                    self.name,
+                    action
+                );
+            }
+            if ["update", "save", "restore"].contains(&action.as_str()) {
+                anyhow::bail!(
+                    "custom plugin '{}' action '{}' clashes with the built-in action; use the '{}:' key instead",
+                    self.name,
+                    action,
+                    action
+                );
+            }
+        }
+        if let Some(ref save_file) = self.save_file
+            && has_path_traversal(Path::new(save_file))
+        {
+            anyhow::bail!(
+                "custom plugin '{}' save_file contains '..' traversal: {}",
+                self.name,
+                save_file
+            );
+        }
+        let commands = self
+            .update
+            .iter()
+            .chain(&self.save)
+            .chain(&self.restore)
+            .chain(self.actions.values().flatten());
+        for command in commands {
+            match shell_words::split(command) {
+                Ok(words) if !words.is_empty() => {}
+                Ok(_) => anyhow::bail!("custom plugin '{}' has an empty command", self.name),
+                Err(e) => anyhow::bail!(
+                    "custom plugin '{}' command '{}' is not valid: {}",
+                    self.name,
+                    command,
+                    e
+                ),
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct PluginConfig {
     pub brew: Option<bool>,
@@ -177,6 +828,9 @@ pub struct PluginConfig {
     pub deno: Option<bool>,
     pub yarn: Option<bool>,
     pub go: Option<bool>,
+    /// Enabled state of custom plugins, keyed by plugin name
+    #[serde(flatten)]
+    pub custom: BTreeMap<String, bool>,
 }
 
 #[derive(Clone)]
@@ -186,7 +840,37 @@ pub struct Config {
     pub brew_save_dir: PathBuf,
     pub cargo_save_dir: PathBuf,
     pub completions_dir: PathBuf,
+    /// Directory for data kept between runs, such as the run history
+    pub state_dir: PathBuf,
     pub max_log_lines: usize,
+    /// Runs kept in the run history; 0 disables recording
+    pub max_history_runs: usize,
+    pub jobs: usize,
+    pub timeouts: Timeouts,
+    pub retry: RetryPolicy,
+    pub hooks: Hooks,
+    pub verify: Verify,
+    pub profiles: BTreeMap<String, Profile>,
+    /// Commit save files to a git repository; `None` leaves them unversioned
+    pub save_repo: Option<SaveRepo>,
+    /// Entries added to the built-in package map used by translated restores
+    pub package_map: PackageMap,
+    /// OSV advisory directory or zip file used by `audit` and
+    /// `--security-only`
+    pub advisory_db: Option<PathBuf>,
+    /// Which updates the os plugin installs
+    pub os_mode: OsUpdateMode,
+    /// Reboot after an OS update that needs one
+    pub auto_reboot: AutoReboot,
+    /// Minutes between scheduling an `if_required` reboot and the reboot
+    pub reboot_grace: u64,
+    /// Restore save files written on another platform, mapping entries that
+    /// can't be used here to alternatives
+    pub translate_restore: bool,
+    /// Restores reinstall the exact versions recorded in the save files
+    pub restore_pinned: bool,
+    /// Compare save files with what is installed instead of restoring
+    pub restore_plan: bool,
     pub sched_minute: String,
     pub sched_hour: String,
     pub sched_day_of_month: String,
@@ -211,7 +895,13 @@ pub struct Config {
     pub yarn_file: PathBuf,
     pub go_file: PathBuf,
     pub gem_file: PathBuf,
+    pub flatpak_file: PathBuf,
+    pub snap_file: PathBuf,
+    pub vscode_file: PathBuf,
     pub plugins_enabled: PluginConfig,
+    pub custom_plugins: Vec<CustomPluginConfig>,
+    /// Discover `updatehauler-plugin-*` executables on PATH
+    pub external_plugins: bool,
     pub cmd_args: Vec<String>,
     pub only_plugin: Option<String>,
 }
@@ -226,7 +916,24 @@ impl Config {
             brew_save_dir: home_path.join(".config/brew"),
             cargo_save_dir: home_path.join(".config/cargo"),
             completions_dir: home_path.join(".local/share"),
+            state_dir: default_state_dir(&home_path),
             max_log_lines: 10000,
+            max_history_runs: 1000,
+            jobs: 4,
+            timeouts: Timeouts::default(),
+            retry: RetryPolicy::default(),
+            hooks: Hooks::default(),
+            verify: Verify::default(),
+            profiles: BTreeMap::new(),
+            save_repo: None,
+            package_map: PackageMap::default(),
+            advisory_db: None,
+            os_mode: OsUpdateMode::Full,
+            auto_reboot: AutoReboot::Never,
+            reboot_grace: 5,
+            translate_restore: false,
+            restore_pinned: false,
+            restore_plan: false,
             sched_minute: "0".to_string(),
             sched_hour: "2".to_string(),
             sched_day_of_month: "*".to_string(),
@@ -251,6 +958,9 @@ impl Config {
             yarn_file: PathBuf::new(),
             go_file: PathBuf::new(),
             gem_file: PathBuf::new(),
+            flatpak_file: PathBuf::new(),
+            snap_file: PathBuf::new(),
+            vscode_file: PathBuf::new(),
             plugins_enabled: PluginConfig {
                 brew: Some(true),
                 cargo: Some(true),
@@ -268,7 +978,10 @@ impl Config {
                 deno: Some(true),
                 yarn: Some(false),
                 go: Some(false),
+                custom: BTreeMap::new(),
             },
+            custom_plugins: Vec::new(),
+            external_plugins: true,
             cmd_args: Vec::new(),
             only_plugin: None,
         }
@@ -323,6 +1036,15 @@ impl Config {
         if let Some(max_log_lines) = config_file_yaml.max_log_lines {
             config.max_log_lines = max_log_lines;
         }
+        if let Some(max_history_runs) = config_file_yaml.max_history_runs {
+            config.max_history_runs = max_history_runs;
+        }
+        if let Some(jobs) = config_file_yaml.jobs {
+            if jobs == 0 {
+                anyhow::bail!("jobs must be at least 1");
+            }
+            config.jobs = jobs;
+        }
         if let Some(logfile) = config_file_yaml.logfile {
             let p = PathBuf::from(&logfile);
             if has_path_traversal(&p) {
@@ -347,6 +1069,13 @@ impl Config {
             }
             config.completions_dir = p;
         }
+        if let Some(statedir) = config_file_yaml.statedir {
+            let p = PathBuf::from(&statedir);
+            if has_path_traversal(&p) {
+                anyhow::bail!("statedir path contains '..' traversal: {}", statedir);
+            }
+            config.state_dir = p;
+        }
         if let Some(brew_save_file) = config_file_yaml.brew_save_file {
             let p = PathBuf::from(&brew_save_file);
             if has_path_traversal(&p) {
@@ -368,34 +1097,163 @@ impl Config {
             config.cargo_file = p;
         }
         if let Some(schedule) = config_file_yaml.schedule {
-            if let Some(minute) = schedule.minute {
-                validate_schedule_value(&minute, "minute")?;
-                config.sched_minute = minute;
+            config.apply_schedule(schedule)?;
+        }
+        if let Some(timeouts) = config_file_yaml.timeouts {
+            if let Some(default) = timeouts.default {
+                config.timeouts.default = default;
+            }
+            if let Some(plugins) = timeouts.plugins {
+                config.timeouts.plugins = plugins;
+            }
+            if let Some(commands) = timeouts.commands {
+                if commands.keys().any(|k| k.trim().is_empty()) {
+                    anyhow::bail!("timeouts.commands keys must not be empty");
+                }
+                config.timeouts.commands = commands;
             }
-            if let Some(hour) = schedule.hour {
-                validate_schedule_value(&hour, "hour")?;
-                config.sched_hour = hour;
+        }
+        if let Some(retry) = config_file_yaml.retry {
+            if let Some(max_attempts) = retry.max_attempts {
+                if max_attempts == 0 {
+                    anyhow::bail!("retry.max_attempts must be at least 1");
+                }
+                config.retry.max_attempts = max_attempts;
+            }
+            if let Some(backoff) = retry.backoff {
+                config.retry.backoff = backoff;
+            }
+            if let Some(max_backoff) = retry.max_backoff {
+                config.retry.max_backoff = max_backoff;
             }
-            if let Some(day_of_month) = schedule.day_of_month {
-                validate_schedule_value(&day_of_month, "day_of_month")?;
-                config.sched_day_of_month = day_of_month;
+            if let Some(plugins) = retry.plugins {
+                config.retry.plugins = plugins;
+            }
+            if let Some(commands) = retry.commands {
+                config.retry.commands = commands;
+            }
+        }
+        if let Some(hooks) = config_file_yaml.hooks {
+            config.hooks = Hooks::from_config(hooks)?;
+        }
+        if let Some(verify) = config_file_yaml.verify {
+            config.verify = Verify::from_config(verify)?;
+        }
+        if let Some(save_repo) = config_file_yaml.save_repo {
+            let mut repo = SaveRepo::new(Path::new(home));
+            if let Some(path) = save_repo.path {
+                let p = expand_home(home, &path);
+                if has_path_traversal(&p) {
+                    anyhow::bail!("save_repo.path contains '..' traversal: {}", path);
+                }
+                repo.path = p;
             }
-            if let Some(month) = schedule.month {
-                validate_schedule_value(&month, "month")?;
-                config.sched_month = month;
+            if let Some(push) = save_repo.push {
+                repo.push = push;
             }
-            if let Some(day_of_week) = schedule.day_of_week {
-                validate_schedule_value(&day_of_week, "day_of_week")?;
-                config.sched_day_of_week = day_of_week;
+            if let Some(remote) = save_repo.remote {
+                if remote.trim().is_empty() || remote.starts_with('-') {
+                    anyhow::bail!("save_repo.remote is not a valid remote name: {:?}", remote);
+                }
+                repo.remote = remote;
+            }
+            config.save_repo = Some(repo);
+        }
+        if let Some(package_map) = config_file_yaml.package_map {
+            config.package_map = package_map;
+        }
+        if let Some(advisory_db) = config_file_yaml.advisory_db {
+            let p = expand_home(home, &advisory_db);
+            if has_path_traversal(&p) {
+                anyhow::bail!("advisory_db path contains '..' traversal: {}", advisory_db);
+            }
+            config.advisory_db = Some(p);
+        }
+        if let Some(mode) = config_file_yaml.os.and_then(|os| os.mode) {
+            config.os_mode = mode;
+        }
+        if let Some(ref auto_reboot) = config_file_yaml.auto_reboot {
+            config.auto_reboot = AutoReboot::parse(auto_reboot)?;
+        }
+        if let Some(grace) = config_file_yaml.reboot_grace {
+            // shutdown +0 reboots before the run is recorded
+            if grace == 0 {
+                anyhow::bail!("reboot_grace must be at least 1 minute");
+            }
+            config.reboot_grace = grace;
+        }
+        if let Some(profiles) = config_file_yaml.profiles {
+            for (name, profile) in profiles {
+                let profile = Profile::from(profile);
+                if !is_valid_profile_name(&name) {
+                    anyhow::bail!(
+                        "invalid profile name '{}' (use lowercase letters, digits, '_' and '-')",
+                        name
+                    );
+                }
+                if profile.actions.is_empty() {
+                    anyhow::bail!("profile '{}' has no actions", name);
+                }
+                if profile.jobs == Some(0) {
+                    anyhow::bail!("profile '{}': jobs must be at least 1", name);
+                }
+                if let Some(ref schedule) = profile.schedule {
+                    schedule
+                        .validate()
+                        .with_context(|| format!("profile '{}'", name))?;
+                }
+                config.profiles.insert(name, profile);
             }
         }
         if let Some(plugins) = config_file_yaml.plugins {
             config.plugins_enabled = plugins;
         }
+        if let Some(v) = config_file_yaml.external_plugins {
+            config.external_plugins = v;
+        }
+        if let Some(custom_plugins) = config_file_yaml.custom_plugins {
+            for (i, plugin) in custom_plugins.iter().enumerate() {
+                plugin.validate()?;
+                if custom_plugins[..i].iter().any(|p| p.name == plugin.name) {
+                    anyhow::bail!("custom plugin '{}' is defined more than once", plugin.name);
+                }
+                if let Some(enabled) = plugin.enabled {
+                    config
+                        .plugins_enabled
+                        .custom
+                        .entry(plugin.name.clone())
+                        .or_insert(enabled);
+                }
+            }
+            config.custom_plugins = custom_plugins;
+        }
+        // External plugins are only discovered when a run needs plugins; the
+        // keys left for them are checked then
+        if !config.external_plugins
+            && let Some(key) = config.unknown_plugin_keys(|_| false).first()
+        {
+            anyhow::bail!(
+                "plugins.{}: no built-in or custom plugin has this name",
+                key
+            );
+        }
 
         Ok(config)
     }
 
+    /// Keys under `plugins:` naming neither a built-in plugin, a custom
+    /// plugin nor one `known` accepts, such as a loaded external plugin
+    pub fn unknown_plugin_keys(&self, known: impl Fn(&str) -> bool) -> Vec<&str> {
+        self.plugins_enabled
+            .custom
+            .keys()
+            .map(String::as_str)
+            // `run` is the one built-in plugin without a field of its own
+            .filter(|key| *key != "run")
+            .filter(|key| !self.custom_plugins.iter().any(|p| p.name == *key) && !known(key))
+            .collect()
+    }
+
     pub fn default_config_path() -> Result<PathBuf> {
         let home = std::env::var("HOME").context("HOME environment variable not set")?;
         Ok(PathBuf::from(home).join(".config/updatehauler/config.yaml"))
@@ -670,6 +1528,31 @@ impl Config {
         Ok(())
     }
 
+    /// Whether plugin `name` is enabled; custom and external plugins are
+    /// enabled unless turned off under `plugins:`
+    pub fn plugin_enabled(&self, name: &str) -> bool {
+        let p = &self.plugins_enabled;
+        match name {
+            "brew" => p.brew.unwrap_or(false),
+            "cargo" => p.cargo.unwrap_or(false),
+            "deno" => p.deno.unwrap_or(false),
+            "docker" => p.docker.unwrap_or(false),
+            "flatpak" => p.flatpak.unwrap_or(false),
+            "gem" => p.gem.unwrap_or(false),
+            "nvim" => p.nvim.unwrap_or(false),
+            "npm" => p.npm.unwrap_or(false),
+            "os" => p.os.unwrap_or(false),
+            "pip" => p.pip.unwrap_or(false),
+            "rustup" => p.rustup.unwrap_or(false),
+            "snap" => p.snap.unwrap_or(false),
+            "uv" => p.uv.unwrap_or(false),
+            "vscode" => p.vscode.unwrap_or(false),
+            "yarn" => p.yarn.unwrap_or(false),
+            "go" => p.go.unwrap_or(false),
+            name => p.custom.get(name).copied().unwrap_or(true),
+        }
+    }
+
     pub fn apply_plugin_enabled(&mut self, name: &str, enabled: bool) {
         match name {
             "brew" => self.plugins_enabled.brew = Some(enabled),
@@ -688,10 +1571,124 @@ impl Config {
             "deno" => self.plugins_enabled.deno = Some(enabled),
             "yarn" => self.plugins_enabled.yarn = Some(enabled),
             "go" => self.plugins_enabled.go = Some(enabled),
-            _ => {}
+            _ => {
+                self.plugins_enabled
+                    .custom
+                    .insert(name.to_string(), enabled);
+            }
         }
     }
 
+    /// File plugin `name` writes on save and reads on restore, for the
+    /// built-in plugins with a save file and custom plugins with `save_file`
+    pub fn save_file(&self, name: &str) -> Option<PathBuf> {
+        match name {
+            "brew" => Some(self.brew_file.clone()),
+            "cargo" => Some(self.cargo_file.clone()),
+            "npm" => Some(self.npm_file.clone()),
+            "pip" => Some(self.pip_file.clone()),
+            "uv" => Some(self.uv_file.clone()),
+            "yarn" => Some(self.yarn_file.clone()),
+            "go" => Some(self.go_file.clone()),
+            "gem" => Some(self.gem_file.clone()),
+            "flatpak" => Some(self.flatpak_file.clone()),
+            "snap" => Some(self.snap_file.clone()),
+            "vscode" => Some(self.vscode_file.clone()),
+            name => self
+                .custom_plugins
+                .iter()
+                .find(|p| p.name == name)
+                .and_then(|p| p.save_file.as_deref())
+                .map(PathBuf::from),
+        }
+    }
+
+    /// Point the save file of plugin `name` at `path`; returns false when the
+    /// plugin has no save file. Custom plugins pick up the change when the
+    /// plugin registry is built again.
+    pub fn set_save_file(&mut self, name: &str, path: PathBuf) -> bool {
+        let field = match name {
+            "brew" => &mut self.brew_file,
+            "cargo" => &mut self.cargo_file,
+            "npm" => &mut self.npm_file,
+            "pip" => &mut self.pip_file,
+            "uv" => &mut self.uv_file,
+            "yarn" => &mut self.yarn_file,
+            "go" => &mut self.go_file,
+            "gem" => &mut self.gem_file,
+            "flatpak" => &mut self.flatpak_file,
+            "snap" => &mut self.snap_file,
+            "vscode" => &mut self.vscode_file,
+            name => {
+                let Some(def) = self
+                    .custom_plugins
+                    .iter_mut()
+                    .find(|p| p.name == name && p.save_file.is_some())
+                else {
+                    return false;
+                };
+                def.save_file = Some(path.to_string_lossy().to_string());
+                return true;
+            }
+        };
+        *field = path;
+        true
+    }
+
+    fn apply_schedule(&mut self, schedule: ScheduleConfig) -> Result<()> {
+        schedule.validate()?;
+        if let Some(minute) = schedule.minute {
+            self.sched_minute = minute;
+        }
+        if let Some(hour) = schedule.hour {
+            self.sched_hour = hour;
+        }
+        if let Some(day_of_month) = schedule.day_of_month {
+            self.sched_day_of_month = day_of_month;
+        }
+        if let Some(month) = schedule.month {
+            self.sched_month = month;
+        }
+        if let Some(day_of_week) = schedule.day_of_week {
+            self.sched_day_of_week = day_of_week;
+        }
+        Ok(())
+    }
+
+    /// Apply the overrides of profile `name` and return its actions
+    pub fn apply_profile(&mut self, name: &str) -> Result<Vec<String>> {
+        let Some(profile) = self.profiles.get(name).cloned() else {
+            if self.profiles.is_empty() {
+                anyhow::bail!("Unknown profile: {} (no profiles configured)", name);
+            }
+            anyhow::bail!(
+                "Unknown profile: {} (valid: {})",
+                name,
+                self.profiles
+                    .keys()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        };
+        if let Some(jobs) = profile.jobs {
+            self.jobs = jobs;
+        }
+        if let Some(timeout) = profile.timeout {
+            self.timeouts.default = timeout;
+        }
+        if let Some(debug) = profile.debug {
+            self.debug = debug;
+        }
+        if let Some(brew_sudo) = profile.brew_sudo {
+            self.brew_sudo = brew_sudo;
+        }
+        if let Some(schedule) = profile.schedule {
+            self.apply_schedule(schedule)?;
+        }
+        Ok(profile.actions)
+    }
+
     pub fn crontab_timing(&self) -> String {
         format!(
             "{} {} {} {} {}",
@@ -704,12 +1701,21 @@ impl Config {
     }
 
     pub fn crontab_entry(&self, app_path: &std::path::Path) -> String {
+        self.crontab_entry_for(app_path, None)
+    }
+
+    /// Crontab line running `profile`, or the default actions when `None`
+    pub fn crontab_entry_for(&self, app_path: &std::path::Path, profile: Option<&str>) -> String {
         let path_env = self.get_scheduler_path();
+        let profile_arg = profile
+            .map(|name| format!(" --profile {}", name))
+            .unwrap_or_default();
         format!(
-            "{} PATH={} {} --logfile-only 2>&1",
+            "{} PATH={} {}{} --logfile-only 2>&1",
             self.crontab_timing(),
             path_env,
-            app_path.display()
+            app_path.display(),
+            profile_arg
         )
     }
 
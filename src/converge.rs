@@ -0,0 +1,180 @@
+use anyhow::Result;
+
+use crate::config::Config;
+use crate::insights::Insights;
+use crate::logger::Logger;
+use crate::plugins::{InstalledPackage, PluginRegistry};
+use crate::restore_plan::{PlanReport, PlanStatus, render_plan};
+use crate::runner::{ActionResult, ActionStatus, record_action};
+
+/// What converging one plugin to its save file changes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConvergeStep {
+    pub plugin: String,
+    /// Saved packages that are missing, and with pinned restores those
+    /// installed at another version
+    pub install: Vec<InstalledPackage>,
+    /// Installed packages the save file doesn't list
+    pub remove: Vec<InstalledPackage>,
+}
+
+impl ConvergeStep {
+    pub fn is_empty(&self) -> bool {
+        self.install.is_empty() && self.remove.is_empty()
+    }
+}
+
+/// The steps that make each planned plugin match its save file. Plugins
+/// already matching it are left out.
+pub fn converge_steps(reports: &[PlanReport], pinned: bool) -> Vec<ConvergeStep> {
+    reports
+        .iter()
+        .map(|report| {
+            let find = |packages: &[InstalledPackage], name: &str| {
+                packages.iter().find(|p| p.name == name).cloned()
+            };
+            let mut step = ConvergeStep {
+                plugin: report.plugin.clone(),
+                install: Vec::new(),
+                remove: Vec::new(),
+            };
+            for entry in &report.entries {
+                match entry.status {
+                    PlanStatus::Missing => step.install.extend(find(&report.saved, &entry.name)),
+                    PlanStatus::Mismatch if pinned => {
+                        step.install.extend(find(&report.saved, &entry.name))
+                    }
+                    PlanStatus::Extra => step.remove.extend(find(&report.installed, &entry.name)),
+                    PlanStatus::Mismatch => {}
+                }
+            }
+            step
+        })
+        .filter(|step| !step.is_empty())
+        .collect()
+}
+
+/// One line per plugin: `npm: install prettier 3.2.0; remove left-pad 1.3.0`
+pub fn render_steps(steps: &[ConvergeStep]) -> Vec<String> {
+    let names = |packages: &[InstalledPackage]| {
+        packages
+            .iter()
+            .map(|p| {
+                if p.version.is_empty() {
+                    p.name.clone()
+                } else {
+                    format!("{} {}", p.name, p.version)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    steps
+        .iter()
+        .map(|step| {
+            let mut parts = Vec::new();
+            if !step.install.is_empty() {
+                parts.push(format!("install {}", names(&step.install)));
+            }
+            if !step.remove.is_empty() {
+                parts.push(format!("remove {}", names(&step.remove)));
+            }
+            format!("{}: {}", step.plugin, parts.join("; "))
+        })
+        .collect()
+}
+
+/// Plan `updatehauler converge` from the restore plan `reports`: print the
+/// plan and log the steps to take. Removals are dropped unless `prune` is
+/// set or the user confirms them at the prompt. Empty when there is nothing
+/// to converge.
+pub fn plan_converge(
+    config: &Config,
+    logger: &mut Logger,
+    reports: &[PlanReport],
+    prune: bool,
+) -> Result<Vec<ConvergeStep>> {
+    print!("{}", render_plan(reports));
+    if reports.iter().any(|r| r.error.is_some()) {
+        anyhow::bail!("Not every plugin could be compared with its save file; nothing was changed");
+    }
+
+    let mut steps = converge_steps(reports, config.restore_pinned);
+    let removals: usize = steps.iter().map(|s| s.remove.len()).sum();
+    if removals > 0 && !prune {
+        let confirmed = if config.dry_run || !atty::is(atty::Stream::Stdin) {
+            false
+        } else {
+            logger.log(&format!(
+                "Remove {} packages the save files don't list? [y/N] ",
+                removals
+            ));
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            matches!(input.trim().to_lowercase().as_str(), "y" | "yes")
+        };
+        if !confirmed {
+            logger.log(&format!(
+                "Keeping {} packages the save files don't list; pass --prune to remove them",
+                removals
+            ));
+            for step in &mut steps {
+                step.remove.clear();
+            }
+            steps.retain(|s| !s.is_empty());
+        }
+    }
+    if steps.is_empty() {
+        logger.log("Nothing to converge");
+    }
+    for line in render_steps(&steps) {
+        logger.log(&line);
+    }
+    Ok(steps)
+}
+
+/// Install and then remove the packages of each step, one plugin at a time.
+/// Each plugin is reported as a `<plugin>-converge` action.
+pub async fn apply_steps(
+    registry: &PluginRegistry<'_>,
+    config: &Config,
+    insights: &Insights,
+    logger: &Logger,
+    steps: &[ConvergeStep],
+) -> Vec<ActionResult> {
+    let mut results = Vec::new();
+    for step in steps {
+        let action = format!("{}-converge", step.plugin);
+        if crate::plugins::cancellation().is_some() {
+            results.push(ActionResult::new(&action, ActionStatus::Cancelled));
+            continue;
+        }
+        let mut logger = logger.with_prefix(&step.plugin);
+        let result = record_action(&step.plugin, &action, async {
+            let plugin = registry
+                .get_plugin(&step.plugin)
+                .ok_or_else(|| anyhow::anyhow!("Unknown plugin: {}", step.plugin))?;
+            if !step.install.is_empty()
+                && !plugin
+                    .install_packages(config, insights, &mut logger, &step.install)
+                    .await?
+            {
+                anyhow::bail!("{} cannot install single packages", step.plugin);
+            }
+            if !step.remove.is_empty()
+                && !plugin
+                    .remove_packages(config, insights, &mut logger, &step.remove)
+                    .await?
+            {
+                anyhow::bail!("{} cannot remove single packages", step.plugin);
+            }
+            Ok(())
+        })
+        .await;
+        if let Some(ref e) = result.error {
+            logger.error(e);
+        }
+        results.push(result);
+    }
+    results
+}
@@ -0,0 +1,395 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::inventory::render_changes;
+use crate::report::{ActionReport, RunReport, format_table};
+
+/// One finished run as stored in the history file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Start time as `YYYYMMDD-HHMMSS`, with `-2`, `-3`, ... appended when
+    /// other recorded runs started in the same second
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Command line of the run
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_file: Option<String>,
+    /// Contents of the config file when the run started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<String>,
+    #[serde(flatten)]
+    pub report: RunReport,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        report: RunReport,
+        profile: Option<String>,
+        args: Vec<String>,
+        config_file: Option<&Path>,
+    ) -> Self {
+        let id = DateTime::parse_from_rfc3339(&report.started_at)
+            .map(|t| t.format("%Y%m%d-%H%M%S").to_string())
+            .unwrap_or_else(|_| report.started_at.clone());
+        let config_file = config_file.filter(|p| p.is_file());
+        Self {
+            id,
+            profile,
+            args,
+            config_file: config_file.map(|p| p.display().to_string()),
+            config: config_file.and_then(|p| std::fs::read_to_string(p).ok()),
+            report,
+        }
+    }
+
+    fn started(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(&self.report.started_at).ok()
+    }
+
+    /// Actions of `plugin` in this run, without its hooks
+    pub fn plugin_actions<'a>(&'a self, plugin: &'a str) -> impl Iterator<Item = &'a ActionReport> {
+        self.report
+            .actions
+            .iter()
+            .filter(move |a| a.plugin.as_deref() == Some(plugin) && a.action_type != "hook")
+    }
+}
+
+/// Which runs `updatehauler history` lists
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+    /// Only runs that included this plugin
+    pub plugin: Option<String>,
+    /// Only runs that failed, or in which `plugin` failed
+    pub failed: bool,
+    pub since: Option<DateTime<FixedOffset>>,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        if let Some(since) = self.since
+            && entry.started().is_none_or(|started| started < since)
+        {
+            return false;
+        }
+        match self.plugin {
+            Some(ref plugin) => {
+                let mut actions = entry.plugin_actions(plugin).peekable();
+                actions.peek().is_some() && (!self.failed || actions.any(|a| a.status != "OK"))
+            }
+            None => !self.failed || entry.report.status != "OK",
+        }
+    }
+}
+
+/// Parse a `--since` value: `YYYY-MM-DD`, `YYYY-MM-DD HH:MM`, an RFC 3339
+/// timestamp, or an age such as `12h`, `7d` or `2w` counted back from `now`
+pub fn parse_since(value: &str, now: DateTime<Local>) -> Result<DateTime<FixedOffset>> {
+    let value = value.trim();
+    if let Some(unit) = value.chars().last()
+        && let Ok(count) = value[..value.len() - unit.len_utf8()].parse::<i64>()
+    {
+        let age = match unit {
+            'h' => Some(chrono::Duration::hours(count)),
+            'd' => Some(chrono::Duration::days(count)),
+            'w' => Some(chrono::Duration::weeks(count)),
+            _ => None,
+        };
+        if let Some(age) = age {
+            return Ok((now - age).fixed_offset());
+        }
+    }
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Ok(t);
+    }
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").or_else(|_| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map(|d| d.and_hms_opt(0, 0, 0).unwrap_or_default())
+    });
+    if let Ok(naive) = naive
+        && let Some(t) = Local.from_local_datetime(&naive).earliest()
+    {
+        return Ok(t.fixed_offset());
+    }
+    anyhow::bail!(
+        "invalid --since value '{}' (expected YYYY-MM-DD, YYYY-MM-DD HH:MM, RFC 3339 or an age like 12h, 7d, 2w)",
+        value
+    )
+}
+
+/// Append-only JSONL file of past runs, one [`HistoryEntry`] per line
+pub struct History {
+    path: PathBuf,
+    max_runs: usize,
+}
+
+impl History {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            path: config.state_dir.join("history.jsonl"),
+            max_runs: config.max_history_runs,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append `entry`, then drop the oldest runs beyond `max_history_runs`.
+    /// The id of `entry` gets a suffix when a recorded run already has it.
+    pub fn record(&self, entry: &mut HistoryEntry) -> Result<()> {
+        if self.max_runs == 0 {
+            return Ok(());
+        }
+        let taken: Vec<String> = self.load()?.into_iter().map(|e| e.id).collect();
+        let base = entry.id.clone();
+        let mut sequence = 1;
+        while taken.contains(&entry.id) {
+            sequence += 1;
+            entry.id = format!("{}-{}", base, sequence);
+        }
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .context(format!("Failed to create state directory: {:?}", dir))?;
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| f.write_all(line.as_bytes()))
+            .context(format!("Failed to write run history: {:?}", self.path))?;
+        self.prune()
+    }
+
+    fn prune(&self) -> Result<()> {
+        let content = std::fs::read_to_string(&self.path)?;
+        let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+        if lines.len() <= self.max_runs {
+            return Ok(());
+        }
+        let kept = lines[lines.len() - self.max_runs..].join("\n") + "\n";
+        let tmp = self.path.with_extension("jsonl.tmp");
+        std::fs::write(&tmp, kept)?;
+        std::fs::rename(&tmp, &self.path)
+            .context(format!("Failed to prune run history: {:?}", self.path))
+    }
+
+    /// Every recorded run, oldest first. Lines that cannot be parsed (such
+    /// as one cut short by a crash) are skipped.
+    pub fn load(&self) -> Result<Vec<HistoryEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = std::fs::read_to_string(&self.path)
+            .context(format!("Failed to read run history: {:?}", self.path))?;
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// The run with this id, or the only run whose id starts with it
+    pub fn find(&self, id: &str) -> Result<HistoryEntry> {
+        let mut entries = self.load()?;
+        if let Some(pos) = entries.iter().rposition(|e| e.id == id) {
+            return Ok(entries.swap_remove(pos));
+        }
+        let mut matches: Vec<HistoryEntry> = entries
+            .into_iter()
+            .filter(|e| e.id.starts_with(id))
+            .collect();
+        match matches.len() {
+            1 => Ok(matches.remove(0)),
+            0 => anyhow::bail!("No run with id '{}' in the history", id),
+            n => anyhow::bail!("Run id '{}' is ambiguous ({} runs match)", id, n),
+        }
+    }
+
+    /// `updatehauler history show`: print the run with this id
+    pub fn show(&self, id: &str, json: bool) -> Result<()> {
+        let entry = self.find(id)?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&entry)?);
+        } else {
+            print!("{}", render_entry(&entry));
+        }
+        Ok(())
+    }
+
+    /// `updatehauler history`: print the runs `filter` matches, newest
+    /// first, at most `limit` of them (0 = all)
+    pub fn list(&self, filter: &HistoryFilter, limit: usize, json: bool) -> Result<()> {
+        let entries = self.load()?;
+        let mut matching: Vec<&HistoryEntry> =
+            entries.iter().rev().filter(|e| filter.matches(e)).collect();
+        if limit > 0 {
+            matching.truncate(limit);
+        }
+        if json {
+            println!("{}", serde_json::to_string_pretty(&matching)?);
+        } else {
+            print!("{}", render_list(&matching, filter.plugin.as_deref()));
+        }
+        Ok(())
+    }
+}
+
+fn format_secs(secs: f64) -> String {
+    let whole = secs.round() as u64;
+    if secs < 60.0 {
+        format!("{:.1}s", secs)
+    } else if whole < 3600 {
+        format!("{}m {:02}s", whole / 60, whole % 60)
+    } else {
+        format!("{}h {:02}m", whole / 3600, whole % 3600 / 60)
+    }
+}
+
+fn format_time(timestamp: &str) -> String {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+/// One line per run; with `plugin`, the actions column shows that plugin's
+/// results instead of the run's totals
+pub fn render_list(entries: &[&HistoryEntry], plugin: Option<&str>) -> String {
+    if entries.is_empty() {
+        return "No matching runs in the history\n".to_string();
+    }
+    let rows: Vec<[String; 5]> = entries
+        .iter()
+        .map(|e| {
+            let mut status = e.report.status.clone();
+            if e.report.dry_run {
+                status.push_str(" (dry run)");
+            }
+            let actions = match plugin {
+                Some(plugin) => e
+                    .plugin_actions(plugin)
+                    .map(|a| format!("{} {}", a.action, a.status))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                None => format!("{} ok, {} failed", e.report.succeeded, e.report.failed),
+            };
+            [
+                e.id.clone(),
+                format_time(&e.report.started_at),
+                format_secs(e.report.duration_secs),
+                status,
+                actions,
+            ]
+        })
+        .collect();
+    let rows: Vec<[&str; 5]> = rows
+        .iter()
+        .map(|r| r.each_ref().map(String::as_str))
+        .collect();
+    format_table(["Run", "Started", "Duration", "Status", "Actions"], &rows)
+}
+
+/// Details of one run: its settings, then every action with the commands
+/// it ran
+pub fn render_entry(entry: &HistoryEntry) -> String {
+    let report = &entry.report;
+    let host = &report.host;
+    let mut out = format!("Run {}\n", entry.id);
+    let mut field = |name: &str, value: &str| out.push_str(&format!("  {:<10}{}\n", name, value));
+    field("Started:", &format_time(&report.started_at));
+    field("Finished:", &format_time(&report.finished_at));
+    field("Duration:", &format_secs(report.duration_secs));
+    field(
+        "Status:",
+        &format!(
+            "{}{} ({} succeeded, {} failed)",
+            report.status,
+            if report.dry_run { " (dry run)" } else { "" },
+            report.succeeded,
+            report.failed
+        ),
+    );
+    field(
+        "Host:",
+        &format!(
+            "{} ({} {}{})",
+            host.hostname,
+            host.os,
+            host.arch,
+            host.pkg_mgr
+                .as_deref()
+                .map(|p| format!(", {}", p))
+                .unwrap_or_default()
+        ),
+    );
+    field("Version:", &report.version);
+    if let Some(ref profile) = entry.profile {
+        field("Profile:", profile);
+    }
+    field("Command:", &shell_words::join(&entry.args));
+    if let Some(ref config_file) = entry.config_file {
+        field("Config:", config_file);
+    }
+
+    out.push('\n');
+    let rows: Vec<[String; 4]> = report
+        .actions
+        .iter()
+        .map(|a| {
+            [
+                a.action.clone(),
+                a.action_type.clone(),
+                a.status.clone(),
+                format_secs(a.duration_secs),
+            ]
+        })
+        .collect();
+    let rows: Vec<[&str; 4]> = rows
+        .iter()
+        .map(|r| r.each_ref().map(String::as_str))
+        .collect();
+    out.push_str(&format_table(
+        ["Action", "Type", "Status", "Duration"],
+        &rows,
+    ));
+
+    if !report.changes.is_empty() {
+        out.push_str("\nPackage changes:\n");
+        for line in render_changes(&report.changes) {
+            out.push_str(&format!("  {}\n", line));
+        }
+    }
+
+    for action in report
+        .actions
+        .iter()
+        .filter(|a| !a.commands.is_empty() || a.error.is_some())
+    {
+        out.push_str(&format!("\n{}:\n", action.action));
+        for cmd in &action.commands {
+            let exit = match (cmd.dry_run, cmd.interrupted.as_deref(), cmd.exit_code) {
+                (true, _, _) => "dry run".to_string(),
+                (_, Some(reason), _) => reason.to_string(),
+                (_, None, Some(code)) => format!("exit {}", code),
+                (_, None, None) => "no exit code".to_string(),
+            };
+            out.push_str(&format!(
+                "  $ {}  ({}, {})\n",
+                cmd.command,
+                exit,
+                format_secs(cmd.duration_secs)
+            ));
+        }
+        if let Some(ref error) = action.error {
+            out.push_str(&format!("  error: {}\n", error));
+        }
+    }
+    out
+}
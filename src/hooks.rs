@@ -0,0 +1,141 @@
+use crate::config::Config;
+use crate::logger::Logger;
+use crate::plugins::{CommandIo, ErrorPolicy, record_commands, run_cmd_io};
+use crate::runner::{ActionResult, ActionStatus};
+use std::time::Instant;
+
+/// What a hook runs for, exported to its commands as `UPDATEHAULER_*`
+/// environment variables
+pub struct HookEvent<'a> {
+    /// Hook name as written in the config, e.g. `pre_os` or `post_run`
+    pub hook: String,
+    pub plugin: Option<&'a str>,
+    pub actions: &'a [String],
+    /// Outcome of what the hook follows; `None` for pre-hooks
+    pub status: Option<ActionStatus>,
+    /// Actions that did not succeed
+    pub failed: Vec<String>,
+}
+
+impl<'a> HookEvent<'a> {
+    /// Event for a hook running before `actions`
+    pub fn before(hook: String, plugin: Option<&'a str>, actions: &'a [String]) -> Self {
+        Self {
+            hook,
+            plugin,
+            actions,
+            status: None,
+            failed: Vec::new(),
+        }
+    }
+
+    /// Event for a hook running once `results` are known
+    pub fn after(
+        hook: String,
+        plugin: Option<&'a str>,
+        actions: &'a [String],
+        results: &[ActionResult],
+    ) -> Self {
+        Self {
+            hook,
+            plugin,
+            actions,
+            status: Some(overall_status(results.iter().map(|r| r.status))),
+            failed: results
+                .iter()
+                .filter(|r| !r.ok())
+                .map(|r| r.action.clone())
+                .collect(),
+        }
+    }
+
+    fn env(&self) -> Vec<(String, String)> {
+        let mut env = vec![
+            ("UPDATEHAULER_HOOK".to_string(), self.hook.clone()),
+            ("UPDATEHAULER_ACTIONS".to_string(), self.actions.join(" ")),
+        ];
+        if let Some(plugin) = self.plugin {
+            env.push(("UPDATEHAULER_PLUGIN".to_string(), plugin.to_string()));
+        }
+        if let Some(status) = self.status {
+            env.push((
+                "UPDATEHAULER_STATUS".to_string(),
+                status.as_str().to_string(),
+            ));
+            env.push(("UPDATEHAULER_FAILED".to_string(), self.failed.join(" ")));
+        }
+        env
+    }
+}
+
+/// The first status that is not OK, or OK when everything succeeded
+pub fn overall_status(statuses: impl IntoIterator<Item = ActionStatus>) -> ActionStatus {
+    statuses
+        .into_iter()
+        .find(|s| !s.is_ok())
+        .unwrap_or(ActionStatus::Ok)
+}
+
+/// Run the commands of a hook with `sh -c`, stopping at the first one that
+/// fails. Returns `None` when no commands are configured for the hook.
+pub async fn run_hook(
+    config: &Config,
+    logger: &mut Logger,
+    event: &HookEvent<'_>,
+    commands: &[String],
+) -> Option<ActionResult> {
+    if commands.is_empty() {
+        return None;
+    }
+    let env = event.env();
+    let io = CommandIo {
+        env: &env,
+        ..CommandIo::default()
+    };
+
+    let started = Instant::now();
+    let mut result = ActionResult::new(&event.hook, ActionStatus::Ok);
+    let (error, recorded) = record_commands(async {
+        for command in commands {
+            if let Err(e) = run_cmd_io(
+                config,
+                logger,
+                ErrorPolicy::Fail,
+                "sh",
+                &["-c", command],
+                io,
+            )
+            .await
+            {
+                return Some(e);
+            }
+        }
+        None
+    })
+    .await;
+    if let Some(e) = error {
+        logger.error(&format!("Hook {} failed: {}", event.hook, e));
+        result.status = ActionStatus::from_error(&e);
+        result.error = Some(e.to_string());
+    }
+    result.duration = started.elapsed();
+    result.commands = recorded;
+    Some(result)
+}
+
+/// Run `on_failure` (only when an action failed) and then `post_run`
+pub async fn run_finish_hooks(
+    config: &Config,
+    logger: &mut Logger,
+    actions: &[String],
+    results: &[ActionResult],
+) -> Vec<ActionResult> {
+    let mut hook_results = Vec::new();
+    if results.iter().any(|r| !r.ok()) {
+        let event = HookEvent::after("on_failure".to_string(), None, actions, results);
+        hook_results.extend(run_hook(config, logger, &event, &config.hooks.on_failure).await);
+    }
+    let event = HookEvent::after("post_run".to_string(), None, actions, results);
+    hook_results.extend(run_hook(config, logger, &event, &config.hooks.post_run).await);
+    hook_results
+}
@@ -5,12 +5,15 @@ use which::which;
 
 #[derive(Clone)]
 pub struct Insights {
+    pub hostname: String,
     pub is_root: bool,
     pub arch: String,
     pub os: String,
     pub is_linux: bool,
     pub is_darwin: bool,
     pub pkg_mgr: Option<String>,
+    /// `ID=` of /etc/os-release on Linux, such as `ubuntu` or `fedora`
+    pub distro: Option<String>,
     pub has_brew: bool,
     pub has_cargo: bool,
     pub has_npm: bool,
@@ -33,6 +36,9 @@ pub struct Insights {
 impl Insights {
     pub fn new() -> Result<Self> {
         let is_root = nix::unistd::Uid::effective().is_root();
+        let hostname = nix::unistd::gethostname()
+            .map(|h| h.to_string_lossy().to_string())
+            .unwrap_or_default();
 
         let arch_str = std::env::consts::ARCH.to_string();
 
@@ -41,9 +47,17 @@ impl Insights {
         let is_darwin = os == "macos";
 
         let mut pkg_mgr: Option<String> = None;
+        let mut distro: Option<String> = None;
 
         if is_linux {
             let linux_full_id = Self::get_linux_id()?;
+            distro = fs::read_to_string("/etc/os-release")
+                .ok()
+                .and_then(|content| {
+                    content
+                        .lines()
+                        .find_map(|line| Self::parse_release_line(line, "ID="))
+                });
 
             pkg_mgr = match linux_full_id.as_str() {
                 id if id.contains("debian") || id.contains("ubuntu") => Some("apt-get".to_string()),
@@ -94,12 +108,14 @@ impl Insights {
             .is_some_and(|d| app_abspath.starts_with(d));
 
         Ok(Self {
+            hostname,
             is_root,
             arch: arch_str,
             os,
             is_linux,
             is_darwin,
             pkg_mgr,
+            distro,
             has_brew,
             has_cargo,
             has_npm,
@@ -0,0 +1,376 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::config::Config;
+use crate::insights::Insights;
+use crate::logger::Logger;
+use crate::plugins::{InstalledPackage, PluginActionType, PluginRegistry, scope_plugin};
+use crate::report::format_table;
+
+/// Installed packages keyed by plugin name
+pub type Inventory = BTreeMap<String, Vec<InstalledPackage>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Upgraded,
+    Downgraded,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Upgraded => "upgraded",
+            Self::Downgraded => "downgraded",
+        }
+    }
+}
+
+/// A package that was added, removed or changed version during a run
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageChange {
+    pub plugin: String,
+    pub name: String,
+    pub source: String,
+    pub kind: ChangeKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+}
+
+/// Plugins whose packages `actions` may change: those updated or restored
+pub fn plugins_to_track(registry: &PluginRegistry, actions: &[String]) -> Vec<String> {
+    let mut plugins: Vec<String> = Vec::new();
+    for action in actions {
+        let Some(plugin) = registry.plugin_name_for_action(action) else {
+            continue;
+        };
+        let changes_packages = plugin == action
+            || matches!(
+                registry
+                    .get_action_by_name(action)
+                    .and_then(|a| a.action_type),
+                Some(PluginActionType::Update | PluginActionType::Restore)
+            );
+        if changes_packages && !plugins.iter().any(|p| p == plugin) {
+            plugins.push(plugin.to_string());
+        }
+    }
+    plugins
+}
+
+/// Installed packages of one plugin, or why they could not be listed
+#[derive(Debug, Serialize)]
+pub struct InventoryReport {
+    pub plugin: String,
+    pub packages: Vec<InstalledPackage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Ask each of `plugins` for its installed packages, up to `config.jobs` at
+/// a time, in the order given. Plugins that cannot list their packages are
+/// left out; failures are logged as warnings and kept in the report.
+pub async fn list_installed(
+    registry: Arc<PluginRegistry<'static>>,
+    config: Arc<Config>,
+    insights: Arc<Insights>,
+    logger: &Logger,
+    plugins: &[String],
+) -> Vec<InventoryReport> {
+    let semaphore = Arc::new(Semaphore::new(config.jobs.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for (i, name) in plugins.iter().enumerate() {
+        let registry = Arc::clone(&registry);
+        let config = Arc::clone(&config);
+        let insights = Arc::clone(&insights);
+        let semaphore = Arc::clone(&semaphore);
+        let mut logger = logger.with_prefix(name);
+        let name = name.clone();
+
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let plugin = registry.get_plugin(&name)?;
+            let result = scope_plugin(
+                &name,
+                plugin.list_installed(&config, &insights, &mut logger),
+            )
+            .await;
+            let report = match result {
+                Ok(None) => return None,
+                Ok(Some(packages)) => InventoryReport {
+                    plugin: name,
+                    packages,
+                    error: None,
+                },
+                Err(e) => {
+                    logger.warn(&format!("Failed to list installed packages: {:#}", e));
+                    InventoryReport {
+                        plugin: name,
+                        packages: Vec::new(),
+                        error: Some(format!("{:#}", e)),
+                    }
+                }
+            };
+            Some((i, report))
+        });
+    }
+
+    let mut reports: Vec<(usize, InventoryReport)> = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok(Some(report)) = joined {
+            reports.push(report);
+        }
+    }
+    reports.sort_by_key(|(i, _)| *i);
+    reports.into_iter().map(|(_, report)| report).collect()
+}
+
+/// [`list_installed`] keyed by plugin, without the plugins that failed
+pub async fn snapshot(
+    registry: Arc<PluginRegistry<'static>>,
+    config: Arc<Config>,
+    insights: Arc<Insights>,
+    logger: &Logger,
+    plugins: &[String],
+) -> Inventory {
+    list_installed(registry, config, insights, logger, plugins)
+        .await
+        .into_iter()
+        .filter(|r| r.error.is_none())
+        .map(|r| (r.plugin, r.packages))
+        .collect()
+}
+
+/// Order two version strings by comparing runs of digits numerically and
+/// everything else as text, so `1.10` sorts after `1.9`. A pre-release
+/// suffix (`~...`, or `-` followed by a letter as in `1.0.0-rc1`) sorts
+/// below the same version without one.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a_release, a_pre) = split_pre_release(a);
+    let (b_release, b_pre) = split_pre_release(b);
+    compare_chunks(a_release, b_release).then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => compare_chunks(a, b),
+    })
+}
+
+/// Split `version` into its release and pre-release parts. `-` followed by
+/// a digit is a packaging revision (`7.81.0-1ubuntu1`, `6.8.0-45-generic`)
+/// and stays part of the release.
+fn split_pre_release(version: &str) -> (&str, Option<&str>) {
+    let bytes = version.as_bytes();
+    let start = (0..bytes.len()).find(|&i| {
+        bytes[i] == b'~'
+            || (bytes[i] == b'-' && bytes.get(i + 1).is_some_and(u8::is_ascii_alphabetic))
+    });
+    match start {
+        Some(i) => (&version[..i], Some(&version[i + 1..])),
+        None => (version, None),
+    }
+}
+
+fn compare_chunks(a: &str, b: &str) -> Ordering {
+    fn chunks(v: &str) -> Vec<&str> {
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        let bytes = v.as_bytes();
+        for i in 1..=bytes.len() {
+            if i == bytes.len() || bytes[i].is_ascii_digit() != bytes[start].is_ascii_digit() {
+                chunks.push(&v[start..i]);
+                start = i;
+            }
+        }
+        chunks
+    }
+    for (x, y) in chunks(a).into_iter().zip(chunks(b)) {
+        let order = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Packages added, removed, upgraded or downgraded between two snapshots.
+/// Only plugins present in both are compared.
+pub fn diff(before: &Inventory, after: &Inventory) -> Vec<PackageChange> {
+    let mut changes = Vec::new();
+    for (plugin, after_packages) in after {
+        let Some(before_packages) = before.get(plugin) else {
+            continue;
+        };
+        let index = |packages: &[InstalledPackage]| -> BTreeMap<(String, String), String> {
+            packages
+                .iter()
+                .map(|p| ((p.name.clone(), p.source.clone()), p.version.clone()))
+                .collect()
+        };
+        let old = index(before_packages);
+        let new = index(after_packages);
+
+        let names: std::collections::BTreeSet<&(String, String)> =
+            old.keys().chain(new.keys()).collect();
+        for key in names {
+            let (kind, from, to) = match (old.get(key), new.get(key)) {
+                (None, Some(to)) => (ChangeKind::Added, None, Some(to)),
+                (Some(from), None) => (ChangeKind::Removed, Some(from), None),
+                (Some(from), Some(to)) if from != to => {
+                    let kind = if compare_versions(from, to) == Ordering::Greater {
+                        ChangeKind::Downgraded
+                    } else {
+                        ChangeKind::Upgraded
+                    };
+                    (kind, Some(from), Some(to))
+                }
+                _ => continue,
+            };
+            changes.push(PackageChange {
+                plugin: plugin.clone(),
+                name: key.0.clone(),
+                source: key.1.clone(),
+                kind,
+                from: from.cloned(),
+                to: to.cloned(),
+            });
+        }
+    }
+    changes
+}
+
+/// One line per plugin: `brew: upgraded git 2.43 → 2.44, added jq 1.7, removed wget`
+pub fn render_changes(changes: &[PackageChange]) -> Vec<String> {
+    let mut by_plugin: Vec<(&str, Vec<String>)> = Vec::new();
+    for change in changes {
+        let text = match (change.kind, &change.from, &change.to) {
+            (ChangeKind::Added, _, Some(to)) => format!("added {} {}", change.name, to),
+            (ChangeKind::Removed, _, _) => format!("removed {}", change.name),
+            (kind, Some(from), Some(to)) => {
+                format!("{} {} {} → {}", kind.as_str(), change.name, from, to)
+            }
+            (kind, _, _) => format!("{} {}", kind.as_str(), change.name),
+        };
+        match by_plugin.last_mut() {
+            Some((plugin, items)) if *plugin == change.plugin => items.push(text),
+            _ => by_plugin.push((&change.plugin, vec![text])),
+        }
+    }
+    by_plugin
+        .into_iter()
+        .map(|(plugin, items)| format!("{}: {}", plugin, items.join(", ")))
+        .collect()
+}
+
+/// Output of `updatehauler inventory`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InventoryFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl FromStr for InventoryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            other => Err(format!(
+                "unknown inventory format '{}' (expected table, json or csv)",
+                other
+            )),
+        }
+    }
+}
+
+/// Every package of `reports`, sorted by manager and name
+pub fn all_packages(reports: &[InventoryReport]) -> Vec<&InstalledPackage> {
+    let mut packages: Vec<&InstalledPackage> = reports.iter().flat_map(|r| &r.packages).collect();
+    packages.sort_by(|a, b| (&a.manager, &a.name).cmp(&(&b.manager, &b.name)));
+    packages
+}
+
+/// Render the packages of `reports` as one list. The table also names the
+/// plugins whose listing failed; JSON and CSV hold only packages.
+pub fn render_inventory(
+    reports: &[InventoryReport],
+    format: InventoryFormat,
+) -> serde_json::Result<String> {
+    let packages = all_packages(reports);
+    match format {
+        InventoryFormat::Json => serde_json::to_string_pretty(&packages).map(|s| s + "\n"),
+        InventoryFormat::Csv => {
+            let mut out = "manager,name,version,scope,source,path\n".to_string();
+            for p in packages {
+                let fields = [
+                    p.manager.as_str(),
+                    &p.name,
+                    &p.version,
+                    p.scope.as_str(),
+                    &p.source,
+                    p.path.as_deref().unwrap_or(""),
+                ];
+                let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+                out.push_str(&fields.join(","));
+                out.push('\n');
+            }
+            Ok(out)
+        }
+        InventoryFormat::Table => {
+            let rows: Vec<[&str; 6]> = packages
+                .iter()
+                .map(|p| {
+                    [
+                        p.manager.as_str(),
+                        &p.name,
+                        &p.version,
+                        p.scope.as_str(),
+                        &p.source,
+                        p.path.as_deref().unwrap_or(""),
+                    ]
+                })
+                .collect();
+            let mut out = if rows.is_empty() {
+                "No installed packages found\n".to_string()
+            } else {
+                format_table(
+                    ["Manager", "Package", "Version", "Scope", "Source", "Path"],
+                    &rows,
+                )
+            };
+            for report in reports {
+                if let Some(ref error) = report.error {
+                    out.push_str(&format!("{}: listing failed: {}\n", report.plugin, error));
+                }
+            }
+            Ok(out)
+        }
+    }
+}
+
+/// Quote a CSV field when it holds a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -1,6 +1,22 @@
+pub mod audit;
 pub mod config;
+pub mod converge;
+pub mod history;
+pub mod hooks;
 pub mod insights;
+pub mod inventory;
 pub mod logger;
+pub mod outdated;
 pub mod plugins;
+pub mod reboot;
+pub mod report;
+pub mod restore_plan;
+pub mod rollback;
+pub mod runner;
+pub mod saves;
+pub mod sbom;
 pub mod scheduler;
 pub mod self_install;
+pub mod snapshot;
+pub mod translate;
+pub mod verify;
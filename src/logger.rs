@@ -4,6 +4,8 @@ use colored::Colorize;
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 use crate::config::Config;
 
@@ -25,9 +27,16 @@ impl LoggerConfig {
     }
 }
 
+/// Logger handle that can be cloned and shared across concurrently running plugins.
+///
+/// Clones share the same output sink, so lines written from different tasks never
+/// interleave mid-line. Use [`Logger::with_prefix`] to tag every line of a task.
+#[derive(Clone)]
 pub struct Logger {
-    config: LoggerConfig,
-    error_triggered: bool,
+    config: Arc<LoggerConfig>,
+    error_triggered: Arc<AtomicBool>,
+    output_lock: Arc<Mutex<()>>,
+    prefix: Option<String>,
 }
 
 impl Logger {
@@ -39,11 +48,25 @@ impl Logger {
             let _ = std::fs::create_dir_all(parent);
         }
         Self {
-            config: log_config,
-            error_triggered: false,
+            config: Arc::new(log_config),
+            error_triggered: Arc::new(AtomicBool::new(false)),
+            output_lock: Arc::new(Mutex::new(())),
+            prefix: None,
         }
     }
 
+    /// Create a logger sharing this logger's sink that tags every line with `[prefix]`
+    pub fn with_prefix(&self, prefix: &str) -> Self {
+        Self {
+            prefix: Some(prefix.to_string()),
+            ..self.clone()
+        }
+    }
+
+    pub fn prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
     pub fn log(&mut self, msg: &str) {
         let timestamp = if self.config.datetime {
             Local::now().format("%FT%T%.6f%:z").to_string()
@@ -51,14 +74,21 @@ impl Logger {
             String::new()
         };
 
+        let msg = match self.prefix {
+            Some(ref prefix) => format!("[{}] {}", prefix, msg),
+            None => msg.to_string(),
+        };
+
         let output = if !timestamp.is_empty() {
             format!("{} {}", timestamp, msg)
         } else {
-            msg.to_string()
+            msg
         };
 
+        let _guard = self.output_lock.lock().unwrap_or_else(|e| e.into_inner());
+
         if !self.config.use_log {
-            if self.error_triggered {
+            if self.error_triggered.load(Ordering::Relaxed) {
                 eprintln!("{}", output);
             } else {
                 println!("{}", output);
@@ -69,7 +99,7 @@ impl Logger {
     }
 
     pub fn error(&mut self, msg: &str) {
-        self.error_triggered = true;
+        self.error_triggered.store(true, Ordering::Relaxed);
 
         let colored_msg = if self.config.color {
             msg.red().to_string()
@@ -80,12 +110,23 @@ impl Logger {
         self.log(&format!("ERROR {}", colored_msg));
     }
 
+    pub fn warn(&mut self, msg: &str) {
+        let colored_msg = if self.config.color {
+            msg.yellow().to_string()
+        } else {
+            msg.to_string()
+        };
+
+        self.log(&format!("WARN {}", colored_msg));
+    }
+
     pub fn audit(&mut self, msg: &str) {
         let timestamp = Local::now().format("%FT%T%.6f%:z").to_string();
         let user = std::env::var("USER")
             .or_else(|_| std::env::var("LOGNAME"))
             .unwrap_or_else(|_| "unknown".to_string());
         let output = format!("{} [AUDIT] user={} {}", timestamp, user, msg);
+        let _guard = self.output_lock.lock().unwrap_or_else(|e| e.into_inner());
         if let Err(e) = self.write_to_log(&output) {
             eprintln!("Failed to write audit log: {}", e);
         }
@@ -7,19 +7,40 @@ use std::fs::{self, File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
 use std::process::ExitCode;
+use std::sync::Arc;
 
 use clap_complete::{Shell, generate};
-use updatehauler::config::{Config, has_path_traversal, validate_schedule_value};
+use updatehauler::audit::{AdvisoryDb, apply_upgrades, ecosystem, render_findings};
+use updatehauler::config::{Config, PreHookFailure, has_path_traversal, validate_schedule_value};
+use updatehauler::converge::{ConvergeStep, apply_steps, plan_converge};
+use updatehauler::history::{History, HistoryEntry, HistoryFilter, parse_since};
+use updatehauler::hooks::{HookEvent, run_finish_hooks, run_hook};
 use updatehauler::insights::Insights;
+use updatehauler::inventory::{
+    InventoryFormat, InventoryReport, all_packages, diff, list_installed, plugins_to_track,
+    render_changes, render_inventory, snapshot,
+};
 use updatehauler::logger::Logger;
+use updatehauler::outdated::{check_outdated, render_table};
+use updatehauler::reboot::check_reboot;
+use updatehauler::report::{OutputFormat, RunReport};
+use updatehauler::restore_plan::{plan_restore, render_plan};
+use updatehauler::rollback::{apply_rollback, plan_rollback};
+use updatehauler::runner::{ActionResult, ActionStatus, ExecutionGraph, forward_signals};
+use updatehauler::saves::{checkout_saves, open_save_repo, record_saves, saves_diff, saves_log};
+use updatehauler::sbom::{SbomFormat, SbomInfo, render_sbom};
 use updatehauler::scheduler::Scheduler;
 use updatehauler::self_install::SelfInstaller;
+use updatehauler::snapshot::{SnapshotJob, default_archive_name};
+use updatehauler::verify::{rollback_unverified, unverified_plugins};
 use updatehauler::{
     plugins::BrewPlugin, plugins::CargoPlugin, plugins::DenoPlugin, plugins::DockerPlugin,
-    plugins::FlatpakPlugin, plugins::GemPlugin, plugins::GoPlugin, plugins::NpmPlugin,
-    plugins::NvimPlugin, plugins::OsPlugin, plugins::PipPlugin, plugins::PluginRegistry,
-    plugins::RunPlugin, plugins::RustupPlugin, plugins::SnapPlugin, plugins::UvPlugin,
-    plugins::VscodePlugin, plugins::YarnPlugin, register_plugins,
+    plugins::ExternalPlugin, plugins::FlatpakPlugin, plugins::GemPlugin, plugins::GoPlugin,
+    plugins::InstalledPackage, plugins::NpmPlugin, plugins::NvimPlugin, plugins::OsPlugin,
+    plugins::PipPlugin, plugins::PluginActionType, plugins::PluginMetadata,
+    plugins::PluginRegistry, plugins::RunPlugin, plugins::RustupPlugin, plugins::SnapPlugin,
+    plugins::UvPlugin, plugins::VscodePlugin, plugins::YarnPlugin, plugins::cancellation,
+    plugins::load_external_plugins, plugins::register_custom_plugins, register_plugins,
 };
 
 fn get_help_text() -> &'static str {
@@ -33,8 +54,11 @@ fn build_help_text() -> String {
    updatehauler                                            # Run all default actions
    updatehauler brew save                                  # Update and save brew packages
    updatehauler brew save --sudo --save-file ./Brewfile    # Brew with options
+   updatehauler brew restore --translate                   # Restore a Brewfile from another OS
    updatehauler cargo save                                 # Update and save cargo packages
    updatehauler npm save                                   # Update and save npm packages
+   updatehauler npm restore --plan                         # Compare saved and installed npm packages
+   updatehauler pip restore --pinned                       # Reinstall the exact saved pip versions
    updatehauler run --cmd "echo hello"                     # Run arbitrary command
    updatehauler schedule enable                            # Enable daily updates at 2 AM
    updatehauler schedule enable --hour 3 --minute 30       # Enable at custom time
@@ -43,13 +67,46 @@ fn build_help_text() -> String {
    updatehauler config merge                               # Interactive merge config
    updatehauler --debug brew save                          # Run with debug output
    updatehauler --dry-run brew save                        # Preview changes
+   updatehauler --profile quick                            # Run the actions of the 'quick' profile
+   updatehauler schedule enable --profile weekend          # Add a separate schedule for a profile
+   updatehauler --output json                              # Print a JSON run report
+   updatehauler --jobs 1                                   # Run plugins one at a time
+   updatehauler --timeout 1800                             # Kill any command running over 30 minutes
    updatehauler --list-plugins                             # List all plugins and status
+   updatehauler outdated                                   # List available updates without upgrading
+   updatehauler outdated --json brew npm                   # Outdated brew and npm packages as JSON
+   updatehauler inventory --format csv > packages.csv      # Every installed package, all managers
+   updatehauler sbom --format spdx-json > sbom.spdx.json   # SBOM of everything the plugins manage
+   updatehauler audit --db ~/osv/all.zip                   # Installed packages with known vulnerabilities
+   updatehauler --security-only                            # Upgrade only the vulnerable packages
+   updatehauler snapshot create -o laptop.tar.gz           # Bundle every save file into one archive
+   updatehauler snapshot restore laptop.tar.gz             # Provision a new machine from the archive
+   updatehauler saves log --plugin brew                    # Versions of the Brewfile in save_repo
+   updatehauler saves checkout HEAD~5 --restore            # Restore the package set of five saves ago
+   updatehauler --dry-run converge                         # Show what converging would install and remove
+   updatehauler converge --plugin npm --prune              # Make global npm packages match the save file
+   updatehauler rollback --plugin npm                      # Reinstall the npm versions before the last upgrade
+   updatehauler history --plugin cargo --since 30d         # Recent runs that included cargo
+   updatehauler history show 20250101-020000               # Actions and commands of one run
    updatehauler install-completions bash zsh               # Install shell completions
 "#
     .to_string()
 }
 
-fn create_plugin_registry() -> PluginRegistry<'static> {
+/// Plugins compiled into the binary; everything else in the registry was
+/// declared under `custom_plugins:` or found as an `updatehauler-plugin-*`
+/// executable
+const BUILTIN_PLUGINS: &[&str] = &[
+    "brew", "cargo", "deno", "docker", "flatpak", "gem", "go", "npm", "nvim", "os", "pip", "run",
+    "rustup", "snap", "uv", "vscode", "yarn",
+];
+
+/// Build the registry from the built-in and custom plugins plus the
+/// `external` plugins already loaded by [`load_external_plugins`]
+fn create_plugin_registry(
+    config: &Config,
+    external: &[ExternalPlugin],
+) -> Result<PluginRegistry<'static>> {
     let mut registry = PluginRegistry::new();
     register_plugins!(
         registry,
@@ -71,17 +128,88 @@ fn create_plugin_registry() -> PluginRegistry<'static> {
         VscodePlugin,
         YarnPlugin,
     );
+    register_custom_plugins(&mut registry, config)?;
+    for plugin in external {
+        registry.register(Box::new(plugin.clone()));
+    }
+    Ok(registry)
+}
+
+/// Whether `command` runs or lists plugins. Discovering external plugins
+/// starts every `updatehauler-plugin-*` on PATH, so it is skipped for the
+/// subcommands that never touch plugins.
+fn uses_plugins(command: Option<&Commands>) -> bool {
+    !matches!(
+        command,
+        Some(
+            Commands::Schedule { .. }
+                | Commands::Config { .. }
+                | Commands::Install
+                | Commands::Update
+                | Commands::Remove
+                | Commands::TrimLogfile
+                | Commands::History { .. }
+                | Commands::Saves {
+                    action: SavesAction::Log { .. } | SavesAction::Diff { .. }
+                }
+        )
+    )
+}
+
+/// Custom and external plugins in the registry
+fn extension_plugins<'r>(registry: &'r PluginRegistry) -> Vec<&'r PluginMetadata> {
     registry
+        .get_all_metadata()
+        .iter()
+        .filter(|m| !BUILTIN_PLUGINS.contains(&m.name.as_str()))
+        .collect()
+}
+
+/// CLI actions of a custom or external plugin with their descriptions, for
+/// shell completions
+fn custom_plugin_cli_actions(plugin: &PluginMetadata) -> Vec<(String, String)> {
+    let prefix = format!("{}-", plugin.name);
+    plugin
+        .actions
+        .iter()
+        .map(|action| {
+            let cli = if action.name == plugin.name {
+                "update"
+            } else {
+                action.name.strip_prefix(&prefix).unwrap_or(&action.name)
+            };
+            (cli.to_string(), action.description.clone())
+        })
+        .collect()
 }
 
-fn generate_custom_bash_completion(config: &Config) -> String {
+fn zsh_quote(s: &str) -> String {
+    s.replace('\'', "'\\''").replace(':', "\\:")
+}
+
+fn generate_custom_bash_completion(config: &Config, extensions: &[&PluginMetadata]) -> String {
+    let custom_names: String = extensions.iter().map(|p| format!(" {}", p.name)).collect();
+    let custom_cases: String = extensions
+        .iter()
+        .map(|p| {
+            let actions: Vec<String> = custom_plugin_cli_actions(p)
+                .into_iter()
+                .map(|(a, _)| a)
+                .collect();
+            format!(
+                "                {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;\n",
+                p.name,
+                actions.join(" ")
+            )
+        })
+        .collect();
     format!(
         r#"#!/usr/bin/env bash
 # Bash completion for {app_name}
 
 _{app_name}() {{
     local cur prev words cword
-    local plugins="brew cargo deno docker flatpak gem go npm nvim os pip run rustup snap uv vscode yarn"
+    local plugins="brew cargo deno docker flatpak gem go npm nvim os pip run rustup snap uv vscode yarn{custom_names}"
     local plugin_actions_brew="update save restore list outdated upgrade-pinned"
     local plugin_actions_cargo="update save restore list outdated"
     local plugin_actions_npm="update save restore"
@@ -91,17 +219,23 @@ _{app_name}() {{
     local plugin_actions_gem="update save restore"
     local plugin_actions_go="update save restore"
     local plugin_actions_yarn="update save restore"
+    local plugin_actions_flatpak="update save restore"
+    local plugin_actions_snap="update save restore"
+    local plugin_actions_vscode="update save restore"
     local plugin_actions_default="update"
-    local global_commands="schedule config install update remove install-completions trim-logfile"
+    local global_commands="schedule config install update remove install-completions trim-logfile outdated inventory sbom audit snapshot saves converge rollback history"
     local schedule_actions="enable disable check"
     local config_actions="init compare merge"
+    local snapshot_actions="create restore"
+    local saves_actions="log diff checkout"
+    local history_actions="show"
     local shell_types="bash zsh fish powershell elvish"
-    local global_flags="--debug --no-debug --datetime --no-datetime --header --no-header --color --no-color --logfile-only --dry-run --no-sudo --confirm-run --notify --logfile --max-log-lines --installdir --completionsdir --config-file --list-plugins --only --enable-plugin --disable-plugin --help --version"
-    local brew_flags="--save-file --sudo --info --search"
+    local global_flags="--debug --no-debug --datetime --no-datetime --header --no-header --color --no-color --logfile-only --dry-run --security-only --no-sudo --confirm-run --notify --logfile --max-log-lines --installdir --completionsdir --config-file --jobs --timeout --list-plugins --only --profile --output --enable-plugin --disable-plugin --help --version"
+    local brew_flags="--save-file --sudo --info --search --translate"
     local cargo_flags="--save-file"
-    local npm_flags="--save-file"
-    local pip_flags="--save-file"
-    local uv_flags="--save-file"
+    local npm_flags="--save-file --pinned --plan"
+    local pip_flags="--save-file --pinned --plan"
+    local uv_flags="--save-file --pinned --plan"
     local run_flags="--cmd"
 
     cur="${{COMP_WORDS[COMP_CWORD]}}"
@@ -123,7 +257,25 @@ _{app_name}() {{
         elif [[ $prev == "run" ]]; then
             COMPREPLY=($(compgen -W "$global_flags $run_flags" -- "$cur"))
         elif [[ $prev == "schedule" ]]; then
-            COMPREPLY=($(compgen -W "--hour --minute --day-of-month --month --day-of-week" -- "$cur"))
+            COMPREPLY=($(compgen -W "--hour --minute --day-of-month --month --day-of-week --profile" -- "$cur"))
+        elif [[ " ${{words[*]}} " == *" inventory "* ]]; then
+            COMPREPLY=($(compgen -W "--plugin --format" -- "$cur"))
+        elif [[ " ${{words[*]}} " == *" sbom "* ]]; then
+            COMPREPLY=($(compgen -W "--plugin --format" -- "$cur"))
+        elif [[ " ${{words[*]}} " == *" audit "* ]]; then
+            COMPREPLY=($(compgen -W "--plugin --db --json" -- "$cur"))
+        elif [[ " ${{words[*]}} " == *" snapshot create "* ]]; then
+            COMPREPLY=($(compgen -W "--output-file" -- "$cur"))
+        elif [[ " ${{words[*]}} " == *" snapshot restore "* ]]; then
+            COMPREPLY=($(compgen -W "--pinned --plan" -- "$cur"))
+        elif [[ " ${{words[*]}} " == *" saves "* ]]; then
+            COMPREPLY=($(compgen -W "--plugin --limit --restore" -- "$cur"))
+        elif [[ " ${{words[*]}} " == *" converge "* ]]; then
+            COMPREPLY=($(compgen -W "--plugin --prune --pinned" -- "$cur"))
+        elif [[ " ${{words[*]}} " == *" rollback "* ]]; then
+            COMPREPLY=($(compgen -W "--run --plugin" -- "$cur"))
+        elif [[ " ${{words[*]}} " == *" history "* ]]; then
+            COMPREPLY=($(compgen -W "--plugin --failed --since --limit --json" -- "$cur"))
         else
             COMPREPLY=($(compgen -W "$global_flags" -- "$cur"))
         fi
@@ -145,10 +297,16 @@ _{app_name}() {{
                 gem) COMPREPLY=($(compgen -W "$plugin_actions_gem" -- "$cur")) ;;
                 go) COMPREPLY=($(compgen -W "$plugin_actions_go" -- "$cur")) ;;
                 yarn) COMPREPLY=($(compgen -W "$plugin_actions_yarn" -- "$cur")) ;;
-                deno|docker|flatpak|os|rustup|snap|vscode)
+                flatpak) COMPREPLY=($(compgen -W "$plugin_actions_flatpak" -- "$cur")) ;;
+                snap) COMPREPLY=($(compgen -W "$plugin_actions_snap" -- "$cur")) ;;
+                vscode) COMPREPLY=($(compgen -W "$plugin_actions_vscode" -- "$cur")) ;;
+                deno|docker|os|rustup)
                     COMPREPLY=($(compgen -W "$plugin_actions_default" -- "$cur")) ;;
-                schedule) COMPREPLY=($(compgen -W "$schedule_actions" -- "$cur")) ;;
+{custom_cases}                schedule) COMPREPLY=($(compgen -W "$schedule_actions" -- "$cur")) ;;
                 config) COMPREPLY=($(compgen -W "$config_actions" -- "$cur")) ;;
+                snapshot) COMPREPLY=($(compgen -W "$snapshot_actions" -- "$cur")) ;;
+                saves) COMPREPLY=($(compgen -W "$saves_actions" -- "$cur")) ;;
+                history) COMPREPLY=($(compgen -W "$history_actions" -- "$cur")) ;;
                 install-completions) COMPREPLY=($(compgen -W "$shell_types" -- "$cur")) ;;
             esac
             ;;
@@ -157,11 +315,38 @@ _{app_name}() {{
 
 complete -F _{app_name} {app_name}
 "#,
-        app_name = config.app_name
+        app_name = config.app_name,
+        custom_names = custom_names,
+        custom_cases = custom_cases,
     )
 }
 
-fn generate_custom_zsh_completion(config: &Config) -> String {
+fn generate_custom_zsh_completion(config: &Config, extensions: &[&PluginMetadata]) -> String {
+    let custom_names: String = extensions.iter().map(|p| format!(" {}", p.name)).collect();
+    let profile_names = config
+        .profiles
+        .keys()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let custom_plugins: String = extensions
+        .iter()
+        .map(|p| format!("        '{}:{}'\n", p.name, zsh_quote(&p.description)))
+        .collect();
+    let custom_cases: String = extensions
+        .iter()
+        .map(|p| {
+            let actions: Vec<String> = custom_plugin_cli_actions(p)
+                .into_iter()
+                .map(|(a, d)| format!("'{}:{}'", a, zsh_quote(&d)))
+                .collect();
+            format!(
+                "            {}) local -a custom_actions=({}); _describe -t actions 'action' custom_actions ;;\n",
+                p.name,
+                actions.join(" ")
+            )
+        })
+        .collect();
     format!(
         r#"#compdef {app_name}
 
@@ -184,7 +369,7 @@ _{app_name}() {{
         'uv:Update uv tools'
         'vscode:Update VSCode/Cursor extensions'
         'yarn:Update globally installed Yarn/PNPM packages'
-    )
+{custom_plugins}    )
 
     local -a global_commands=(
         'schedule:Manage scheduled updates'
@@ -194,6 +379,15 @@ _{app_name}() {{
         'remove:Remove this script from system'
         'install-completions:Install shell completions'
         'trim-logfile:Trim logfile to max lines'
+        'outdated:List packages with newer versions available'
+        'inventory:List installed packages across every package manager'
+        'sbom:Print a software bill of materials of the installed packages'
+        'audit:List installed packages with known vulnerabilities'
+        'snapshot:Bundle every plugin save file into one archive, or restore from one'
+        'saves:Browse and check out versions of the save files kept in save_repo'
+        'converge:Install what the save files list and remove what they do not'
+        'rollback:Reinstall the package versions a past run upgraded'
+        'history:List past runs recorded in the run history'
     )
 
     local -a brew_actions=(
@@ -260,6 +454,24 @@ _{app_name}() {{
         'restore:Restore Yarn packages'
     )
 
+    local -a flatpak_actions=(
+        'update:Update Flatpak applications'
+        'save:Save installed Flatpak applications to a list'
+        'restore:Restore Flatpak applications from saved list'
+    )
+
+    local -a snap_actions=(
+        'update:Update Snap packages'
+        'save:Save installed snaps to a list'
+        'restore:Restore snaps from saved list'
+    )
+
+    local -a vscode_actions=(
+        'update:Update VSCode/Cursor extensions'
+        'save:Save installed extensions to a list'
+        'restore:Restore extensions from saved list'
+    )
+
     local -a simple_actions=(
         'update:Update packages'
     )
@@ -276,6 +488,21 @@ _{app_name}() {{
         'merge:Interactive merge from defaults into local config'
     )
 
+    local -a snapshot_actions=(
+        'create:Run every plugin save and bundle the files with a manifest'
+        'restore:Verify an archive and run each plugin restore from its files'
+    )
+
+    local -a saves_actions=(
+        'log:List the commits of the save repository'
+        'diff:Show how the save files changed since a revision'
+        'checkout:Write the save files of a revision back to their save file locations'
+    )
+
+    local -a history_actions=(
+        'show:Show the actions and commands of one run'
+    )
+
     local -a shell_types=(
         'bash:Generate bash completions'
         'zsh:Generate zsh completions'
@@ -295,6 +522,7 @@ _{app_name}() {{
         '--no-color[Disable color output]' \
         '--logfile-only[Enable output to only logfile]' \
         '--dry-run[Dry-run mode - show what would be done without making changes]' \
+        '--security-only[Only upgrade packages with known vulnerabilities]' \
         '--no-sudo[Skip sudo elevation - run commands as current user]' \
         '--confirm-run[Prompt for confirmation before running arbitrary commands]' \
         '--notify[Send desktop notification when updates complete]' \
@@ -303,10 +531,14 @@ _{app_name}() {{
         '--installdir+[Location to install this script]:DIR:_directories' \
         '--completionsdir+[Completion install directory]:DIR:_directories' \
         '--config-file+[YAML configuration file path]:FILE:_files' \
+        '(-j --jobs)'{{-j,--jobs}}'+[Maximum number of plugins to run in parallel]:N:_numbers' \
+        '--timeout+[Default command timeout in seconds (0 = no limit)]:SECS:_numbers' \
         '--list-plugins[List available plugins and their status]' \
-        '--only+[Run only the specified plugin]:PLUGIN:(brew cargo deno docker flatpak gem npm nvim os pip rustup snap uv vscode yarn)' \
-        '*--enable-plugin+[Enable a specific plugin]:PLUGIN:(brew cargo deno docker flatpak gem npm nvim os pip rustup snap uv vscode yarn)' \
-        '*--disable-plugin+[Disable a specific plugin]:PLUGIN:(brew cargo deno docker flatpak gem npm nvim os pip rustup snap uv vscode yarn)' \
+        '--profile+[Run a named profile from the config]:PROFILE:({profile_names})' \
+        '--output+[Print a report when the run finishes]:FORMAT:(json yaml)' \
+        '--only+[Run only the specified plugin]:PLUGIN:(brew cargo deno docker flatpak gem npm nvim os pip rustup snap uv vscode yarn{custom_names})' \
+        '*--enable-plugin+[Enable a specific plugin]:PLUGIN:(brew cargo deno docker flatpak gem npm nvim os pip rustup snap uv vscode yarn{custom_names})' \
+        '*--disable-plugin+[Disable a specific plugin]:PLUGIN:(brew cargo deno docker flatpak gem npm nvim os pip rustup snap uv vscode yarn{custom_names})' \
         '(-h --help)'{{-h,--help}}'[Print help]' \
         '(-V --version)'{{-V,--version}}'[Print version]' \
         '*:: :->args'
@@ -329,10 +561,16 @@ _{app_name}() {{
             gem) _describe -t actions 'action' gem_actions ;;
             go) _describe -t actions 'action' go_actions ;;
             yarn) _describe -t actions 'action' yarn_actions ;;
-            deno|docker|flatpak|os|rustup|snap|vscode)
+            flatpak) _describe -t actions 'action' flatpak_actions ;;
+            snap) _describe -t actions 'action' snap_actions ;;
+            vscode) _describe -t actions 'action' vscode_actions ;;
+            deno|docker|os|rustup)
                 _describe -t actions 'action' simple_actions ;;
-            schedule) _describe -t actions 'action' schedule_actions ;;
+{custom_cases}            schedule) _describe -t actions 'action' schedule_actions ;;
             config) _describe -t actions 'action' config_actions ;;
+            snapshot) _describe -t actions 'action' snapshot_actions ;;
+            saves) _describe -t actions 'action' saves_actions ;;
+            history) _describe -t actions 'action' history_actions ;;
             install-completions) _describe -t shells 'shell' shell_types ;;
         esac
     fi
@@ -347,16 +585,29 @@ _{app_name}() {{
                     '--save-file+[Brew save file location]:FILE:_files' \
                     '--sudo[Use sudo for brew upgrade commands]' \
                     '--info[Show information about a brew formula]' \
-                    '--search[Search for brew formulas]:query:'
+                    '--search[Search for brew formulas]:query:' \
+                    '--translate[Map a Brewfile from another platform to packages usable here]'
                 ;;
             cargo)
                 _arguments '--save-file+[Cargo save file location]:FILE:_files' ;;
             npm)
-                _arguments '--save-file+[NPM save file location]:FILE:_files' ;;
+                _arguments \
+                    '--save-file+[NPM save file location]:FILE:_files' \
+                    '--pinned[Restore the exact saved versions]' \
+                    '--plan[Compare the save file with what is installed]'
+                ;;
             pip)
-                _arguments '--save-file+[Pip save file location]:FILE:_files' ;;
+                _arguments \
+                    '--save-file+[Pip save file location]:FILE:_files' \
+                    '--pinned[Restore the exact saved versions]' \
+                    '--plan[Compare the save file with what is installed]'
+                ;;
             uv)
-                _arguments '--save-file+[UV save file location]:FILE:_files' ;;
+                _arguments \
+                    '--save-file+[UV save file location]:FILE:_files' \
+                    '--pinned[Restore the exact saved versions]' \
+                    '--plan[Compare the save file with what is installed]'
+                ;;
             run)
                 _arguments '*--cmd+[Command to run]:CMD:_cmdstring' ;;
             schedule)
@@ -365,7 +616,68 @@ _{app_name}() {{
                     '--minute+[Schedule minute (0-59)]:MIN:_numbers' \
                     '--day-of-month+[Schedule day of month]:DAY:_numbers' \
                     '--month+[Schedule month]:MONTH:_numbers' \
-                    '--day-of-week+[Schedule day of week]:DAY:_numbers'
+                    '--day-of-week+[Schedule day of week]:DAY:_numbers' \
+                    '--profile+[Schedule a profile instead of the default run]:PROFILE:({profile_names})'
+                ;;
+            inventory)
+                _arguments \
+                    '*--plugin+[Only list the packages of this plugin]:PLUGIN:(brew cargo flatpak gem npm os pip snap uv vscode)' \
+                    '--format+[Output format]:FORMAT:(table json csv)'
+                ;;
+            sbom)
+                _arguments \
+                    '*--plugin+[Only include the packages of this plugin]:PLUGIN:(brew cargo flatpak gem npm os pip snap uv vscode)' \
+                    '--format+[Document format]:FORMAT:(cyclonedx-json spdx-json)'
+                ;;
+            audit)
+                _arguments \
+                    '*--plugin+[Only audit the packages of this plugin]:PLUGIN:(cargo gem npm pip uv)' \
+                    '--db+[OSV advisory directory or zip file]:PATH:_files' \
+                    '--json[Print JSON instead of a table]'
+                ;;
+            snapshot)
+                case $action in
+                    create)
+                        _arguments '(-o --output-file)'{{-o,--output-file}}'+[Archive to write]:FILE:_files' ;;
+                    restore)
+                        _arguments \
+                            '--pinned[Restore the exact saved versions]' \
+                            '--plan[Compare the save files with what is installed]' \
+                            '1:archive:_files -g "*.tar.gz"' ;;
+                esac
+                ;;
+            saves)
+                case $action in
+                    log)
+                        _arguments \
+                            '--plugin+[Only commits that changed this plugin]:PLUGIN:' \
+                            '(-n --limit)'{{-n,--limit}}'+[Number of commits to show]:N:_numbers' ;;
+                    diff)
+                        _arguments '--plugin+[Only the save file of this plugin]:PLUGIN:' ;;
+                    checkout)
+                        _arguments \
+                            '*--plugin+[Only the save file of this plugin]:PLUGIN:' \
+                            '--restore[Run the restore of every checked-out plugin]' ;;
+                esac
+                ;;
+            converge)
+                _arguments \
+                    '*--plugin+[Only converge this plugin]:PLUGIN:(cargo flatpak gem npm pip snap uv vscode)' \
+                    '--prune[Remove packages the save files do not list without asking]' \
+                    '--pinned[Install the exact saved versions]'
+                ;;
+            rollback)
+                _arguments \
+                    '--run+[Run to roll back]:RUN_ID:' \
+                    '*--plugin+[Only roll back this plugin]:PLUGIN:(cargo gem npm pip uv vscode)'
+                ;;
+            history)
+                _arguments \
+                    '--plugin+[Only runs that included this plugin]:PLUGIN:(brew cargo deno docker flatpak gem npm nvim os pip rustup snap uv vscode yarn{custom_names})' \
+                    '--failed[Only failed runs]' \
+                    '--since+[Only runs started after DATE]:DATE:' \
+                    '(-n --limit)'{{-n,--limit}}'+[Number of runs to show]:N:_numbers' \
+                    '--json[Print JSON instead of a table]'
                 ;;
         esac
     fi
@@ -373,11 +685,16 @@ _{app_name}() {{
 
 _{app_name} "$@"
 "#,
-        app_name = config.app_name
+        app_name = config.app_name,
+        custom_names = custom_names,
+        custom_plugins = custom_plugins,
+        custom_cases = custom_cases,
+        profile_names = profile_names,
     )
 }
 
-fn install_completions(config: &Config, shells: &[&str]) -> Result<()> {
+fn install_completions(config: &Config, registry: &PluginRegistry, shells: &[&str]) -> Result<()> {
+    let extensions = extension_plugins(registry);
     let mut cmd = Args::command();
 
     for shell in shells {
@@ -419,11 +736,11 @@ fn install_completions(config: &Config, shells: &[&str]) -> Result<()> {
         let completion_path = completion_dir.join(filename);
 
         if shell == &"zsh" {
-            let completion_content = generate_custom_zsh_completion(config);
+            let completion_content = generate_custom_zsh_completion(config, &extensions);
             fs::write(&completion_path, completion_content)
                 .context("Failed to write zsh completion")?;
         } else if shell == &"bash" {
-            let completion_content = generate_custom_bash_completion(config);
+            let completion_content = generate_custom_bash_completion(config, &extensions);
             fs::write(&completion_path, completion_content)
                 .context("Failed to write bash completion")?;
         } else {
@@ -501,6 +818,12 @@ struct Args {
     )]
     dry_run: bool,
 
+    #[arg(
+        long,
+        help = "Upgrade only installed packages with known vulnerabilities, to versions that fix them (see audit)"
+    )]
+    security_only: bool,
+
     #[arg(long, help = "Skip sudo elevation - run commands as current user")]
     no_sudo: bool,
 
@@ -537,12 +860,35 @@ struct Args {
     #[arg(long, value_name = "FILE", help = "YAML configuration file path")]
     config_file: Option<String>,
 
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        help = "Maximum number of plugins to run in parallel (default: 4)"
+    )]
+    jobs: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "SECS",
+        help = "Default command timeout in seconds (0 = no limit)"
+    )]
+    timeout: Option<u64>,
+
     #[arg(long, help = "List available plugins and their enabled status")]
     list_plugins: bool,
 
     #[arg(long, value_name = "PLUGIN", help = "Run only the specified plugin")]
     only: Option<String>,
 
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        help = "Run a named profile from the config (with schedule: manage its own entry)"
+    )]
+    profile: Option<String>,
+
     #[arg(
         long,
         value_name = "PLUGIN",
@@ -560,6 +906,13 @@ struct Args {
     #[arg(long, help = "Send desktop notification when updates complete")]
     notify: bool,
 
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        help = "Print a json or yaml report when the run finishes (log output goes to the logfile)"
+    )]
+    output: Option<OutputFormat>,
+
     // -- Subcommand --
     #[command(subcommand)]
     command: Option<Commands>,
@@ -588,6 +941,14 @@ enum Commands {
         /// Search for brew formulas and casks
         #[arg(long, value_name = "QUERY")]
         search: Option<String>,
+
+        /// Restore a Brewfile from another platform, mapping casks, App Store apps and formulas to flatpak or system packages
+        #[arg(long)]
+        translate: bool,
+
+        /// With restore: list missing and extra formulae and casks without changing anything
+        #[arg(long)]
+        plan: bool,
     },
 
     #[command(about = "Upgrade cargo installed packages")]
@@ -638,6 +999,14 @@ enum Commands {
         /// NPM save file location
         #[arg(long, value_name = "FILE")]
         save_file: Option<String>,
+
+        /// With restore: reinstall the exact versions recorded in the save file
+        #[arg(long)]
+        pinned: bool,
+
+        /// With restore: list missing, extra and version-mismatched packages without changing anything
+        #[arg(long)]
+        plan: bool,
     },
 
     #[command(about = "Update Neovim plugins")]
@@ -660,6 +1029,14 @@ enum Commands {
         /// Pip save file location
         #[arg(long, value_name = "FILE")]
         save_file: Option<String>,
+
+        /// With restore: reinstall the exact versions recorded in the save file
+        #[arg(long)]
+        pinned: bool,
+
+        /// With restore: list missing, extra and version-mismatched packages without changing anything
+        #[arg(long)]
+        plan: bool,
     },
 
     #[command(about = "Run an arbitrary command")]
@@ -689,6 +1066,14 @@ enum Commands {
         /// UV save file location
         #[arg(long, value_name = "FILE")]
         save_file: Option<String>,
+
+        /// With restore: reinstall the exact versions recorded in the save file
+        #[arg(long)]
+        pinned: bool,
+
+        /// With restore: list missing, extra and version-mismatched packages without changing anything
+        #[arg(long)]
+        plan: bool,
     },
 
     #[command(about = "Update VSCode/Cursor extensions")]
@@ -703,6 +1088,10 @@ enum Commands {
         action: Option<String>,
     },
 
+    // -- Plugins declared under custom_plugins: in the config file --
+    #[command(external_subcommand)]
+    Custom(Vec<String>),
+
     // -- Non-plugin subcommands --
     #[command(about = "Manage scheduled updates")]
     Schedule {
@@ -733,6 +1122,196 @@ enum Commands {
 
     #[command(about = "Trim logfile to maximum lines")]
     TrimLogfile,
+
+    #[command(about = "List packages with newer versions available, without upgrading")]
+    Outdated {
+        /// Plugins to check (default: every enabled and available plugin)
+        #[arg(value_name = "PLUGIN")]
+        plugins: Vec<String>,
+
+        /// Print JSON instead of a table; log output goes to the logfile
+        #[arg(long)]
+        json: bool,
+    },
+
+    #[command(about = "List installed packages across every package manager")]
+    Inventory {
+        /// Only list the packages of this plugin (repeatable; default: every enabled and available plugin)
+        #[arg(long = "plugin", value_name = "PLUGIN")]
+        plugins: Vec<String>,
+
+        /// Output format: table, json or csv; log output goes to the logfile for json and csv
+        #[arg(long, value_name = "FORMAT", default_value = "table")]
+        format: InventoryFormat,
+    },
+
+    #[command(about = "Print a software bill of materials of the installed packages")]
+    Sbom {
+        /// Only include the packages of this plugin (repeatable; default: every enabled and available plugin)
+        #[arg(long = "plugin", value_name = "PLUGIN")]
+        plugins: Vec<String>,
+
+        /// Document format: cyclonedx-json or spdx-json; log output goes to the logfile
+        #[arg(long, value_name = "FORMAT", default_value = "cyclonedx-json")]
+        format: SbomFormat,
+    },
+
+    #[command(
+        about = "List installed packages with known vulnerabilities from a local advisory database"
+    )]
+    Audit {
+        /// Only audit the packages of this plugin (repeatable; default: every enabled and available cargo, gem, npm, pip and uv plugin)
+        #[arg(long = "plugin", value_name = "PLUGIN")]
+        plugins: Vec<String>,
+
+        /// OSV advisory directory, OSV zip export or RustSec advisory-db checkout (default: advisory_db from the config)
+        #[arg(long, value_name = "PATH")]
+        db: Option<PathBuf>,
+
+        /// Print JSON instead of a table; log output goes to the logfile
+        #[arg(long)]
+        json: bool,
+    },
+
+    #[command(about = "Bundle every plugin's save file into one archive, or restore from one")]
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+
+    #[command(about = "Browse and check out versions of the save files kept in save_repo")]
+    Saves {
+        #[command(subcommand)]
+        action: SavesAction,
+    },
+
+    #[command(about = "Install what the save files list and remove what they don't")]
+    Converge {
+        /// Only converge this plugin (repeatable; default: every enabled and available plugin with a save file)
+        #[arg(long = "plugin", value_name = "PLUGIN")]
+        plugins: Vec<String>,
+
+        /// Remove packages the save files don't list without asking
+        #[arg(long)]
+        prune: bool,
+
+        /// Install the exact saved versions, also replacing packages installed at another version
+        #[arg(long)]
+        pinned: bool,
+    },
+
+    #[command(about = "Reinstall the package versions a past run upgraded")]
+    Rollback {
+        /// Run to roll back (default: the latest run that upgraded or removed packages)
+        #[arg(long = "run", value_name = "RUN_ID")]
+        run_id: Option<String>,
+
+        /// Only roll back this plugin (repeatable)
+        #[arg(long = "plugin", value_name = "PLUGIN")]
+        plugins: Vec<String>,
+    },
+
+    #[command(about = "List past runs recorded in the run history")]
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryAction>,
+
+        /// Only runs that included this plugin
+        #[arg(long, value_name = "PLUGIN")]
+        plugin: Option<String>,
+
+        /// Only failed runs (with --plugin: runs in which the plugin failed)
+        #[arg(long)]
+        failed: bool,
+
+        /// Only runs started after DATE (YYYY-MM-DD, YYYY-MM-DD HH:MM, RFC 3339, or an age like 7d)
+        #[arg(long, value_name = "DATE")]
+        since: Option<String>,
+
+        /// Number of most recent runs to show (0 = all)
+        #[arg(short = 'n', long, value_name = "N", default_value_t = 20)]
+        limit: usize,
+
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HistoryAction {
+    #[command(about = "Show the actions and commands of one run")]
+    Show {
+        /// Run id, or a unique prefix of it
+        run_id: String,
+
+        /// Print the stored JSON record
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SnapshotAction {
+    #[command(about = "Run every plugin's save and bundle the files with a manifest")]
+    Create {
+        /// Archive to write (default: updatehauler-snapshot-<host>-<timestamp>.tar.gz)
+        #[arg(short = 'o', long = "output-file", value_name = "FILE")]
+        output_file: Option<PathBuf>,
+    },
+
+    #[command(about = "Verify an archive and run each plugin's restore from its files")]
+    Restore {
+        /// Archive written by `snapshot create`
+        archive: PathBuf,
+
+        /// Reinstall the exact versions recorded in the save files
+        #[arg(long)]
+        pinned: bool,
+
+        /// List missing, extra and version-mismatched packages without changing anything
+        #[arg(long)]
+        plan: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SavesAction {
+    #[command(about = "List the commits of the save repository")]
+    Log {
+        /// Only commits that changed the save file of this plugin
+        #[arg(long, value_name = "PLUGIN")]
+        plugin: Option<String>,
+
+        /// Number of most recent commits to show (0 = all)
+        #[arg(short = 'n', long, value_name = "N", default_value_t = 20)]
+        limit: usize,
+    },
+
+    #[command(about = "Show how the save files changed since a revision")]
+    Diff {
+        /// Revision to compare the latest commit with
+        #[arg(default_value = "HEAD~1")]
+        rev: String,
+
+        /// Only the save file of this plugin
+        #[arg(long, value_name = "PLUGIN")]
+        plugin: Option<String>,
+    },
+
+    #[command(about = "Write the save files of a revision back to their save file locations")]
+    Checkout {
+        /// Commit, tag or other git revision, e.g. HEAD~3 or main@{1.month.ago}
+        rev: String,
+
+        /// Only the save file of this plugin (repeatable)
+        #[arg(long = "plugin", value_name = "PLUGIN")]
+        plugins: Vec<String>,
+
+        /// Run the restore of every checked-out plugin afterwards
+        #[arg(long)]
+        restore: bool,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -786,6 +1365,11 @@ fn main() -> Result<ExitCode> {
 
     let config_path: Option<PathBuf> = args.config_file.as_deref().map(PathBuf::from);
     let mut config = Config::load_from_yaml(&home, config_path.as_ref())?;
+    // Profile overrides sit between the YAML file and command-line flags
+    let profile_actions = match args.profile {
+        Some(ref name) => Some(config.apply_profile(name)?),
+        None => None,
+    };
 
     // Apply global flags to config
     if args.debug {
@@ -794,7 +1378,7 @@ fn main() -> Result<ExitCode> {
     config.datetime = args.datetime && !args.no_datetime;
     config.show_header = args.header && !args.no_header;
     config.color = args.color && !args.no_color;
-    if args.logfile_only {
+    if args.logfile_only || args.output.is_some() {
         config.use_log = true;
     }
     config.dry_run = args.dry_run;
@@ -811,6 +1395,15 @@ fn main() -> Result<ExitCode> {
     if let Some(max_lines) = args.max_log_lines {
         config.max_log_lines = max_lines;
     }
+    if let Some(jobs) = args.jobs {
+        if jobs == 0 {
+            anyhow::bail!("--jobs must be at least 1");
+        }
+        config.jobs = jobs;
+    }
+    if let Some(timeout) = args.timeout {
+        config.timeouts.default = timeout;
+    }
     if let Some(ref install_dir) = args.installdir {
         let p = PathBuf::from(&install_dir);
         if has_path_traversal(&p) {
@@ -854,14 +1447,48 @@ fn main() -> Result<ExitCode> {
     config.uv_file = PathBuf::from(&home)
         .join(".config/uv")
         .join(format!("{}-uv-tools.json", insights.os));
+    config.gem_file = PathBuf::from(&home)
+        .join(".config/gem")
+        .join(format!("{}-gems.txt", insights.os));
+    config.flatpak_file = PathBuf::from(&home)
+        .join(".config/flatpak")
+        .join(format!("{}-flatpak-apps.txt", insights.os));
+    config.snap_file = PathBuf::from(&home)
+        .join(".config/snap")
+        .join(format!("{}-snaps.txt", insights.os));
+    config.vscode_file = PathBuf::from(&home)
+        .join(".config/vscode")
+        .join(format!("{}-vscode-extensions.txt", insights.os));
 
     let rt = tokio::runtime::Runtime::new()?;
     let mut logger = Logger::new(&config);
 
-    let plugin_registry = create_plugin_registry();
+    let external_plugins = if uses_plugins(args.command.as_ref()) {
+        let registry = create_plugin_registry(&config, &[])?;
+        rt.block_on(load_external_plugins(&registry, &config, &mut logger))
+    } else {
+        Vec::new()
+    };
+    let mut plugin_registry = Arc::new(create_plugin_registry(&config, &external_plugins)?);
+    if uses_plugins(args.command.as_ref()) {
+        for key in config.unknown_plugin_keys(|name| plugin_registry.get_plugin(name).is_some()) {
+            logger.warn(&format!(
+                "Ignoring plugins.{} in the config file: no plugin has this name",
+                key
+            ));
+        }
+    }
 
     // -- Handle subcommands --
     let mut actions: Vec<String> = Vec::new();
+    let mut snapshot_job: Option<SnapshotJob> = None;
+    let mut converge_job: Option<Vec<ConvergeStep>> = None;
+    let mut rollback_job: Option<Vec<(String, Vec<InstalledPackage>)>> = None;
+    let mut security_job: Option<Vec<(String, Vec<InstalledPackage>)>> = None;
+
+    if args.security_only && args.command.is_some() {
+        anyhow::bail!("--security-only applies to runs without a subcommand");
+    }
 
     match args.command {
         // No subcommand: populate default actions from config
@@ -876,6 +1503,9 @@ fn main() -> Result<ExitCode> {
             apply_plugin_overrides(&args, &plugin_registry, &mut config)?;
 
             // Handle --only
+            if args.only.is_some() && args.profile.is_some() {
+                anyhow::bail!("--only and --profile cannot be used together");
+            }
             if let Some(ref only_plugin) = args.only {
                 if plugin_registry.get_plugin(only_plugin).is_some() {
                     config.only_plugin = Some(only_plugin.clone());
@@ -896,8 +1526,41 @@ fn main() -> Result<ExitCode> {
             // Populate default actions from config (same as original)
             if let Some(ref only_plugin) = config.only_plugin {
                 actions.push(only_plugin.clone());
+            } else if let Some(profile_actions) = profile_actions {
+                for action in profile_actions {
+                    if action != "trim-logfile"
+                        && plugin_registry.get_action_by_name(&action).is_none()
+                    {
+                        anyhow::bail!(
+                            "Unknown action in profile {}: {}",
+                            args.profile.as_deref().unwrap_or_default(),
+                            action
+                        );
+                    }
+                    actions.push(action);
+                }
+                if !actions.iter().any(|a| a == "trim-logfile") {
+                    actions.push("trim-logfile".to_string());
+                }
             } else {
-                populate_default_actions(&config, &insights, &mut actions);
+                populate_default_actions(&config, &insights, &rt, &plugin_registry, &mut actions);
+            }
+
+            if args.security_only {
+                let upgrades = plan_security_upgrades(
+                    &config,
+                    &insights,
+                    &rt,
+                    &plugin_registry,
+                    &mut logger,
+                    &actions,
+                )?;
+                if upgrades.is_empty() {
+                    return Ok(ExitCode::SUCCESS);
+                }
+                // Only the vulnerable packages are upgraded
+                actions.retain(|a| a == "trim-logfile");
+                security_job = Some(upgrades);
             }
         }
 
@@ -908,8 +1571,11 @@ fn main() -> Result<ExitCode> {
             sudo,
             info,
             search,
+            translate,
+            plan,
         }) => {
             config.brew_sudo = sudo;
+            config.translate_restore = translate;
             apply_save_file(&save_file, &mut config.brew_file)?;
             if let Some(query) = search {
                 actions.push(format!("brew-search:{}", query));
@@ -917,51 +1583,64 @@ fn main() -> Result<ExitCode> {
                 actions.push(format!("brew-info:{}", formula));
             } else {
                 let act = action.as_deref().unwrap_or("update");
-                actions.push(map_plugin_action("brew", act)?);
+                apply_restore_flags(&mut config, act, false, plan)?;
+                actions.push(map_plugin_action(&plugin_registry, "brew", act)?);
             }
         }
         Some(Commands::Cargo { action, save_file }) => {
             apply_save_file(&save_file, &mut config.cargo_file)?;
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("cargo", act)?);
+            actions.push(map_plugin_action(&plugin_registry, "cargo", act)?);
         }
         Some(Commands::Deno { action }) => {
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("deno", act)?);
+            actions.push(map_plugin_action(&plugin_registry, "deno", act)?);
         }
         Some(Commands::Docker { action }) => {
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("docker", act)?);
+            actions.push(map_plugin_action(&plugin_registry, "docker", act)?);
         }
         Some(Commands::Flatpak { action }) => {
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("flatpak", act)?);
+            actions.push(map_plugin_action(&plugin_registry, "flatpak", act)?);
         }
         Some(Commands::Gem { action }) => {
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("gem", act)?);
+            actions.push(map_plugin_action(&plugin_registry, "gem", act)?);
         }
         Some(Commands::Go { action }) => {
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("go", act)?);
+            actions.push(map_plugin_action(&plugin_registry, "go", act)?);
         }
-        Some(Commands::Npm { action, save_file }) => {
+        Some(Commands::Npm {
+            action,
+            save_file,
+            pinned,
+            plan,
+        }) => {
             apply_save_file(&save_file, &mut config.npm_file)?;
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("npm", act)?);
+            apply_restore_flags(&mut config, act, pinned, plan)?;
+            actions.push(map_plugin_action(&plugin_registry, "npm", act)?);
         }
         Some(Commands::Nvim { action }) => {
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("nvim", act)?);
+            actions.push(map_plugin_action(&plugin_registry, "nvim", act)?);
         }
         Some(Commands::Os { action }) => {
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("os", act)?);
+            actions.push(map_plugin_action(&plugin_registry, "os", act)?);
         }
-        Some(Commands::Pip { action, save_file }) => {
+        Some(Commands::Pip {
+            action,
+            save_file,
+            pinned,
+            plan,
+        }) => {
             apply_save_file(&save_file, &mut config.pip_file)?;
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("pip", act)?);
+            apply_restore_flags(&mut config, act, pinned, plan)?;
+            actions.push(map_plugin_action(&plugin_registry, "pip", act)?);
         }
         Some(Commands::Run { cmd }) => {
             if cmd.is_empty() {
@@ -979,24 +1658,51 @@ fn main() -> Result<ExitCode> {
         }
         Some(Commands::Rustup { action }) => {
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("rustup", act)?);
+            actions.push(map_plugin_action(&plugin_registry, "rustup", act)?);
         }
         Some(Commands::Snap { action }) => {
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("snap", act)?);
+            actions.push(map_plugin_action(&plugin_registry, "snap", act)?);
         }
-        Some(Commands::Uv { action, save_file }) => {
+        Some(Commands::Uv {
+            action,
+            save_file,
+            pinned,
+            plan,
+        }) => {
             apply_save_file(&save_file, &mut config.uv_file)?;
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("uv", act)?);
+            apply_restore_flags(&mut config, act, pinned, plan)?;
+            actions.push(map_plugin_action(&plugin_registry, "uv", act)?);
         }
         Some(Commands::Vscode { action }) => {
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("vscode", act)?);
+            actions.push(map_plugin_action(&plugin_registry, "vscode", act)?);
         }
         Some(Commands::Yarn { action }) => {
             let act = action.as_deref().unwrap_or("update");
-            actions.push(map_plugin_action("yarn", act)?);
+            actions.push(map_plugin_action(&plugin_registry, "yarn", act)?);
+        }
+
+        Some(Commands::Custom(argv)) => {
+            let (plugin, rest) = argv
+                .split_first()
+                .context("missing plugin name for custom plugin subcommand")?;
+            if plugin_registry.get_plugin(plugin).is_none() {
+                anyhow::bail!(
+                    "Unknown command: {}. Run 'updatehauler --help' for available commands.",
+                    plugin
+                );
+            }
+            if rest.len() > 1 {
+                anyhow::bail!(
+                    "Unexpected arguments for {}: {}",
+                    plugin,
+                    rest[1..].join(" ")
+                );
+            }
+            let act = rest.first().map(String::as_str).unwrap_or("update");
+            actions.push(map_plugin_action(&plugin_registry, plugin, act)?);
         }
 
         // Schedule subcommand
@@ -1029,15 +1735,18 @@ fn main() -> Result<ExitCode> {
                         validate_schedule_value(&v, "--day-of-week")?;
                         config.sched_day_of_week = v;
                     }
-                    let mut scheduler = Scheduler::new(&config, &insights, &mut logger);
+                    let mut scheduler = Scheduler::new(&config, &insights, &mut logger)
+                        .with_profile(args.profile.as_deref());
                     scheduler.enable()?
                 }
                 ScheduleAction::Disable => {
-                    let mut scheduler = Scheduler::new(&config, &insights, &mut logger);
+                    let mut scheduler = Scheduler::new(&config, &insights, &mut logger)
+                        .with_profile(args.profile.as_deref());
                     scheduler.disable()?
                 }
                 ScheduleAction::Check => {
-                    let mut scheduler = Scheduler::new(&config, &insights, &mut logger);
+                    let mut scheduler = Scheduler::new(&config, &insights, &mut logger)
+                        .with_profile(args.profile.as_deref());
                     scheduler.check()?
                 }
             }
@@ -1074,10 +1783,11 @@ fn main() -> Result<ExitCode> {
         // Install completions
         Some(Commands::InstallCompletions { shells }) => {
             if shells.is_empty() {
-                install_completions(&config, &["bash", "zsh"])?;
+                install_completions(&config, &plugin_registry, &["bash", "zsh"])?;
             } else {
                 install_completions(
                     &config,
+                    &plugin_registry,
                     &shells.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
                 )?;
             }
@@ -1089,62 +1799,391 @@ fn main() -> Result<ExitCode> {
             trim_logfile(&config, &mut logger)?;
             return Ok(ExitCode::SUCCESS);
         }
+
+        Some(Commands::Outdated { plugins, json }) => {
+            return run_outdated(config, insights, &rt, &plugin_registry, plugins, json);
+        }
+
+        Some(Commands::Inventory { plugins, format }) => {
+            return run_inventory(
+                config,
+                insights,
+                &rt,
+                &plugin_registry,
+                plugins,
+                format != InventoryFormat::Table,
+                |reports| Ok(render_inventory(reports, format)?),
+            );
+        }
+
+        Some(Commands::Sbom { plugins, format }) => {
+            let info = SbomInfo::new(&insights);
+            return run_inventory(
+                config,
+                insights,
+                &rt,
+                &plugin_registry,
+                plugins,
+                true,
+                |reports| Ok(render_sbom(&all_packages(reports), format, &info)?),
+            );
+        }
+
+        Some(Commands::Audit { plugins, db, json }) => {
+            return run_audit(config, insights, &rt, &plugin_registry, plugins, db, json);
+        }
+
+        Some(Commands::Snapshot {
+            action: SnapshotAction::Create { output_file },
+        }) => {
+            if output_file.as_deref().is_some_and(has_path_traversal) {
+                anyhow::bail!("--output-file path contains '..' traversal");
+            }
+            let output = output_file
+                .unwrap_or_else(|| default_archive_name(&insights.hostname, chrono::Local::now()));
+            let plugins = selected_plugins(&config, &insights, &rt, &plugin_registry, Vec::new())?;
+            let (job, save_actions) =
+                SnapshotJob::create(&mut config, &plugin_registry, plugins, output)?;
+            actions.extend(save_actions);
+            // Custom plugins hold their own copy of save_file
+            plugin_registry = Arc::new(create_plugin_registry(&config, &external_plugins)?);
+            snapshot_job = Some(job);
+        }
+
+        Some(Commands::Snapshot {
+            action:
+                SnapshotAction::Restore {
+                    archive,
+                    pinned,
+                    plan,
+                },
+        }) => {
+            config.restore_pinned = pinned;
+            config.restore_plan = plan;
+            let (job, restore_actions) = rt.block_on(SnapshotJob::restore(
+                &mut config,
+                &insights,
+                &plugin_registry,
+                &mut logger,
+                &archive,
+            ))?;
+            actions.extend(restore_actions);
+            plugin_registry = Arc::new(create_plugin_registry(&config, &external_plugins)?);
+            snapshot_job = Some(job);
+        }
+
+        Some(Commands::Converge {
+            plugins,
+            prune,
+            pinned,
+        }) => {
+            config.restore_pinned = pinned;
+            let all = plugins.is_empty();
+            let plugins: Vec<String> =
+                selected_plugins(&config, &insights, &rt, &plugin_registry, plugins)?
+                    .into_iter()
+                    .filter(|p| !all || config.save_file(p).is_some())
+                    .collect();
+            let reports = rt.block_on(plan_restore(
+                Arc::clone(&plugin_registry),
+                Arc::new(config.clone()),
+                Arc::new(insights.clone()),
+                &logger,
+                &plugins,
+            ));
+            let steps = plan_converge(&config, &mut logger, &reports, prune)?;
+            if steps.is_empty() {
+                return Ok(ExitCode::SUCCESS);
+            }
+            converge_job = Some(steps);
+        }
+
+        Some(Commands::Rollback { run_id, plugins }) => {
+            let versions = plan_rollback(&config, &mut logger, run_id.as_deref(), &plugins)?;
+            if versions.is_empty() {
+                return Ok(ExitCode::SUCCESS);
+            }
+            rollback_job = Some(versions);
+        }
+
+        Some(Commands::Saves { action }) => {
+            let repo = open_save_repo(&config)?;
+            let (rev, plugins, restore) = match action {
+                SavesAction::Log { plugin, limit } => {
+                    return Ok(exit_code(saves_log(&repo, plugin, limit)?));
+                }
+                SavesAction::Diff { rev, plugin } => {
+                    return Ok(exit_code(saves_diff(&repo, rev, plugin)?));
+                }
+                SavesAction::Checkout {
+                    rev,
+                    plugins,
+                    restore,
+                } => (rev, plugins, restore),
+            };
+            let checked_out = checkout_saves(&config, &mut logger, &repo, &rev, &plugins)?;
+            if !restore {
+                return Ok(ExitCode::SUCCESS);
+            }
+            actions.extend(
+                checked_out
+                    .iter()
+                    .map(|plugin| format!("{}-restore", plugin))
+                    .filter(|action| plugin_registry.get_action_by_name(action).is_some()),
+            );
+        }
+
+        Some(Commands::History {
+            action,
+            plugin,
+            failed,
+            since,
+            limit,
+            json,
+        }) => {
+            let history = History::new(&config);
+            match action {
+                Some(HistoryAction::Show { run_id, json }) => history.show(&run_id, json)?,
+                None => {
+                    let filter = HistoryFilter {
+                        since: match since {
+                            Some(ref s) => Some(parse_since(s, chrono::Local::now())?),
+                            None => None,
+                        },
+                        plugin,
+                        failed,
+                    };
+                    history.list(&filter, limit, json)?;
+                }
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+
+    if config.restore_plan {
+        return run_restore_plan(config, insights, &rt, &plugin_registry, &actions);
     }
 
     // -- Execute plugin actions --
+    let started = chrono::Local::now();
     logger.log(&format!("{} Main → Start", config.app_name));
 
-    let mut results: Vec<(&str, bool)> = Vec::new();
+    // trim-logfile is not a plugin; run it once every plugin has finished
+    let trim_requested = actions.iter().any(|a| a == "trim-logfile");
+    actions.retain(|a| a != "trim-logfile");
 
-    for action in &actions {
-        match action.as_str() {
-            "trim-logfile" => {
-                let r = trim_logfile(&config, &mut logger);
-                results.push((action, r.is_ok()));
+    let graph = ExecutionGraph::build(&plugin_registry, &actions)?;
+    // Dry runs change nothing, so there is nothing to compare
+    let tracked = if config.dry_run {
+        Vec::new()
+    } else {
+        let mut tracked = plugins_to_track(&plugin_registry, &actions);
+        tracked.extend(converge_job.iter().flatten().map(|s| s.plugin.clone()));
+        tracked.extend(rollback_job.iter().flatten().map(|(p, _)| p.clone()));
+        tracked.extend(security_job.iter().flatten().map(|(p, _)| p.clone()));
+        tracked
+    };
+    let config = Arc::new(config);
+    let insights = Arc::new(insights);
+    let signals = rt.spawn(forward_signals(logger.clone()));
+    let mut changes = Vec::new();
+    let mut reboot_required = false;
+    let mut results: Vec<ActionResult> = rt.block_on(async {
+        let event = HookEvent::before("pre_run".to_string(), None, &actions);
+        let pre_run = run_hook(&config, &mut logger, &event, &config.hooks.pre_run).await;
+        let skip_run = pre_run.as_ref().is_some_and(|r| !r.ok())
+            && config.hooks.pre_failure == PreHookFailure::Skip;
+        let mut results: Vec<ActionResult> = pre_run.into_iter().collect();
+        if skip_run {
+            logger.error("Skipping all actions: pre_run hook failed");
+            results.extend(
+                actions
+                    .iter()
+                    .map(|action| ActionResult::new(action, ActionStatus::Skipped)),
+            );
+        } else {
+            let take_snapshot = || {
+                snapshot(
+                    Arc::clone(&plugin_registry),
+                    Arc::clone(&config),
+                    Arc::clone(&insights),
+                    &logger,
+                    &tracked,
+                )
+            };
+            let before = take_snapshot().await;
+            results.extend(
+                graph
+                    .execute(
+                        Arc::clone(&plugin_registry),
+                        Arc::clone(&config),
+                        Arc::clone(&insights),
+                        &logger,
+                        config.jobs,
+                    )
+                    .await,
+            );
+            let unverified = unverified_plugins(&plugin_registry, &results);
+            if config.verify.rollback
+                && !config.dry_run
+                && !unverified.is_empty()
+                && cancellation().is_none()
+            {
+                results.extend(
+                    rollback_unverified(
+                        Arc::clone(&plugin_registry),
+                        Arc::clone(&config),
+                        Arc::clone(&insights),
+                        &logger,
+                        &before,
+                        &unverified,
+                    )
+                    .await,
+                );
             }
-            _ => {
-                let r = rt.block_on(plugin_registry.execute_action(
-                    action,
-                    &config,
-                    &insights,
-                    &mut logger,
-                ));
-                results.push((action, r.is_ok()));
-                if let Err(e) = r {
-                    logger.error(&e.to_string());
-                }
+            if let Some(ref steps) = converge_job {
+                results.extend(
+                    apply_steps(&plugin_registry, &config, &insights, &logger, steps).await,
+                );
+            }
+            if let Some(ref versions) = rollback_job {
+                results.extend(
+                    apply_rollback(&plugin_registry, &config, &insights, &logger, versions).await,
+                );
             }
+            if let Some(ref upgrades) = security_job {
+                results.extend(
+                    apply_upgrades(&plugin_registry, &config, &insights, &logger, upgrades).await,
+                );
+            }
+            if !before.is_empty() && cancellation().is_none() {
+                changes = diff(&before, &take_snapshot().await);
+            }
+            reboot_required = check_reboot(&config, &insights, &logger, &mut results).await;
         }
+        results
+    });
+
+    if trim_requested {
+        let started = std::time::Instant::now();
+        let mut result = ActionResult::new("trim-logfile", ActionStatus::Ok);
+        if let Err(e) = trim_logfile(&config, &mut logger) {
+            result.status = ActionStatus::Failed;
+            result.error = Some(e.to_string());
+        }
+        result.duration = started.elapsed();
+        results.push(result);
     }
 
+    // Snapshot saves go to a staging directory, not the usual save files
+    if let Some(ref repo) = config.save_repo
+        && !matches!(snapshot_job, Some(SnapshotJob::Create { .. }))
+    {
+        let saved: Vec<(String, PathBuf)> = results
+            .iter()
+            .filter(|r| r.ok() && r.action.ends_with("-save"))
+            .filter_map(|r| plugin_registry.plugin_name_for_action(&r.action))
+            .filter_map(|plugin| Some((plugin.to_string(), config.save_file(plugin)?)))
+            .filter(|(_, path)| config.dry_run || path.is_file())
+            .collect();
+        if !saved.is_empty() {
+            let started = std::time::Instant::now();
+            let mut result = ActionResult::new("save-repo", ActionStatus::Ok);
+            let recorded = rt.block_on(record_saves(
+                &config,
+                &mut logger,
+                repo,
+                &insights.hostname,
+                &saved,
+            ));
+            if let Err(e) = recorded {
+                logger.error(&format!("Failed to commit save files: {:#}", e));
+                result.status = ActionStatus::Failed;
+                result.error = Some(format!("{:#}", e));
+            }
+            result.duration = started.elapsed();
+            results.push(result);
+        }
+    }
+
+    if let Some(ref job) = snapshot_job {
+        results.extend(job.finish(&config, &insights, &mut logger, &results));
+    }
+    drop(snapshot_job);
+
+    let finish_hooks = rt.block_on(run_finish_hooks(&config, &mut logger, &actions, &results));
+    results.extend(finish_hooks);
+    signals.abort();
+    let cancelled = cancellation().is_some();
+
     logger.log(&format!("{} Main → End", config.app_name));
 
     if config.show_header {
-        let success_count = results.iter().filter(|(_, s)| *s).count();
-        let fail_count = results.iter().filter(|(_, s)| !*s).count();
+        let success_count = results.iter().filter(|r| r.ok()).count();
+        let fail_count = results.iter().filter(|r| !r.ok()).count();
         logger.log(&format!(
             "{}   {} succeeded, {} failed",
             config.app_name, success_count, fail_count
         ));
         if !results.is_empty() {
             logger.log(&format!("{}   Summary:", config.app_name));
-            for (action, ok) in &results {
-                let status = if *ok { "OK" } else { "FAIL" };
+            for result in &results {
+                let notes = if result.notes.is_empty() {
+                    String::new()
+                } else {
+                    format!(" ({})", result.notes.join("; "))
+                };
                 logger.log(&format!(
-                    "{}     {:<25} {}",
-                    config.app_name, action, status
+                    "{}     {:<25} {}{}",
+                    config.app_name,
+                    result.action,
+                    result.status.as_str(),
+                    notes
                 ));
             }
         }
+        if !changes.is_empty() {
+            logger.log(&format!("{}   Package changes:", config.app_name));
+            for line in render_changes(&changes) {
+                logger.log(&format!("{}     {}", config.app_name, line));
+            }
+        }
     }
 
-    let fail_count = results.iter().filter(|(_, s)| !*s).count();
+    let report = RunReport::new(
+        &plugin_registry,
+        &insights,
+        started,
+        chrono::Local::now(),
+        config.dry_run,
+        &results,
+    )
+    .with_changes(changes);
+    if let Some(format) = args.output {
+        print!("{}", report.render(format)?);
+    }
+    let mut entry = HistoryEntry::new(
+        report,
+        args.profile.clone(),
+        env::args().collect(),
+        config_path
+            .or_else(|| Config::default_config_path().ok())
+            .as_deref(),
+    );
+    if let Err(e) = History::new(&config).record(&mut entry) {
+        logger.error(&format!("Failed to record run history: {:#}", e));
+    }
 
-    if fail_count > 0 {
-        notify_result(&config, &insights, fail_count as u32);
+    let fail_count = results.iter().filter(|r| !r.ok()).count();
+
+    if cancelled {
+        notify_result(&config, &insights, fail_count as u32, reboot_required);
+        Ok(ExitCode::from(130))
+    } else if fail_count > 0 {
+        notify_result(&config, &insights, fail_count as u32, reboot_required);
         Ok(ExitCode::FAILURE)
     } else {
-        notify_result(&config, &insights, 0);
+        notify_result(&config, &insights, 0, reboot_required);
         Ok(ExitCode::SUCCESS)
     }
 }
@@ -1153,7 +2192,7 @@ fn main() -> Result<ExitCode> {
 // Helpers
 // ---------------------------------------------------------------------------
 
-fn map_plugin_action(plugin: &str, action: &str) -> Result<String> {
+fn map_plugin_action(registry: &PluginRegistry, plugin: &str, action: &str) -> Result<String> {
     match action {
         "update" => Ok(plugin.to_string()),
         "save" => Ok(format!("{}-save", plugin)),
@@ -1166,7 +2205,6 @@ fn map_plugin_action(plugin: &str, action: &str) -> Result<String> {
         "uvx" => Ok("uvx".to_string()),
         other => {
             // Check if it's a valid action for this plugin
-            let registry = create_plugin_registry();
             let full_name = format!("{}-{}", plugin, other);
             if registry.get_action_by_name(&full_name).is_some() {
                 Ok(full_name)
@@ -1183,6 +2221,68 @@ fn map_plugin_action(plugin: &str, action: &str) -> Result<String> {
     }
 }
 
+/// Set the restore modes of `--pinned` and `--plan`, which only apply to
+/// the restore action
+fn apply_restore_flags(config: &mut Config, action: &str, pinned: bool, plan: bool) -> Result<()> {
+    if (pinned || plan) && action != "restore" {
+        anyhow::bail!("--pinned and --plan only apply to restore");
+    }
+    config.restore_pinned = pinned;
+    config.restore_plan = plan;
+    Ok(())
+}
+
+/// Print how the installed packages differ from the save files the restore
+/// actions in `actions` would read, without running them
+fn run_restore_plan(
+    config: Config,
+    insights: Insights,
+    rt: &tokio::runtime::Runtime,
+    registry: &Arc<PluginRegistry<'static>>,
+    actions: &[String],
+) -> Result<ExitCode> {
+    let plugins: Vec<String> = actions
+        .iter()
+        .filter(|action| {
+            registry
+                .get_action_by_name(action)
+                .and_then(|a| a.action_type)
+                == Some(PluginActionType::Restore)
+        })
+        .filter_map(|action| registry.plugin_name_for_action(action))
+        .map(str::to_string)
+        .collect();
+
+    let logger = Logger::new(&config);
+    let signals = rt.spawn(forward_signals(logger.clone()));
+    let reports = rt.block_on(plan_restore(
+        Arc::clone(registry),
+        Arc::new(config),
+        Arc::new(insights),
+        &logger,
+        &plugins,
+    ));
+    signals.abort();
+
+    print!("{}", render_plan(&reports));
+
+    if cancellation().is_some() {
+        return Ok(ExitCode::from(130));
+    }
+    if reports.iter().any(|r| r.error.is_some()) {
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn exit_code(succeeded: bool) -> ExitCode {
+    if succeeded {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
 fn apply_save_file(file: &Option<String>, target: &mut PathBuf) -> Result<()> {
     if let Some(f) = file {
         let p = PathBuf::from(f);
@@ -1234,7 +2334,13 @@ fn apply_plugin_overrides(
     Ok(())
 }
 
-fn populate_default_actions(config: &Config, insights: &Insights, actions: &mut Vec<String>) {
+fn populate_default_actions(
+    config: &Config,
+    insights: &Insights,
+    rt: &tokio::runtime::Runtime,
+    registry: &PluginRegistry,
+    actions: &mut Vec<String>,
+) {
     if config.plugins_enabled.os.unwrap_or(true) {
         actions.push("os".to_string());
     }
@@ -1286,9 +2392,300 @@ fn populate_default_actions(config: &Config, insights: &Insights, actions: &mut
     if config.plugins_enabled.gem.unwrap_or(false) && insights.has_gem {
         actions.extend_from_slice(&["gem".to_string(), "gem-save".to_string()]);
     }
+    for metadata in extension_plugins(registry) {
+        let enabled = config
+            .plugins_enabled
+            .custom
+            .get(&metadata.name)
+            .copied()
+            .unwrap_or(true);
+        let available = registry
+            .get_plugin(&metadata.name)
+            .is_some_and(|p| rt.block_on(p.check_available(config, insights)));
+        if enabled && available {
+            actions.push(metadata.name.clone());
+            actions.extend(
+                metadata
+                    .actions
+                    .iter()
+                    .filter(|a| a.action_type == Some(PluginActionType::Save))
+                    .map(|a| a.name.clone()),
+            );
+        }
+    }
     actions.push("trim-logfile".to_string());
 }
 
+/// The `outdated` subcommand: print the outdated packages of `plugins`, or of
+/// every enabled and available plugin, as a table or JSON
+fn run_outdated(
+    mut config: Config,
+    insights: Insights,
+    rt: &tokio::runtime::Runtime,
+    registry: &Arc<PluginRegistry<'static>>,
+    plugins: Vec<String>,
+    json: bool,
+) -> Result<ExitCode> {
+    let plugins = selected_plugins(&config, &insights, rt, registry, plugins)?;
+    if json {
+        // Keep stdout clean for the JSON document
+        config.use_log = true;
+    }
+
+    let logger = Logger::new(&config);
+    let signals = rt.spawn(forward_signals(logger.clone()));
+    let reports = rt.block_on(check_outdated(
+        Arc::clone(registry),
+        Arc::new(config),
+        Arc::new(insights),
+        &logger,
+        &plugins,
+    ));
+    signals.abort();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        print!("{}", render_table(&reports));
+    }
+
+    if cancellation().is_some() {
+        return Ok(ExitCode::from(130));
+    }
+    if reports.iter().any(|r| r.error.is_some()) {
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The `inventory` and `sbom` subcommands: list the installed packages of
+/// `plugins`, or of every enabled and available plugin, and print what
+/// `render` makes of them. `machine_output` sends log output to the logfile.
+fn run_inventory(
+    mut config: Config,
+    insights: Insights,
+    rt: &tokio::runtime::Runtime,
+    registry: &Arc<PluginRegistry<'static>>,
+    plugins: Vec<String>,
+    machine_output: bool,
+    render: impl FnOnce(&[InventoryReport]) -> Result<String>,
+) -> Result<ExitCode> {
+    let plugins = selected_plugins(&config, &insights, rt, registry, plugins)?;
+    if machine_output {
+        // Keep stdout clean for the document
+        config.use_log = true;
+    }
+
+    let logger = Logger::new(&config);
+    let signals = rt.spawn(forward_signals(logger.clone()));
+    let reports = rt.block_on(list_installed(
+        Arc::clone(registry),
+        Arc::new(config),
+        Arc::new(insights),
+        &logger,
+        &plugins,
+    ));
+    signals.abort();
+
+    print!("{}", render(&reports)?);
+
+    if cancellation().is_some() {
+        return Ok(ExitCode::from(130));
+    }
+    if reports.iter().any(|r| r.error.is_some()) {
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The advisory database at `path`, or at `advisory_db` from the config
+fn load_advisories(
+    config: &Config,
+    logger: &mut Logger,
+    path: Option<PathBuf>,
+) -> Result<AdvisoryDb> {
+    let path = path.or_else(|| config.advisory_db.clone()).context(
+        "No advisory database configured; set advisory_db in the config file (or pass --db to audit)",
+    )?;
+    let db = AdvisoryDb::load(&path)?;
+    if let Some((file, error)) = db.unreadable.first() {
+        logger.warn(&format!(
+            "Skipped {} unreadable advisory files ({}: {})",
+            db.unreadable.len(),
+            file,
+            error
+        ));
+    }
+    logger.log(&format!(
+        "Loaded {} advisories from {}",
+        db.len(),
+        path.display()
+    ));
+    Ok(db)
+}
+
+/// `plugins`, or every enabled and available plugin, limited to those whose
+/// packages advisories are published for
+fn audited_plugins(
+    config: &Config,
+    insights: &Insights,
+    rt: &tokio::runtime::Runtime,
+    registry: &PluginRegistry,
+    plugins: Vec<String>,
+) -> Result<Vec<String>> {
+    if let Some(name) = plugins.iter().find(|p| ecosystem(p).is_none()) {
+        anyhow::bail!(
+            "{} packages can't be audited (supported: cargo, gem, npm, pip, uv)",
+            name
+        );
+    }
+    Ok(selected_plugins(config, insights, rt, registry, plugins)?
+        .into_iter()
+        .filter(|p| ecosystem(p).is_some())
+        .collect())
+}
+
+/// The `audit` subcommand: print the advisories affecting the installed
+/// packages of `plugins`. Exits with status 1 when any package is affected.
+fn run_audit(
+    mut config: Config,
+    insights: Insights,
+    rt: &tokio::runtime::Runtime,
+    registry: &Arc<PluginRegistry<'static>>,
+    plugins: Vec<String>,
+    db: Option<PathBuf>,
+    json: bool,
+) -> Result<ExitCode> {
+    if json {
+        // Keep stdout clean for the JSON document
+        config.use_log = true;
+    }
+    let mut logger = Logger::new(&config);
+    let db = load_advisories(&config, &mut logger, db)?;
+    let plugins = audited_plugins(&config, &insights, rt, registry, plugins)?;
+
+    let signals = rt.spawn(forward_signals(logger.clone()));
+    let reports = rt.block_on(list_installed(
+        Arc::clone(registry),
+        Arc::new(config),
+        Arc::new(insights),
+        &logger,
+        &plugins,
+    ));
+    signals.abort();
+
+    let packages: Vec<InstalledPackage> = reports
+        .iter()
+        .flat_map(|r| r.packages.iter().cloned())
+        .collect();
+    let findings = db.audit(&packages);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&findings)?);
+    } else {
+        print!("{}", render_findings(&findings, packages.len()));
+        for report in &reports {
+            if let Some(ref error) = report.error {
+                println!("{}: listing failed: {}", report.plugin, error);
+            }
+        }
+    }
+
+    if cancellation().is_some() {
+        return Ok(ExitCode::from(130));
+    }
+    if !findings.is_empty() || reports.iter().any(|r| r.error.is_some()) {
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The upgrades of a `--security-only` run: the installed packages of the
+/// plugins `actions` update that advisories affect, at versions that fix
+/// them; empty when nothing needs upgrading.
+fn plan_security_upgrades(
+    config: &Config,
+    insights: &Insights,
+    rt: &tokio::runtime::Runtime,
+    registry: &Arc<PluginRegistry<'static>>,
+    logger: &mut Logger,
+    actions: &[String],
+) -> Result<Vec<(String, Vec<InstalledPackage>)>> {
+    let db = load_advisories(config, logger, None)?;
+    let plugins: Vec<String> = plugins_to_track(registry, actions)
+        .into_iter()
+        .filter(|p| ecosystem(p).is_some())
+        .collect();
+    let reports = rt.block_on(list_installed(
+        Arc::clone(registry),
+        Arc::new(config.clone()),
+        Arc::new(insights.clone()),
+        logger,
+        &plugins,
+    ));
+    let packages: Vec<InstalledPackage> = reports
+        .iter()
+        .flat_map(|r| r.packages.iter().cloned())
+        .collect();
+    for finding in db.audit(&packages) {
+        logger.log(&format!(
+            "{}: {} {} is affected by {}: {}",
+            finding.plugin, finding.name, finding.version, finding.id, finding.summary
+        ));
+    }
+    let (upgrades, unfixed) = db.upgrades(&packages);
+    for package in unfixed {
+        logger.warn(&format!(
+            "{}: no version of {} fixing every advisory is known; leaving {} installed",
+            package.manager, package.name, package.version
+        ));
+    }
+    if upgrades.is_empty() {
+        logger.log(&format!(
+            "Nothing to upgrade: no fixable vulnerabilities in {} packages",
+            packages.len()
+        ));
+        return Ok(upgrades);
+    }
+    for (plugin, packages) in &upgrades {
+        let specs: Vec<String> = packages
+            .iter()
+            .map(|p| format!("{} → {}", p.name, p.version))
+            .collect();
+        logger.log(&format!("{}: upgrading {}", plugin, specs.join(", ")));
+    }
+    Ok(upgrades)
+}
+
+/// `plugins` after checking that each exists, or every enabled and available
+/// plugin when none are given
+fn selected_plugins(
+    config: &Config,
+    insights: &Insights,
+    rt: &tokio::runtime::Runtime,
+    registry: &PluginRegistry,
+    plugins: Vec<String>,
+) -> Result<Vec<String>> {
+    for name in &plugins {
+        if registry.get_plugin(name).is_none() {
+            anyhow::bail!("Unknown plugin: {}", name);
+        }
+    }
+    if !plugins.is_empty() {
+        return Ok(plugins);
+    }
+    Ok(registry
+        .get_all_metadata()
+        .iter()
+        .map(|m| m.name.clone())
+        .filter(|name| config.plugin_enabled(name))
+        .filter(|name| {
+            registry
+                .get_plugin(name)
+                .is_some_and(|p| rt.block_on(p.check_available(config, insights)))
+        })
+        .collect())
+}
+
 fn print_plugin_list(
     config: &Config,
     insights: &Insights,
@@ -1301,25 +2698,7 @@ fn print_plugin_list(
     );
     println!("{:-<20} {:-<10} {:-<10}  {:-<40}", "", "", "", "");
     for metadata in registry.get_all_metadata() {
-        let enabled = match metadata.name.as_str() {
-            "brew" => config.plugins_enabled.brew.unwrap_or(false),
-            "cargo" => config.plugins_enabled.cargo.unwrap_or(false),
-            "deno" => config.plugins_enabled.deno.unwrap_or(false),
-            "docker" => config.plugins_enabled.docker.unwrap_or(false),
-            "flatpak" => config.plugins_enabled.flatpak.unwrap_or(false),
-            "gem" => config.plugins_enabled.gem.unwrap_or(false),
-            "nvim" => config.plugins_enabled.nvim.unwrap_or(false),
-            "npm" => config.plugins_enabled.npm.unwrap_or(false),
-            "os" => config.plugins_enabled.os.unwrap_or(false),
-            "pip" => config.plugins_enabled.pip.unwrap_or(false),
-            "rustup" => config.plugins_enabled.rustup.unwrap_or(false),
-            "snap" => config.plugins_enabled.snap.unwrap_or(false),
-            "uv" => config.plugins_enabled.uv.unwrap_or(false),
-            "vscode" => config.plugins_enabled.vscode.unwrap_or(false),
-            "yarn" => config.plugins_enabled.yarn.unwrap_or(false),
-            "go" => config.plugins_enabled.go.unwrap_or(false),
-            _ => true,
-        };
+        let enabled = config.plugin_enabled(&metadata.name);
         let plugin = registry.get_plugin(&metadata.name).unwrap();
         let available = rt.block_on(plugin.check_available(config, insights));
         println!(
@@ -1332,15 +2711,18 @@ fn print_plugin_list(
     }
 }
 
-fn notify_result(config: &Config, insights: &Insights, fail_count: u32) {
+fn notify_result(config: &Config, insights: &Insights, fail_count: u32, reboot_required: bool) {
     if !config.notify {
         return;
     }
-    let msg = if fail_count > 0 {
+    let mut msg = if fail_count > 0 {
         format!("{}: {} action(s) failed", config.app_name, fail_count)
     } else {
         format!("{}: all actions completed successfully", config.app_name)
     };
+    if reboot_required {
+        msg.push_str("; reboot required");
+    }
     if insights.is_darwin {
         let escaped_msg = msg.replace('\\', "\\\\").replace('"', "\\\"");
         let escaped_title = config.app_name.replace('\\', "\\\\").replace('"', "\\\"");
@@ -0,0 +1,114 @@
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::config::Config;
+use crate::insights::Insights;
+use crate::logger::Logger;
+use crate::plugins::{OutdatedPackage, PluginRegistry, scope_plugin};
+use crate::report::format_table;
+
+/// Outdated packages of one plugin, or why they could not be listed
+#[derive(Debug, Serialize)]
+pub struct OutdatedReport {
+    pub plugin: String,
+    pub packages: Vec<OutdatedPackage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Ask each of `plugins` for its outdated packages, up to `config.jobs` at a
+/// time. Plugins that cannot check for updates are left out of the result.
+pub async fn check_outdated(
+    registry: Arc<PluginRegistry<'static>>,
+    config: Arc<Config>,
+    insights: Arc<Insights>,
+    logger: &Logger,
+    plugins: &[String],
+) -> Vec<OutdatedReport> {
+    let semaphore = Arc::new(Semaphore::new(config.jobs.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for (i, name) in plugins.iter().enumerate() {
+        let registry = Arc::clone(&registry);
+        let config = Arc::clone(&config);
+        let insights = Arc::clone(&insights);
+        let semaphore = Arc::clone(&semaphore);
+        let mut logger = logger.with_prefix(name);
+        let name = name.clone();
+
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let Some(plugin) = registry.get_plugin(&name) else {
+                return (i, None);
+            };
+            let result = scope_plugin(
+                &name,
+                plugin.check_outdated(&config, &insights, &mut logger),
+            )
+            .await;
+            let report = match result {
+                Ok(None) => {
+                    logger.log("Checking for updates is not supported");
+                    return (i, None);
+                }
+                Ok(Some(packages)) => OutdatedReport {
+                    plugin: name,
+                    packages,
+                    error: None,
+                },
+                Err(e) => {
+                    logger.error(&format!("Failed to check for updates: {:#}", e));
+                    OutdatedReport {
+                        plugin: name,
+                        packages: Vec::new(),
+                        error: Some(format!("{:#}", e)),
+                    }
+                }
+            };
+            (i, Some(report))
+        });
+    }
+
+    let mut reports: Vec<(usize, OutdatedReport)> = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok((i, Some(report))) = joined {
+            reports.push((i, report));
+        }
+    }
+    reports.sort_by_key(|(i, _)| *i);
+    reports.into_iter().map(|(_, report)| report).collect()
+}
+
+/// One aligned table of every outdated package, followed by the plugins
+/// whose check failed
+pub fn render_table(reports: &[OutdatedReport]) -> String {
+    let header = ["Plugin", "Package", "Installed", "Available"];
+    let rows: Vec<[&str; 4]> = reports
+        .iter()
+        .flat_map(|r| {
+            r.packages.iter().map(|p| {
+                [
+                    r.plugin.as_str(),
+                    p.name.as_str(),
+                    p.installed.as_deref().unwrap_or("?"),
+                    p.available.as_str(),
+                ]
+            })
+        })
+        .collect();
+
+    let mut out = if rows.is_empty() {
+        "Everything is up to date\n".to_string()
+    } else {
+        format_table(header, &rows)
+    };
+
+    for report in reports {
+        if let Some(ref error) = report.error {
+            out.push_str(&format!("{}: check failed: {}\n", report.plugin, error));
+        }
+    }
+    out
+}
@@ -1,23 +1,104 @@
 use async_trait::async_trait;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{
+    CommandOutcome, ErrorPolicy, InstallScope, InstalledPackage, OutdatedPackage, Plugin,
+    PluginAction, PluginActionType, PluginMetadata,
+};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
-use anyhow::Result;
+use crate::translate::{
+    EntryKind, PackageMap, RestoreTarget, install_command, parse_brewfile, translate_brewfile_at,
+};
+use anyhow::{Context, Result};
+use std::io::Write;
 
-fn brew_run_cmd(
+async fn brew_run_cmd(
     config: &Config,
     logger: &mut Logger,
-    show_error: bool,
+    policy: ErrorPolicy,
     command: &str,
     args: &[&str],
-) -> Result<()> {
+) -> Result<CommandOutcome> {
     if config.brew_sudo {
-        super::run_with_sudo(config, logger, show_error, command, args)
+        super::run_with_sudo(config, logger, policy, command, args).await
     } else {
-        super::run_cmd(config, logger, show_error, command, args)
+        super::run_cmd(config, logger, policy, command, args).await
+    }
+}
+
+/// Run `brew bundle <subcommand> --file` on a temporary Brewfile holding `content`
+async fn bundle_temp_brewfile(
+    config: &Config,
+    logger: &mut Logger,
+    subcommand: &str,
+    content: &str,
+) -> Result<()> {
+    let mut brew_file = tempfile::Builder::new()
+        .prefix("updatehauler-Brewfile-")
+        .tempfile()
+        .context("Failed to create a temporary Brewfile")?;
+    brew_file
+        .write_all(content.as_bytes())
+        .and_then(|()| brew_file.flush())
+        .with_context(|| format!("Failed to write {}", brew_file.path().display()))?;
+    let result = brew_run_cmd(
+        config,
+        logger,
+        ErrorPolicy::Fail,
+        "brew",
+        &[
+            "bundle",
+            subcommand,
+            "--file",
+            &brew_file.path().to_string_lossy(),
+        ],
+    )
+    .await;
+    result.map(|_| ())
+}
+
+/// Restore a Brewfile written on another platform: what brew can install
+/// here goes to `brew bundle`, other entries to their mapped alternatives
+async fn restore_translated(
+    config: &Config,
+    insights: &Insights,
+    logger: &mut Logger,
+) -> Result<()> {
+    let map = PackageMap::builtin().with_overrides(&config.package_map);
+    let translation =
+        translate_brewfile_at(&config.brew_file, &RestoreTarget::new(insights), &map)?;
+    let mapped: usize = translation.installs.values().map(Vec::len).sum();
+    logger.log(&format!(
+        "Translated {}: {} entries for brew, {} mapped to other package managers, {} without an alternative",
+        config.brew_file.display(),
+        translation.brewfile.len(),
+        mapped,
+        translation.unmapped.len()
+    ));
+
+    if !translation.brewfile.is_empty() && insights.has_brew {
+        bundle_temp_brewfile(config, logger, "install", &translation.brewfile_content()).await?;
+    }
+
+    for (manager, packages) in &translation.installs {
+        let Some(command) = install_command(manager, packages) else {
+            continue;
+        };
+        let (program, args) = command.split_first().expect("install command");
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        // flatpak asks for privileges itself
+        if manager == "flatpak" || insights.is_root {
+            super::run_cmd(config, logger, ErrorPolicy::Fail, program, &args).await?;
+        } else {
+            super::run_with_sudo(config, logger, ErrorPolicy::Fail, program, &args).await?;
+        }
+    }
+
+    for line in &translation.unmapped {
+        logger.warn(&format!("No alternative on this platform for: {}", line));
     }
+    Ok(())
 }
 
 pub struct BrewPlugin;
@@ -74,6 +155,7 @@ impl Plugin for BrewPlugin {
                     action_type: None,
                 },
             ],
+            run_after: vec!["os".to_string()],
         }
     }
 
@@ -86,15 +168,22 @@ impl Plugin for BrewPlugin {
     ) -> Result<bool> {
         match action_name {
             "brew-list" => {
-                brew_run_cmd(config, logger, true, "brew", &["list"])?;
+                brew_run_cmd(config, logger, ErrorPolicy::Fail, "brew", &["list"]).await?;
                 Ok(true)
             }
             "brew-outdated" => {
-                brew_run_cmd(config, logger, true, "brew", &["outdated"])?;
+                brew_run_cmd(config, logger, ErrorPolicy::Fail, "brew", &["outdated"]).await?;
                 Ok(true)
             }
             "brew-upgrade-pinned" => {
-                brew_run_cmd(config, logger, true, "brew", &["upgrade", "--pinned"])?;
+                brew_run_cmd(
+                    config,
+                    logger,
+                    ErrorPolicy::Fail,
+                    "brew",
+                    &["upgrade", "--pinned"],
+                )
+                .await?;
                 Ok(true)
             }
             "brew-info" => {
@@ -111,8 +200,9 @@ impl Plugin for BrewPlugin {
         }
     }
 
-    async fn check_available(&self, _config: &Config, insights: &Insights) -> bool {
-        insights.has_brew
+    async fn check_available(&self, config: &Config, insights: &Insights) -> bool {
+        // A translated restore can install a Brewfile without brew
+        insights.has_brew || config.translate_restore
     }
 
     async fn update(
@@ -125,33 +215,61 @@ impl Plugin for BrewPlugin {
             return Ok(());
         }
 
-        brew_run_cmd(config, logger, true, "brew", &["update"])?;
-        brew_run_cmd(config, logger, true, "brew", &["upgrade", "--yes"])?;
-        brew_run_cmd(config, logger, true, "brew", &["cleanup", "-q"])?;
-        let _ = brew_run_cmd(config, logger, false, "brew", &["doctor", "-q"]);
+        brew_run_cmd(config, logger, ErrorPolicy::Fail, "brew", &["update"]).await?;
         brew_run_cmd(
             config,
             logger,
-            true,
+            ErrorPolicy::Fail,
+            "brew",
+            &["upgrade", "--yes"],
+        )
+        .await?;
+        brew_run_cmd(
+            config,
+            logger,
+            ErrorPolicy::Fail,
+            "brew",
+            &["cleanup", "-q"],
+        )
+        .await?;
+        brew_run_cmd(config, logger, ErrorPolicy::Warn, "brew", &["doctor", "-q"]).await?;
+        brew_run_cmd(
+            config,
+            logger,
+            ErrorPolicy::Fail,
             "brew",
             &["upgrade", "--cask", "--yes"],
-        )?;
-        if duct::cmd("brew", &["cu", "--version"])
-            .stdout_null()
-            .stderr_null()
-            .run()
+        )
+        .await?;
+        if super::query_cmd(config, logger, "brew", &["cu", "--version"], &[])
+            .await
             .is_ok()
         {
             brew_run_cmd(
                 config,
                 logger,
-                true,
+                ErrorPolicy::Fail,
                 "brew",
                 &["cu", "-a", "-f", "--cleanup", "-y"],
-            )?;
+            )
+            .await?;
         }
-        brew_run_cmd(config, logger, true, "brew", &["cleanup", "-q"])?;
-        let _ = brew_run_cmd(config, logger, false, "brew", &["doctor", "--verbose"]);
+        brew_run_cmd(
+            config,
+            logger,
+            ErrorPolicy::Fail,
+            "brew",
+            &["cleanup", "-q"],
+        )
+        .await?;
+        brew_run_cmd(
+            config,
+            logger,
+            ErrorPolicy::Warn,
+            "brew",
+            &["doctor", "--verbose"],
+        )
+        .await?;
 
         Ok(())
     }
@@ -172,10 +290,11 @@ impl Plugin for BrewPlugin {
         brew_run_cmd(
             config,
             logger,
-            true,
+            ErrorPolicy::Fail,
             "brew",
             &["bundle", "dump", "--force", "--file", &brew_file],
-        )?;
+        )
+        .await?;
 
         logger.log("Success savefile written");
 
@@ -188,7 +307,7 @@ impl Plugin for BrewPlugin {
         insights: &Insights,
         logger: &mut Logger,
     ) -> Result<()> {
-        if !insights.has_brew {
+        if !insights.has_brew && !config.translate_restore {
             return Ok(());
         }
 
@@ -202,14 +321,218 @@ impl Plugin for BrewPlugin {
             return Ok(());
         }
 
+        if config.translate_restore {
+            return restore_translated(config, insights, logger).await;
+        }
+
         brew_run_cmd(
             config,
             logger,
-            true,
+            ErrorPolicy::Fail,
             "brew",
             &["bundle", "--file", &brew_file],
-        )?;
+        )
+        .await?;
 
         Ok(())
     }
+
+    fn saved_packages(&self, config: &Config) -> Result<Option<Vec<InstalledPackage>>> {
+        let content = super::read_save_file(&config.brew_file)?;
+        Ok(Some(parse_brewfile_packages(&content)))
+    }
+
+    async fn install_packages(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        // The saved entries as written, so their taps and options apply
+        let content = super::read_save_file(&config.brew_file)?;
+        bundle_temp_brewfile(
+            config,
+            logger,
+            "install",
+            &brewfile_subset(&content, packages),
+        )
+        .await?;
+        Ok(true)
+    }
+
+    /// Runs `brew bundle cleanup` against the save file rather than
+    /// uninstalling `packages` one by one: brew keeps whatever a listed
+    /// formula or cask depends on, which a plain `brew uninstall` would break
+    async fn remove_packages(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        _packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        logger.log("Removing what the Brewfile doesn't list, except dependencies of its entries");
+        let brew_file = config.brew_file.to_string_lossy();
+        brew_run_cmd(
+            config,
+            logger,
+            ErrorPolicy::Fail,
+            "brew",
+            &["bundle", "cleanup", "--force", "--file", &brew_file],
+        )
+        .await?;
+        Ok(true)
+    }
+
+    async fn check_outdated(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<OutdatedPackage>>> {
+        let json =
+            super::query_cmd(config, logger, "brew", &["outdated", "--json=v2"], &[]).await?;
+        parse_brew_outdated(&json).map(Some)
+    }
+
+    async fn list_installed(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        let formulae = super::query_cmd(
+            config,
+            logger,
+            "brew",
+            &["list", "--formula", "--versions"],
+            &[],
+        )
+        .await?;
+        let casks = super::query_cmd(
+            config,
+            logger,
+            "brew",
+            &["list", "--cask", "--versions"],
+            &[],
+        )
+        .await?;
+        let prefix = super::query_cmd(config, logger, "brew", &["--prefix"], &[]).await?;
+        let prefix = prefix.trim();
+        let mut packages = parse_brew_list_versions(&formulae, "formula");
+        packages.extend(parse_brew_list_versions(&casks, "cask"));
+        if !prefix.is_empty() {
+            let scope = InstallScope::of_path(prefix);
+            packages = packages
+                .into_iter()
+                .map(|p| {
+                    let dir = if p.source == "cask" {
+                        "Caskroom"
+                    } else {
+                        "Cellar"
+                    };
+                    let path = format!("{}/{}/{}/{}", prefix, dir, p.name, p.version);
+                    p.with_scope(scope).with_path(path)
+                })
+                .collect();
+        }
+        Ok(Some(packages))
+    }
+}
+
+/// Name `brew list` reports for a Brewfile entry: tapped formulae such as
+/// `owner/tap/tool` are listed as `tool`
+fn short_name(name: &str) -> &str {
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+/// The formulae and casks of a Brewfile, without versions since a Brewfile
+/// doesn't record them
+pub fn parse_brewfile_packages(content: &str) -> Vec<InstalledPackage> {
+    parse_brewfile(content)
+        .iter()
+        .filter_map(|entry| {
+            let source = match entry.kind {
+                EntryKind::Brew => "formula",
+                EntryKind::Cask => "cask",
+                _ => return None,
+            };
+            Some(InstalledPackage::new(
+                "brew",
+                short_name(&entry.name),
+                "",
+                source,
+            ))
+        })
+        .collect()
+}
+
+/// The lines of a Brewfile that install `packages`, with every tap line
+pub fn brewfile_subset(content: &str, packages: &[InstalledPackage]) -> String {
+    let wanted = |kind: EntryKind, name: &str| {
+        let source = match kind {
+            EntryKind::Tap => return true,
+            EntryKind::Brew => "formula",
+            EntryKind::Cask => "cask",
+            _ => return false,
+        };
+        packages
+            .iter()
+            .any(|p| p.source == source && p.name == short_name(name))
+    };
+    parse_brewfile(content)
+        .iter()
+        .filter(|entry| wanted(entry.kind, &entry.name))
+        .map(|entry| format!("{}\n", entry.line))
+        .collect()
+}
+
+/// Parse `brew list --versions`: `git 2.43.0 2.44.0`, a package followed by
+/// every version still installed; the last one is kept
+pub fn parse_brew_list_versions(list: &str, source: &str) -> Vec<InstalledPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let name = words.next()?;
+            let version = words.last()?;
+            Some(InstalledPackage::new("brew", name, version, source))
+        })
+        .collect()
+}
+
+#[derive(serde::Deserialize)]
+struct BrewOutdated {
+    #[serde(default)]
+    formulae: Vec<BrewOutdatedEntry>,
+    #[serde(default)]
+    casks: Vec<BrewOutdatedEntry>,
+}
+
+#[derive(serde::Deserialize)]
+struct BrewOutdatedEntry {
+    name: String,
+    /// A list for formulae; older brew versions report a string for casks
+    installed_versions: serde_json::Value,
+    current_version: String,
+}
+
+/// Parse `brew outdated --json=v2`
+pub fn parse_brew_outdated(json: &str) -> Result<Vec<OutdatedPackage>> {
+    if json.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let outdated: BrewOutdated = serde_json::from_str(json)?;
+    Ok(outdated
+        .formulae
+        .iter()
+        .chain(&outdated.casks)
+        .map(|entry| {
+            let installed = match &entry.installed_versions {
+                serde_json::Value::Array(versions) => versions.last().and_then(|v| v.as_str()),
+                serde_json::Value::String(version) => Some(version.as_str()),
+                _ => None,
+            };
+            OutdatedPackage::new(&entry.name, installed, &entry.current_version)
+        })
+        .collect())
 }
@@ -1,7 +1,9 @@
 use async_trait::async_trait;
-use duct::cmd;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{
+    ErrorPolicy, InstallScope, InstalledPackage, OutdatedPackage, Plugin, PluginAction,
+    PluginActionType, PluginMetadata,
+};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
@@ -51,6 +53,7 @@ impl Plugin for CargoPlugin {
                     action_type: None,
                 },
             ],
+            run_after: vec!["rustup".to_string()],
         }
     }
 
@@ -63,11 +66,18 @@ impl Plugin for CargoPlugin {
     ) -> Result<bool> {
         match action_name {
             "cargo-list" => {
-                super::run_cmd(config, logger, true, "cargo", &["install", "--list"])?;
+                super::run_cmd(
+                    config,
+                    logger,
+                    ErrorPolicy::Fail,
+                    "cargo",
+                    &["install", "--list"],
+                )
+                .await?;
                 Ok(true)
             }
             "cargo-outdated" => {
-                super::run_cmd(config, logger, false, "cargo", &["outdated"])?;
+                super::run_cmd(config, logger, ErrorPolicy::Warn, "cargo", &["outdated"]).await?;
                 Ok(true)
             }
             _ => Ok(false),
@@ -88,13 +98,27 @@ impl Plugin for CargoPlugin {
             return Ok(());
         }
 
-        let check = cmd("cargo", &["install-update", "--version"]).run();
+        let check = super::query_cmd(
+            config,
+            logger,
+            "cargo",
+            &["install-update", "--version"],
+            &[],
+        )
+        .await;
         if check.is_err() {
             logger.error("cargo-install-update not found — install it with: cargo install cargo-install-update");
             return Ok(());
         }
 
-        super::run_cmd(config, logger, true, "cargo", &["install-update", "-a"])?;
+        super::run_cmd(
+            config,
+            logger,
+            ErrorPolicy::Fail,
+            "cargo",
+            &["install-update", "-a"],
+        )
+        .await?;
 
         Ok(())
     }
@@ -110,7 +134,7 @@ impl Plugin for CargoPlugin {
             std::fs::create_dir_all(parent)?;
         }
 
-        let check = cmd("cargo", &["backup", "--version"]).run();
+        let check = super::query_cmd(config, logger, "cargo", &["backup", "--version"], &[]).await;
         if check.is_err() {
             logger.error("cargo-backup not found — install it with: cargo install cargo-backup");
             return Ok(());
@@ -121,10 +145,11 @@ impl Plugin for CargoPlugin {
         super::run_cmd(
             config,
             logger,
-            true,
+            ErrorPolicy::Fail,
             "cargo",
             &["backup", "-o", &cargo_file],
-        )?;
+        )
+        .await?;
 
         logger.log("Success savefile written");
 
@@ -151,7 +176,7 @@ impl Plugin for CargoPlugin {
             return Ok(());
         }
 
-        let check = cmd("cargo", &["restore", "--version"]).run();
+        let check = super::query_cmd(config, logger, "cargo", &["restore", "--version"], &[]).await;
         if check.is_err() {
             logger.error("cargo-restore not found — install it with: cargo install cargo-restore");
             return Ok(());
@@ -160,7 +185,7 @@ impl Plugin for CargoPlugin {
         super::run_cmd(
             config,
             logger,
-            true,
+            ErrorPolicy::Fail,
             "cargo",
             &[
                 "restore",
@@ -170,8 +195,192 @@ impl Plugin for CargoPlugin {
                 "--backup",
                 &cargo_file,
             ],
-        )?;
+        )
+        .await?;
 
         Ok(())
     }
+
+    fn saved_packages(&self, config: &Config) -> Result<Option<Vec<InstalledPackage>>> {
+        let json = super::read_save_file(&config.cargo_file)?;
+        parse_cargo_backup(&json).map(Some)
+    }
+
+    async fn install_packages(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let specs: Vec<String> = packages
+            .iter()
+            .map(|p| super::package_spec(config, p, "@"))
+            .collect();
+        let mut args = vec!["install"];
+        args.extend(specs.iter().map(String::as_str));
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "cargo", &args).await?;
+        Ok(true)
+    }
+
+    async fn install_versions(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        // Only registry packages can be installed by version
+        if let Some(p) = packages.iter().find(|p| p.source != "crates.io") {
+            anyhow::bail!(
+                "{} was installed from {}; only crates.io packages can be reinstalled by version",
+                p.name,
+                p.source
+            );
+        }
+        for package in packages {
+            super::run_cmd(
+                config,
+                logger,
+                ErrorPolicy::Fail,
+                "cargo",
+                &["install", &package.name, "--version", &package.version],
+            )
+            .await?;
+        }
+        Ok(true)
+    }
+
+    async fn remove_packages(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let mut args = vec!["uninstall"];
+        args.extend(packages.iter().map(|p| p.name.as_str()));
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "cargo", &args).await?;
+        Ok(true)
+    }
+
+    async fn check_outdated(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<OutdatedPackage>>> {
+        if super::query_cmd(
+            config,
+            logger,
+            "cargo",
+            &["install-update", "--version"],
+            &[],
+        )
+        .await
+        .is_err()
+        {
+            anyhow::bail!(
+                "cargo-install-update not found — install it with: cargo install cargo-install-update"
+            );
+        }
+        let table =
+            super::query_cmd(config, logger, "cargo", &["install-update", "--list"], &[]).await?;
+        Ok(Some(parse_cargo_install_update(&table)))
+    }
+
+    async fn list_installed(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        let list = super::query_cmd(config, logger, "cargo", &["install", "--list"], &[]).await?;
+        Ok(Some(parse_cargo_install_list(&list, &cargo_bin_dir())))
+    }
+}
+
+/// Parse `cargo install --list`: `ripgrep v14.1.0:` per package, with the
+/// path or git URL in parentheses for packages not installed from crates.io,
+/// followed by indented binary names. A package's path is its first binary
+/// in `bin_dir`.
+pub fn parse_cargo_install_list(list: &str, bin_dir: &str) -> Vec<InstalledPackage> {
+    let mut packages: Vec<InstalledPackage> = Vec::new();
+    for line in list.lines() {
+        if line.starts_with(char::is_whitespace) {
+            if let Some(package) = packages.last_mut()
+                && package.path.is_none()
+            {
+                package.path = Some(format!("{}/{}", bin_dir, line.trim()));
+            }
+            continue;
+        }
+        let Some((name, rest)) = line
+            .trim_end()
+            .strip_suffix(':')
+            .and_then(|l| l.split_once(' '))
+        else {
+            continue;
+        };
+        let (version, source) = match rest.split_once(' ') {
+            Some((version, source)) => (version, source.trim_matches(['(', ')'])),
+            None => (rest, "crates.io"),
+        };
+        packages.push(
+            InstalledPackage::new("cargo", name, version.trim_start_matches('v'), source)
+                .with_scope(InstallScope::of_path(bin_dir)),
+        );
+    }
+    packages
+}
+
+#[derive(serde::Deserialize)]
+struct CargoBackup {
+    #[serde(default)]
+    packages: Vec<CargoBackupPackage>,
+}
+
+#[derive(serde::Deserialize)]
+struct CargoBackupPackage {
+    name: String,
+    #[serde(default)]
+    version: String,
+}
+
+/// Parse a `cargo backup` file: `{"packages": [{"name": ..., "version": ...}]}`
+pub fn parse_cargo_backup(json: &str) -> Result<Vec<InstalledPackage>> {
+    let backup: CargoBackup = serde_json::from_str(json)?;
+    Ok(backup
+        .packages
+        .iter()
+        .map(|p| InstalledPackage::new("cargo", &p.name, &p.version, "crates.io"))
+        .collect())
+}
+
+/// Where `cargo install` puts binaries: `$CARGO_INSTALL_ROOT/bin`, else
+/// `$CARGO_HOME/bin`, else `~/.cargo/bin`
+fn cargo_bin_dir() -> String {
+    let root = std::env::var("CARGO_INSTALL_ROOT")
+        .or_else(|_| std::env::var("CARGO_HOME"))
+        .unwrap_or_else(|_| format!("{}/.cargo", std::env::var("HOME").unwrap_or_default()));
+    format!("{}/bin", root.trim_end_matches('/'))
+}
+
+/// Parse the table printed by `cargo install-update --list`, keeping the
+/// packages whose "Needs update" column says Yes
+pub fn parse_cargo_install_update(table: &str) -> Vec<OutdatedPackage> {
+    table
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [name, installed, latest, "Yes"] => Some(OutdatedPackage::new(
+                    name,
+                    Some(installed.trim_start_matches('v')),
+                    latest.trim_start_matches('v'),
+                )),
+                _ => None,
+            }
+        })
+        .collect()
 }
@@ -0,0 +1,809 @@
+use anyhow::Result;
+use nix::sys::signal::{Signal, kill, killpg};
+use nix::unistd::Pid;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::io::IsTerminal;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::watch;
+
+use crate::config::Config;
+use crate::logger::Logger;
+
+const SUDO_PATH: &str = "/usr/bin/sudo";
+
+/// How long a signalled command gets to exit before it is sent SIGKILL
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// Signal that cancelled the run, `None` while it is still going
+static CANCEL: LazyLock<watch::Sender<Option<Signal>>> = LazyLock::new(|| watch::channel(None).0);
+
+tokio::task_local! {
+    static CURRENT_PLUGIN: String;
+    static COMMAND_LOG: RefCell<Vec<CommandRecord>>;
+    static ACTION_NOTES: RefCell<Vec<String>>;
+}
+
+/// Run `fut` with every command it starts attributed to `plugin`, so that
+/// per-plugin settings such as timeouts apply to them
+pub async fn scope_plugin<F: Future>(plugin: &str, fut: F) -> F::Output {
+    CURRENT_PLUGIN.scope(plugin.to_string(), fut).await
+}
+
+/// Plugin the current task is running commands for, if any
+pub fn current_plugin() -> Option<String> {
+    CURRENT_PLUGIN.try_with(|p| p.clone()).ok()
+}
+
+/// A command started through [`run_cmd`], as listed in the run report
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommandRecord {
+    pub command: String,
+    /// `None` when the process could not be started or was killed by a signal
+    pub exit_code: Option<i32>,
+    pub duration_secs: f64,
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    /// `timeout` or `cancelled` when the command was stopped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interrupted: Option<String>,
+}
+
+impl From<&CommandOutcome> for CommandRecord {
+    fn from(outcome: &CommandOutcome) -> Self {
+        Self {
+            command: outcome.command.clone(),
+            exit_code: outcome.exit_code,
+            duration_secs: outcome.duration.as_secs_f64(),
+            attempts: outcome.attempts,
+            dry_run: outcome.dry_run,
+            interrupted: outcome.interrupted.map(|i| match i {
+                Interruption::TimedOut(_) => "timeout".to_string(),
+                Interruption::Cancelled => "cancelled".to_string(),
+            }),
+        }
+    }
+}
+
+/// Run `fut` and return the commands it started, in order
+pub async fn record_commands<F: Future>(fut: F) -> (F::Output, Vec<CommandRecord>) {
+    COMMAND_LOG
+        .scope(RefCell::new(Vec::new()), async move {
+            let output = fut.await;
+            (output, COMMAND_LOG.with(RefCell::take))
+        })
+        .await
+}
+
+/// Run `fut` and return the notes it added with [`note_action`], in order
+pub async fn record_notes<F: Future>(fut: F) -> (F::Output, Vec<String>) {
+    ACTION_NOTES
+        .scope(RefCell::new(Vec::new()), async move {
+            let output = fut.await;
+            (output, ACTION_NOTES.with(RefCell::take))
+        })
+        .await
+}
+
+/// Attach `note` to the action being recorded, such as how many updates it
+/// installed. Notes are shown next to the action's status in the summary.
+pub fn note_action(note: impl Into<String>) {
+    let _ = ACTION_NOTES.try_with(|notes| notes.borrow_mut().push(note.into()));
+}
+
+fn record(outcome: &CommandOutcome) {
+    let _ = COMMAND_LOG.try_with(|log| log.borrow_mut().push(outcome.into()));
+}
+
+/// Forward `signal` to every running command and refuse to start new ones
+/// for the rest of the process
+pub fn cancel_commands(signal: Signal) {
+    CANCEL.send_replace(Some(signal));
+}
+
+/// Signal passed to [`cancel_commands`], once the run has been cancelled
+pub fn cancellation() -> Option<Signal> {
+    *CANCEL.borrow()
+}
+
+/// What to do when a command exits with a non-zero status or cannot be started
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Log an error and return `Err(CommandFailed)` so the action is marked FAIL
+    Fail,
+    /// Log a warning and keep going; the action stays OK
+    Warn,
+    /// Log the return code like any other and keep going
+    Ignore,
+}
+
+/// Why a command was stopped before it exited on its own
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interruption {
+    /// The configured timeout elapsed
+    TimedOut(Duration),
+    /// The run was cancelled with SIGINT/SIGTERM
+    Cancelled,
+}
+
+/// Result of a single command executed through [`run_cmd`]
+#[derive(Clone, Debug)]
+pub struct CommandOutcome {
+    pub command: String,
+    /// `None` when the process could not be started or was killed by a signal
+    pub exit_code: Option<i32>,
+    pub duration: Duration,
+    pub stdout: String,
+    pub stderr: String,
+    pub dry_run: bool,
+    pub interrupted: Option<Interruption>,
+    /// Number of times the command was started, including retries
+    pub attempts: u32,
+}
+
+impl CommandOutcome {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Error returned by [`run_cmd`] under [`ErrorPolicy::Fail`], and for every
+/// interrupted command regardless of policy; carries the full outcome
+#[derive(Debug)]
+pub struct CommandFailed(pub CommandOutcome);
+
+impl fmt::Display for CommandFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.interrupted {
+            Some(Interruption::TimedOut(limit)) => {
+                return write!(f, "{} timed out after {}s", self.0.command, limit.as_secs());
+            }
+            Some(Interruption::Cancelled) => {
+                return write!(f, "{} was cancelled", self.0.command);
+            }
+            None => {}
+        }
+        match self.0.exit_code {
+            Some(code) => write!(f, "{} exited with return code {}", self.0.command, code),
+            None if !self.0.stderr.is_empty() => {
+                write!(f, "{} failed: {}", self.0.command, self.0.stderr.trim())
+            }
+            None => write!(f, "{} was terminated by a signal", self.0.command),
+        }
+    }
+}
+
+impl std::error::Error for CommandFailed {}
+
+/// Handles a stdout line of a command speaking a line protocol; returns true
+/// when the line was consumed and must not be logged verbatim
+pub type LineFilter = fn(&mut Logger, &str) -> bool;
+
+/// Extra stdio plumbing for [`run_cmd_io`]
+#[derive(Clone, Copy, Default)]
+pub struct CommandIo<'a> {
+    /// Written to the command's stdin, which is closed afterwards; stdin is
+    /// inherited when `None`
+    pub stdin: Option<&'a str>,
+    pub stdout_filter: Option<LineFilter>,
+    /// Extra environment variables for the command
+    pub env: &'a [(String, String)],
+}
+
+fn validate_sudo_path() -> Result<()> {
+    if !std::path::Path::new(SUDO_PATH).exists() {
+        anyhow::bail!(
+            "sudo not found at expected path '{}'. \
+             This may indicate a compromised system or unusual PATH configuration.",
+            SUDO_PATH
+        );
+    }
+    Ok(())
+}
+
+pub fn sudo_command(
+    config: &Config,
+    program: &str,
+    args: &[&str],
+) -> Result<std::process::Command> {
+    if config.no_sudo || std::env::var("UPDATEHAULER_NO_SUDO").is_ok() {
+        let mut cmd = std::process::Command::new(program);
+        cmd.args(args);
+        return Ok(cmd);
+    }
+
+    validate_sudo_path()?;
+
+    let mut cmd = std::process::Command::new(SUDO_PATH);
+    cmd.arg(program).args(args);
+    Ok(cmd)
+}
+
+pub async fn run_with_sudo(
+    config: &Config,
+    logger: &mut Logger,
+    policy: ErrorPolicy,
+    command: &str,
+    args: &[&str],
+) -> Result<CommandOutcome> {
+    if config.no_sudo || std::env::var("UPDATEHAULER_NO_SUDO").is_ok() {
+        return run_cmd(config, logger, policy, command, args).await;
+    }
+
+    validate_sudo_path()?;
+
+    let mut sudo_args: Vec<&str> = vec![command];
+    sudo_args.extend(args);
+    run_cmd(config, logger, policy, SUDO_PATH, &sudo_args).await
+}
+
+/// Forward every line read from `reader` to the logger as soon as it arrives
+/// and return everything that was read.
+async fn forward_lines<R: AsyncRead + Unpin>(
+    reader: Option<R>,
+    mut logger: Logger,
+    header: Option<String>,
+    filter: Option<LineFilter>,
+) -> String {
+    let Some(reader) = reader else {
+        return String::new();
+    };
+    let mut reader = BufReader::new(reader);
+    let mut captured = String::new();
+    let mut buf = Vec::new();
+
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let text = String::from_utf8_lossy(&buf);
+        let line = text.trim_end_matches(['\n', '\r']);
+        captured.push_str(line);
+        captured.push('\n');
+        if filter.is_some_and(|filter| filter(&mut logger, line)) {
+            continue;
+        }
+        match header {
+            Some(ref header) => logger.log(&format!("{} → {}", header, line)),
+            None => logger.log(line),
+        }
+    }
+
+    captured
+}
+
+/// Command line used to match `timeouts.commands` keys: the program's
+/// basename followed by its arguments, without a leading sudo
+fn command_line(command: &str, args: &[&str], is_sudo: bool) -> String {
+    let mut parts: Vec<&str> = if is_sudo {
+        args.to_vec()
+    } else {
+        std::iter::once(command)
+            .chain(args.iter().copied())
+            .collect()
+    };
+    if let Some(first) = parts.first_mut() {
+        *first = std::path::Path::new(*first)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(first);
+    }
+    parts.join(" ")
+}
+
+/// Processes started by `root`, directly or not, given the `(pid, ppid)`
+/// pairs of a process table
+pub fn descendants(table: &[(i32, i32)], root: i32) -> Vec<i32> {
+    let mut found = Vec::new();
+    let mut parents = vec![root];
+    while let Some(parent) = parents.pop() {
+        for &(pid, ppid) in table {
+            if ppid == parent && pid != root && !found.contains(&pid) {
+                found.push(pid);
+                parents.push(pid);
+            }
+        }
+    }
+    found
+}
+
+/// Processes started by `root`, as listed by `ps`
+async fn process_descendants(root: i32) -> Result<Vec<i32>> {
+    let output = Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid="])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await?;
+    if !output.status.success() {
+        anyhow::bail!("ps exited with {}", output.status);
+    }
+    let table: Vec<(i32, i32)> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some((fields.next()?.parse().ok()?, fields.next()?.parse().ok()?))
+        })
+        .collect();
+    Ok(descendants(&table, root))
+}
+
+/// Send `signal` to the command's process group, or without one to the
+/// command and the processes in `tree`. Returns how many of those could not
+/// be signalled for lack of permission, such as ones running under sudo.
+fn send_signal(pid: Pid, own_group: bool, tree: &[i32], signal: Signal) -> usize {
+    if own_group {
+        let _ = killpg(pid, signal);
+        return 0;
+    }
+    let _ = kill(pid, signal);
+    tree.iter()
+        .filter(|&&p| kill(Pid::from_raw(p), signal) == Err(nix::errno::Errno::EPERM))
+        .count()
+}
+
+/// Send `signal` to the command, escalate to SIGKILL after [`KILL_GRACE`] and
+/// collect whatever the command produced before it went away.
+///
+/// A command outside its own process group may have started processes the
+/// signal would not reach; they are looked up with `ps` and signalled one
+/// by one, with a warning for any that cannot be.
+async fn stop<F: Future>(
+    mut run: Pin<&mut F>,
+    logger: &Logger,
+    short_cmd: &str,
+    pid: Option<u32>,
+    own_group: bool,
+    signal: Signal,
+) -> Option<F::Output> {
+    let Some(pid) = pid.and_then(|p| i32::try_from(p).ok()) else {
+        return tokio::time::timeout(KILL_GRACE, run).await.ok();
+    };
+    let mut tree: Vec<i32> = Vec::new();
+    let mut warned = false;
+    for signal in [signal, Signal::SIGKILL] {
+        if !own_group {
+            match process_descendants(pid).await {
+                // Keep processes found before: they may have been orphaned since
+                Ok(found) => {
+                    for p in found {
+                        if !tree.contains(&p) {
+                            tree.push(p);
+                        }
+                    }
+                }
+                Err(e) if !warned => {
+                    warned = true;
+                    logger.clone().warn(&format!(
+                        "Could not list the processes started by {} ({:#}); only {} itself is signalled",
+                        short_cmd, e, short_cmd
+                    ));
+                }
+                Err(_) => {}
+            }
+        }
+        let unreachable = send_signal(Pid::from_raw(pid), own_group, &tree, signal);
+        if unreachable > 0 && !warned {
+            warned = true;
+            logger.clone().warn(&format!(
+                "{} processes started by {} cannot be signalled: permission denied",
+                unreachable, short_cmd
+            ));
+        }
+        if let Ok(result) = tokio::time::timeout(KILL_GRACE, run.as_mut()).await {
+            return Some(result);
+        }
+    }
+    None
+}
+
+/// Resolve once [`cancel_commands`] has been called, with the forwarded signal
+async fn wait_for_cancel() -> Signal {
+    let mut cancel_rx = CANCEL.subscribe();
+    let signal = cancel_rx
+        .wait_for(Option::is_some)
+        .await
+        .map(|signal| signal.unwrap_or(Signal::SIGTERM));
+    match signal {
+        Ok(signal) => signal,
+        Err(_) => std::future::pending().await,
+    }
+}
+
+/// Human-readable final status of a command, as shown after its `→`
+fn describe(outcome: &CommandOutcome) -> String {
+    match (outcome.interrupted, outcome.exit_code) {
+        (Some(Interruption::TimedOut(limit)), _) => {
+            format!("Timed out after {}s", limit.as_secs())
+        }
+        (Some(Interruption::Cancelled), _) => "Cancelled".to_string(),
+        (None, Some(code)) => format!("Return code {}", code),
+        (None, None) if !outcome.stderr.is_empty() => format!("Error: {}", outcome.stderr),
+        (None, None) => "Terminated by signal".to_string(),
+    }
+}
+
+/// Output fragments (lower-case) of failures worth retrying: the network
+/// was down or timed out, or another process held a package manager lock.
+/// Generic messages such as "connection refused" or "failed to fetch" are
+/// left out, since a stopped local daemon or a 404 fails the same way again.
+const TRANSIENT_PATTERNS: &[&str] = &[
+    // network
+    "could not resolve host",
+    "temporary failure in name resolution",
+    "name or service not known",
+    "nodename nor servname provided",
+    "network is unreachable",
+    "no route to host",
+    "connection timed out",
+    "connection reset",
+    "operation timed out",
+    "tls handshake timeout",
+    "503 service unavailable",
+    "502 bad gateway",
+    "504 gateway",
+    "eai_again",
+    "etimedout",
+    "econnreset",
+    "enotfound",
+    // lock contention
+    "could not get lock",
+    "unable to acquire the dpkg frontend lock",
+    "unable to lock directory",
+    "waiting for cache lock",
+    "another app is currently holding the yum lock",
+    "blocking waiting for file lock",
+    "change in progress",
+    "database is locked",
+];
+
+/// Whether a failed command looks like it may succeed when simply run again.
+/// Timeouts, cancellations and commands that could not be started never are.
+pub fn is_transient(outcome: &CommandOutcome) -> bool {
+    if outcome.interrupted.is_some() || outcome.success() || outcome.exit_code.is_none() {
+        return false;
+    }
+    let stdout = outcome.stdout.to_lowercase();
+    let stderr = outcome.stderr.to_lowercase();
+    TRANSIENT_PATTERNS
+        .iter()
+        .any(|p| stderr.contains(p) || stdout.contains(p))
+}
+
+/// How [`execute_once`] starts and supervises a command, fixed across retries
+struct Launch<'a> {
+    short_cmd: &'a str,
+    own_group: bool,
+    timeout: Option<Duration>,
+    io: CommandIo<'a>,
+}
+
+/// Spawn the command once, streaming its output, and wait for it to exit,
+/// time out or be cancelled
+async fn execute_once(
+    config: &Config,
+    logger: &Logger,
+    command: &str,
+    args: &[&str],
+    launch: &Launch<'_>,
+) -> CommandOutcome {
+    let Launch {
+        short_cmd,
+        own_group,
+        timeout,
+        io,
+    } = *launch;
+    let cmd_str = format!("{} {}", command, args.join(" "));
+    let started = Instant::now();
+    let mut cmd = Command::new(command);
+    cmd.args(args)
+        .envs(io.env.iter().map(|(k, v)| (k, v)))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if io.stdin.is_some() {
+        cmd.stdin(Stdio::piped());
+    }
+    if own_group {
+        cmd.process_group(0);
+    }
+
+    match cmd.spawn() {
+        Ok(mut child) => {
+            let (stdout_header, stderr_header) = if config.show_header {
+                (
+                    Some(short_cmd.to_string()),
+                    Some(format!("{} [stderr]", short_cmd)),
+                )
+            } else {
+                (None, None)
+            };
+
+            if let (Some(input), Some(mut stdin)) = (io.stdin, child.stdin.take()) {
+                let input = input.to_string();
+                // A command that exits without reading its input is not an error
+                tokio::spawn(async move {
+                    let _ = stdin.write_all(input.as_bytes()).await;
+                });
+            }
+
+            let pid = child.id();
+            let stdout_reader = child.stdout.take();
+            let stderr_reader = child.stderr.take();
+            let run = async {
+                let (stdout, stderr) = tokio::join!(
+                    forward_lines(
+                        stdout_reader,
+                        logger.clone(),
+                        stdout_header,
+                        io.stdout_filter
+                    ),
+                    forward_lines(stderr_reader, logger.clone(), stderr_header, None),
+                );
+                (stdout, stderr, child.wait().await)
+            };
+            tokio::pin!(run);
+
+            let deadline = async {
+                match timeout {
+                    Some(limit) => {
+                        tokio::time::sleep(limit).await;
+                        limit
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+
+            let finished = tokio::select! {
+                result = &mut run => Ok(result),
+                limit = deadline => Err((Interruption::TimedOut(limit), Signal::SIGTERM)),
+                signal = wait_for_cancel() => Err((Interruption::Cancelled, signal)),
+            };
+
+            let (result, interrupted) = match finished {
+                Ok(result) => (Some(result), None),
+                Err((interruption, signal)) => (
+                    stop(run.as_mut(), logger, short_cmd, pid, own_group, signal).await,
+                    Some(interruption),
+                ),
+            };
+
+            let (stdout, stderr, status) = match result {
+                Some((stdout, stderr, status)) => (stdout, stderr, status.ok()),
+                None => (String::new(), String::new(), None),
+            };
+
+            CommandOutcome {
+                command: cmd_str,
+                exit_code: status.and_then(|s| s.code()),
+                duration: started.elapsed(),
+                stdout,
+                stderr,
+                dry_run: false,
+                interrupted,
+                attempts: 1,
+            }
+        }
+        Err(e) => CommandOutcome {
+            command: cmd_str,
+            exit_code: None,
+            duration: started.elapsed(),
+            stdout: String::new(),
+            stderr: e.to_string(),
+            dry_run: false,
+            interrupted: None,
+            attempts: 1,
+        },
+    }
+}
+
+/// Run a read-only query and return its stdout, which is captured instead of
+/// logged. Queries change nothing, so they also run in dry-run mode.
+/// `ok_codes` lists non-zero exit codes that still mean success, e.g. 100
+/// for `dnf check-update`, which reports "updates found" that way.
+pub async fn query_cmd(
+    config: &Config,
+    logger: &mut Logger,
+    command: &str,
+    args: &[&str],
+    ok_codes: &[i32],
+) -> Result<String> {
+    let live;
+    let config = if config.dry_run {
+        live = Config {
+            dry_run: false,
+            ..config.clone()
+        };
+        &live
+    } else {
+        config
+    };
+    let io = CommandIo {
+        stdout_filter: Some(|_, _| true),
+        ..CommandIo::default()
+    };
+    let outcome = run_cmd_io(config, logger, ErrorPolicy::Ignore, command, args, io).await?;
+    if !outcome.success() && !outcome.exit_code.is_some_and(|c| ok_codes.contains(&c)) {
+        anyhow::bail!(
+            "{} failed ({})",
+            outcome.command,
+            outcome
+                .exit_code
+                .map_or("no exit code".to_string(), |c| format!("exit code {}", c))
+        );
+    }
+    Ok(outcome.stdout)
+}
+
+pub async fn run_cmd(
+    config: &Config,
+    logger: &mut Logger,
+    policy: ErrorPolicy,
+    command: &str,
+    args: &[&str],
+) -> Result<CommandOutcome> {
+    run_cmd_io(config, logger, policy, command, args, CommandIo::default()).await
+}
+
+/// [`run_cmd`] for commands that read a request from stdin and may answer
+/// with protocol lines on stdout
+pub async fn run_cmd_io(
+    config: &Config,
+    logger: &mut Logger,
+    policy: ErrorPolicy,
+    command: &str,
+    args: &[&str],
+    io: CommandIo<'_>,
+) -> Result<CommandOutcome> {
+    let cmd_str = format!("{} {}", command, args.join(" "));
+
+    let is_sudo = std::path::Path::new(command)
+        .file_name()
+        .is_some_and(|n| n == "sudo");
+    let short_cmd = if is_sudo && args.len() >= 4 {
+        args[3]
+    } else {
+        command
+    };
+
+    if config.dry_run {
+        if config.show_header {
+            logger.log(&format!("{} → Start (DRY-RUN)", cmd_str));
+        }
+        logger.log(&format!("Would execute: {}", cmd_str));
+        if config.show_header {
+            logger.log(&format!("{} → Return code 0 (DRY-RUN)", cmd_str));
+        }
+        let outcome = CommandOutcome {
+            command: cmd_str,
+            exit_code: Some(0),
+            duration: Duration::ZERO,
+            stdout: String::new(),
+            stderr: String::new(),
+            dry_run: true,
+            interrupted: None,
+            attempts: 1,
+        };
+        record(&outcome);
+        return Ok(outcome);
+    }
+
+    if cancellation().is_some() {
+        logger.log(&format!("{} → Skipped (run cancelled)", cmd_str));
+        return Err(CommandFailed(CommandOutcome {
+            command: cmd_str,
+            exit_code: None,
+            duration: Duration::ZERO,
+            stdout: String::new(),
+            stderr: String::new(),
+            dry_run: false,
+            interrupted: Some(Interruption::Cancelled),
+            attempts: 0,
+        })
+        .into());
+    }
+
+    let line = command_line(command, args, is_sudo);
+    let plugin = current_plugin();
+    let timeout = config.timeouts.resolve(plugin.as_deref(), &line);
+    let max_attempts = if config.retry.applies_to(plugin.as_deref(), &line) {
+        config.retry.max_attempts.max(1)
+    } else {
+        1
+    };
+
+    // Unattended, each command gets its own process group so a timeout or
+    // cancellation can take down everything it spawned. From a terminal,
+    // commands stay in its foreground group: a background group is stopped
+    // with SIGTTIN as soon as anything in it prompts (sudo passwords, git
+    // credentials, confirmations), and Ctrl-C reaches the whole group anyway.
+    let own_group = !std::io::stdin().is_terminal();
+
+    let launch = Launch {
+        short_cmd,
+        own_group,
+        timeout,
+        io,
+    };
+
+    let mut attempt = 1;
+    let outcome = loop {
+        if config.show_header {
+            if attempt == 1 {
+                logger.log(&format!("{} → Start", cmd_str));
+            } else {
+                logger.log(&format!(
+                    "{} → Start (attempt {}/{})",
+                    cmd_str, attempt, max_attempts
+                ));
+            }
+        }
+
+        let mut outcome = execute_once(config, logger, command, args, &launch).await;
+        outcome.attempts = attempt;
+
+        if attempt >= max_attempts || !is_transient(&outcome) {
+            break outcome;
+        }
+
+        let delay = config.retry.delay(attempt);
+        logger.warn(&format!(
+            "{} → {}, transient failure (attempt {}/{}), retrying in {}s",
+            outcome.command,
+            describe(&outcome),
+            attempt,
+            max_attempts,
+            delay.as_secs()
+        ));
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = wait_for_cancel() => {
+                outcome.interrupted = Some(Interruption::Cancelled);
+                break outcome;
+            }
+        }
+        attempt += 1;
+    };
+
+    record(&outcome);
+    let status = describe(&outcome);
+
+    if outcome.success() && outcome.interrupted.is_none() {
+        if config.show_header {
+            logger.log(&format!("{} → {}", outcome.command, status));
+        }
+        return Ok(outcome);
+    }
+
+    if outcome.interrupted.is_some() {
+        logger.error(&format!("{} → {}", outcome.command, status));
+        return Err(CommandFailed(outcome).into());
+    }
+
+    match policy {
+        ErrorPolicy::Fail => {
+            if config.show_header {
+                logger.error(&format!("{} → {}", outcome.command, status));
+            }
+            Err(CommandFailed(outcome).into())
+        }
+        ErrorPolicy::Warn => {
+            logger.warn(&format!("{} → {}", outcome.command, status));
+            Ok(outcome)
+        }
+        ErrorPolicy::Ignore => {
+            if config.show_header {
+                logger.log(&format!("{} → {}", outcome.command, status));
+            }
+            Ok(outcome)
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use which::which;
+
+use super::{ErrorPolicy, Plugin, PluginAction, PluginActionType, PluginMetadata, PluginRegistry};
+use crate::config::{Config, CustomPluginConfig};
+use crate::insights::Insights;
+use crate::logger::Logger;
+
+/// Expand a leading `~` to `$HOME`, since custom commands do not go through a shell
+fn expand_home(word: &str) -> String {
+    match (word.strip_prefix('~'), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home, rest)
+        }
+        _ => word.to_string(),
+    }
+}
+
+/// Binary whose presence on PATH makes a custom plugin available
+pub fn detect_binary(def: &CustomPluginConfig) -> Option<String> {
+    def.detect.clone().or_else(|| {
+        def.update
+            .first()
+            .and_then(|c| shell_words::split(c).ok())
+            .and_then(|words| words.into_iter().next())
+    })
+}
+
+pub fn custom_plugin_available(def: &CustomPluginConfig) -> bool {
+    detect_binary(def).is_some_and(|bin| which(expand_home(&bin)).is_ok())
+}
+
+/// Register every plugin declared under `custom_plugins:`, refusing names
+/// already taken by a built-in plugin
+pub fn register_custom_plugins(registry: &mut PluginRegistry, config: &Config) -> Result<()> {
+    for def in &config.custom_plugins {
+        if registry.get_plugin(&def.name).is_some() {
+            anyhow::bail!(
+                "custom plugin '{}' conflicts with the built-in plugin of the same name",
+                def.name
+            );
+        }
+        registry.register(Box::new(CustomPlugin::new(def.clone())));
+    }
+    Ok(())
+}
+
+/// Plugin built at startup from a `custom_plugins:` entry in the config file
+pub struct CustomPlugin {
+    def: CustomPluginConfig,
+}
+
+impl CustomPlugin {
+    pub fn new(def: CustomPluginConfig) -> Self {
+        Self { def }
+    }
+
+    fn save_file(&self) -> Option<PathBuf> {
+        self.def
+            .save_file
+            .as_deref()
+            .map(|f| PathBuf::from(expand_home(f)))
+    }
+
+    /// Run `commands` in order and return their combined stdout
+    async fn run_commands(
+        &self,
+        config: &Config,
+        logger: &mut Logger,
+        commands: &[String],
+    ) -> Result<String> {
+        let save_file = self
+            .save_file()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut stdout = String::new();
+
+        for command in commands {
+            let words: Vec<String> = shell_words::split(command)
+                .with_context(|| {
+                    format!("Invalid command in plugin {}: {}", self.def.name, command)
+                })?
+                .iter()
+                .map(|w| expand_home(&w.replace("{save_file}", &save_file)))
+                .collect();
+            let Some((program, args)) = words.split_first() else {
+                continue;
+            };
+            let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+            let outcome = super::run_cmd(config, logger, ErrorPolicy::Fail, program, &args).await?;
+            stdout.push_str(&outcome.stdout);
+        }
+
+        Ok(stdout)
+    }
+}
+
+#[async_trait]
+impl Plugin for CustomPlugin {
+    fn name(&self) -> &str {
+        &self.def.name
+    }
+
+    fn get_metadata(&self) -> PluginMetadata {
+        let name = &self.def.name;
+        let mut actions = vec![PluginAction {
+            name: name.clone(),
+            description: format!("Update {} packages", name),
+            action_type: Some(PluginActionType::Update),
+        }];
+        if !self.def.save.is_empty() {
+            actions.push(PluginAction {
+                name: format!("{}-save", name),
+                description: format!("Save {} state", name),
+                action_type: Some(PluginActionType::Save),
+            });
+        }
+        if !self.def.restore.is_empty() {
+            actions.push(PluginAction {
+                name: format!("{}-restore", name),
+                description: format!("Restore {} state", name),
+                action_type: Some(PluginActionType::Restore),
+            });
+        }
+        for action in self.def.actions.keys() {
+            actions.push(PluginAction {
+                name: format!("{}-{}", name, action),
+                description: format!("Run custom {} action '{}'", name, action),
+                action_type: None,
+            });
+        }
+
+        PluginMetadata {
+            name: name.clone(),
+            description: self
+                .def
+                .description
+                .clone()
+                .unwrap_or_else(|| format!("Custom plugin {}", name)),
+            actions,
+            run_after: self.def.run_after.clone(),
+        }
+    }
+
+    async fn check_available(&self, _config: &Config, _insights: &Insights) -> bool {
+        custom_plugin_available(&self.def)
+    }
+
+    async fn update(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<()> {
+        self.run_commands(config, logger, &self.def.update).await?;
+        Ok(())
+    }
+
+    async fn save(&self, config: &Config, _insights: &Insights, logger: &mut Logger) -> Result<()> {
+        if self.def.save.is_empty() {
+            logger.log("No save needed for this plugin");
+            return Ok(());
+        }
+        let stdout = self.run_commands(config, logger, &self.def.save).await?;
+
+        if let Some(save_file) = self.save_file() {
+            if config.dry_run {
+                logger.log(&format!("Would write savefile {}", save_file.display()));
+                return Ok(());
+            }
+            if let Some(parent) = save_file.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&save_file, stdout)
+                .with_context(|| format!("Failed to write {}", save_file.display()))?;
+            logger.log(&format!("Success savefile {} written", save_file.display()));
+        }
+        Ok(())
+    }
+
+    async fn restore(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<()> {
+        if self.def.restore.is_empty() {
+            logger.log("No restore needed for this plugin");
+            return Ok(());
+        }
+        if let Some(save_file) = self.save_file()
+            && !save_file.exists()
+        {
+            anyhow::bail!(
+                "missing dependency — {}'s backup file {} is not found",
+                self.def.name,
+                save_file.display()
+            );
+        }
+        self.run_commands(config, logger, &self.def.restore).await?;
+        Ok(())
+    }
+
+    async fn handle_custom_action(
+        &self,
+        action_name: &str,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<bool> {
+        let Some(commands) = action_name
+            .strip_prefix(self.def.name.as_str())
+            .and_then(|a| a.strip_prefix('-'))
+            .and_then(|a| self.def.actions.get(a))
+        else {
+            return Ok(false);
+        };
+        self.run_commands(config, logger, commands).await?;
+        Ok(true)
+    }
+}
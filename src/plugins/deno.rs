@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{ErrorPolicy, Plugin, PluginAction, PluginActionType, PluginMetadata};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
@@ -23,6 +23,7 @@ impl Plugin for DenoPlugin {
                 description: "Upgrade Deno to the latest version".to_string(),
                 action_type: Some(PluginActionType::Update),
             }],
+            run_after: Vec::new(),
         }
     }
 
@@ -36,7 +37,7 @@ impl Plugin for DenoPlugin {
         _insights: &Insights,
         logger: &mut Logger,
     ) -> Result<()> {
-        super::run_cmd(config, logger, true, "deno", &["upgrade"])?;
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "deno", &["upgrade"]).await?;
         Ok(())
     }
 }
@@ -1,27 +1,23 @@
 use async_trait::async_trait;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{ErrorPolicy, Plugin, PluginAction, PluginActionType, PluginMetadata};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
 use anyhow::Result;
-use duct::cmd;
 
 pub struct DockerPlugin;
 
 impl DockerPlugin {
-    fn daemon_running(logger: &mut Logger) -> bool {
-        let result = cmd("docker", &["info"])
-            .stdout_capture()
-            .stderr_capture()
-            .run();
-        match result {
-            Ok(output) => output.status.success(),
-            Err(_) => {
-                logger.log("Docker daemon is not running, skipping prune");
-                false
-            }
+    async fn daemon_running(config: &Config, logger: &mut Logger) -> bool {
+        if super::query_cmd(config, logger, "docker", &["info"], &[])
+            .await
+            .is_ok()
+        {
+            return true;
         }
+        logger.log("Docker daemon is not running, skipping prune");
+        false
     }
 }
 
@@ -40,6 +36,7 @@ impl Plugin for DockerPlugin {
                 description: "Prune unused Docker images, containers, and networks".to_string(),
                 action_type: Some(PluginActionType::Update),
             }],
+            run_after: Vec::new(),
         }
     }
 
@@ -53,16 +50,17 @@ impl Plugin for DockerPlugin {
         _insights: &Insights,
         logger: &mut Logger,
     ) -> Result<()> {
-        if !Self::daemon_running(logger) {
+        if !Self::daemon_running(config, logger).await {
             return Ok(());
         }
         super::run_cmd(
             config,
             logger,
-            true,
+            ErrorPolicy::Fail,
             "docker",
             &["system", "prune", "--force"],
-        )?;
+        )
+        .await?;
         Ok(())
     }
 }
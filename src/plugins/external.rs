@@ -0,0 +1,497 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::ffi::OsStr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::{
+    CommandIo, ErrorPolicy, Plugin, PluginAction, PluginActionType, PluginMetadata, PluginRegistry,
+};
+use crate::config::{Config, RESERVED_PLUGIN_NAMES, is_valid_plugin_name};
+use crate::insights::Insights;
+use crate::logger::Logger;
+
+/// File name prefix of external plugin executables
+pub const EXTERNAL_PLUGIN_PREFIX: &str = "updatehauler-plugin-";
+
+/// Version of the JSON-over-stdio protocol spoken with external plugins
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Deadline for the `metadata` and `check_available` requests, unless
+/// `timeouts.commands` sets one for them
+const QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Request written as one JSON line to the plugin's stdin
+#[derive(Serialize)]
+struct Request<'a> {
+    protocol: u32,
+    request: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action: Option<&'a str>,
+    debug: bool,
+    os: &'a str,
+    arch: &'a str,
+}
+
+fn request_json(config: &Config, request: &str, action: Option<&str>) -> String {
+    let request = Request {
+        protocol: PROTOCOL_VERSION,
+        request,
+        action,
+        debug: config.debug,
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+    };
+    let mut json = serde_json::to_string(&request).unwrap_or_default();
+    json.push('\n');
+    json
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum ActionKind {
+    Save,
+    Restore,
+    #[default]
+    Custom,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ActionSpec {
+    name: String,
+    description: Option<String>,
+    #[serde(rename = "type", default)]
+    kind: ActionKind,
+}
+
+/// Result of the `metadata` request
+#[derive(Debug, Deserialize)]
+struct MetadataResponse {
+    protocol: u32,
+    name: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    actions: Vec<ActionSpec>,
+    #[serde(default)]
+    run_after: Vec<String>,
+}
+
+/// Result of every other request; fields not meaningful for a request are ignored
+#[derive(Debug, Default, Deserialize)]
+struct Response {
+    ok: Option<bool>,
+    error: Option<String>,
+    available: Option<bool>,
+    handled: Option<bool>,
+}
+
+/// Log `{"type":"log"}` lines at their level and keep `{"type":"result"}`
+/// lines out of the log; anything else is logged verbatim
+fn handle_protocol_line(logger: &mut Logger, line: &str) -> bool {
+    let Ok(Value::Object(message)) = serde_json::from_str::<Value>(line) else {
+        return false;
+    };
+    match message.get("type").and_then(Value::as_str) {
+        Some("log") => {
+            let text = message
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            match message.get("level").and_then(Value::as_str) {
+                Some("warn") => logger.warn(text),
+                Some("error") => logger.error(text),
+                _ => logger.log(text),
+            }
+            true
+        }
+        Some("result") => true,
+        _ => false,
+    }
+}
+
+/// Last `{"type":"result"}` line a plugin wrote to stdout
+fn find_result(stdout: &str) -> Option<Value> {
+    stdout
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .find(|v| v.get("type").and_then(Value::as_str) == Some("result"))
+}
+
+/// Send `request` to the plugin and wait for its result without logging its
+/// stdout; used for the quick `metadata` and `check_available` requests.
+/// Like [`super::query_cmd`] these change nothing, so they also run in
+/// dry-run mode.
+async fn query(path: &Path, config: &Config, logger: &mut Logger, request: &str) -> Result<Value> {
+    let program = path.to_string_lossy();
+    let mut config = Config {
+        dry_run: false,
+        ..config.clone()
+    };
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    config
+        .timeouts
+        .commands
+        .entry(format!("{} {}", file_name, request))
+        .or_insert(QUERY_TIMEOUT.as_secs());
+
+    let input = request_json(&config, request, None);
+    let io = CommandIo {
+        stdin: Some(&input),
+        stdout_filter: Some(|_, _| true),
+        ..CommandIo::default()
+    };
+    let outcome = super::run_cmd_io(
+        &config,
+        logger,
+        ErrorPolicy::Ignore,
+        &program,
+        &[request],
+        io,
+    )
+    .await?;
+
+    if !outcome.success() {
+        anyhow::bail!(
+            "'{}' request failed: {}",
+            request,
+            super::CommandFailed(outcome)
+        );
+    }
+    find_result(&outcome.stdout)
+        .with_context(|| format!("no result line in answer to '{}'", request))
+}
+
+/// `updatehauler-plugin-<name>` executables in the directories of
+/// `search_path` (a PATH-style list); as with a shell lookup the first
+/// directory providing a name wins. Names that are not valid plugin names
+/// (e.g. containing '-' or '.') are ignored.
+pub fn discover_external_plugins(search_path: &OsStr) -> Vec<(String, PathBuf)> {
+    let mut found: Vec<(String, PathBuf)> = Vec::new();
+
+    for dir in std::env::split_paths(search_path) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut entries: Vec<_> = entries.flatten().collect();
+        entries.sort_by_key(|e| e.file_name());
+
+        for entry in entries {
+            let file_name = entry.file_name();
+            let Some(name) = file_name
+                .to_str()
+                .and_then(|n| n.strip_prefix(EXTERNAL_PLUGIN_PREFIX))
+            else {
+                continue;
+            };
+            if !is_valid_plugin_name(name) || found.iter().any(|(n, _)| n == name) {
+                continue;
+            }
+            let path = entry.path();
+            let executable = std::fs::metadata(&path)
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+            if executable {
+                found.push((name.to_string(), path));
+            }
+        }
+    }
+
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+    found
+}
+
+/// Load every external plugin found on PATH. A plugin that cannot be
+/// loaded or whose name is already taken in `registry` is skipped with a
+/// warning, so a broken executable never blocks a run.
+pub async fn load_external_plugins(
+    registry: &PluginRegistry<'_>,
+    config: &Config,
+    logger: &mut Logger,
+) -> Vec<ExternalPlugin> {
+    let mut loaded = Vec::new();
+    let Some(search_path) = std::env::var_os("PATH") else {
+        return loaded;
+    };
+    if !config.external_plugins {
+        return loaded;
+    }
+    for (name, path) in discover_external_plugins(&search_path) {
+        if registry.get_plugin(&name).is_some() || RESERVED_PLUGIN_NAMES.contains(&name.as_str()) {
+            logger.warn(&format!(
+                "Ignoring external plugin {}: the name '{}' is already taken",
+                path.display(),
+                name
+            ));
+            continue;
+        }
+        match ExternalPlugin::load(&name, &path, config, logger).await {
+            Ok(plugin) => loaded.push(plugin),
+            Err(e) => logger.warn(&format!(
+                "Ignoring external plugin {}: {:#}",
+                path.display(),
+                e
+            )),
+        }
+    }
+    loaded
+}
+
+/// Adapter for an `updatehauler-plugin-<name>` executable. Every request
+/// starts the executable with the request name as its only argument and a
+/// JSON request line on stdin; it answers with JSON lines on stdout.
+#[derive(Clone)]
+pub struct ExternalPlugin {
+    name: String,
+    path: PathBuf,
+    description: String,
+    actions: Vec<ActionSpec>,
+    run_after: Vec<String>,
+    /// Logger for the `check_available` request, which gets none passed in
+    logger: Logger,
+}
+
+impl ExternalPlugin {
+    /// Ask the executable at `path` for its metadata
+    pub async fn load(
+        name: &str,
+        path: &Path,
+        config: &Config,
+        logger: &mut Logger,
+    ) -> Result<Self> {
+        let metadata: MetadataResponse =
+            serde_json::from_value(query(path, config, logger, "metadata").await?)
+                .context("invalid metadata")?;
+
+        if metadata.protocol != PROTOCOL_VERSION {
+            anyhow::bail!(
+                "speaks protocol version {}, expected {}",
+                metadata.protocol,
+                PROTOCOL_VERSION
+            );
+        }
+        if let Some(ref reported) = metadata.name
+            && reported != name
+        {
+            anyhow::bail!(
+                "reports the name '{}' but is installed as '{}'",
+                reported,
+                name
+            );
+        }
+        for action in &metadata.actions {
+            let valid = !action.name.is_empty()
+                && action
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_-".contains(c));
+            if !valid || action.name == "update" {
+                anyhow::bail!("invalid action name '{}'", action.name);
+            }
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            description: metadata
+                .description
+                .unwrap_or_else(|| format!("External plugin {}", name)),
+            actions: metadata.actions,
+            run_after: metadata.run_after,
+            logger: logger.clone(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Declared action of `kind` invoked as `action_name`
+    /// (`<plugin>-<action>`), or the first one of that kind when
+    /// `action_name` is `None`
+    fn action_of_kind(&self, kind: ActionKind, action_name: Option<&str>) -> Option<&ActionSpec> {
+        let action = action_name.and_then(|a| {
+            a.strip_prefix(self.name.as_str())
+                .and_then(|a| a.strip_prefix('-'))
+        });
+        self.actions
+            .iter()
+            .filter(|spec| spec.kind == kind)
+            .find(|spec| action.is_none_or(|a| spec.name == a))
+    }
+
+    /// Send a `save` or `restore` request naming the action it was invoked
+    /// for, so a plugin declaring several of them can tell which one runs
+    async fn request_action(
+        &self,
+        config: &Config,
+        logger: &mut Logger,
+        kind: ActionKind,
+        action_name: Option<&str>,
+    ) -> Result<()> {
+        let (request, noop) = match kind {
+            ActionKind::Save => ("save", "No save needed for this plugin"),
+            ActionKind::Restore => ("restore", "No restore needed for this plugin"),
+            ActionKind::Custom => unreachable!("custom actions go through handle_custom_action"),
+        };
+        let Some(spec) = self.action_of_kind(kind, action_name) else {
+            logger.log(noop);
+            return Ok(());
+        };
+        self.request(config, logger, request, Some(&spec.name))
+            .await?;
+        Ok(())
+    }
+
+    /// Run a request through [`super::run_cmd_io`], so it is logged, timed out
+    /// and cancelled like any other command, and check its result
+    async fn request(
+        &self,
+        config: &Config,
+        logger: &mut Logger,
+        request: &str,
+        action: Option<&str>,
+    ) -> Result<Response> {
+        let input = request_json(config, request, action);
+        let io = CommandIo {
+            stdin: Some(&input),
+            stdout_filter: Some(handle_protocol_line),
+            ..CommandIo::default()
+        };
+        let program = self.path.to_string_lossy();
+        let outcome =
+            super::run_cmd_io(config, logger, ErrorPolicy::Fail, &program, &[request], io).await?;
+
+        let response: Response = match find_result(&outcome.stdout) {
+            Some(result) => serde_json::from_value(result)
+                .with_context(|| format!("invalid result from {}", self.name))?,
+            None => Response::default(),
+        };
+        if response.ok == Some(false) {
+            anyhow::bail!(
+                "{} {} failed: {}",
+                self.name,
+                action.unwrap_or(request),
+                response.error.as_deref().unwrap_or("no reason given")
+            );
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl Plugin for ExternalPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_metadata(&self) -> PluginMetadata {
+        let mut actions = vec![PluginAction {
+            name: self.name.clone(),
+            description: format!("Update {} packages", self.name),
+            action_type: Some(PluginActionType::Update),
+        }];
+        for action in &self.actions {
+            actions.push(PluginAction {
+                name: format!("{}-{}", self.name, action.name),
+                description: action
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| format!("Run {} action '{}'", self.name, action.name)),
+                action_type: match action.kind {
+                    ActionKind::Save => Some(PluginActionType::Save),
+                    ActionKind::Restore => Some(PluginActionType::Restore),
+                    ActionKind::Custom => None,
+                },
+            });
+        }
+
+        PluginMetadata {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            actions,
+            run_after: self.run_after.clone(),
+        }
+    }
+
+    async fn check_available(&self, config: &Config, _insights: &Insights) -> bool {
+        let mut logger = self.logger.clone();
+        query(&self.path, config, &mut logger, "check_available")
+            .await
+            .ok()
+            .and_then(|value| serde_json::from_value::<Response>(value).ok())
+            .and_then(|response| response.available)
+            .unwrap_or(false)
+    }
+
+    async fn update(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<()> {
+        self.request(config, logger, "update", None).await?;
+        Ok(())
+    }
+
+    async fn save(&self, config: &Config, _insights: &Insights, logger: &mut Logger) -> Result<()> {
+        self.request_action(config, logger, ActionKind::Save, None)
+            .await
+    }
+
+    async fn restore(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<()> {
+        self.request_action(config, logger, ActionKind::Restore, None)
+            .await
+    }
+
+    async fn save_action(
+        &self,
+        action_name: &str,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<()> {
+        self.request_action(config, logger, ActionKind::Save, Some(action_name))
+            .await
+    }
+
+    async fn restore_action(
+        &self,
+        action_name: &str,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<()> {
+        self.request_action(config, logger, ActionKind::Restore, Some(action_name))
+            .await
+    }
+
+    async fn handle_custom_action(
+        &self,
+        action_name: &str,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<bool> {
+        let Some(action) = action_name
+            .strip_prefix(self.name.as_str())
+            .and_then(|a| a.strip_prefix('-'))
+            .filter(|a| self.actions.iter().any(|spec| spec.name == *a))
+        else {
+            return Ok(false);
+        };
+        let response = self.request(config, logger, "custom", Some(action)).await?;
+        // Declared in the metadata, so it must not fall back to an update
+        if response.handled == Some(false) {
+            anyhow::bail!("{} did not handle its action '{}'", self.name, action);
+        }
+        Ok(true)
+    }
+}
@@ -1,6 +1,9 @@
 use async_trait::async_trait;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{
+    ErrorPolicy, InstallScope, InstalledPackage, OutdatedPackage, Plugin, PluginAction,
+    PluginActionType, PluginMetadata,
+};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
@@ -8,6 +11,12 @@ use anyhow::Result;
 
 pub struct FlatpakPlugin;
 
+/// `flatpak list` as parsed by [`parse_flatpak_list`], also the save file format
+const FLATPAK_LIST_ARGS: [&str; 2] = [
+    "list",
+    "--columns=application,version,branch,origin,installation",
+];
+
 #[async_trait]
 impl Plugin for FlatpakPlugin {
     fn name(&self) -> &str {
@@ -18,11 +27,26 @@ impl Plugin for FlatpakPlugin {
         PluginMetadata {
             name: "flatpak".to_string(),
             description: "Update Flatpak applications".to_string(),
-            actions: vec![PluginAction {
-                name: "flatpak".to_string(),
-                description: "Update all Flatpak applications".to_string(),
-                action_type: Some(PluginActionType::Update),
-            }],
+            actions: vec![
+                PluginAction {
+                    name: "flatpak".to_string(),
+                    description: "Update all Flatpak applications".to_string(),
+                    action_type: Some(PluginActionType::Update),
+                },
+                PluginAction {
+                    name: "flatpak-save".to_string(),
+                    description: "Save installed Flatpak applications and runtimes to a list"
+                        .to_string(),
+                    action_type: Some(PluginActionType::Save),
+                },
+                PluginAction {
+                    name: "flatpak-restore".to_string(),
+                    description: "Install the Flatpak applications and runtimes of the saved list"
+                        .to_string(),
+                    action_type: Some(PluginActionType::Restore),
+                },
+            ],
+            run_after: Vec::new(),
         }
     }
 
@@ -30,13 +54,189 @@ impl Plugin for FlatpakPlugin {
         insights.has_flatpak
     }
 
+    fn exclusive(&self) -> bool {
+        true
+    }
+
     async fn update(
         &self,
         config: &Config,
         _insights: &Insights,
         logger: &mut Logger,
     ) -> Result<()> {
-        super::run_cmd(config, logger, true, "flatpak", &["update", "-y"])?;
+        super::run_cmd(
+            config,
+            logger,
+            ErrorPolicy::Fail,
+            "flatpak",
+            &["update", "-y"],
+        )
+        .await?;
         Ok(())
     }
+
+    async fn save(&self, config: &Config, _insights: &Insights, logger: &mut Logger) -> Result<()> {
+        logger.log(&format!(
+            "Saving Flatpak applications to {}",
+            config.flatpak_file.display()
+        ));
+        let list = super::query_cmd(config, logger, "flatpak", &FLATPAK_LIST_ARGS, &[]).await?;
+        super::write_save_file(config, logger, &config.flatpak_file, &list)
+    }
+
+    async fn restore(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<()> {
+        if !config.flatpak_file.exists() {
+            logger.error(&format!(
+                "missing dependency — {} flatpak's backup file is not found",
+                config.flatpak_file.display()
+            ));
+            return Ok(());
+        }
+        logger.log(&format!(
+            "Restoring Flatpak applications from {}",
+            config.flatpak_file.display()
+        ));
+        let packages = parse_flatpak_list(&super::read_save_file(&config.flatpak_file)?);
+        if !packages.is_empty() {
+            self.install_packages(config, insights, logger, &packages)
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn saved_packages(&self, config: &Config) -> Result<Option<Vec<InstalledPackage>>> {
+        let list = super::read_save_file(&config.flatpak_file)?;
+        Ok(Some(parse_flatpak_list(&list)))
+    }
+
+    async fn install_packages(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        // One install per remote and installation
+        let mut groups: std::collections::BTreeMap<(&str, bool), Vec<&str>> =
+            std::collections::BTreeMap::new();
+        for package in packages {
+            let user = package.scope == InstallScope::User;
+            groups
+                .entry((package.source.as_str(), user))
+                .or_default()
+                .push(&package.name);
+        }
+        for ((remote, user), names) in groups {
+            let mut args = vec!["install", "-y", "--noninteractive"];
+            if user {
+                args.push("--user");
+            }
+            args.push(remote);
+            args.extend(names);
+            super::run_cmd(config, logger, ErrorPolicy::Fail, "flatpak", &args).await?;
+        }
+        Ok(true)
+    }
+
+    async fn remove_packages(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let mut args = vec!["uninstall", "-y", "--noninteractive"];
+        args.extend(packages.iter().map(|p| p.name.as_str()));
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "flatpak", &args).await?;
+        Ok(true)
+    }
+
+    async fn check_outdated(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<OutdatedPackage>>> {
+        let updates = super::query_cmd(
+            config,
+            logger,
+            "flatpak",
+            &[
+                "remote-ls",
+                "--updates",
+                "--columns=application,version,branch",
+            ],
+            &[],
+        )
+        .await?;
+        let installed = super::query_cmd(
+            config,
+            logger,
+            "flatpak",
+            &["list", "--columns=application,version"],
+            &[],
+        )
+        .await?;
+        Ok(Some(parse_flatpak_updates(&updates, &installed)))
+    }
+
+    async fn list_installed(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        let list = super::query_cmd(config, logger, "flatpak", &FLATPAK_LIST_ARGS, &[]).await?;
+        Ok(Some(parse_flatpak_list(&list)))
+    }
+}
+
+/// Match `flatpak remote-ls --updates --columns=application,version,branch`
+/// against `flatpak list --columns=application,version`. Runtimes often have
+/// no version, in which case the branch is shown.
+pub fn parse_flatpak_updates(updates: &str, installed: &str) -> Vec<OutdatedPackage> {
+    let installed: std::collections::HashMap<&str, &str> = installed
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split('\t');
+            Some((columns.next()?.trim(), columns.next().unwrap_or("").trim()))
+        })
+        .collect();
+    updates
+        .lines()
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split('\t').map(str::trim).collect();
+            let name = *columns.first().filter(|n| !n.is_empty())?;
+            let version = columns.get(1).copied().unwrap_or("");
+            let branch = columns.get(2).copied().unwrap_or("");
+            let available = if version.is_empty() { branch } else { version };
+            let current = installed.get(name).copied().filter(|v| !v.is_empty());
+            Some(OutdatedPackage::new(name, current, available))
+        })
+        .collect()
+}
+
+/// Parse `flatpak list --columns=application,version,branch,origin,installation`;
+/// the source is the remote the app was installed from
+pub fn parse_flatpak_list(list: &str) -> Vec<InstalledPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split('\t').map(str::trim).collect();
+            let [name, version, branch, origin, installation] = columns.as_slice() else {
+                return None;
+            };
+            let version = if version.is_empty() { branch } else { version };
+            let scope = if *installation == "user" {
+                InstallScope::User
+            } else {
+                InstallScope::System
+            };
+            Some(InstalledPackage::new("flatpak", name, version, origin).with_scope(scope))
+        })
+        .collect()
 }
@@ -1,6 +1,9 @@
 use async_trait::async_trait;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{
+    ErrorPolicy, InstallScope, InstalledPackage, OutdatedPackage, Plugin, PluginAction,
+    PluginActionType, PluginMetadata,
+};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
@@ -35,6 +38,7 @@ impl Plugin for GemPlugin {
                     action_type: Some(PluginActionType::Restore),
                 },
             ],
+            run_after: Vec::new(),
         }
     }
 
@@ -48,27 +52,32 @@ impl Plugin for GemPlugin {
         _insights: &Insights,
         logger: &mut Logger,
     ) -> Result<()> {
-        super::run_cmd(config, logger, true, "gem", &["update", "--system"])?;
-        super::run_cmd(config, logger, true, "gem", &["update"])?;
-        super::run_cmd(config, logger, true, "gem", &["cleanup"])?;
+        super::run_cmd(
+            config,
+            logger,
+            ErrorPolicy::Fail,
+            "gem",
+            &["update", "--system"],
+        )
+        .await?;
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "gem", &["update"]).await?;
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "gem", &["cleanup"]).await?;
         Ok(())
     }
 
     async fn save(&self, config: &Config, _insights: &Insights, logger: &mut Logger) -> Result<()> {
-        let gem_file = config.gem_file.to_string_lossy().to_string();
-        if let Some(parent) = config.gem_file.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-        logger.log(&format!("Saving Ruby gems list to {}", gem_file));
-        super::run_cmd(config, logger, true, "gem", &["list", "--local"])?;
-        logger.log("Success savefile written");
-        Ok(())
+        logger.log(&format!(
+            "Saving Ruby gems list to {}",
+            config.gem_file.display()
+        ));
+        let list = super::query_cmd(config, logger, "gem", &["list", "--local"], &[]).await?;
+        super::write_save_file(config, logger, &config.gem_file, &list)
     }
 
     async fn restore(
         &self,
         config: &Config,
-        _insights: &Insights,
+        insights: &Insights,
         logger: &mut Logger,
     ) -> Result<()> {
         let gem_file = config.gem_file.to_string_lossy().to_string();
@@ -80,7 +89,140 @@ impl Plugin for GemPlugin {
             return Ok(());
         }
         logger.log(&format!("Restoring Ruby gems from {}", gem_file));
-        logger.log("Ruby gems must be reinstalled manually — run: gem install <gemname>");
+        let packages = parse_gem_list(&super::read_save_file(&config.gem_file)?);
+        if !packages.is_empty() {
+            self.install_packages(config, insights, logger, &packages)
+                .await?;
+        }
         Ok(())
     }
+
+    fn saved_packages(&self, config: &Config) -> Result<Option<Vec<InstalledPackage>>> {
+        let list = super::read_save_file(&config.gem_file)?;
+        Ok(Some(parse_gem_list(&list)))
+    }
+
+    async fn install_packages(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let specs: Vec<String> = packages
+            .iter()
+            .map(|p| super::package_spec(config, p, ":"))
+            .collect();
+        let mut args = vec!["install"];
+        args.extend(specs.iter().map(String::as_str));
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "gem", &args).await?;
+        Ok(true)
+    }
+
+    async fn install_versions(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        // Gems install side by side, so the newer versions are uninstalled
+        // for the previous one to be used again
+        for package in packages {
+            let newer = format!("> {}", package.version);
+            super::run_cmd(
+                config,
+                logger,
+                ErrorPolicy::Fail,
+                "gem",
+                &["install", &package.name, "-v", &package.version],
+            )
+            .await?;
+            super::run_cmd(
+                config,
+                logger,
+                ErrorPolicy::Fail,
+                "gem",
+                &["uninstall", &package.name, "-v", &newer, "--executables"],
+            )
+            .await?;
+        }
+        Ok(true)
+    }
+
+    async fn remove_packages(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        // Every installed version, with its executables, without asking
+        let mut args = vec!["uninstall", "--all", "--executables"];
+        args.extend(packages.iter().map(|p| p.name.as_str()));
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "gem", &args).await?;
+        Ok(true)
+    }
+
+    async fn check_outdated(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<OutdatedPackage>>> {
+        let list = super::query_cmd(config, logger, "gem", &["outdated"], &[]).await?;
+        Ok(Some(parse_gem_outdated(&list)))
+    }
+
+    async fn list_installed(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        let list = super::query_cmd(config, logger, "gem", &["list", "--local"], &[]).await?;
+        let gem_dir =
+            super::query_cmd(config, logger, "gem", &["environment", "gemdir"], &[]).await?;
+        let gem_dir = gem_dir.trim();
+        let packages = parse_gem_list(&list);
+        if gem_dir.is_empty() {
+            return Ok(Some(packages));
+        }
+        let scope = InstallScope::of_path(gem_dir);
+        Ok(Some(
+            packages
+                .into_iter()
+                .map(|p| {
+                    let path = format!("{}/gems/{}-{}", gem_dir, p.name, p.version);
+                    p.with_scope(scope).with_path(path)
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Parse `gem outdated`: `rake (13.0.6 < 13.1.0)`, where several installed
+/// versions are listed comma separated
+pub fn parse_gem_outdated(list: &str) -> Vec<OutdatedPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let (name, versions) = line.trim().split_once(" (")?;
+            let (installed, latest) = versions.trim_end_matches(')').split_once(" < ")?;
+            let installed = installed.rsplit(", ").next();
+            Some(OutdatedPackage::new(name, installed, latest.trim()))
+        })
+        .collect()
+}
+
+/// Parse `gem list --local`: `rake (13.1.0, 13.0.6)`, newest version first;
+/// default gems are marked `default: `
+pub fn parse_gem_list(list: &str) -> Vec<InstalledPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let (name, versions) = line.trim().split_once(" (")?;
+            let newest = versions.trim_end_matches(')').split(", ").next()?;
+            let newest = newest.trim_start_matches("default: ");
+            Some(InstalledPackage::new("gem", name, newest, "gem"))
+        })
+        .collect()
 }
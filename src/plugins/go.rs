@@ -1,7 +1,6 @@
 use async_trait::async_trait;
-use duct::cmd;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{ErrorPolicy, Plugin, PluginAction, PluginActionType, PluginMetadata};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
@@ -36,6 +35,7 @@ impl Plugin for GoPlugin {
                     action_type: Some(PluginActionType::Restore),
                 },
             ],
+            run_after: Vec::new(),
         }
     }
 
@@ -49,7 +49,7 @@ impl Plugin for GoPlugin {
         _insights: &Insights,
         logger: &mut Logger,
     ) -> Result<()> {
-        super::run_cmd(config, logger, true, "go", &["version"])?;
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "go", &["version"]).await?;
         logger.log("Go updates — upgrade Go toolchain via your package manager or download from https://go.dev/dl");
         logger.log(
             "Go binaries installed via 'go install' — run 'go install <path>@latest' for each",
@@ -64,10 +64,10 @@ impl Plugin for GoPlugin {
         }
         logger.log(&format!("Saving Go binaries list to {}", go_file));
 
-        let gopath_output = cmd("go", &["env", "GOPATH"]).stdout_capture().run();
+        let gopath_output = super::query_cmd(config, logger, "go", &["env", "GOPATH"], &[]).await;
         match gopath_output {
             Ok(output) => {
-                let gopath = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                let gopath = output.trim().to_string();
                 let bin_dir = std::path::Path::new(&gopath).join("bin");
                 if bin_dir.is_dir() {
                     let mut entries: Vec<String> = std::fs::read_dir(&bin_dir)?
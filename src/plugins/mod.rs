@@ -1,7 +1,10 @@
 pub mod brew;
 pub mod cargo;
+pub mod command;
+pub mod custom;
 pub mod deno;
 pub mod docker;
+pub mod external;
 pub mod flatpak;
 pub mod gem;
 pub mod go;
@@ -16,13 +19,20 @@ pub mod uv;
 pub mod vscode;
 pub mod yarn;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 pub use brew::BrewPlugin;
 pub use cargo::CargoPlugin;
+pub use command::{
+    CommandFailed, CommandIo, CommandOutcome, CommandRecord, ErrorPolicy, Interruption, LineFilter,
+    cancel_commands, cancellation, current_plugin, descendants, is_transient, note_action,
+    query_cmd, record_commands, record_notes, run_cmd, run_cmd_io, run_with_sudo, scope_plugin,
+    sudo_command,
+};
+pub use custom::{CustomPlugin, custom_plugin_available, register_custom_plugins};
 pub use deno::DenoPlugin;
 pub use docker::DockerPlugin;
-use duct::cmd;
+pub use external::{ExternalPlugin, discover_external_plugins, load_external_plugins};
 pub use flatpak::FlatpakPlugin;
 pub use gem::GemPlugin;
 pub use go::GoPlugin;
@@ -66,10 +76,145 @@ pub struct PluginAction {
     pub action_type: Option<PluginActionType>,
 }
 
+/// A package with a newer version available, as reported by
+/// [`Plugin::check_outdated`]
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct OutdatedPackage {
+    pub name: String,
+    /// `None` when the package manager does not report the installed version
+    pub installed: Option<String>,
+    pub available: String,
+}
+
+impl OutdatedPackage {
+    pub fn new(name: &str, installed: Option<&str>, available: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            installed: installed.map(str::to_string),
+            available: available.to_string(),
+        }
+    }
+}
+
+/// Whether a package is installed for the current user or for the machine
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallScope {
+    User,
+    System,
+}
+
+impl InstallScope {
+    /// `User` for paths inside the home directory, `System` otherwise
+    pub fn of_path(path: &str) -> Self {
+        match std::env::var("HOME") {
+            Ok(home) if !home.is_empty() && std::path::Path::new(path).starts_with(&home) => {
+                Self::User
+            }
+            _ => Self::System,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::System => "system",
+        }
+    }
+}
+
+/// A package as reported by [`Plugin::list_installed`]
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct InstalledPackage {
+    /// Tool that installed the package: `brew`, `cargo`, `apt`, `code`, ...
+    pub manager: String,
+    pub name: String,
+    pub version: String,
+    /// Where the package came from when a manager has more than one kind
+    /// (`formula`/`cask`, a flatpak remote, a cargo registry), otherwise
+    /// the manager name
+    pub source: String,
+    pub scope: InstallScope,
+    /// Install location, when the manager reports one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+impl InstalledPackage {
+    /// A system-wide package without a known path
+    pub fn new(manager: &str, name: &str, version: &str, source: &str) -> Self {
+        Self {
+            manager: manager.to_string(),
+            name: name.to_string(),
+            version: version.to_string(),
+            source: source.to_string(),
+            scope: InstallScope::System,
+            path: None,
+        }
+    }
+
+    pub fn with_scope(mut self, scope: InstallScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+}
+
+/// Write `contents` to the save file at `path`, creating its directory,
+/// or only log where it would go in a dry run
+pub fn write_save_file(
+    config: &Config,
+    logger: &mut Logger,
+    path: &std::path::Path,
+    contents: &str,
+) -> Result<()> {
+    if config.dry_run {
+        logger.log(&format!("Would write {}", path.display()));
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    logger.log("Success savefile written");
+    Ok(())
+}
+
+/// `package` as an install argument: `name<separator>version` when restores
+/// are pinned and the version is known, otherwise the name alone
+pub fn package_spec(config: &Config, package: &InstalledPackage, separator: &str) -> String {
+    if config.restore_pinned {
+        versioned_spec(package, separator)
+    } else {
+        package.name.clone()
+    }
+}
+
+/// `name<separator>version`, or the name alone when the version is unknown
+pub fn versioned_spec(package: &InstalledPackage, separator: &str) -> String {
+    if package.version.is_empty() {
+        package.name.clone()
+    } else {
+        format!("{}{}{}", package.name, separator, package.version)
+    }
+}
+
+/// Contents of the save file at `path`
+pub fn read_save_file(path: &std::path::Path) -> Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
 pub struct PluginMetadata {
     pub name: String,
     pub description: String,
     pub actions: Vec<PluginAction>,
+    /// Plugins that must finish before this one starts when both are part of a run
+    pub run_after: Vec<String>,
 }
 
 #[async_trait]
@@ -80,6 +225,13 @@ pub trait Plugin: Send + Sync {
 
     async fn check_available(&self, config: &Config, insights: &Insights) -> bool;
 
+    /// Whether the plugin must run alone: nothing else runs in parallel
+    /// with it, e.g. because it prompts for a sudo password or holds the
+    /// system package manager's lock
+    fn exclusive(&self) -> bool {
+        false
+    }
+
     async fn update(&self, config: &Config, insights: &Insights, logger: &mut Logger)
     -> Result<()>;
 
@@ -103,6 +255,97 @@ pub trait Plugin: Send + Sync {
         Ok(())
     }
 
+    /// Run the save action `action_name` (e.g. `brew-save`). Plugins that
+    /// declare several save actions override this to tell them apart; the
+    /// default runs [`Plugin::save`].
+    async fn save_action(
+        &self,
+        _action_name: &str,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<()> {
+        self.save(config, insights, logger).await
+    }
+
+    /// Run the restore action `action_name`, see [`Plugin::save_action`]
+    async fn restore_action(
+        &self,
+        _action_name: &str,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<()> {
+        self.restore(config, insights, logger).await
+    }
+
+    /// List packages with a newer version available without upgrading
+    /// anything. Returns `None` when the plugin cannot check for updates.
+    async fn check_outdated(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> Result<Option<Vec<OutdatedPackage>>> {
+        Ok(None)
+    }
+
+    /// List the packages this plugin manages with their installed versions.
+    /// Returns `None` when the plugin cannot list them.
+    async fn list_installed(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        Ok(None)
+    }
+
+    /// Read the packages listed in this plugin's save file, with the saved
+    /// versions when the file records them. Returns `None` when the plugin
+    /// cannot read its save file.
+    fn saved_packages(&self, _config: &Config) -> Result<Option<Vec<InstalledPackage>>> {
+        Ok(None)
+    }
+
+    /// Install `packages`, as read by [`Plugin::saved_packages`], at the
+    /// saved versions when `config.restore_pinned` is set. Returns false when
+    /// the plugin cannot install single packages.
+    async fn install_packages(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+        _packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Uninstall `packages`, as listed by [`Plugin::list_installed`].
+    /// Returns false when the plugin cannot remove single packages.
+    async fn remove_packages(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+        _packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Reinstall `packages` at exactly their versions, replacing the
+    /// installed ones, to roll back an upgrade. Returns false when the
+    /// plugin cannot install a given version.
+    async fn install_versions(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+        _packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        Ok(false)
+    }
+
     /// Handle custom actions (optional)
     /// Override this method to handle custom actions beyond update/save/restore
     /// Returns false if the action is not recognized
@@ -159,6 +402,19 @@ impl<'a> PluginRegistry<'a> {
         None
     }
 
+    /// Resolve the plugin that owns an action (e.g. "brew-save" → "brew")
+    pub fn plugin_name_for_action(&self, action_name: &str) -> Option<&str> {
+        if let Some(plugin) = self.get_plugin(action_name) {
+            return Some(plugin.name());
+        }
+        let (plugin_name, _) = action_name.split_once('-')?;
+        self.get_plugin(plugin_name).map(|p| p.name())
+    }
+
+    pub fn get_metadata(&self, plugin_name: &str) -> Option<&PluginMetadata> {
+        self.metadata_cache.iter().find(|m| m.name == plugin_name)
+    }
+
     pub fn get_all_metadata(&self) -> &Vec<PluginMetadata> {
         &self.metadata_cache
     }
@@ -225,8 +481,16 @@ impl<'a> PluginRegistry<'a> {
         {
             match action_meta.action_type {
                 Some(PluginActionType::Update) => plugin.update(config, insights, logger).await?,
-                Some(PluginActionType::Save) => plugin.save(config, insights, logger).await?,
-                Some(PluginActionType::Restore) => plugin.restore(config, insights, logger).await?,
+                Some(PluginActionType::Save) => {
+                    plugin
+                        .save_action(action_name, config, insights, logger)
+                        .await?
+                }
+                Some(PluginActionType::Restore) => {
+                    plugin
+                        .restore_action(action_name, config, insights, logger)
+                        .await?
+                }
                 None => {
                     // Custom action - call handle_custom_action
                     if plugin
@@ -257,144 +521,6 @@ impl<'a> PluginRegistry<'a> {
     }
 }
 
-const SUDO_PATH: &str = "/usr/bin/sudo";
-
-fn validate_sudo_path() -> Result<()> {
-    if !std::path::Path::new(SUDO_PATH).exists() {
-        anyhow::bail!(
-            "sudo not found at expected path '{}'. \
-             This may indicate a compromised system or unusual PATH configuration.",
-            SUDO_PATH
-        );
-    }
-    Ok(())
-}
-
-pub fn sudo_command(
-    config: &Config,
-    program: &str,
-    args: &[&str],
-) -> Result<std::process::Command> {
-    if config.no_sudo || std::env::var("UPDATEHAULER_NO_SUDO").is_ok() {
-        let mut cmd = std::process::Command::new(program);
-        cmd.args(args);
-        return Ok(cmd);
-    }
-
-    validate_sudo_path()?;
-
-    let mut cmd = std::process::Command::new(SUDO_PATH);
-    cmd.arg(program).args(args);
-    Ok(cmd)
-}
-
-pub(crate) fn run_with_sudo(
-    config: &Config,
-    logger: &mut Logger,
-    show_error: bool,
-    command: &str,
-    args: &[&str],
-) -> Result<()> {
-    if config.no_sudo || std::env::var("UPDATEHAULER_NO_SUDO").is_ok() {
-        return run_cmd(config, logger, show_error, command, args);
-    }
-
-    validate_sudo_path()?;
-
-    let mut sudo_args: Vec<&str> = vec![command];
-    sudo_args.extend(args);
-    run_cmd(config, logger, show_error, SUDO_PATH, &sudo_args)
-}
-
-pub(crate) fn run_cmd(
-    config: &Config,
-    logger: &mut Logger,
-    show_error: bool,
-    command: &str,
-    args: &[&str],
-) -> Result<()> {
-    let cmd_str = format!("{} {}", command, args.join(" "));
-
-    let is_sudo = std::path::Path::new(command)
-        .file_name()
-        .is_some_and(|n| n == "sudo");
-    let short_cmd = if is_sudo && args.len() >= 4 {
-        args[3]
-    } else {
-        command
-    };
-
-    if config.dry_run {
-        if config.show_header {
-            logger.log(&format!("{} → Start (DRY-RUN)", cmd_str));
-        }
-        logger.log(&format!("Would execute: {}", cmd_str));
-        if config.show_header {
-            logger.log(&format!("{} → Return code 0 (DRY-RUN)", cmd_str));
-        }
-        return Ok(());
-    }
-
-    if config.show_header {
-        logger.log(&format!("{} → Start", cmd_str));
-    }
-
-    let result = cmd(command, args).stdout_capture().stderr_capture().run();
-
-    match result {
-        Ok(output) => {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
-
-            for line in stdout.lines() {
-                let formatted = if config.show_header {
-                    format!("{} → {}", short_cmd, line)
-                } else {
-                    line.to_string()
-                };
-                logger.log(&formatted);
-            }
-
-            for line in stderr.lines() {
-                let formatted = if config.show_header {
-                    format!("{} → {}", short_cmd, line)
-                } else {
-                    line.to_string()
-                };
-                logger.log(&formatted);
-            }
-
-            if config.show_header {
-                if show_error && !output.status.success() {
-                    logger.error(&format!(
-                        "{} → Return code {}",
-                        cmd_str,
-                        output.status.code().unwrap_or(1)
-                    ));
-                } else {
-                    logger.log(&format!(
-                        "{} → Return code {}",
-                        cmd_str,
-                        output.status.code().unwrap_or(0)
-                    ));
-                }
-            }
-
-            Ok(())
-        }
-        Err(e) => {
-            if config.show_header {
-                if show_error {
-                    logger.error(&format!("{} → Error: {}", cmd_str, e));
-                } else {
-                    logger.log(&format!("{} → Return code {}", cmd_str, 1));
-                }
-            }
-            Err(anyhow::anyhow!("{}", e))
-        }
-    }
-}
-
 impl<'a> Default for PluginRegistry<'a> {
     fn default() -> Self {
         Self::new()
@@ -1,11 +1,13 @@
 use async_trait::async_trait;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{
+    ErrorPolicy, InstallScope, InstalledPackage, OutdatedPackage, Plugin, PluginAction,
+    PluginActionType, PluginMetadata,
+};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
 use anyhow::Result;
-use duct::cmd;
 
 pub struct NpmPlugin;
 
@@ -36,6 +38,7 @@ impl Plugin for NpmPlugin {
                     action_type: Some(PluginActionType::Restore),
                 },
             ],
+            run_after: Vec::new(),
         }
     }
 
@@ -49,7 +52,7 @@ impl Plugin for NpmPlugin {
         _insights: &Insights,
         logger: &mut Logger,
     ) -> Result<()> {
-        super::run_cmd(config, logger, true, "npm", &["update", "-g"])?;
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "npm", &["update", "-g"]).await?;
         Ok(())
     }
 
@@ -62,16 +65,17 @@ impl Plugin for NpmPlugin {
 
         logger.log(&format!("Saving npm global packages to {}", npm_file));
 
-        let output = cmd("npm", &["list", "-g", "--depth=0", "--json"])
-            .stdout_capture()
-            .stderr_capture()
-            .run()?;
-
-        if !output.stdout.is_empty() {
-            std::fs::write(&npm_file, &output.stdout)?;
-        }
-        if !output.stderr.is_empty() {
-            logger.log(&String::from_utf8_lossy(&output.stderr));
+        let json = super::query_cmd(
+            config,
+            logger,
+            "npm",
+            &["list", "-g", "--depth=0", "--json"],
+            &[],
+        )
+        .await?;
+
+        if !json.is_empty() {
+            std::fs::write(&npm_file, json)?;
         }
 
         logger.log("Success savefile written");
@@ -82,7 +86,7 @@ impl Plugin for NpmPlugin {
     async fn restore(
         &self,
         config: &Config,
-        _insights: &Insights,
+        insights: &Insights,
         logger: &mut Logger,
     ) -> Result<()> {
         let npm_file = config.npm_file.to_string_lossy().to_string();
@@ -98,21 +102,154 @@ impl Plugin for NpmPlugin {
         logger.log(&format!("Restoring npm global packages from {}", npm_file));
 
         let content = std::fs::read_to_string(&npm_file)?;
-        let json: serde_json::Value = serde_json::from_str(&content)?;
-
-        if let Some(deps) = json.get("dependencies").and_then(|d| d.as_object()) {
-            let packages: Vec<&str> = deps.keys().map(|k| k.as_str()).collect();
-            if !packages.is_empty() {
-                let mut args: Vec<&str> = vec!["install", "-g"];
-                args.extend(packages);
-                super::run_cmd(config, logger, true, "npm", &args)?;
-            } else {
-                logger.log("No packages found in save file");
-            }
+        let packages = parse_npm_list(&content)?;
+        if packages.is_empty() {
+            logger.log("No packages found in save file");
         } else {
-            logger.log("No dependencies found in save file");
+            self.install_packages(config, insights, logger, &packages)
+                .await?;
         }
 
         Ok(())
     }
+
+    fn saved_packages(&self, config: &Config) -> Result<Option<Vec<InstalledPackage>>> {
+        let content = super::read_save_file(&config.npm_file)?;
+        parse_npm_list(&content).map(Some)
+    }
+
+    async fn install_packages(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let specs: Vec<String> = packages
+            .iter()
+            .map(|p| super::package_spec(config, p, "@"))
+            .collect();
+        let mut args = vec!["install", "-g"];
+        args.extend(specs.iter().map(String::as_str));
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "npm", &args).await?;
+        Ok(true)
+    }
+
+    async fn install_versions(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let specs: Vec<String> = packages
+            .iter()
+            .map(|p| super::versioned_spec(p, "@"))
+            .collect();
+        let mut args = vec!["install", "-g"];
+        args.extend(specs.iter().map(String::as_str));
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "npm", &args).await?;
+        Ok(true)
+    }
+
+    async fn remove_packages(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let mut args = vec!["uninstall", "-g"];
+        args.extend(packages.iter().map(|p| p.name.as_str()));
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "npm", &args).await?;
+        Ok(true)
+    }
+
+    async fn check_outdated(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<OutdatedPackage>>> {
+        // npm outdated exits 1 when it finds outdated packages
+        let json =
+            super::query_cmd(config, logger, "npm", &["outdated", "-g", "--json"], &[1]).await?;
+        parse_npm_outdated(&json).map(Some)
+    }
+
+    async fn list_installed(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        let json = super::query_cmd(
+            config,
+            logger,
+            "npm",
+            &["list", "-g", "--depth=0", "--json"],
+            &[],
+        )
+        .await?;
+        let root = super::query_cmd(config, logger, "npm", &["root", "-g"], &[]).await?;
+        let root = root.trim();
+        let packages = parse_npm_list(&json)?;
+        if root.is_empty() {
+            return Ok(Some(packages));
+        }
+        let scope = InstallScope::of_path(root);
+        Ok(Some(
+            packages
+                .into_iter()
+                .map(|p| {
+                    let path = format!("{}/{}", root, p.name);
+                    p.with_scope(scope).with_path(path)
+                })
+                .collect(),
+        ))
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct NpmOutdatedEntry {
+    current: Option<String>,
+    latest: String,
+}
+
+/// Parse `npm outdated -g --json`, an object keyed by package name
+pub fn parse_npm_outdated(json: &str) -> Result<Vec<OutdatedPackage>> {
+    if json.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let outdated: std::collections::BTreeMap<String, NpmOutdatedEntry> =
+        serde_json::from_str(json)?;
+    Ok(outdated
+        .iter()
+        .map(|(name, entry)| OutdatedPackage::new(name, entry.current.as_deref(), &entry.latest))
+        .collect())
+}
+
+#[derive(serde::Deserialize)]
+struct NpmList {
+    #[serde(default)]
+    dependencies: std::collections::BTreeMap<String, NpmListEntry>,
+}
+
+#[derive(serde::Deserialize)]
+struct NpmListEntry {
+    #[serde(default)]
+    version: String,
+}
+
+/// Parse `npm list -g --depth=0 --json`
+pub fn parse_npm_list(json: &str) -> Result<Vec<InstalledPackage>> {
+    if json.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let list: NpmList = serde_json::from_str(json)?;
+    Ok(list
+        .dependencies
+        .iter()
+        .map(|(name, entry)| InstalledPackage::new("npm", name, &entry.version, "npm"))
+        .collect())
 }
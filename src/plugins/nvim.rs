@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use std::path::PathBuf;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{ErrorPolicy, Plugin, PluginAction, PluginActionType, PluginMetadata};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
@@ -19,21 +19,24 @@ impl NvimPlugin {
             .is_some_and(|p| p.exists())
     }
 
-    fn update_mason(config: &Config, logger: &mut Logger) -> Result<()> {
+    async fn update_mason(config: &Config, logger: &mut Logger) -> Result<()> {
         super::run_cmd(
             config,
             logger,
-            false,
+            ErrorPolicy::Warn,
             "nvim",
             &["--headless", "-c", "MasonUpdate", "-c", "qa"],
-        )?;
+        )
+        .await?;
         super::run_cmd(
             config,
             logger,
-            false,
+            ErrorPolicy::Warn,
             "nvim",
             &["--headless", "-c", "MasonToolsUpdate", "-c", "qa"],
         )
+        .await?;
+        Ok(())
     }
 
     fn get_metadata_internal(&self) -> PluginMetadata {
@@ -77,6 +80,7 @@ impl NvimPlugin {
                     action_type: None,
                 },
             ],
+            run_after: Vec::new(),
         }
     }
 
@@ -102,34 +106,40 @@ impl NvimPlugin {
         }
     }
 
-    fn update_lazy_nvim(config: &Config, logger: &mut Logger) -> Result<()> {
+    async fn update_lazy_nvim(config: &Config, logger: &mut Logger) -> Result<()> {
         super::run_cmd(
             config,
             logger,
-            false,
+            ErrorPolicy::Warn,
             "nvim",
             &["--headless", "-c", "Lazy! sync", "+qa"],
         )
+        .await?;
+        Ok(())
     }
 
-    fn update_packer_nvim(config: &Config, logger: &mut Logger) -> Result<()> {
+    async fn update_packer_nvim(config: &Config, logger: &mut Logger) -> Result<()> {
         super::run_cmd(
             config,
             logger,
-            false,
+            ErrorPolicy::Warn,
             "nvim",
             &["--headless", "-c", "PackerSync", "+qa"],
         )
+        .await?;
+        Ok(())
     }
 
-    fn update_vim_plug(config: &Config, logger: &mut Logger) -> Result<()> {
+    async fn update_vim_plug(config: &Config, logger: &mut Logger) -> Result<()> {
         super::run_cmd(
             config,
             logger,
-            false,
+            ErrorPolicy::Warn,
             "nvim",
             &["--headless", "-c", "PlugUpdate --sync", "+qa"],
         )
+        .await?;
+        Ok(())
     }
 
     fn save_lazy_nvim(_config: &Config, logger: &mut Logger) -> Result<()> {
@@ -147,34 +157,40 @@ impl NvimPlugin {
         Ok(())
     }
 
-    fn restore_lazy_nvim(config: &Config, logger: &mut Logger) -> Result<()> {
+    async fn restore_lazy_nvim(config: &Config, logger: &mut Logger) -> Result<()> {
         super::run_cmd(
             config,
             logger,
-            false,
+            ErrorPolicy::Warn,
             "nvim",
             &["--headless", "-c", "Lazy! sync", "+qa"],
         )
+        .await?;
+        Ok(())
     }
 
-    fn restore_packer_nvim(config: &Config, logger: &mut Logger) -> Result<()> {
+    async fn restore_packer_nvim(config: &Config, logger: &mut Logger) -> Result<()> {
         super::run_cmd(
             config,
             logger,
-            false,
+            ErrorPolicy::Warn,
             "nvim",
             &["--headless", "-c", "PackerInstall", "+qa"],
         )
+        .await?;
+        Ok(())
     }
 
-    fn restore_vim_plug(config: &Config, logger: &mut Logger) -> Result<()> {
+    async fn restore_vim_plug(config: &Config, logger: &mut Logger) -> Result<()> {
         super::run_cmd(
             config,
             logger,
-            false,
+            ErrorPolicy::Warn,
             "nvim",
             &["--headless", "-c", "PlugInstall --sync", "+qa"],
         )
+        .await?;
+        Ok(())
     }
 }
 
@@ -212,28 +228,31 @@ impl Plugin for NvimPlugin {
                         super::run_cmd(
                             config,
                             logger,
-                            false,
+                            ErrorPolicy::Warn,
                             "nvim",
                             &["--headless", "-c", "Lazy! clean", "+qa"],
-                        )?;
+                        )
+                        .await?;
                     }
                     Some("packer.nvim") => {
                         super::run_cmd(
                             config,
                             logger,
-                            false,
+                            ErrorPolicy::Warn,
                             "nvim",
                             &["--headless", "-c", "PackerClean", "+qa"],
-                        )?;
+                        )
+                        .await?;
                     }
                     Some("vim-plug") => {
                         super::run_cmd(
                             config,
                             logger,
-                            false,
+                            ErrorPolicy::Warn,
                             "nvim",
                             &["--headless", "-c", "PlugClean!", "+qa"],
-                        )?;
+                        )
+                        .await?;
                     }
                     _ => {
                         logger.log("No supported nvim plugin manager detected");
@@ -322,15 +341,15 @@ end
 
         let plugin_manager_found = match plugin_manager.as_deref() {
             Some("lazy.nvim") => {
-                Self::update_lazy_nvim(config, logger)?;
+                Self::update_lazy_nvim(config, logger).await?;
                 true
             }
             Some("packer.nvim") => {
-                Self::update_packer_nvim(config, logger)?;
+                Self::update_packer_nvim(config, logger).await?;
                 true
             }
             Some("vim-plug") => {
-                Self::update_vim_plug(config, logger)?;
+                Self::update_vim_plug(config, logger).await?;
                 true
             }
             _ => false,
@@ -343,7 +362,7 @@ end
 
         if Self::detect_mason() {
             logger.log("Updating Mason tools...");
-            Self::update_mason(config, logger)?;
+            Self::update_mason(config, logger).await?;
         }
 
         Ok(())
@@ -373,9 +392,9 @@ end
         let plugin_manager = Self::detect_plugin_manager();
 
         match plugin_manager.as_deref() {
-            Some("lazy.nvim") => Self::restore_lazy_nvim(config, logger)?,
-            Some("packer.nvim") => Self::restore_packer_nvim(config, logger)?,
-            Some("vim-plug") => Self::restore_vim_plug(config, logger)?,
+            Some("lazy.nvim") => Self::restore_lazy_nvim(config, logger).await?,
+            Some("packer.nvim") => Self::restore_packer_nvim(config, logger).await?,
+            Some("vim-plug") => Self::restore_vim_plug(config, logger).await?,
             _ => {
                 logger.log("No supported nvim plugin manager detected");
             }
@@ -1,7 +1,11 @@
 use async_trait::async_trait;
+use std::collections::HashMap;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
-use crate::config::Config;
+use super::{
+    ErrorPolicy, InstalledPackage, OutdatedPackage, Plugin, PluginAction, PluginActionType,
+    PluginMetadata,
+};
+use crate::config::{Config, OsUpdateMode};
 use crate::insights::Insights;
 use crate::logger::Logger;
 use anyhow::Result;
@@ -23,6 +27,7 @@ impl Plugin for OsPlugin {
                 description: "Update OS & app based packages".to_string(),
                 action_type: Some(PluginActionType::Update),
             }],
+            run_after: Vec::new(),
         }
     }
 
@@ -30,6 +35,10 @@ impl Plugin for OsPlugin {
         insights.is_darwin || insights.is_linux
     }
 
+    fn exclusive(&self) -> bool {
+        true
+    }
+
     async fn update(
         &self,
         config: &Config,
@@ -37,25 +46,33 @@ impl Plugin for OsPlugin {
         logger: &mut Logger,
     ) -> Result<()> {
         if insights.is_darwin {
+            if config.os_mode != OsUpdateMode::Full {
+                anyhow::bail!(
+                    "os.mode {} is not supported on macOS; set os.mode to full",
+                    config.os_mode.as_str()
+                );
+            }
             let softwareupdate_result = super::run_with_sudo(
                 config,
                 logger,
-                false,
+                ErrorPolicy::Ignore,
                 "/usr/sbin/softwareupdate",
                 &["-a", "-i", "--verbose"],
-            );
+            )
+            .await;
 
-            if softwareupdate_result.is_err() {
+            if !matches!(softwareupdate_result, Ok(ref o) if o.success()) {
                 super::run_cmd(
                     config,
                     logger,
-                    true,
+                    ErrorPolicy::Fail,
                     "/usr/sbin/softwareupdate",
                     &["-a", "-i", "--verbose"],
-                )?;
+                )
+                .await?;
             }
 
-            super::run_cmd(config, logger, true, "mas", &["update"])?;
+            super::run_cmd(config, logger, ErrorPolicy::Fail, "mas", &["update"]).await?;
 
             return Ok(());
         }
@@ -63,16 +80,13 @@ impl Plugin for OsPlugin {
         if insights.is_linux {
             if let Some(ref pkg_mgr) = insights.pkg_mgr {
                 let commands = match pkg_mgr.as_str() {
-                    "dnf" => vec![
-                        vec!["dnf", "-y", "update"],
-                        vec!["dnf", "-y", "upgrade"],
-                        vec!["dnf", "-y", "update"],
-                    ],
-                    "apt-get" => vec![
-                        vec!["apt-get", "-y", "update"],
-                        vec!["apt-get", "-y", "upgrade"],
-                        vec!["apt-get", "-y", "update"],
-                    ],
+                    "dnf" => return update_dnf(config, insights, logger).await,
+                    "apt-get" => return update_apt(config, insights, logger).await,
+                    _ if config.os_mode != OsUpdateMode::Full => anyhow::bail!(
+                        "os.mode {} is not supported with {}; only apt and dnf can select updates",
+                        config.os_mode.as_str(),
+                        pkg_mgr
+                    ),
                     "apk" => vec![
                         vec!["apk", "update"],
                         vec!["apk", "-y", "upgrade"],
@@ -87,13 +101,7 @@ impl Plugin for OsPlugin {
                 };
 
                 for cmd_args in commands {
-                    let (program, args) = cmd_args.split_first().unwrap();
-                    if insights.is_root {
-                        let args: Vec<&str> = args.to_vec();
-                        super::run_cmd(config, logger, true, program, &args)?;
-                    } else {
-                        super::run_with_sudo(config, logger, true, program, args)?;
-                    }
+                    run_privileged(config, insights, logger, &cmd_args).await?;
                 }
             } else {
                 logger.error("OS not supported for updates");
@@ -102,4 +110,472 @@ impl Plugin for OsPlugin {
 
         Ok(())
     }
+
+    async fn check_outdated(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<OutdatedPackage>>> {
+        if insights.is_darwin {
+            let list =
+                super::query_cmd(config, logger, "/usr/sbin/softwareupdate", &["--list"], &[])
+                    .await?;
+            let mut packages = parse_softwareupdate_list(&list);
+            if which::which("mas").is_ok() {
+                let apps = super::query_cmd(config, logger, "mas", &["outdated"], &[]).await?;
+                packages.extend(parse_mas_outdated(&apps));
+            }
+            return Ok(Some(packages));
+        }
+
+        let packages = match insights.pkg_mgr.as_deref() {
+            Some("apt-get") => parse_apt_upgradable(
+                &super::query_cmd(config, logger, "apt", &["list", "--upgradable"], &[]).await?,
+            ),
+            // dnf exits 100 when updates are available
+            Some("dnf") => parse_dnf_check_update(
+                &super::query_cmd(config, logger, "dnf", &["check-update", "-q"], &[100]).await?,
+            ),
+            Some("apk") => parse_apk_version(
+                &super::query_cmd(config, logger, "apk", &["version", "-l", "<"], &[]).await?,
+            ),
+            // pacman exits 1 when nothing is outdated
+            Some("arch") => parse_pacman_updates(
+                &super::query_cmd(config, logger, "pacman", &["-Qu"], &[1]).await?,
+            ),
+            _ => return Ok(None),
+        };
+        Ok(Some(packages))
+    }
+
+    async fn list_installed(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        if insights.is_darwin {
+            // softwareupdate has no list of what it installed; App Store apps do
+            if which::which("mas").is_err() {
+                return Ok(None);
+            }
+            let apps = super::query_cmd(config, logger, "mas", &["list"], &[]).await?;
+            return Ok(Some(parse_mas_list(&apps)));
+        }
+
+        let packages = match insights.pkg_mgr.as_deref() {
+            Some("apt-get") => parse_dpkg_query(
+                &super::query_cmd(
+                    config,
+                    logger,
+                    "dpkg-query",
+                    &["-W", "-f=${db:Status-Abbrev}\t${Package}\t${Version}\n"],
+                    &[],
+                )
+                .await?,
+            ),
+            Some("dnf") => parse_rpm_query(
+                &super::query_cmd(
+                    config,
+                    logger,
+                    "rpm",
+                    &["-qa", "--qf", "%{NAME}\t%{VERSION}-%{RELEASE}\n"],
+                    &[],
+                )
+                .await?,
+            ),
+            Some("apk") => parse_apk_info(
+                &super::query_cmd(config, logger, "apk", &["info", "-v"], &[]).await?,
+            ),
+            Some("arch") => {
+                parse_pacman_query(&super::query_cmd(config, logger, "pacman", &["-Q"], &[]).await?)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(packages))
+    }
+}
+
+/// Run `cmd_args` as root, through sudo unless already root
+pub(crate) async fn run_privileged(
+    config: &Config,
+    insights: &Insights,
+    logger: &mut Logger,
+    cmd_args: &[&str],
+) -> Result<()> {
+    let (program, args) = cmd_args.split_first().unwrap();
+    if insights.is_root {
+        super::run_cmd(config, logger, ErrorPolicy::Fail, program, args).await?;
+    } else {
+        super::run_with_sudo(config, logger, ErrorPolicy::Fail, program, args).await?;
+    }
+    Ok(())
+}
+
+/// Refresh the package lists, then upgrade everything (`full`) or install
+/// the upgrades from the security (and for `minimal`, `-updates`) suites
+async fn update_apt(config: &Config, insights: &Insights, logger: &mut Logger) -> Result<()> {
+    run_privileged(config, insights, logger, &["apt-get", "-y", "update"]).await?;
+    let listed = super::query_cmd(config, logger, "apt", &["list", "--upgradable"], &[])
+        .await
+        .map(|list| parse_apt_update_kinds(&list));
+
+    let updates = match config.os_mode {
+        OsUpdateMode::Full => {
+            run_privileged(config, insights, logger, &["apt-get", "-y", "upgrade"]).await?;
+            run_privileged(config, insights, logger, &["apt-get", "-y", "update"]).await?;
+            match listed {
+                Ok(updates) => updates,
+                Err(e) => {
+                    logger.warn(&format!("Could not count the available updates: {}", e));
+                    return Ok(());
+                }
+            }
+        }
+        mode => {
+            let updates = listed?;
+            let mut args = vec!["apt-get", "-y", "install", "--only-upgrade"];
+            args.extend(
+                updates
+                    .iter()
+                    .filter(|u| u.kind.included_in(mode))
+                    .map(|u| u.name.as_str()),
+            );
+            if args.len() == 4 {
+                logger.log(&format!("No {} updates available", mode.as_str()));
+            } else {
+                run_privileged(config, insights, logger, &args).await?;
+            }
+            updates
+        }
+    };
+    super::note_action(update_note(&updates, config.os_mode));
+    Ok(())
+}
+
+/// Upgrade everything (`full`) or only the packages with security (and for
+/// `minimal`, bug fix) advisories
+async fn update_dnf(config: &Config, insights: &Insights, logger: &mut Logger) -> Result<()> {
+    // Counted first: once upgraded, nothing is left to list
+    let listed = async {
+        let check_update =
+            super::query_cmd(config, logger, "dnf", &["check-update", "-q"], &[100]).await?;
+        let advisories =
+            super::query_cmd(config, logger, "dnf", &["-q", "updateinfo", "list"], &[]).await?;
+        anyhow::Ok(parse_dnf_update_kinds(&check_update, &advisories))
+    }
+    .await;
+
+    let commands: &[&[&str]] = match config.os_mode {
+        OsUpdateMode::Full => &[
+            &["dnf", "-y", "update"],
+            &["dnf", "-y", "upgrade"],
+            &["dnf", "-y", "update"],
+        ],
+        OsUpdateMode::Security => &[&["dnf", "-y", "upgrade", "--security"]],
+        OsUpdateMode::Minimal => &[&["dnf", "-y", "upgrade", "--security", "--bugfix"]],
+    };
+    for cmd_args in commands {
+        run_privileged(config, insights, logger, cmd_args).await?;
+    }
+
+    match listed {
+        Ok(updates) => super::note_action(update_note(&updates, config.os_mode)),
+        Err(e) => logger.warn(&format!("Could not count the available updates: {}", e)),
+    }
+    Ok(())
+}
+
+/// What an OS update fixes, from the suite (apt) or advisory (dnf) it comes
+/// from. Ordered so that a package with several advisories takes the most
+/// important one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UpdateKind {
+    Other,
+    Bugfix,
+    Security,
+}
+
+impl UpdateKind {
+    /// Whether updates of this kind are installed in `mode`
+    pub fn included_in(self, mode: OsUpdateMode) -> bool {
+        match mode {
+            OsUpdateMode::Full => true,
+            OsUpdateMode::Security => self == Self::Security,
+            OsUpdateMode::Minimal => self >= Self::Bugfix,
+        }
+    }
+}
+
+/// An available OS package update
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClassifiedUpdate {
+    pub name: String,
+    pub kind: UpdateKind,
+}
+
+impl ClassifiedUpdate {
+    pub fn new(name: &str, kind: UpdateKind) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+        }
+    }
+}
+
+/// Summary note for the updates `mode` installs: `12 updates, 3 security`
+pub fn update_note(updates: &[ClassifiedUpdate], mode: OsUpdateMode) -> String {
+    let installed: Vec<&ClassifiedUpdate> = updates
+        .iter()
+        .filter(|u| u.kind.included_in(mode))
+        .collect();
+    let security = installed
+        .iter()
+        .filter(|u| u.kind == UpdateKind::Security)
+        .count();
+    format!(
+        "{} update{}, {} security",
+        installed.len(),
+        if installed.len() == 1 { "" } else { "s" },
+        security
+    )
+}
+
+/// Parse `apt list --upgradable`, classifying each upgrade by the suites it
+/// is available from: `curl/jammy-updates,jammy-security 7.81.0-1ubuntu1.16
+/// amd64 [upgradable from: 7.81.0-1ubuntu1.15]` is a security update, one
+/// from `-updates` alone a bug fix
+pub fn parse_apt_update_kinds(list: &str) -> Vec<ClassifiedUpdate> {
+    list.lines()
+        .filter(|line| line.contains("[upgradable from:"))
+        .filter_map(|line| {
+            let (name, suites) = line.split_whitespace().next()?.split_once('/')?;
+            let suites: Vec<&str> = suites.split(',').collect();
+            let kind = if suites.iter().any(|s| s.ends_with("-security")) {
+                UpdateKind::Security
+            } else if suites.iter().any(|s| s.ends_with("-updates")) {
+                UpdateKind::Bugfix
+            } else {
+                UpdateKind::Other
+            };
+            Some(ClassifiedUpdate::new(name, kind))
+        })
+        .collect()
+}
+
+/// Classify the updates of `dnf check-update -q` by the advisories of
+/// `dnf -q updateinfo list`. dnf 4 prints `FEDORA-2024-1 Moderate/Sec.
+/// curl-8.6.0-1.fc40.x86_64`, dnf 5 `FEDORA-2024-1 security Moderate
+/// curl-8.6.0-1.fc40.x86_64 2024-02-01 00:00:00`. Updates without an
+/// advisory are `Other`.
+pub fn parse_dnf_update_kinds(check_update: &str, updateinfo: &str) -> Vec<ClassifiedUpdate> {
+    let mut kinds: HashMap<&str, UpdateKind> = HashMap::new();
+    for line in updateinfo.lines() {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        let (kind, package) = match columns.as_slice() {
+            [_, kind, package] => (*kind, *package),
+            [_, kind, _severity, package, ..] => (*kind, *package),
+            _ => continue,
+        };
+        let kind = match kind {
+            "security" => UpdateKind::Security,
+            "bugfix" => UpdateKind::Bugfix,
+            k if k.ends_with("Sec.") => UpdateKind::Security,
+            _ => UpdateKind::Other,
+        };
+        // name-version-release.arch
+        let Some(name) = package
+            .rsplit_once('.')
+            .and_then(|(nvr, _arch)| nvr.rsplitn(3, '-').nth(2))
+        else {
+            continue;
+        };
+        let entry = kinds.entry(name).or_insert(kind);
+        *entry = (*entry).max(kind);
+    }
+    parse_dnf_check_update(check_update)
+        .into_iter()
+        .map(|p| {
+            let kind = kinds.get(p.name.as_str()).copied();
+            ClassifiedUpdate::new(&p.name, kind.unwrap_or(UpdateKind::Other))
+        })
+        .collect()
+}
+
+/// Parse `dpkg-query -W -f='${db:Status-Abbrev}\t${Package}\t${Version}\n'`,
+/// keeping installed (`ii`) packages
+pub fn parse_dpkg_query(list: &str) -> Vec<InstalledPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let mut columns = line.split('\t');
+            let status = columns.next()?;
+            let (name, version) = (columns.next()?, columns.next()?);
+            status
+                .starts_with("ii")
+                .then(|| InstalledPackage::new("apt", name, version, "apt"))
+        })
+        .collect()
+}
+
+/// Parse `rpm -qa --qf '%{NAME}\t%{VERSION}-%{RELEASE}\n'`
+pub fn parse_rpm_query(list: &str) -> Vec<InstalledPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let (name, version) = line.split_once('\t')?;
+            Some(InstalledPackage::new("dnf", name, version.trim(), "dnf"))
+        })
+        .collect()
+}
+
+/// Parse `apk info -v`: `busybox-1.36.1-r2`
+pub fn parse_apk_info(list: &str) -> Vec<InstalledPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let mut parts = line.trim().rsplitn(3, '-');
+            let release = parts.next()?;
+            let version = parts.next()?;
+            let name = parts.next()?;
+            Some(InstalledPackage::new(
+                "apk",
+                name,
+                &format!("{}-{}", version, release),
+                "apk",
+            ))
+        })
+        .collect()
+}
+
+/// Parse `pacman -Q`: `linux 6.1.1-1`
+pub fn parse_pacman_query(list: &str) -> Vec<InstalledPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let (name, version) = line.trim().split_once(' ')?;
+            Some(InstalledPackage::new(
+                "pacman",
+                name,
+                version.trim(),
+                "pacman",
+            ))
+        })
+        .collect()
+}
+
+/// Parse `mas list`: `497799835  Xcode  (15.1)`
+pub fn parse_mas_list(list: &str) -> Vec<InstalledPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let (app, version) = line.trim().rsplit_once(" (")?;
+            let (_id, name) = app.split_once(' ')?;
+            let name = name.trim();
+            Some(
+                InstalledPackage::new("mas", name, version.trim_end_matches(')'), "mas")
+                    .with_path(format!("/Applications/{}.app", name)),
+            )
+        })
+        .collect()
+}
+
+/// Parse `apt list --upgradable`:
+/// `curl/jammy-updates 7.81.0-1ubuntu1.16 amd64 [upgradable from: 7.81.0-1ubuntu1.15]`
+pub fn parse_apt_upgradable(list: &str) -> Vec<OutdatedPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let (package, from) = line.split_once("[upgradable from:")?;
+            let mut words = package.split_whitespace();
+            let name = words.next()?.split('/').next()?;
+            let available = words.next()?;
+            Some(OutdatedPackage::new(
+                name,
+                Some(from.trim().trim_end_matches(']')),
+                available,
+            ))
+        })
+        .collect()
+}
+
+/// Parse `dnf check-update -q`: `curl.x86_64  7.76.1-26.el9  baseos`. dnf
+/// does not print the installed version.
+pub fn parse_dnf_check_update(list: &str) -> Vec<OutdatedPackage> {
+    list.lines()
+        .take_while(|line| !line.starts_with("Obsoleting"))
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let [package, version, _repo] = columns.as_slice() else {
+                return None;
+            };
+            let name = package.rsplit_once('.').map_or(*package, |(name, _)| name);
+            Some(OutdatedPackage::new(name, None, version))
+        })
+        .collect()
+}
+
+/// Parse `apk version -l '<'`: `busybox-1.36.1-r2  < 1.36.1-r5`
+pub fn parse_apk_version(list: &str) -> Vec<OutdatedPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let (package, available) = line.split_once('<')?;
+            // name-version-rN, where the name itself may contain dashes
+            let mut parts = package.trim().rsplitn(3, '-');
+            let release = parts.next()?;
+            let version = parts.next()?;
+            let name = parts.next()?;
+            let installed = format!("{}-{}", version, release);
+            Some(OutdatedPackage::new(
+                name,
+                Some(&installed),
+                available.trim(),
+            ))
+        })
+        .collect()
+}
+
+/// Parse `pacman -Qu`: `linux 6.1.1-1 -> 6.1.2-1`
+pub fn parse_pacman_updates(list: &str) -> Vec<OutdatedPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            match columns.as_slice() {
+                [name, installed, "->", available, ..] => {
+                    Some(OutdatedPackage::new(name, Some(installed), available))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Parse `softwareupdate --list`: a `* Label: ...` line followed by a
+/// `Title: ..., Version: ..., ...` line per update
+pub fn parse_softwareupdate_list(list: &str) -> Vec<OutdatedPackage> {
+    let mut packages = Vec::new();
+    let mut label: Option<&str> = None;
+    for line in list.lines().map(str::trim) {
+        if let Some(l) = line.strip_prefix("* Label:") {
+            label = Some(l.trim());
+        } else if let Some(label) = label.take() {
+            let version = line
+                .split(", ")
+                .find_map(|field| field.strip_prefix("Version: "))
+                .unwrap_or("");
+            packages.push(OutdatedPackage::new(label, None, version));
+        }
+    }
+    packages
+}
+
+/// Parse `mas outdated`: `497799835 Xcode (15.0 -> 15.1)`
+pub fn parse_mas_outdated(list: &str) -> Vec<OutdatedPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let (app, versions) = line.trim().rsplit_once(" (")?;
+            let (_id, name) = app.split_once(' ')?;
+            let (installed, available) = versions.trim_end_matches(')').split_once(" -> ")?;
+            Some(OutdatedPackage::new(
+                name.trim(),
+                Some(installed),
+                available,
+            ))
+        })
+        .collect()
 }
@@ -1,17 +1,82 @@
 use async_trait::async_trait;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{
+    ErrorPolicy, InstallScope, InstalledPackage, OutdatedPackage, Plugin, PluginAction,
+    PluginActionType, PluginMetadata,
+};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
 use anyhow::Result;
-use duct::cmd;
 
 pub struct PipPlugin;
 
+/// `pip install <args>`, through `uv pip` when uv is installed
+async fn pip_install(
+    config: &Config,
+    insights: &Insights,
+    logger: &mut Logger,
+    specs: &[String],
+) -> Result<()> {
+    let specs: Vec<&str> = specs.iter().map(String::as_str).collect();
+    if insights.has_uv {
+        let args = uv_pip_install_args(&specs);
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "uv", &args).await?;
+    } else {
+        let mut args = vec!["install"];
+        args.extend(specs);
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "pip", &args).await?;
+    }
+    Ok(())
+}
+
+/// Arguments of `uv pip <subcommand> <args>` against the system interpreter;
+/// uv refuses to modify it outside a virtualenv without `--system`
+pub fn uv_pip_args<'a>(subcommand: &'a str, args: &[&'a str]) -> Vec<&'a str> {
+    let mut uv_args = vec!["pip", subcommand, "--system"];
+    uv_args.extend(args);
+    uv_args
+}
+
+/// [`uv_pip_args`] for `uv pip install`, which is also allowed into an
+/// interpreter the distribution marks as externally managed. Uninstalls
+/// never are, so they cannot take out distribution packages.
+pub fn uv_pip_install_args<'a>(args: &[&'a str]) -> Vec<&'a str> {
+    let mut uv_args = uv_pip_args("install", &["--break-system-packages"]);
+    uv_args.extend(args);
+    uv_args
+}
+
+/// Install arguments for a package read by [`PipPlugin::saved_packages`]:
+/// the requirement as written for editable, URL and range requirements
+fn install_spec(config: &Config, package: &InstalledPackage) -> Vec<String> {
+    if package.source == "pip" {
+        return vec![super::package_spec(config, package, "==")];
+    }
+    match package
+        .source
+        .strip_prefix("--editable")
+        .or_else(|| package.source.strip_prefix("-e"))
+    {
+        Some(target) => vec![
+            "-e".to_string(),
+            target.trim_start_matches([' ', '=']).to_string(),
+        ],
+        None => vec![package.source.clone()],
+    }
+}
+
+/// An entry of `pip list --format=json`, with `latest_version` when
+/// `--outdated` is given and `location` when `-v` is
 #[derive(serde::Deserialize)]
 struct PipOutdatedPackage {
     name: String,
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    latest_version: String,
+    #[serde(default)]
+    location: Option<String>,
 }
 
 #[async_trait]
@@ -41,6 +106,7 @@ impl Plugin for PipPlugin {
                     action_type: Some(PluginActionType::Restore),
                 },
             ],
+            run_after: Vec::new(),
         }
     }
 
@@ -60,13 +126,13 @@ impl Plugin for PipPlugin {
             ("pip", &["list", "--outdated", "--format=json"])
         };
 
-        let output = cmd(prog, list_args).stdout_capture().run()?;
+        let json = super::query_cmd(config, logger, prog, list_args, &[]).await?;
 
-        if output.stdout.is_empty() {
+        if json.trim().is_empty() {
             return Ok(());
         }
 
-        let packages: Vec<PipOutdatedPackage> = match serde_json::from_slice(&output.stdout) {
+        let packages: Vec<PipOutdatedPackage> = match serde_json::from_str(&json) {
             Ok(pkgs) => pkgs,
             Err(e) => {
                 logger.error(&format!("Failed to parse outdated package list: {}", e));
@@ -81,19 +147,13 @@ impl Plugin for PipPlugin {
         let names: Vec<&str> = packages.iter().map(|p| p.name.as_str()).collect();
 
         if insights.has_uv {
-            let mut args = vec![
-                "pip",
-                "install",
-                "--upgrade",
-                "--system",
-                "--break-system-packages",
-            ];
+            let mut args = uv_pip_install_args(&["--upgrade"]);
             args.extend(names);
-            super::run_cmd(config, logger, true, "uv", &args)?;
+            super::run_cmd(config, logger, ErrorPolicy::Fail, "uv", &args).await?;
         } else {
             let mut args = vec!["install", "--upgrade"];
             args.extend(names);
-            super::run_cmd(config, logger, true, "pip", &args)?;
+            super::run_cmd(config, logger, ErrorPolicy::Fail, "pip", &args).await?;
         }
 
         Ok(())
@@ -114,13 +174,10 @@ impl Plugin for PipPlugin {
             ("pip", &["freeze"] as &[&str])
         };
 
-        let output = cmd(prog, args).stdout_capture().stderr_capture().run()?;
+        let freeze = super::query_cmd(config, logger, prog, args, &[]).await?;
 
-        if !output.stdout.is_empty() {
-            std::fs::write(&pip_file, &output.stdout)?;
-        }
-        if !output.stderr.is_empty() {
-            logger.log(&String::from_utf8_lossy(&output.stderr));
+        if !freeze.is_empty() {
+            std::fs::write(&pip_file, freeze)?;
         }
 
         logger.log("Success savefile written");
@@ -145,18 +202,227 @@ impl Plugin for PipPlugin {
         }
 
         logger.log(&format!("Restoring pip packages from {}", pip_file));
+        let mut install = Vec::new();
+        if config.restore_pinned {
+            let content = std::fs::read_to_string(&config.pip_file)?;
+            for requirement in unpinned_requirements(&content) {
+                logger.warn(&format!(
+                    "{} has no exact version; installing it as written",
+                    requirement
+                ));
+            }
+            // A freeze file pins every dependency; don't let the resolver pick others
+            install.push("--no-deps".to_string());
+        }
+        install.extend(["-r".to_string(), pip_file]);
+        pip_install(config, insights, logger, &install).await?;
+
+        Ok(())
+    }
+
+    fn saved_packages(&self, config: &Config) -> Result<Option<Vec<InstalledPackage>>> {
+        let content = super::read_save_file(&config.pip_file)?;
+        Ok(Some(parse_pip_requirements(&content)))
+    }
+
+    async fn install_packages(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let specs: Vec<String> = packages
+            .iter()
+            .flat_map(|p| install_spec(config, p))
+            .collect();
+        pip_install(config, insights, logger, &specs).await?;
+        Ok(true)
+    }
+
+    async fn install_versions(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let specs: Vec<String> = packages
+            .iter()
+            .map(|p| super::versioned_spec(p, "=="))
+            .collect();
+        pip_install(config, insights, logger, &specs).await?;
+        Ok(true)
+    }
+
+    async fn remove_packages(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        // Only what the user installed themselves; system site-packages
+        // belong to the distribution or the admin
+        let (user, system): (Vec<&InstalledPackage>, Vec<&InstalledPackage>) =
+            packages.iter().partition(|p| p.scope == InstallScope::User);
+        if !system.is_empty() {
+            logger.warn(&format!(
+                "Keeping {} packages not installed in the home directory: {}",
+                system.len(),
+                system
+                    .iter()
+                    .map(|p| p.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if user.is_empty() {
+            return Ok(true);
+        }
+        let names: Vec<&str> = user.iter().map(|p| p.name.as_str()).collect();
         if insights.has_uv {
-            super::run_cmd(
-                config,
-                logger,
-                true,
-                "uv",
-                &["pip", "install", "-r", &pip_file],
-            )?;
+            let args = uv_pip_args("uninstall", &names);
+            super::run_cmd(config, logger, ErrorPolicy::Fail, "uv", &args).await?;
         } else {
-            super::run_cmd(config, logger, true, "pip", &["install", "-r", &pip_file])?;
+            let mut args = vec!["uninstall", "-y"];
+            args.extend(names);
+            super::run_cmd(config, logger, ErrorPolicy::Fail, "pip", &args).await?;
         }
+        Ok(true)
+    }
 
-        Ok(())
+    async fn check_outdated(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<OutdatedPackage>>> {
+        let (prog, list_args): (&str, &[&str]) = if insights.has_uv {
+            ("uv", &["pip", "list", "--outdated", "--format=json"])
+        } else {
+            ("pip", &["list", "--outdated", "--format=json"])
+        };
+        let json = super::query_cmd(config, logger, prog, list_args, &[]).await?;
+        parse_pip_outdated(&json).map(Some)
     }
+
+    async fn list_installed(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        let (prog, list_args): (&str, &[&str]) = if insights.has_uv {
+            ("uv", &["pip", "list", "--format=json"])
+        } else {
+            ("pip", &["list", "-v", "--format=json"])
+        };
+        let json = super::query_cmd(config, logger, prog, list_args, &[]).await?;
+        parse_pip_list(&json).map(Some)
+    }
+}
+
+/// Parse `pip list -v --format=json` (or `uv pip list --format=json`, which
+/// has no install location)
+pub fn parse_pip_list(json: &str) -> Result<Vec<InstalledPackage>> {
+    if json.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let packages: Vec<PipOutdatedPackage> = serde_json::from_str(json)?;
+    Ok(packages
+        .iter()
+        .map(|p| {
+            let package = InstalledPackage::new("pip", &p.name, &p.version, "pip");
+            match p.location {
+                Some(ref location) => package
+                    .with_scope(InstallScope::of_path(location))
+                    .with_path(location.as_str()),
+                None => package,
+            }
+        })
+        .collect())
+}
+
+/// Parse `pip list --outdated --format=json` (or the `uv pip` equivalent)
+pub fn parse_pip_outdated(json: &str) -> Result<Vec<OutdatedPackage>> {
+    if json.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let packages: Vec<PipOutdatedPackage> = serde_json::from_str(json)?;
+    Ok(packages
+        .iter()
+        .map(|p| OutdatedPackage::new(&p.name, Some(&p.version), &p.latest_version))
+        .collect())
+}
+
+/// Requirements of a `pip freeze` file without comments, markers and option
+/// lines (`--index-url`, ...); editable installs are kept
+fn requirements(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .map(|line| line.split(" #").next().unwrap_or(line))
+        .map(|line| line.split(';').next().unwrap_or(line).trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter(|line| {
+            !line.starts_with('-') || line.starts_with("-e ") || line.starts_with("--editable")
+        })
+}
+
+/// Parse the `name==version` lines of a `pip freeze` file
+pub fn parse_pip_freeze(content: &str) -> Vec<InstalledPackage> {
+    requirements(content)
+        .filter_map(|line| {
+            let (name, version) = line.split_once("==")?;
+            let name = name.split('[').next().unwrap_or(name).trim();
+            let version = version.trim_start_matches('=').trim();
+            (!name.is_empty() && !version.is_empty())
+                .then(|| InstalledPackage::new("pip", name, version, "pip"))
+        })
+        .collect()
+}
+
+/// Every requirement of a `pip freeze` file that names a package: the
+/// pinned ones as [`parse_pip_freeze`] reads them, and editable, URL and
+/// range requirements without a version and with the requirement as
+/// written for their source
+pub fn parse_pip_requirements(content: &str) -> Vec<InstalledPackage> {
+    let mut packages = parse_pip_freeze(content);
+    packages.extend(
+        unpinned_requirements(content)
+            .into_iter()
+            .filter_map(|line| {
+                let name = requirement_name(line)?;
+                Some(InstalledPackage::new("pip", name, "", line))
+            }),
+    );
+    packages
+}
+
+/// Distribution name of an unpinned requirement: the `#egg=` fragment of an
+/// editable install or else the last component of its path, otherwise
+/// whatever precedes the version specifier or URL
+fn requirement_name(line: &str) -> Option<&str> {
+    let name = match line.split_once("#egg=") {
+        Some((_, egg)) => egg,
+        None if line.starts_with('-') => {
+            let target = line.trim_end_matches('/');
+            let last = target.rsplit(['/', ' ', '=']).next()?;
+            last.strip_suffix(".git").unwrap_or(last)
+        }
+        None => line,
+    };
+    let name = name
+        .split(|c: char| !(c.is_ascii_alphanumeric() || "-_.".contains(c)))
+        .next()?
+        .trim();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Requirements of a `pip freeze` file that don't pin an exact version:
+/// editable installs, direct URLs and ranges
+pub fn unpinned_requirements(content: &str) -> Vec<&str> {
+    requirements(content)
+        .filter(|line| line.starts_with('-') || !line.contains("==") || line.contains(" @ "))
+        .collect()
 }
@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{ErrorPolicy, Plugin, PluginAction, PluginActionType, PluginMetadata};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
@@ -23,6 +23,7 @@ impl Plugin for RunPlugin {
                 description: "Run a command specified via --cmd".to_string(),
                 action_type: Some(PluginActionType::Update),
             }],
+            run_after: Vec::new(),
         }
     }
 
@@ -75,7 +76,7 @@ impl Plugin for RunPlugin {
             }
         }
 
-        super::run_cmd(config, logger, true, &program, &args)?;
+        super::run_cmd(config, logger, ErrorPolicy::Fail, &program, &args).await?;
         Ok(())
     }
 
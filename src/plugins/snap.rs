@@ -1,6 +1,9 @@
 use async_trait::async_trait;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{
+    ErrorPolicy, InstalledPackage, OutdatedPackage, Plugin, PluginAction, PluginActionType,
+    PluginMetadata,
+};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
@@ -8,6 +11,21 @@ use anyhow::Result;
 
 pub struct SnapPlugin;
 
+/// Installing and removing snaps needs root
+async fn snap_run_cmd(
+    config: &Config,
+    insights: &Insights,
+    logger: &mut Logger,
+    args: &[&str],
+) -> Result<()> {
+    if insights.is_root {
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "snap", args).await?;
+    } else {
+        super::run_with_sudo(config, logger, ErrorPolicy::Fail, "snap", args).await?;
+    }
+    Ok(())
+}
+
 #[async_trait]
 impl Plugin for SnapPlugin {
     fn name(&self) -> &str {
@@ -18,11 +36,24 @@ impl Plugin for SnapPlugin {
         PluginMetadata {
             name: "snap".to_string(),
             description: "Update Snap packages".to_string(),
-            actions: vec![PluginAction {
-                name: "snap".to_string(),
-                description: "Refresh all Snap packages".to_string(),
-                action_type: Some(PluginActionType::Update),
-            }],
+            actions: vec![
+                PluginAction {
+                    name: "snap".to_string(),
+                    description: "Refresh all Snap packages".to_string(),
+                    action_type: Some(PluginActionType::Update),
+                },
+                PluginAction {
+                    name: "snap-save".to_string(),
+                    description: "Save installed Snap packages to a list".to_string(),
+                    action_type: Some(PluginActionType::Save),
+                },
+                PluginAction {
+                    name: "snap-restore".to_string(),
+                    description: "Install the Snap packages of the saved list".to_string(),
+                    action_type: Some(PluginActionType::Restore),
+                },
+            ],
+            run_after: Vec::new(),
         }
     }
 
@@ -30,13 +61,130 @@ impl Plugin for SnapPlugin {
         insights.has_snap
     }
 
+    fn exclusive(&self) -> bool {
+        true
+    }
+
     async fn update(
         &self,
         config: &Config,
         _insights: &Insights,
         logger: &mut Logger,
     ) -> Result<()> {
-        super::run_cmd(config, logger, true, "snap", &["refresh"])?;
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "snap", &["refresh"]).await?;
         Ok(())
     }
+
+    async fn save(&self, config: &Config, _insights: &Insights, logger: &mut Logger) -> Result<()> {
+        logger.log(&format!(
+            "Saving Snap packages to {}",
+            config.snap_file.display()
+        ));
+        let list = super::query_cmd(config, logger, "snap", &["list"], &[]).await?;
+        super::write_save_file(config, logger, &config.snap_file, &list)
+    }
+
+    async fn restore(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<()> {
+        if !config.snap_file.exists() {
+            logger.error(&format!(
+                "missing dependency — {} snap's backup file is not found",
+                config.snap_file.display()
+            ));
+            return Ok(());
+        }
+        logger.log(&format!(
+            "Restoring Snap packages from {}",
+            config.snap_file.display()
+        ));
+        let packages = parse_snap_list(&super::read_save_file(&config.snap_file)?);
+        if !packages.is_empty() {
+            self.install_packages(config, insights, logger, &packages)
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn saved_packages(&self, config: &Config) -> Result<Option<Vec<InstalledPackage>>> {
+        let list = super::read_save_file(&config.snap_file)?;
+        Ok(Some(parse_snap_list(&list)))
+    }
+
+    async fn install_packages(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let mut args = vec!["install"];
+        args.extend(packages.iter().map(|p| p.name.as_str()));
+        snap_run_cmd(config, insights, logger, &args).await?;
+        Ok(true)
+    }
+
+    async fn remove_packages(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let mut args = vec!["remove"];
+        args.extend(packages.iter().map(|p| p.name.as_str()));
+        snap_run_cmd(config, insights, logger, &args).await?;
+        Ok(true)
+    }
+
+    async fn check_outdated(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<OutdatedPackage>>> {
+        let updates = super::query_cmd(config, logger, "snap", &["refresh", "--list"], &[]).await?;
+        let installed = super::query_cmd(config, logger, "snap", &["list"], &[]).await?;
+        Ok(Some(parse_snap_refresh_list(&updates, &installed)))
+    }
+
+    async fn list_installed(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        let installed = super::query_cmd(config, logger, "snap", &["list"], &[]).await?;
+        Ok(Some(parse_snap_list(&installed)))
+    }
+}
+
+/// First two columns of a snap table, without its `Name Version ...` header
+fn snap_versions(table: &str) -> impl Iterator<Item = (&str, &str)> {
+    table.lines().skip(1).filter_map(|line| {
+        let mut columns = line.split_whitespace();
+        Some((columns.next()?, columns.next()?))
+    })
+}
+
+/// Match `snap refresh --list` against `snap list`; both print a table
+/// starting with Name and Version columns
+pub fn parse_snap_refresh_list(updates: &str, installed: &str) -> Vec<OutdatedPackage> {
+    let installed: std::collections::HashMap<&str, &str> = snap_versions(installed).collect();
+    snap_versions(updates)
+        .map(|(name, version)| OutdatedPackage::new(name, installed.get(name).copied(), version))
+        .collect()
+}
+
+/// Parse `snap list`
+pub fn parse_snap_list(installed: &str) -> Vec<InstalledPackage> {
+    snap_versions(installed)
+        .map(|(name, version)| {
+            InstalledPackage::new("snap", name, version, "snap")
+                .with_path(format!("/snap/{}/current", name))
+        })
+        .collect()
 }
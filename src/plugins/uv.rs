@@ -1,11 +1,13 @@
 use async_trait::async_trait;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{
+    ErrorPolicy, InstallScope, InstalledPackage, OutdatedPackage, Plugin, PluginAction,
+    PluginActionType, PluginMetadata,
+};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
 use anyhow::Result;
-use duct::cmd;
 
 pub struct UvPlugin;
 
@@ -48,6 +50,7 @@ impl Plugin for UvPlugin {
                     action_type: None,
                 },
             ],
+            run_after: Vec::new(),
         }
     }
 
@@ -64,7 +67,7 @@ impl Plugin for UvPlugin {
     ) -> Result<bool> {
         match action_name {
             "uv-list" => {
-                super::run_cmd(config, logger, true, "uv", &["tool", "list"])?;
+                super::run_cmd(config, logger, ErrorPolicy::Fail, "uv", &["tool", "list"]).await?;
                 Ok(true)
             }
             "uvx" => {
@@ -84,7 +87,14 @@ impl Plugin for UvPlugin {
         _insights: &Insights,
         logger: &mut Logger,
     ) -> Result<()> {
-        super::run_cmd(config, logger, true, "uv", &["tool", "upgrade", "--all"])?;
+        super::run_cmd(
+            config,
+            logger,
+            ErrorPolicy::Fail,
+            "uv",
+            &["tool", "upgrade", "--all"],
+        )
+        .await?;
         Ok(())
     }
 
@@ -108,16 +118,10 @@ impl Plugin for UvPlugin {
             return Ok(());
         }
 
-        let output = cmd("uv", &["tool", "list"])
-            .stdout_capture()
-            .stderr_capture()
-            .run()?;
+        let list = super::query_cmd(config, logger, "uv", &["tool", "list"], &[]).await?;
 
-        if !output.stdout.is_empty() {
-            std::fs::write(&uv_file, &output.stdout)?;
-        }
-        if !output.stderr.is_empty() {
-            logger.log(&String::from_utf8_lossy(&output.stderr));
+        if !list.is_empty() {
+            std::fs::write(&uv_file, list)?;
         }
 
         self.update(config, insights, logger).await?;
@@ -152,11 +156,157 @@ impl Plugin for UvPlugin {
                 continue;
             }
             let name = tool.split(&['@', ' '][..]).next().unwrap_or(tool);
-            if !name.is_empty() {
-                super::run_cmd(config, logger, true, "uv", &["tool", "install", name])?;
+            if name.is_empty() {
+                continue;
             }
+            let version = tool
+                .split_whitespace()
+                .nth(1)
+                .and_then(|v| v.strip_prefix('v'));
+            let spec = match version {
+                Some(version) if config.restore_pinned => format!("{}=={}", name, version),
+                _ => name.to_string(),
+            };
+            super::run_cmd(
+                config,
+                logger,
+                ErrorPolicy::Fail,
+                "uv",
+                &["tool", "install", &spec],
+            )
+            .await?;
         }
 
         Ok(())
     }
+
+    fn saved_packages(&self, config: &Config) -> Result<Option<Vec<InstalledPackage>>> {
+        let content = super::read_save_file(&config.uv_file)?;
+        Ok(Some(parse_uv_tool_list(&content)))
+    }
+
+    async fn install_packages(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        // uv tool install takes one tool at a time
+        for package in packages {
+            let spec = super::package_spec(config, package, "==");
+            super::run_cmd(
+                config,
+                logger,
+                ErrorPolicy::Fail,
+                "uv",
+                &["tool", "install", &spec],
+            )
+            .await?;
+        }
+        Ok(true)
+    }
+
+    async fn install_versions(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        for package in packages {
+            let spec = super::versioned_spec(package, "==");
+            super::run_cmd(
+                config,
+                logger,
+                ErrorPolicy::Fail,
+                "uv",
+                &["tool", "install", &spec],
+            )
+            .await?;
+        }
+        Ok(true)
+    }
+
+    async fn remove_packages(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let mut args = vec!["tool", "uninstall"];
+        args.extend(packages.iter().map(|p| p.name.as_str()));
+        super::run_cmd(config, logger, ErrorPolicy::Fail, "uv", &args).await?;
+        Ok(true)
+    }
+
+    async fn check_outdated(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<OutdatedPackage>>> {
+        let list =
+            super::query_cmd(config, logger, "uv", &["tool", "list", "--outdated"], &[]).await?;
+        Ok(Some(parse_uv_tool_outdated(&list)))
+    }
+
+    async fn list_installed(
+        &self,
+        config: &Config,
+        _insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        let list = super::query_cmd(config, logger, "uv", &["tool", "list"], &[]).await?;
+        let dir = super::query_cmd(config, logger, "uv", &["tool", "dir"], &[]).await?;
+        let dir = dir.trim();
+        let packages = parse_uv_tool_list(&list)
+            .into_iter()
+            .map(|p| p.with_scope(InstallScope::User));
+        if dir.is_empty() {
+            return Ok(Some(packages.collect()));
+        }
+        Ok(Some(
+            packages
+                .map(|p| {
+                    let path = format!("{}/{}", dir, p.name);
+                    p.with_scope(InstallScope::of_path(dir)).with_path(path)
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Parse `uv tool list --outdated`: `ruff v0.4.0 [latest: 0.5.0]` lines,
+/// each followed by `- executable` lines
+pub fn parse_uv_tool_outdated(list: &str) -> Vec<OutdatedPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let (tool, latest) = line.split_once("[latest:")?;
+            let mut words = tool.split_whitespace();
+            let name = words.next()?;
+            let installed = words.next().map(|v| v.trim_start_matches('v'));
+            let latest = latest.trim().trim_end_matches(']').trim();
+            Some(OutdatedPackage::new(
+                name,
+                installed,
+                latest.trim_start_matches('v'),
+            ))
+        })
+        .collect()
+}
+
+/// Parse `uv tool list`: `ruff v0.4.0` lines, each followed by
+/// `- executable` lines
+pub fn parse_uv_tool_list(list: &str) -> Vec<InstalledPackage> {
+    list.lines()
+        .filter(|line| !line.starts_with('-'))
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let name = words.next()?;
+            let version = words.next()?.strip_prefix('v')?;
+            Some(InstalledPackage::new("uv", name, version, "uv"))
+        })
+        .collect()
 }
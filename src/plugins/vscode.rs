@@ -1,13 +1,31 @@
 use async_trait::async_trait;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{
+    ErrorPolicy, InstallScope, InstalledPackage, Plugin, PluginAction, PluginActionType,
+    PluginMetadata,
+};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
-use anyhow::Result;
+use anyhow::{Context, Result};
 
 pub struct VscodePlugin;
 
+/// Run the detected editor's CLI with `args`
+async fn editor_run_cmd(
+    config: &Config,
+    insights: &Insights,
+    logger: &mut Logger,
+    args: &[&str],
+) -> Result<()> {
+    let editor = insights
+        .vscode_bin
+        .as_deref()
+        .context("No VSCode or Cursor command found")?;
+    super::run_cmd(config, logger, ErrorPolicy::Fail, editor, args).await?;
+    Ok(())
+}
+
 #[async_trait]
 impl Plugin for VscodePlugin {
     fn name(&self) -> &str {
@@ -18,11 +36,25 @@ impl Plugin for VscodePlugin {
         PluginMetadata {
             name: "vscode".to_string(),
             description: "Update VSCode/Cursor extensions".to_string(),
-            actions: vec![PluginAction {
-                name: "vscode".to_string(),
-                description: "Update all VSCode/Cursor extensions".to_string(),
-                action_type: Some(PluginActionType::Update),
-            }],
+            actions: vec![
+                PluginAction {
+                    name: "vscode".to_string(),
+                    description: "Update all VSCode/Cursor extensions".to_string(),
+                    action_type: Some(PluginActionType::Update),
+                },
+                PluginAction {
+                    name: "vscode-save".to_string(),
+                    description: "Save installed VSCode/Cursor extensions to a list".to_string(),
+                    action_type: Some(PluginActionType::Save),
+                },
+                PluginAction {
+                    name: "vscode-restore".to_string(),
+                    description: "Install the VSCode/Cursor extensions of the saved list"
+                        .to_string(),
+                    action_type: Some(PluginActionType::Restore),
+                },
+            ],
+            run_after: Vec::new(),
         }
     }
 
@@ -37,8 +69,157 @@ impl Plugin for VscodePlugin {
         logger: &mut Logger,
     ) -> Result<()> {
         if let Some(ref editor) = insights.vscode_bin {
-            super::run_cmd(config, logger, true, editor, &["--update-extensions"])?;
+            super::run_cmd(
+                config,
+                logger,
+                ErrorPolicy::Fail,
+                editor,
+                &["--update-extensions"],
+            )
+            .await?;
         }
         Ok(())
     }
+
+    async fn save(&self, config: &Config, insights: &Insights, logger: &mut Logger) -> Result<()> {
+        let Some(ref editor) = insights.vscode_bin else {
+            return Ok(());
+        };
+        logger.log(&format!(
+            "Saving {} extensions to {}",
+            editor,
+            config.vscode_file.display()
+        ));
+        let list = super::query_cmd(
+            config,
+            logger,
+            editor,
+            &["--list-extensions", "--show-versions"],
+            &[],
+        )
+        .await?;
+        super::write_save_file(config, logger, &config.vscode_file, &list)
+    }
+
+    async fn restore(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<()> {
+        if !config.vscode_file.exists() {
+            logger.error(&format!(
+                "missing dependency — {} vscode's backup file is not found",
+                config.vscode_file.display()
+            ));
+            return Ok(());
+        }
+        logger.log(&format!(
+            "Restoring extensions from {}",
+            config.vscode_file.display()
+        ));
+        let packages =
+            parse_vscode_extensions(&super::read_save_file(&config.vscode_file)?, "vscode");
+        if !packages.is_empty() {
+            self.install_packages(config, insights, logger, &packages)
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn saved_packages(&self, config: &Config) -> Result<Option<Vec<InstalledPackage>>> {
+        let list = super::read_save_file(&config.vscode_file)?;
+        Ok(Some(parse_vscode_extensions(&list, "vscode")))
+    }
+
+    async fn install_packages(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let specs: Vec<String> = packages
+            .iter()
+            .map(|p| super::package_spec(config, p, "@"))
+            .collect();
+        let args: Vec<&str> = specs
+            .iter()
+            .flat_map(|spec| ["--install-extension", spec.as_str()])
+            .collect();
+        editor_run_cmd(config, insights, logger, &args).await?;
+        Ok(true)
+    }
+
+    async fn install_versions(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let specs: Vec<String> = packages
+            .iter()
+            .map(|p| super::versioned_spec(p, "@"))
+            .collect();
+        let mut args: Vec<&str> = specs
+            .iter()
+            .flat_map(|spec| ["--install-extension", spec.as_str()])
+            .collect();
+        // Without --force the editor keeps the newer version
+        args.push("--force");
+        editor_run_cmd(config, insights, logger, &args).await?;
+        Ok(true)
+    }
+
+    async fn remove_packages(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        let args: Vec<&str> = packages
+            .iter()
+            .flat_map(|p| ["--uninstall-extension", p.name.as_str()])
+            .collect();
+        editor_run_cmd(config, insights, logger, &args).await?;
+        Ok(true)
+    }
+
+    async fn list_installed(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        let Some(ref editor) = insights.vscode_bin else {
+            return Ok(None);
+        };
+        let list = super::query_cmd(
+            config,
+            logger,
+            editor,
+            &["--list-extensions", "--show-versions"],
+            &[],
+        )
+        .await?;
+        let source = std::path::Path::new(editor)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(editor);
+        Ok(Some(parse_vscode_extensions(&list, source)))
+    }
+}
+
+/// Parse `code --list-extensions --show-versions`: `publisher.name@1.2.3`
+pub fn parse_vscode_extensions(list: &str, editor: &str) -> Vec<InstalledPackage> {
+    list.lines()
+        .filter_map(|line| {
+            let (name, version) = line.trim().rsplit_once('@')?;
+            Some(
+                InstalledPackage::new(editor, name, version, editor).with_scope(InstallScope::User),
+            )
+        })
+        .collect()
 }
@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 
-use super::{Plugin, PluginAction, PluginActionType, PluginMetadata};
+use super::{ErrorPolicy, Plugin, PluginAction, PluginActionType, PluginMetadata};
 use crate::config::Config;
 use crate::insights::Insights;
 use crate::logger::Logger;
@@ -35,6 +35,7 @@ impl Plugin for YarnPlugin {
                     action_type: Some(PluginActionType::Restore),
                 },
             ],
+            run_after: Vec::new(),
         }
     }
 
@@ -48,7 +49,14 @@ impl Plugin for YarnPlugin {
         _insights: &Insights,
         logger: &mut Logger,
     ) -> Result<()> {
-        super::run_cmd(config, logger, true, "yarn", &["global", "upgrade"])?;
+        super::run_cmd(
+            config,
+            logger,
+            ErrorPolicy::Fail,
+            "yarn",
+            &["global", "upgrade"],
+        )
+        .await?;
         Ok(())
     }
 
@@ -58,7 +66,14 @@ impl Plugin for YarnPlugin {
             std::fs::create_dir_all(parent)?;
         }
         logger.log(&format!("Saving yarn global packages to {}", yarn_file));
-        super::run_cmd(config, logger, true, "yarn", &["global", "list", "--json"])?;
+        super::run_cmd(
+            config,
+            logger,
+            ErrorPolicy::Fail,
+            "yarn",
+            &["global", "list", "--json"],
+        )
+        .await?;
         logger.log("Success savefile written");
         Ok(())
     }
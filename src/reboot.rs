@@ -0,0 +1,170 @@
+use anyhow::Result;
+use std::cmp::Ordering;
+use std::path::Path;
+
+use crate::config::{AutoReboot, Config};
+use crate::insights::Insights;
+use crate::inventory::compare_versions;
+use crate::logger::Logger;
+use crate::plugins::os::run_privileged;
+use crate::plugins::{cancellation, query_cmd};
+use crate::runner::{ActionResult, ActionStatus, record_action};
+
+/// Once the `os` update in `results` has run, note on its result whether
+/// the machine needs a reboot and schedule the reboot `auto_reboot` asks
+/// for, reported as a `reboot` action. Returns whether a reboot is needed.
+pub async fn check_reboot(
+    config: &Config,
+    insights: &Insights,
+    logger: &Logger,
+    results: &mut Vec<ActionResult>,
+) -> bool {
+    let Some(os) = results.iter_mut().find(|r| {
+        r.action == "os" && !matches!(r.status, ActionStatus::Skipped | ActionStatus::Cancelled)
+    }) else {
+        return false;
+    };
+    if !insights.is_linux {
+        return false;
+    }
+    let mut logger = logger.with_prefix("os");
+    let reasons = reboot_reasons(config, insights, &mut logger).await;
+    if reasons.is_empty() {
+        os.notes.push("no reboot required".to_string());
+        return false;
+    }
+    os.notes
+        .push(format!("reboot required: {}", reasons.join(", ")));
+    logger.warn(&format!("Reboot required: {}", reasons.join(", ")));
+
+    if config.auto_reboot != AutoReboot::Never && cancellation().is_none() {
+        let result = record_action(
+            "os",
+            "reboot",
+            schedule_reboot(config, insights, &mut logger),
+        )
+        .await;
+        if let Some(ref e) = result.error {
+            logger.error(e);
+        }
+        results.push(result);
+    }
+    true
+}
+
+/// Why the machine needs a reboot after an OS update, one reason per check
+/// that found one: the Debian `reboot-required` flag, `dnf needs-restarting`
+/// and a newer kernel than the running one. Checks that cannot run are
+/// logged and skipped.
+pub async fn reboot_reasons(
+    config: &Config,
+    insights: &Insights,
+    logger: &mut Logger,
+) -> Vec<String> {
+    let mut reasons = Vec::new();
+    if !insights.is_linux {
+        return reasons;
+    }
+    if let Some(reason) = debian_reboot_required(Path::new("/var/run")) {
+        reasons.push(reason);
+    }
+    if insights.pkg_mgr.as_deref() == Some("dnf") {
+        // Exits 1 when a reboot is needed
+        match query_cmd(config, logger, "dnf", &["needs-restarting", "-r"], &[1]).await {
+            Ok(output) if parse_needs_restarting(&output) => {
+                reasons.push("core libraries or services updated".to_string());
+            }
+            Ok(_) => {}
+            Err(e) => logger.log(&format!("Could not run dnf needs-restarting: {}", e)),
+        }
+    }
+    if let Ok(running) = std::fs::read_to_string("/proc/sys/kernel/osrelease") {
+        let installed = installed_kernels(Path::new("/lib/modules"));
+        if let Some(newest) = newer_kernel(running.trim(), &installed) {
+            reasons.push(format!("kernel {} → {}", running.trim(), newest));
+        }
+    }
+    reasons
+}
+
+/// Debian and Ubuntu packages create `reboot-required` in `run_dir` when
+/// they need a reboot, and list themselves in `reboot-required.pkgs`
+pub fn debian_reboot_required(run_dir: &Path) -> Option<String> {
+    if !run_dir.join("reboot-required").exists() {
+        return None;
+    }
+    let mut packages: Vec<String> = std::fs::read_to_string(run_dir.join("reboot-required.pkgs"))
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+    packages.sort();
+    packages.dedup();
+    if packages.is_empty() {
+        Some("reboot-required flag set".to_string())
+    } else {
+        Some(format!("{} updated", packages.join(" ")))
+    }
+}
+
+/// Whether `dnf needs-restarting -r` asks for a reboot
+pub fn parse_needs_restarting(output: &str) -> bool {
+    output.contains("Reboot is required")
+}
+
+/// Kernel versions with modules in `modules_dir`. Directories without
+/// `modules.dep` are leftovers of removed kernels.
+pub fn installed_kernels(modules_dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(modules_dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| e.path().join("modules.dep").is_file())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .collect()
+}
+
+/// The newest of the `installed` kernels when it is newer than `running`
+pub fn newer_kernel<'a>(running: &str, installed: &'a [String]) -> Option<&'a str> {
+    installed
+        .iter()
+        .max_by(|a, b| compare_versions(a, b))
+        .map(String::as_str)
+        .filter(|newest| compare_versions(newest, running) == Ordering::Greater)
+}
+
+/// `shutdown` arguments rebooting as `config.auto_reboot` asks, `None` when
+/// it never reboots
+pub fn shutdown_args(config: &Config) -> Option<Vec<String>> {
+    let when = match config.auto_reboot {
+        AutoReboot::Never => return None,
+        AutoReboot::IfRequired => format!("+{}", config.reboot_grace),
+        AutoReboot::At(ref time) => time.clone(),
+    };
+    Some(vec![
+        "-r".to_string(),
+        when,
+        format!(
+            "{}: rebooting to finish installing updates",
+            config.app_name
+        ),
+    ])
+}
+
+/// Schedule the reboot with `shutdown`, which warns logged-in users and can
+/// be called off with `shutdown -c`
+pub async fn schedule_reboot(
+    config: &Config,
+    insights: &Insights,
+    logger: &mut Logger,
+) -> Result<()> {
+    let Some(args) = shutdown_args(config) else {
+        return Ok(());
+    };
+    let mut cmd_args = vec!["shutdown"];
+    cmd_args.extend(args.iter().map(String::as_str));
+    run_privileged(config, insights, logger, &cmd_args).await
+}
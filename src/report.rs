@@ -0,0 +1,209 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::hooks::overall_status;
+use crate::insights::Insights;
+use crate::inventory::PackageChange;
+use crate::plugins::{CommandRecord, PluginActionType, PluginRegistry};
+use crate::runner::ActionResult;
+
+/// Format of the report printed by `--output`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    Yaml,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "yaml" => Ok(Self::Yaml),
+            other => Err(format!(
+                "unknown output format '{}' (expected json or yaml)",
+                other
+            )),
+        }
+    }
+}
+
+/// Structured description of a finished run, printed by `--output`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunReport {
+    pub version: String,
+    pub started_at: String,
+    pub finished_at: String,
+    pub duration_secs: f64,
+    pub dry_run: bool,
+    pub host: HostReport,
+    /// `OK`, or the first status that is not OK
+    pub status: String,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub actions: Vec<ActionReport>,
+    /// Packages that changed between the inventory snapshots taken before
+    /// and after the run
+    #[serde(default)]
+    pub changes: Vec<PackageChange>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HostReport {
+    pub hostname: String,
+    pub os: String,
+    pub arch: String,
+    pub pkg_mgr: Option<String>,
+    pub is_root: bool,
+    pub app_path: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActionReport {
+    pub action: String,
+    pub plugin: Option<String>,
+    /// `update`, `save`, `restore`, `custom`, `hook` or `builtin`
+    #[serde(rename = "type")]
+    pub action_type: String,
+    pub status: String,
+    pub duration_secs: f64,
+    pub commands: Vec<CommandRecord>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+}
+
+impl HostReport {
+    pub fn new(insights: &Insights) -> Self {
+        Self {
+            hostname: insights.hostname.clone(),
+            os: insights.os.clone(),
+            arch: insights.arch.clone(),
+            pkg_mgr: insights.pkg_mgr.clone(),
+            is_root: insights.is_root,
+            app_path: insights.app_abspath.display().to_string(),
+        }
+    }
+}
+
+/// Hooks are named `pre_run`, `post_run`, `on_failure`, `pre_<plugin>` or
+/// `post_<plugin>`; returns the plugin a hook guards, if any
+fn hook_plugin(action: &str) -> Option<Option<&str>> {
+    match action {
+        "pre_run" | "post_run" | "on_failure" => Some(None),
+        _ => action
+            .strip_prefix("pre_")
+            .or_else(|| action.strip_prefix("post_"))
+            .map(Some),
+    }
+}
+
+fn classify(registry: &PluginRegistry, action: &str) -> (Option<String>, &'static str) {
+    if let Some(plugin) = registry.plugin_name_for_action(action) {
+        let action_type = if plugin == action {
+            "update"
+        } else {
+            match registry
+                .get_action_by_name(action)
+                .and_then(|a| a.action_type)
+            {
+                Some(PluginActionType::Update) => "update",
+                Some(PluginActionType::Save) => "save",
+                Some(PluginActionType::Restore) => "restore",
+                None => "custom",
+            }
+        };
+        return (Some(plugin.to_string()), action_type);
+    }
+    match hook_plugin(action) {
+        Some(plugin) => (plugin.map(str::to_string), "hook"),
+        None => (None, "builtin"),
+    }
+}
+
+impl RunReport {
+    pub fn new(
+        registry: &PluginRegistry,
+        insights: &Insights,
+        started: DateTime<Local>,
+        finished: DateTime<Local>,
+        dry_run: bool,
+        results: &[ActionResult],
+    ) -> Self {
+        let actions = results
+            .iter()
+            .map(|r| {
+                let (plugin, action_type) = classify(registry, &r.action);
+                ActionReport {
+                    action: r.action.clone(),
+                    plugin,
+                    action_type: action_type.to_string(),
+                    status: r.status.as_str().to_string(),
+                    duration_secs: r.duration.as_secs_f64(),
+                    commands: r.commands.clone(),
+                    error: r.error.clone(),
+                    notes: r.notes.clone(),
+                }
+            })
+            .collect();
+        let succeeded = results.iter().filter(|r| r.ok()).count();
+
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at: started.to_rfc3339(),
+            finished_at: finished.to_rfc3339(),
+            duration_secs: (finished - started).as_seconds_f64(),
+            dry_run,
+            host: HostReport::new(insights),
+            status: overall_status(results.iter().map(|r| r.status))
+                .as_str()
+                .to_string(),
+            succeeded,
+            failed: results.len() - succeeded,
+            actions,
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn with_changes(mut self, changes: Vec<PackageChange>) -> Self {
+        self.changes = changes;
+        self
+    }
+
+    pub fn render(&self, format: OutputFormat) -> Result<String> {
+        Ok(match format {
+            OutputFormat::Json => serde_json::to_string_pretty(self)? + "\n",
+            OutputFormat::Yaml => serde_yaml::to_string(self)?,
+        })
+    }
+}
+
+/// Left-aligned columns separated by two spaces, with a dashed line under
+/// the header
+pub(crate) fn format_table<const N: usize>(header: [&str; N], rows: &[[&str; N]]) -> String {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let dashes = widths.map(|w| "-".repeat(w));
+    let dashes = dashes.each_ref().map(String::as_str);
+
+    let mut out = String::new();
+    for row in std::iter::once(&header).chain([&dashes]).chain(rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
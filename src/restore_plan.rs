@@ -0,0 +1,209 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::insights::Insights;
+use crate::inventory::list_installed;
+use crate::logger::Logger;
+use crate::plugins::{InstalledPackage, PluginRegistry};
+use crate::report::format_table;
+
+/// How an installed package differs from the save file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanStatus {
+    /// In the save file, not installed: the restore installs it
+    Missing,
+    /// Installed, not in the save file: the restore leaves it alone
+    Extra,
+    /// Installed with another version than the one saved
+    Mismatch,
+}
+
+impl PlanStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Missing => "missing",
+            Self::Extra => "extra",
+            Self::Mismatch => "version mismatch",
+        }
+    }
+}
+
+/// A package whose restore would change something, or that it would leave
+/// different from the save file
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PlanEntry {
+    pub plugin: String,
+    pub name: String,
+    pub status: PlanStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saved: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installed: Option<String>,
+}
+
+/// Name used to match saved and installed packages. Python normalizes
+/// case, `_` and `.` in distribution names, so pip's are compared the same way.
+fn package_key(package: &InstalledPackage) -> String {
+    if package.manager == "pip" {
+        package.name.to_lowercase().replace(['_', '.'], "-")
+    } else {
+        package.name.clone()
+    }
+}
+
+/// Packages `pip freeze` leaves out; they are never extra
+const PIP_TOOLING: &[&str] = &["pip", "setuptools", "wheel", "distribute"];
+
+/// Compare the packages of a save file with the installed ones. Saved
+/// packages without a version (Brewfile entries) only count as missing.
+pub fn plan(
+    plugin: &str,
+    saved: &[InstalledPackage],
+    installed: &[InstalledPackage],
+) -> Vec<PlanEntry> {
+    let index = |packages: &[InstalledPackage]| -> BTreeMap<String, InstalledPackage> {
+        packages
+            .iter()
+            .map(|p| (package_key(p), p.clone()))
+            .collect()
+    };
+    let saved = index(saved);
+    let installed = index(installed);
+
+    let keys: std::collections::BTreeSet<&String> = saved.keys().chain(installed.keys()).collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let (saved, installed) = (saved.get(key), installed.get(key));
+            let status = match (saved, installed) {
+                (Some(_), None) => PlanStatus::Missing,
+                (None, Some(i)) if i.manager == "pip" && PIP_TOOLING.contains(&key.as_str()) => {
+                    return None;
+                }
+                (None, Some(_)) => PlanStatus::Extra,
+                (Some(s), Some(i)) if !s.version.is_empty() && s.version != i.version => {
+                    PlanStatus::Mismatch
+                }
+                _ => return None,
+            };
+            let package = saved.or(installed)?;
+            let version = |p: Option<&InstalledPackage>| {
+                p.map(|p| p.version.clone()).filter(|v| !v.is_empty())
+            };
+            Some(PlanEntry {
+                plugin: plugin.to_string(),
+                name: package.name.clone(),
+                status,
+                saved: version(saved),
+                installed: version(installed),
+            })
+        })
+        .collect()
+}
+
+/// Restore plan of one plugin, or why it could not be made
+#[derive(Debug, Serialize)]
+pub struct PlanReport {
+    pub plugin: String,
+    pub entries: Vec<PlanEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Packages of the save file
+    #[serde(skip)]
+    pub saved: Vec<InstalledPackage>,
+    /// Packages installed now
+    #[serde(skip)]
+    pub installed: Vec<InstalledPackage>,
+}
+
+/// Read the save file of each of `plugins` and compare it with what is
+/// installed, without changing anything. Plugins that cannot read their save
+/// file or list their packages are left out.
+pub async fn plan_restore(
+    registry: Arc<PluginRegistry<'static>>,
+    config: Arc<Config>,
+    insights: Arc<Insights>,
+    logger: &Logger,
+    plugins: &[String],
+) -> Vec<PlanReport> {
+    let mut saved: BTreeMap<String, Vec<InstalledPackage>> = BTreeMap::new();
+    let mut reports: Vec<PlanReport> = Vec::new();
+    for name in plugins {
+        let Some(plugin) = registry.get_plugin(name) else {
+            continue;
+        };
+        let mut logger = logger.with_prefix(name);
+        match plugin.saved_packages(&config) {
+            Ok(Some(packages)) => {
+                saved.insert(name.clone(), packages);
+            }
+            Ok(None) => logger.log("Planning a restore is not supported"),
+            Err(e) => {
+                logger.error(&format!("Failed to read the save file: {:#}", e));
+                reports.push(PlanReport {
+                    plugin: name.clone(),
+                    entries: Vec::new(),
+                    error: Some(format!("{:#}", e)),
+                    saved: Vec::new(),
+                    installed: Vec::new(),
+                });
+            }
+        }
+    }
+
+    let readable: Vec<String> = plugins
+        .iter()
+        .filter(|p| saved.contains_key(*p))
+        .cloned()
+        .collect();
+    for report in list_installed(registry, config, insights, logger, &readable).await {
+        let saved = saved.remove(&report.plugin).unwrap_or_default();
+        let entries = match report.error {
+            Some(_) => Vec::new(),
+            None => plan(&report.plugin, &saved, &report.packages),
+        };
+        reports.push(PlanReport {
+            plugin: report.plugin,
+            entries,
+            error: report.error,
+            saved,
+            installed: report.packages,
+        });
+    }
+    reports.sort_by_key(|r| plugins.iter().position(|p| *p == r.plugin));
+    reports
+}
+
+/// One aligned table of every difference, followed by the plugins whose
+/// plan failed
+pub fn render_plan(reports: &[PlanReport]) -> String {
+    let header = ["Plugin", "Package", "Status", "Saved", "Installed"];
+    let rows: Vec<[&str; 5]> = reports
+        .iter()
+        .flat_map(|r| &r.entries)
+        .map(|e| {
+            [
+                e.plugin.as_str(),
+                e.name.as_str(),
+                e.status.as_str(),
+                e.saved.as_deref().unwrap_or("-"),
+                e.installed.as_deref().unwrap_or("-"),
+            ]
+        })
+        .collect();
+
+    let mut out = if rows.is_empty() {
+        "Installed packages match the save files\n".to_string()
+    } else {
+        format_table(header, &rows)
+    };
+
+    for report in reports {
+        if let Some(ref error) = report.error {
+            out.push_str(&format!("{}: plan failed: {}\n", report.plugin, error));
+        }
+    }
+    out
+}
@@ -0,0 +1,170 @@
+use anyhow::{Context, Result};
+
+use crate::config::Config;
+use crate::history::{History, HistoryEntry};
+use crate::insights::Insights;
+use crate::inventory::{ChangeKind, PackageChange};
+use crate::logger::Logger;
+use crate::plugins::{InstalledPackage, PluginRegistry};
+use crate::runner::{ActionResult, ActionStatus, record_action};
+
+/// Action name suffix of the per-plugin results of a rollback
+const ROLLBACK_SUFFIX: &str = "-rollback";
+
+/// Whether a rollback can undo `change`: the package was upgraded,
+/// downgraded or removed, so there is a previous version to reinstall
+pub fn revertible(change: &PackageChange) -> bool {
+    change.kind != ChangeKind::Added && change.from.is_some()
+}
+
+/// Changes of `entry` a rollback undoes: packages that were upgraded,
+/// downgraded or removed, of `plugins` when any are given. Added packages
+/// have no previous version and are left installed.
+pub fn rollback_changes<'a>(entry: &'a HistoryEntry, plugins: &[String]) -> Vec<&'a PackageChange> {
+    entry
+        .report
+        .changes
+        .iter()
+        .filter(|c| revertible(c))
+        .filter(|c| plugins.is_empty() || plugins.contains(&c.plugin))
+        .collect()
+}
+
+/// Whether `entry` is itself a rollback
+fn is_rollback(entry: &HistoryEntry) -> bool {
+    entry
+        .report
+        .actions
+        .iter()
+        .any(|a| a.action_type == "builtin" && a.action.ends_with(ROLLBACK_SUFFIX))
+}
+
+/// The latest run that changed packages a rollback can undo, skipping dry
+/// runs and earlier rollbacks
+pub fn last_run<'a>(entries: &'a [HistoryEntry], plugins: &[String]) -> Option<&'a HistoryEntry> {
+    entries
+        .iter()
+        .rev()
+        .find(|e| !e.report.dry_run && !is_rollback(e) && !rollback_changes(e, plugins).is_empty())
+}
+
+/// The previous versions to reinstall, grouped by plugin in the order of
+/// `changes`
+pub fn previous_versions(changes: &[&PackageChange]) -> Vec<(String, Vec<InstalledPackage>)> {
+    let mut by_plugin: Vec<(String, Vec<InstalledPackage>)> = Vec::new();
+    for change in changes {
+        let Some(ref from) = change.from else {
+            continue;
+        };
+        let package = InstalledPackage::new(&change.plugin, &change.name, from, &change.source);
+        match by_plugin.iter_mut().find(|(p, _)| *p == change.plugin) {
+            Some((_, packages)) => packages.push(package),
+            None => by_plugin.push((change.plugin.clone(), vec![package])),
+        }
+    }
+    by_plugin
+}
+
+/// Plan `updatehauler rollback`: find run `run_id`, or the latest run that
+/// changed packages of `plugins`, log what rolling it back reinstalls and
+/// return the previous versions. Empty when the run changed nothing a
+/// rollback can undo.
+pub fn plan_rollback(
+    config: &Config,
+    logger: &mut Logger,
+    run_id: Option<&str>,
+    plugins: &[String],
+) -> Result<Vec<(String, Vec<InstalledPackage>)>> {
+    let history = History::new(config);
+    let entry = match run_id {
+        Some(id) => history.find(id)?,
+        None => {
+            let entries = history.load()?;
+            last_run(&entries, plugins)
+                .cloned()
+                .context("No run in the history upgraded or removed packages")?
+        }
+    };
+    let changes = rollback_changes(&entry, plugins);
+    if changes.is_empty() {
+        logger.log(&format!(
+            "Run {} upgraded or removed no packages; nothing to roll back",
+            entry.id
+        ));
+        return Ok(Vec::new());
+    }
+    logger.log(&format!("Rolling back run {}", entry.id));
+    for line in render_rollback(&changes) {
+        logger.log(&line);
+    }
+    Ok(previous_versions(&changes))
+}
+
+/// One line per plugin: `npm: typescript 5.5.2 → 5.4.5, left-pad (removed) → 1.3.0`
+pub fn render_rollback(changes: &[&PackageChange]) -> Vec<String> {
+    let mut by_plugin: Vec<(&str, Vec<String>)> = Vec::new();
+    for change in changes {
+        let current = change.to.as_deref().unwrap_or("(removed)");
+        let text = format!(
+            "{} {} → {}",
+            change.name,
+            current,
+            change.from.as_deref().unwrap_or_default()
+        );
+        match by_plugin.last_mut() {
+            Some((plugin, items)) if *plugin == change.plugin => items.push(text),
+            _ => by_plugin.push((&change.plugin, vec![text])),
+        }
+    }
+    by_plugin
+        .into_iter()
+        .map(|(plugin, items)| format!("{}: {}", plugin, items.join(", ")))
+        .collect()
+}
+
+/// Reinstall the previous versions, one plugin at a time. Each plugin is
+/// reported as a `<plugin>-rollback` action, which fails for plugins that
+/// cannot install a given version.
+pub async fn apply_rollback(
+    registry: &PluginRegistry<'_>,
+    config: &Config,
+    insights: &Insights,
+    logger: &Logger,
+    versions: &[(String, Vec<InstalledPackage>)],
+) -> Vec<ActionResult> {
+    let mut results = Vec::new();
+    for (plugin_name, packages) in versions {
+        let action = format!("{}{}", plugin_name, ROLLBACK_SUFFIX);
+        if crate::plugins::cancellation().is_some() {
+            results.push(ActionResult::new(&action, ActionStatus::Cancelled));
+            continue;
+        }
+        let mut logger = logger.with_prefix(plugin_name);
+        let result = record_action(plugin_name, &action, async {
+            let plugin = registry
+                .get_plugin(plugin_name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown plugin: {}", plugin_name))?;
+            if !plugin
+                .install_versions(config, insights, &mut logger, packages)
+                .await?
+            {
+                let specs: Vec<String> = packages
+                    .iter()
+                    .map(|p| format!("{} {}", p.name, p.version))
+                    .collect();
+                anyhow::bail!(
+                    "{} cannot install previous versions; reinstall {} by hand",
+                    plugin_name,
+                    specs.join(", ")
+                );
+            }
+            Ok(())
+        })
+        .await;
+        if let Some(ref e) = result.error {
+            logger.error(e);
+        }
+        results.push(result);
+    }
+    results
+}
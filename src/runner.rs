@@ -0,0 +1,439 @@
+use anyhow::Result;
+use nix::sys::signal::Signal;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::config::{Config, PreHookFailure};
+use crate::hooks::{HookEvent, run_hook};
+use crate::insights::Insights;
+use crate::logger::Logger;
+use crate::plugins::{
+    CommandFailed, CommandRecord, Interruption, PluginRegistry, cancel_commands, cancellation,
+    record_commands, record_notes, scope_plugin,
+};
+use crate::verify::{VerifyFailed, run_verify};
+
+/// A group of actions belonging to the same plugin, executed sequentially
+pub struct ExecutionNode {
+    pub name: String,
+    pub actions: Vec<String>,
+    /// Indices of nodes that must finish before this node starts
+    pub depends_on: Vec<usize>,
+    /// Runs with no other node in parallel, see [`crate::plugins::Plugin::exclusive`]
+    pub exclusive: bool,
+    positions: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActionStatus {
+    Ok,
+    Failed,
+    /// A command of the action exceeded its timeout and was killed
+    TimedOut,
+    /// The run was cancelled before or while the action ran
+    Cancelled,
+    /// Not run because a pre-hook guarding it failed
+    Skipped,
+    /// The update ran but a `verify:` command failed afterwards
+    VerifyFailed,
+}
+
+impl ActionStatus {
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        let interrupted = error
+            .chain()
+            .find_map(|e| e.downcast_ref::<CommandFailed>())
+            .and_then(|failed| failed.0.interrupted);
+        match interrupted {
+            _ if error.downcast_ref::<VerifyFailed>().is_some() => Self::VerifyFailed,
+            Some(Interruption::TimedOut(_)) => Self::TimedOut,
+            Some(Interruption::Cancelled) => Self::Cancelled,
+            None => Self::Failed,
+        }
+    }
+
+    pub fn is_ok(self) -> bool {
+        self == Self::Ok
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "OK",
+            Self::Failed => "FAIL",
+            Self::TimedOut => "TIMEOUT",
+            Self::Cancelled => "CANCELLED",
+            Self::Skipped => "SKIPPED",
+            Self::VerifyFailed => "VERIFY_FAIL",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ActionResult {
+    pub action: String,
+    pub status: ActionStatus,
+    pub duration: Duration,
+    /// Commands the action started, in order
+    pub commands: Vec<CommandRecord>,
+    /// Why the action did not succeed
+    pub error: Option<String>,
+    /// What the action reported about its work, such as update counts
+    pub notes: Vec<String>,
+}
+
+impl ActionResult {
+    /// A result for an action that did not run any commands
+    pub fn new(action: &str, status: ActionStatus) -> Self {
+        Self {
+            action: action.to_string(),
+            status,
+            duration: Duration::ZERO,
+            commands: Vec::new(),
+            error: None,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn ok(&self) -> bool {
+        self.status.is_ok()
+    }
+}
+
+/// What a node task reports back: the result of each of its actions by
+/// position, and the results of the hooks that ran around them
+struct NodeReport {
+    results: Vec<(usize, ActionResult)>,
+    pre_hook: Option<ActionResult>,
+    post_hook: Option<ActionResult>,
+}
+
+/// Run `action` of `plugin`, timing it and recording the commands it starts.
+/// The error, if any, is kept in the result for the caller to log.
+pub async fn record_action(
+    plugin: &str,
+    action: &str,
+    run: impl Future<Output = Result<()>>,
+) -> ActionResult {
+    let started = Instant::now();
+    let ((r, commands), notes) = record_notes(record_commands(scope_plugin(plugin, run))).await;
+    let mut result = ActionResult::new(action, ActionStatus::Ok);
+    result.duration = started.elapsed();
+    result.commands = commands;
+    result.notes = notes;
+    if let Err(ref e) = r {
+        result.status = ActionStatus::from_error(e);
+        result.error = Some(e.to_string());
+    }
+    result
+}
+
+/// Wait for SIGINT/SIGTERM and forward it to every running command, so the
+/// run ends with a partial summary instead of leaving orphaned processes.
+/// A second signal exits immediately.
+pub async fn forward_signals(mut logger: Logger) {
+    let (Ok(mut sigint), Ok(mut sigterm)) = (
+        signal(SignalKind::interrupt()),
+        signal(SignalKind::terminate()),
+    ) else {
+        return;
+    };
+
+    loop {
+        let received = tokio::select! {
+            _ = sigint.recv() => Signal::SIGINT,
+            _ = sigterm.recv() => Signal::SIGTERM,
+        };
+        if cancellation().is_some() {
+            std::process::exit(128 + received as i32);
+        }
+        logger.error(&format!(
+            "Received {}, stopping running commands (repeat to exit immediately)",
+            received.as_str()
+        ));
+        cancel_commands(received);
+    }
+}
+
+/// Execution plan built from a flat action list and the `run_after`
+/// constraints declared in each plugin's metadata.
+///
+/// Actions of the same plugin stay in their original order inside one node;
+/// independent nodes run in parallel up to the configured job limit, except
+/// exclusive nodes, which wait for running nodes to finish and hold off the
+/// others while they run. A failed node does not prevent its dependents from
+/// running, it only orders them.
+pub struct ExecutionGraph {
+    nodes: Vec<ExecutionNode>,
+    order: Vec<String>,
+}
+
+impl ExecutionGraph {
+    pub fn build(registry: &PluginRegistry, actions: &[String]) -> Result<Self> {
+        let mut nodes: Vec<ExecutionNode> = Vec::new();
+
+        for (pos, action) in actions.iter().enumerate() {
+            let name = registry
+                .plugin_name_for_action(action)
+                .unwrap_or(action)
+                .to_string();
+            match nodes.iter_mut().find(|n| n.name == name) {
+                Some(node) => {
+                    node.actions.push(action.clone());
+                    node.positions.push(pos);
+                }
+                None => nodes.push(ExecutionNode {
+                    exclusive: registry.get_plugin(&name).is_some_and(|p| p.exclusive()),
+                    name,
+                    actions: vec![action.clone()],
+                    depends_on: Vec::new(),
+                    positions: vec![pos],
+                }),
+            }
+        }
+
+        let index: HashMap<String, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.name.clone(), i))
+            .collect();
+
+        for node in nodes.iter_mut() {
+            if let Some(metadata) = registry.get_metadata(&node.name) {
+                for dep in &metadata.run_after {
+                    if let Some(&i) = index.get(dep)
+                        && !node.depends_on.contains(&i)
+                    {
+                        node.depends_on.push(i);
+                    }
+                }
+            }
+        }
+
+        let graph = Self {
+            nodes,
+            order: actions.to_vec(),
+        };
+        graph.topological_order()?;
+        Ok(graph)
+    }
+
+    pub fn nodes(&self) -> &[ExecutionNode] {
+        &self.nodes
+    }
+
+    /// Node names in an order that satisfies every dependency
+    pub fn topological_order(&self) -> Result<Vec<&str>> {
+        let mut remaining: Vec<usize> = self.nodes.iter().map(|n| n.depends_on.len()).collect();
+        let dependents = self.dependents();
+        let mut ready: VecDeque<usize> = (0..self.nodes.len())
+            .filter(|&i| remaining[i] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+
+        while let Some(i) = ready.pop_front() {
+            order.push(self.nodes[i].name.as_str());
+            for &d in &dependents[i] {
+                remaining[d] -= 1;
+                if remaining[d] == 0 {
+                    ready.push_back(d);
+                }
+            }
+        }
+
+        if order.len() != self.nodes.len() {
+            let cyclic: Vec<&str> = self
+                .nodes
+                .iter()
+                .filter(|n| !order.contains(&n.name.as_str()))
+                .map(|n| n.name.as_str())
+                .collect();
+            anyhow::bail!("Dependency cycle between plugins: {}", cyclic.join(", "));
+        }
+
+        Ok(order)
+    }
+
+    fn dependents(&self) -> Vec<Vec<usize>> {
+        let mut dependents = vec![Vec::new(); self.nodes.len()];
+        for (i, node) in self.nodes.iter().enumerate() {
+            for &dep in &node.depends_on {
+                dependents[dep].push(i);
+            }
+        }
+        dependents
+    }
+
+    /// Run every node, at most `jobs` at a time, and return one result per
+    /// action in the order the actions were originally requested.
+    pub async fn execute(
+        self,
+        registry: Arc<PluginRegistry<'static>>,
+        config: Arc<Config>,
+        insights: Arc<Insights>,
+        logger: &Logger,
+        jobs: usize,
+    ) -> Vec<ActionResult> {
+        let jobs = jobs.max(1);
+        let parallel = jobs > 1 && self.nodes.len() > 1;
+        let semaphore = Arc::new(Semaphore::new(jobs));
+        let dependents = self.dependents();
+        let mut remaining: Vec<usize> = self.nodes.iter().map(|n| n.depends_on.len()).collect();
+
+        // Actions of a node whose task aborted are left as failed
+        let mut outcomes: Vec<Option<ActionResult>> = vec![None; self.order.len()];
+        // Hook results are shown right before/after the actions they guard
+        let mut hooks_before: HashMap<usize, ActionResult> = HashMap::new();
+        let mut hooks_after: HashMap<usize, ActionResult> = HashMap::new();
+        let mut tasks = JoinSet::new();
+        let mut task_nodes = HashMap::new();
+
+        let spawn = |i: usize, tasks: &mut JoinSet<NodeReport>| {
+            let node = &self.nodes[i];
+            let actions: Vec<(usize, String)> = node
+                .positions
+                .iter()
+                .copied()
+                .zip(node.actions.iter().cloned())
+                .collect();
+            let mut logger = if parallel {
+                logger.with_prefix(&node.name)
+            } else {
+                logger.clone()
+            };
+            let registry = Arc::clone(&registry);
+            let config = Arc::clone(&config);
+            let insights = Arc::clone(&insights);
+            let semaphore = Arc::clone(&semaphore);
+            let name = node.name.clone();
+            // Holding every permit keeps all other nodes out
+            let permits = if node.exclusive {
+                u32::try_from(jobs).unwrap_or(u32::MAX)
+            } else {
+                1
+            };
+
+            tasks
+                .spawn(async move {
+                    let _permit = semaphore.acquire_many_owned(permits).await;
+                    let action_names: Vec<String> =
+                        actions.iter().map(|(_, a)| a.clone()).collect();
+
+                    let event =
+                        HookEvent::before(format!("pre_{}", name), Some(&name), &action_names);
+                    let pre_hook = scope_plugin(
+                        &name,
+                        run_hook(&config, &mut logger, &event, config.hooks.pre(&name)),
+                    )
+                    .await;
+                    let skip = pre_hook.as_ref().is_some_and(|r| !r.ok())
+                        && config.hooks.pre_failure == PreHookFailure::Skip;
+                    if skip {
+                        logger.error(&format!("Skipping {}: pre_{} hook failed", name, name));
+                    }
+
+                    let mut results = Vec::with_capacity(actions.len());
+                    for (pos, action) in actions {
+                        if cancellation().is_some() {
+                            results
+                                .push((pos, ActionResult::new(&action, ActionStatus::Cancelled)));
+                            continue;
+                        }
+                        if skip {
+                            results.push((pos, ActionResult::new(&action, ActionStatus::Skipped)));
+                            continue;
+                        }
+                        let result = record_action(&name, &action, async {
+                            registry
+                                .execute_action(&action, &config, &insights, &mut logger)
+                                .await?;
+                            // Only an update is followed by its smoke tests
+                            if action == name {
+                                run_verify(&config, &mut logger, &name).await?;
+                            }
+                            Ok(())
+                        })
+                        .await;
+                        if let Some(ref e) = result.error {
+                            logger.error(e);
+                        }
+                        results.push((pos, result));
+                    }
+
+                    let post_hook = if skip {
+                        None
+                    } else {
+                        let finished: Vec<ActionResult> =
+                            results.iter().map(|(_, r)| r.clone()).collect();
+                        let event = HookEvent::after(
+                            format!("post_{}", name),
+                            Some(&name),
+                            &action_names,
+                            &finished,
+                        );
+                        scope_plugin(
+                            &name,
+                            run_hook(&config, &mut logger, &event, config.hooks.post(&name)),
+                        )
+                        .await
+                    };
+
+                    NodeReport {
+                        results,
+                        pre_hook,
+                        post_hook,
+                    }
+                })
+                .id()
+        };
+
+        for i in (0..self.nodes.len()).filter(|&i| remaining[i] == 0) {
+            task_nodes.insert(spawn(i, &mut tasks), i);
+        }
+
+        while let Some(joined) = tasks.join_next_with_id().await {
+            let i = match joined {
+                Ok((id, report)) => {
+                    let first = report.results.first().map(|(pos, _)| *pos);
+                    let last = report.results.last().map(|(pos, _)| *pos);
+                    for (pos, result) in report.results {
+                        outcomes[pos] = Some(result);
+                    }
+                    if let (Some(pos), Some(hook)) = (first, report.pre_hook) {
+                        hooks_before.insert(pos, hook);
+                    }
+                    if let (Some(pos), Some(hook)) = (last, report.post_hook) {
+                        hooks_after.insert(pos, hook);
+                    }
+                    task_nodes[&id]
+                }
+                Err(e) => {
+                    let i = task_nodes[&e.id()];
+                    logger
+                        .clone()
+                        .error(&format!("{} aborted: {}", self.nodes[i].name, e));
+                    i
+                }
+            };
+
+            for &d in &dependents[i] {
+                remaining[d] -= 1;
+                if remaining[d] == 0 {
+                    task_nodes.insert(spawn(d, &mut tasks), d);
+                }
+            }
+        }
+
+        let mut results = Vec::with_capacity(self.order.len());
+        for (pos, (action, outcome)) in self.order.into_iter().zip(outcomes).enumerate() {
+            results.extend(hooks_before.remove(&pos));
+            results
+                .push(outcome.unwrap_or_else(|| ActionResult::new(&action, ActionStatus::Failed)));
+            results.extend(hooks_after.remove(&pos));
+        }
+        results
+    }
+}
@@ -0,0 +1,330 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, SaveRepo};
+use crate::logger::Logger;
+use crate::plugins::{ErrorPolicy, query_cmd, run_cmd};
+
+/// Where the save file of `plugin` is kept in the save repository:
+/// `<plugin>/<file name of save_file>`
+pub fn tracked_path(plugin: &str, save_file: &Path) -> PathBuf {
+    let name = save_file
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| format!("{}-save", plugin));
+    Path::new(plugin).join(name)
+}
+
+/// A file staged for commit, from `git diff --cached --name-status`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangedFile {
+    /// `added`, `modified`, `deleted`, ...
+    pub change: &'static str,
+    pub path: String,
+}
+
+pub fn parse_name_status(output: &str) -> Vec<ChangedFile> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (status, path) = line.split_once('\t')?;
+            let change = match status.chars().next()? {
+                'A' => "added",
+                'M' => "modified",
+                'D' => "deleted",
+                'R' => "renamed",
+                'T' => "type changed",
+                _ => "changed",
+            };
+            // Renames list the old and the new path; keep the new one
+            let path = path.rsplit('\t').next().unwrap_or(path);
+            Some(ChangedFile {
+                change,
+                path: path.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Commit message naming the plugins whose save files changed on the
+/// subject line and every changed file in the body
+pub fn commit_message(hostname: &str, changed: &[ChangedFile]) -> String {
+    let mut plugins: Vec<&str> = changed
+        .iter()
+        .map(|f| f.path.split('/').next().unwrap_or(&f.path))
+        .collect();
+    plugins.dedup();
+    let host = if hostname.is_empty() {
+        String::new()
+    } else {
+        format!(" on {}", hostname)
+    };
+    let mut message = format!("Update {} saves{}\n\n", plugins.join(", "), host);
+    for file in changed {
+        message.push_str(&format!("- {}: {}\n", file.path, file.change));
+    }
+    message
+}
+
+/// Copy the save files of `saved` (plugin and save file) into the save
+/// repository and commit them, pushing when configured. Returns the commit
+/// message, or `None` when nothing changed.
+pub async fn record_saves(
+    config: &Config,
+    logger: &mut Logger,
+    repo: &SaveRepo,
+    hostname: &str,
+    saved: &[(String, PathBuf)],
+) -> Result<Option<String>> {
+    let dir = repo.path.to_string_lossy().to_string();
+    if config.dry_run {
+        for (plugin, save_file) in saved {
+            logger.log(&format!(
+                "Would copy {} to {}",
+                save_file.display(),
+                repo.path.join(tracked_path(plugin, save_file)).display()
+            ));
+        }
+        logger.log(&format!("Would commit changed save files in {}", dir));
+        return Ok(None);
+    }
+
+    if !repo.path.join(".git").exists() {
+        std::fs::create_dir_all(&repo.path).with_context(|| format!("Failed to create {}", dir))?;
+        run_cmd(
+            config,
+            logger,
+            ErrorPolicy::Fail,
+            "git",
+            &["-C", &dir, "init", "-q"],
+        )
+        .await?;
+    }
+    for (plugin, save_file) in saved {
+        let target = repo.path.join(tracked_path(plugin, save_file));
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(save_file, &target).with_context(|| {
+            format!(
+                "Failed to copy {} to {}",
+                save_file.display(),
+                target.display()
+            )
+        })?;
+    }
+
+    run_cmd(
+        config,
+        logger,
+        ErrorPolicy::Fail,
+        "git",
+        &["-C", &dir, "add", "--all"],
+    )
+    .await?;
+    let status = query_cmd(
+        config,
+        logger,
+        "git",
+        &["-C", &dir, "diff", "--cached", "--name-status"],
+        &[],
+    )
+    .await?;
+    let changed = parse_name_status(&status);
+    if changed.is_empty() {
+        logger.log("Save files unchanged, nothing to commit");
+        return Ok(None);
+    }
+
+    let message = commit_message(hostname, &changed);
+    // Commit as updatehauler on machines without a git identity
+    let identity = query_cmd(
+        config,
+        logger,
+        "git",
+        &["-C", &dir, "config", "user.email"],
+        &[1],
+    )
+    .await?;
+    let mut args = vec!["-C", dir.as_str()];
+    if identity.trim().is_empty() {
+        args.extend([
+            "-c",
+            "user.name=updatehauler",
+            "-c",
+            "user.email=updatehauler@localhost",
+        ]);
+    }
+    args.extend(["commit", "-q", "-m", message.as_str()]);
+    run_cmd(config, logger, ErrorPolicy::Fail, "git", &args).await?;
+    logger.log(&format!(
+        "Committed {} changed save files to {}",
+        changed.len(),
+        dir
+    ));
+
+    if repo.push {
+        run_cmd(
+            config,
+            logger,
+            ErrorPolicy::Fail,
+            "git",
+            &["-C", &dir, "push", "-q", &repo.remote, "HEAD"],
+        )
+        .await?;
+    }
+    Ok(Some(message))
+}
+
+fn git(repo: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Reject revisions git would parse as an option
+pub fn validate_rev(rev: &str) -> Result<()> {
+    if rev.is_empty() || rev.starts_with('-') {
+        anyhow::bail!("Invalid revision: {:?}", rev);
+    }
+    Ok(())
+}
+
+/// Save files in the save repository at `rev`, as `(plugin, contents)`.
+/// Only the file each plugin's save file is tracked as is read; anything
+/// else under a plugin's directory is left alone.
+pub fn saves_at(repo: &Path, rev: &str, config: &Config) -> Result<Vec<(String, Vec<u8>)>> {
+    validate_rev(rev)?;
+    let listing = git(repo, &["ls-tree", "-r", "--name-only", rev])?;
+    let listing = String::from_utf8_lossy(&listing);
+    let mut plugins: Vec<&str> = listing
+        .lines()
+        .filter_map(|path| Some(path.split_once('/')?.0))
+        .collect();
+    plugins.dedup();
+    plugins
+        .into_iter()
+        .filter_map(|plugin| {
+            let path = tracked_path(plugin, &config.save_file(plugin)?)
+                .to_string_lossy()
+                .to_string();
+            listing
+                .lines()
+                .any(|p| p == path)
+                .then(|| (plugin.to_string(), path))
+        })
+        .map(|(plugin, path)| {
+            let contents = git(repo, &["show", &format!("{}:{}", rev, path)])?;
+            Ok((plugin, contents))
+        })
+        .collect()
+}
+
+/// The configured save repository, once a run has created it
+pub fn open_save_repo(config: &Config) -> Result<SaveRepo> {
+    let repo = config
+        .save_repo
+        .clone()
+        .context("save_repo is not configured")?;
+    if !repo.path.join(".git").exists() {
+        anyhow::bail!(
+            "{} is not a git repository yet; it is created by the first run that saves packages",
+            repo.path.display()
+        );
+    }
+    Ok(repo)
+}
+
+/// Run git in `repo` with its output going straight to the terminal;
+/// returns whether it succeeded
+fn run_git(repo: &Path, args: &[String]) -> Result<bool> {
+    let status = std::process::Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .status()
+        .context("Failed to run git")?;
+    Ok(status.success())
+}
+
+/// `updatehauler saves log`: the latest `limit` commits (0 = all), of
+/// `plugin` when given
+pub fn saves_log(repo: &SaveRepo, plugin: Option<String>, limit: usize) -> Result<bool> {
+    let mut git_args = vec!["log".to_string()];
+    if limit > 0 {
+        git_args.push(format!("--max-count={}", limit));
+    }
+    git_args.extend(["--stat".to_string(), "--".to_string()]);
+    git_args.extend(plugin);
+    run_git(&repo.path, &git_args)
+}
+
+/// `updatehauler saves diff`: how the save files changed since `rev`
+pub fn saves_diff(repo: &SaveRepo, rev: String, plugin: Option<String>) -> Result<bool> {
+    validate_rev(&rev)?;
+    let mut git_args = vec!["diff".to_string(), rev, "HEAD".to_string()];
+    git_args.push("--".to_string());
+    git_args.extend(plugin);
+    run_git(&repo.path, &git_args)
+}
+
+/// `updatehauler saves checkout`: write the save files of `plugins` (all
+/// when empty) at `rev` back to their save file locations. Returns the
+/// plugins whose save file was checked out.
+pub fn checkout_saves(
+    config: &Config,
+    logger: &mut Logger,
+    repo: &SaveRepo,
+    rev: &str,
+    plugins: &[String],
+) -> Result<Vec<String>> {
+    for plugin in plugins {
+        if config.save_file(plugin).is_none() {
+            anyhow::bail!("Plugin {} has no save file", plugin);
+        }
+    }
+    let mut checked_out = Vec::new();
+    for (plugin, contents) in saves_at(&repo.path, rev, config)? {
+        if !plugins.is_empty() && !plugins.contains(&plugin) {
+            continue;
+        }
+        let Some(target) = config
+            .save_file(&plugin)
+            .filter(|p| !p.as_os_str().is_empty())
+        else {
+            logger.warn(&format!(
+                "Skipping {}: not a plugin with a save file",
+                plugin
+            ));
+            continue;
+        };
+        if config.dry_run {
+            logger.log(&format!("Would write {}", target.display()));
+        } else {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&target, contents)
+                .with_context(|| format!("Failed to write {}", target.display()))?;
+            logger.log(&format!(
+                "Checked out {} save file {} from {}",
+                plugin,
+                target.display(),
+                rev
+            ));
+        }
+        checked_out.push(plugin);
+    }
+    Ok(checked_out)
+}
@@ -0,0 +1,385 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+
+use crate::insights::Insights;
+use crate::plugins::InstalledPackage;
+
+/// Document format of `updatehauler sbom`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SbomFormat {
+    CycloneDxJson,
+    SpdxJson,
+}
+
+impl FromStr for SbomFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cyclonedx-json" => Ok(Self::CycloneDxJson),
+            "spdx-json" => Ok(Self::SpdxJson),
+            other => Err(format!(
+                "unknown SBOM format '{}' (expected cyclonedx-json or spdx-json)",
+                other
+            )),
+        }
+    }
+}
+
+/// The machine an SBOM describes and when it was created
+#[derive(Clone, Debug)]
+pub struct SbomInfo {
+    pub hostname: String,
+    /// Linux distribution, used as the namespace of deb, rpm, apk and alpm
+    /// package URLs
+    pub distro: Option<String>,
+    pub created: DateTime<Utc>,
+    /// Random UUID identifying this document
+    pub serial: String,
+}
+
+impl SbomInfo {
+    pub fn new(insights: &Insights) -> Self {
+        Self {
+            hostname: insights.hostname.clone(),
+            distro: insights.distro.clone(),
+            created: Utc::now(),
+            serial: random_uuid(),
+        }
+    }
+}
+
+/// A version 4 UUID from the standard library's randomly seeded hasher
+fn random_uuid() -> String {
+    let mut bytes = [0u8; 16];
+    for half in bytes.chunks_mut(8) {
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u128(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or_default(),
+        );
+        half.copy_from_slice(&hasher.finish().to_be_bytes());
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Percent-encode everything but the unreserved characters of RFC 3986
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Package URL of `package`. Managers without a purl type of their own
+/// (flatpak, snap, mas, editor extensions) use `pkg:generic/<manager>/...`.
+pub fn purl(package: &InstalledPackage, distro: Option<&str>) -> String {
+    let name = package.name.as_str();
+    let (kind, namespace, name) = match package.manager.as_str() {
+        "brew" => ("brew", None, name.to_string()),
+        "cargo" => ("cargo", None, name.to_string()),
+        "gem" => ("gem", None, name.to_string()),
+        "npm" => match name.strip_prefix('@').and_then(|n| n.split_once('/')) {
+            Some((scope, name)) => ("npm", Some(format!("@{}", scope)), name.to_string()),
+            None => ("npm", None, name.to_string()),
+        },
+        // PyPI names are case-insensitive with `_` and `-` equivalent
+        "pip" | "uv" => ("pypi", None, name.to_lowercase().replace('_', "-")),
+        "apt" => (
+            "deb",
+            Some(distro.unwrap_or("debian").to_string()),
+            name.to_string(),
+        ),
+        "dnf" => ("rpm", distro.map(str::to_string), name.to_string()),
+        "apk" => ("apk", Some("alpine".to_string()), name.to_string()),
+        "pacman" => ("alpm", Some("arch".to_string()), name.to_string()),
+        manager => ("generic", Some(manager.to_string()), name.to_string()),
+    };
+    let mut purl = format!("pkg:{}/", kind);
+    if let Some(namespace) = namespace {
+        purl.push_str(&encode(&namespace));
+        purl.push('/');
+    }
+    purl.push_str(&encode(&name));
+    if !package.version.is_empty() {
+        purl.push('@');
+        purl.push_str(&encode(&package.version));
+    }
+    purl
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CycloneDx {
+    bom_format: &'static str,
+    spec_version: &'static str,
+    serial_number: String,
+    version: u32,
+    metadata: CycloneDxMetadata,
+    components: Vec<CycloneDxComponent>,
+}
+
+#[derive(Serialize)]
+struct CycloneDxMetadata {
+    timestamp: String,
+    tools: CycloneDxTools,
+    component: CycloneDxComponent,
+}
+
+#[derive(Serialize)]
+struct CycloneDxTools {
+    components: Vec<CycloneDxComponent>,
+}
+
+#[derive(Serialize)]
+struct CycloneDxComponent {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(rename = "bom-ref", skip_serializing_if = "Option::is_none")]
+    bom_ref: Option<String>,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    purl: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    properties: Vec<CycloneDxProperty>,
+}
+
+#[derive(Serialize)]
+struct CycloneDxProperty {
+    name: &'static str,
+    value: String,
+}
+
+impl CycloneDxComponent {
+    fn new(kind: &'static str, name: &str) -> Self {
+        Self {
+            kind,
+            bom_ref: None,
+            name: name.to_string(),
+            version: None,
+            purl: None,
+            properties: Vec::new(),
+        }
+    }
+}
+
+/// Apps rather than libraries: casks, flatpaks, snaps and App Store apps
+fn is_application(package: &InstalledPackage) -> bool {
+    matches!(package.manager.as_str(), "flatpak" | "snap" | "mas")
+        || (package.manager == "brew" && package.source == "cask")
+}
+
+fn cyclonedx(packages: &[&InstalledPackage], info: &SbomInfo) -> CycloneDx {
+    let mut refs = std::collections::HashSet::new();
+    let components = packages
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let purl = purl(p, info.distro.as_deref());
+            // bom-refs must be unique; the same package can be installed twice
+            let bom_ref = if refs.insert(purl.clone()) {
+                purl.clone()
+            } else {
+                format!("{}#{}", purl, i + 1)
+            };
+            let mut properties = vec![
+                CycloneDxProperty {
+                    name: "updatehauler:manager",
+                    value: p.manager.clone(),
+                },
+                CycloneDxProperty {
+                    name: "updatehauler:source",
+                    value: p.source.clone(),
+                },
+                CycloneDxProperty {
+                    name: "updatehauler:scope",
+                    value: p.scope.as_str().to_string(),
+                },
+            ];
+            if let Some(ref path) = p.path {
+                properties.push(CycloneDxProperty {
+                    name: "updatehauler:path",
+                    value: path.clone(),
+                });
+            }
+            CycloneDxComponent {
+                bom_ref: Some(bom_ref),
+                version: Some(p.version.clone()),
+                purl: Some(purl),
+                properties,
+                ..CycloneDxComponent::new(
+                    if is_application(p) {
+                        "application"
+                    } else {
+                        "library"
+                    },
+                    &p.name,
+                )
+            }
+        })
+        .collect();
+
+    CycloneDx {
+        bom_format: "CycloneDX",
+        spec_version: "1.5",
+        serial_number: format!("urn:uuid:{}", info.serial),
+        version: 1,
+        metadata: CycloneDxMetadata {
+            timestamp: info.created.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            tools: CycloneDxTools {
+                components: vec![CycloneDxComponent {
+                    version: Some(env!("CARGO_PKG_VERSION").to_string()),
+                    ..CycloneDxComponent::new("application", "updatehauler")
+                }],
+            },
+            component: CycloneDxComponent::new("device", &info.hostname),
+        },
+        components,
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Spdx {
+    spdx_version: &'static str,
+    data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: &'static str,
+    name: String,
+    document_namespace: String,
+    creation_info: SpdxCreationInfo,
+    packages: Vec<SpdxPackage>,
+    relationships: Vec<SpdxRelationship>,
+}
+
+#[derive(Serialize)]
+struct SpdxCreationInfo {
+    created: String,
+    creators: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxPackage {
+    name: String,
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    version_info: String,
+    download_location: &'static str,
+    files_analyzed: bool,
+    external_refs: Vec<SpdxExternalRef>,
+    comment: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxExternalRef {
+    reference_category: &'static str,
+    reference_type: &'static str,
+    reference_locator: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxRelationship {
+    spdx_element_id: &'static str,
+    relationship_type: &'static str,
+    related_spdx_element: String,
+}
+
+fn spdx(packages: &[&InstalledPackage], info: &SbomInfo) -> Spdx {
+    let packages: Vec<SpdxPackage> = packages
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            // SPDX ids allow only letters, digits, `.` and `-`
+            let manager: String = p
+                .manager
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+                .collect();
+            SpdxPackage {
+                name: p.name.clone(),
+                spdx_id: format!("SPDXRef-Package-{}-{}", manager, i + 1),
+                version_info: p.version.clone(),
+                download_location: "NOASSERTION",
+                files_analyzed: false,
+                external_refs: vec![SpdxExternalRef {
+                    reference_category: "PACKAGE-MANAGER",
+                    reference_type: "purl",
+                    reference_locator: purl(p, info.distro.as_deref()),
+                }],
+                comment: format!(
+                    "Installed by {} ({}, {} scope){}",
+                    p.manager,
+                    p.source,
+                    p.scope.as_str(),
+                    p.path
+                        .as_deref()
+                        .map(|path| format!(" at {}", path))
+                        .unwrap_or_default()
+                ),
+            }
+        })
+        .collect();
+    let relationships = packages
+        .iter()
+        .map(|p| SpdxRelationship {
+            spdx_element_id: "SPDXRef-DOCUMENT",
+            relationship_type: "DESCRIBES",
+            related_spdx_element: p.spdx_id.clone(),
+        })
+        .collect();
+
+    Spdx {
+        spdx_version: "SPDX-2.3",
+        data_license: "CC0-1.0",
+        spdx_id: "SPDXRef-DOCUMENT",
+        name: format!("updatehauler-{}", info.hostname),
+        document_namespace: format!(
+            "https://spdx.org/spdxdocs/updatehauler-{}-{}",
+            encode(&info.hostname),
+            info.serial
+        ),
+        creation_info: SpdxCreationInfo {
+            created: info.created.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            creators: vec![format!("Tool: updatehauler-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        packages,
+        relationships,
+    }
+}
+
+/// An SBOM document listing `packages`
+pub fn render_sbom(
+    packages: &[&InstalledPackage],
+    format: SbomFormat,
+    info: &SbomInfo,
+) -> serde_json::Result<String> {
+    let document = match format {
+        SbomFormat::CycloneDxJson => serde_json::to_string_pretty(&cyclonedx(packages, info)),
+        SbomFormat::SpdxJson => serde_json::to_string_pretty(&spdx(packages, info)),
+    };
+    document.map(|s| s + "\n")
+}
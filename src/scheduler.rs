@@ -15,10 +15,20 @@ fn xml_escape(s: &str) -> String {
         .replace('\'', "&apos;")
 }
 
+const LAUNCHD_LABEL: &str = "net.franksplace.wake-update-hauler";
+
+/// The profile a crontab line runs, if any
+fn cron_line_profile(line: &str) -> Option<&str> {
+    let mut words = line.split_whitespace();
+    words.find(|w| *w == "--profile")?;
+    words.next()
+}
+
 pub struct Scheduler<'a> {
     config: &'a Config,
     insights: &'a Insights,
     logger: &'a mut Logger,
+    profile: Option<&'a str>,
 }
 
 impl<'a> Scheduler<'a> {
@@ -27,6 +37,24 @@ impl<'a> Scheduler<'a> {
             config,
             insights,
             logger,
+            profile: None,
+        }
+    }
+
+    /// Manage the entry that runs `profile` instead of the default entry
+    pub fn with_profile(mut self, profile: Option<&'a str>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    fn is_own_cron_line(&self, line: &str, app_path: &str) -> bool {
+        line.contains(app_path) && cron_line_profile(line) == self.profile
+    }
+
+    fn launchd_label(&self) -> String {
+        match self.profile {
+            Some(profile) => format!("{}.{}", LAUNCHD_LABEL, profile),
+            None => LAUNCHD_LABEL.to_string(),
         }
     }
 
@@ -65,10 +93,15 @@ impl<'a> Scheduler<'a> {
     fn cron_enable(&mut self) -> Result<()> {
         let current_tab = self.get_crontab()?;
 
-        let crontab_entry = self.config.crontab_entry(&self.insights.app_abspath);
+        let crontab_entry = self
+            .config
+            .crontab_entry_for(&self.insights.app_abspath, self.profile);
 
         let app_path = self.insights.app_abspath.to_string_lossy().to_string();
-        if current_tab.lines().any(|line| line.contains(&app_path)) {
+        if current_tab
+            .lines()
+            .any(|line| self.is_own_cron_line(line, &app_path))
+        {
             self.logger.log("Cron entry already enabled");
             return Ok(());
         }
@@ -96,14 +129,17 @@ impl<'a> Scheduler<'a> {
 
         let app_path = self.insights.app_abspath.to_string_lossy().to_string();
 
-        if !current_tab.lines().any(|line| line.contains(&app_path)) {
+        if !current_tab
+            .lines()
+            .any(|line| self.is_own_cron_line(line, &app_path))
+        {
             self.logger.log("cron entry not found");
             return Ok(());
         }
 
         let new_tab: String = current_tab
             .lines()
-            .filter(|line| !line.contains(&app_path))
+            .filter(|line| !self.is_own_cron_line(line, &app_path))
             .collect::<Vec<_>>()
             .join("\n");
 
@@ -175,7 +211,7 @@ impl<'a> Scheduler<'a> {
     }
 
     fn darwin_enable(&mut self) -> Result<()> {
-        let label = "net.franksplace.wake-update-hauler";
+        let label = self.launchd_label();
         let home = std::env::var("HOME")?;
         let launch_agents_dir = format!("{}/Library/LaunchAgents", home);
         let plist_path = format!("{}/{}.plist", launch_agents_dir, label);
@@ -186,6 +222,15 @@ impl<'a> Scheduler<'a> {
 
         let app_path = self.insights.app_abspath.to_string_lossy().to_string();
         let path_env = self.config.get_scheduler_path();
+        let profile_args = self
+            .profile
+            .map(|p| {
+                format!(
+                    "\n    <string>--profile</string>\n    <string>{}</string>",
+                    p
+                )
+            })
+            .unwrap_or_default();
 
         let plist_content = format!(
             r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -197,7 +242,7 @@ impl<'a> Scheduler<'a> {
 
   <key>ProgramArguments</key>
   <array>
-    <string>{}</string>
+    <string>{}</string>{}
     <string>--logfile-only</string>
   </array>
 
@@ -212,34 +257,42 @@ impl<'a> Scheduler<'a> {
 "#,
             label,
             app_path,
+            profile_args,
             path_env,
             self.build_calendar_interval()
         );
 
         fs::write(&plist_path, plist_content)?;
 
-        let mut pm_hour = self.config.sched_hour.clone();
-        let mut pm_min = self.config.sched_minute.clone();
+        // pmset holds a single repeating wake event, which belongs to the
+        // default entry; profile entries only run while the Mac is awake
+        let wake = if self.profile.is_none() {
+            let mut pm_hour = self.config.sched_hour.clone();
+            let mut pm_min = self.config.sched_minute.clone();
 
-        if pm_min == "*" {
-            pm_min = "0".to_string();
-        }
-        if pm_hour == "*" {
-            pm_hour = "2".to_string();
-        }
+            if pm_min == "*" {
+                pm_min = "0".to_string();
+            }
+            if pm_hour == "*" {
+                pm_hour = "2".to_string();
+            }
 
-        let time_str = format!("{}:{}:00", pm_hour, pm_min);
+            let time_str = format!("{}:{}:00", pm_hour, pm_min);
 
-        let pmset_result = sudo_command(
-            self.config,
-            "/usr/bin/pmset",
-            &["repeat", "wakeorpoweron", "MTWRFSU", &time_str],
-        )?
-        .status();
-        if let Err(e) = pmset_result {
-            self.logger
-                .error(&format!("Failed to set pmset wake schedule: {}", e));
-        }
+            let pmset_result = sudo_command(
+                self.config,
+                "/usr/bin/pmset",
+                &["repeat", "wakeorpoweron", "MTWRFSU", &time_str],
+            )?
+            .status();
+            if let Err(e) = pmset_result {
+                self.logger
+                    .error(&format!("Failed to set pmset wake schedule: {}", e));
+            }
+            format!("pmset {}", time_str)
+        } else {
+            "no pmset wake (profile entry)".to_string()
+        };
 
         let uid = nix::unistd::Uid::effective().as_raw();
         let domain_target = format!("gui/{}", uid);
@@ -259,25 +312,27 @@ impl<'a> Scheduler<'a> {
             .status()?;
 
         self.logger.log(&format!(
-            "schedule for Darwin enabled {} with StartCalendarInterval and pmset {}",
-            label, time_str
+            "schedule for Darwin enabled {} with StartCalendarInterval and {}",
+            label, wake
         ));
 
         Ok(())
     }
 
     fn darwin_disable(&mut self) -> Result<()> {
-        let label = "net.franksplace.wake-update-hauler";
+        let label = self.launchd_label();
         let home = std::env::var("HOME")?;
         let launch_agents_dir = format!("{}/Library/LaunchAgents", home);
         let plist_path = format!("{}/{}.plist", launch_agents_dir, label);
         let plist_path = PathBuf::from(&plist_path);
 
-        let pmset_result =
-            sudo_command(self.config, "/usr/bin/pmset", &["repeat", "cancel"])?.status();
-        if let Err(e) = pmset_result {
-            self.logger
-                .error(&format!("Failed to cancel pmset schedule: {}", e));
+        if self.profile.is_none() {
+            let pmset_result =
+                sudo_command(self.config, "/usr/bin/pmset", &["repeat", "cancel"])?.status();
+            if let Err(e) = pmset_result {
+                self.logger
+                    .error(&format!("Failed to cancel pmset schedule: {}", e));
+            }
         }
 
         let uid = nix::unistd::Uid::effective().as_raw();
@@ -294,15 +349,20 @@ impl<'a> Scheduler<'a> {
         }
 
         self.logger.log(&format!(
-            "schedule for Darwin disabled {} and cleared pmset repeat schedule",
-            label
+            "schedule for Darwin disabled {}{}",
+            label,
+            if self.profile.is_none() {
+                " and cleared pmset repeat schedule"
+            } else {
+                ""
+            }
         ));
 
         Ok(())
     }
 
     fn darwin_check(&mut self) -> Result<()> {
-        let label = "net.franksplace.wake-update-hauler";
+        let label = self.launchd_label();
         let home = std::env::var("HOME")?;
         let launch_agents_dir = format!("{}/Library/LaunchAgents", home);
         let plist_path = format!("{}/{}.plist", launch_agents_dir, label);
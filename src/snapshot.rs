@@ -0,0 +1,380 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, has_path_traversal};
+use crate::insights::Insights;
+use crate::logger::Logger;
+use crate::plugins::PluginRegistry;
+use crate::runner::{ActionResult, ActionStatus};
+use crate::saves::tracked_path;
+
+/// Manifest format written by this version
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Name of the manifest at the root of a snapshot archive
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+/// Contents of a snapshot archive: the machine it was taken on and the save
+/// file of every plugin
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: u32,
+    pub created_at: String,
+    pub updatehauler_version: String,
+    pub hostname: String,
+    pub os: String,
+    pub arch: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distro: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pkg_mgr: Option<String>,
+    pub plugins: Vec<SnapshotFile>,
+}
+
+/// The save file of one plugin inside a snapshot archive
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    pub plugin: String,
+    /// Path relative to the archive root, `files/<plugin>/<name>`
+    pub file: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl Manifest {
+    pub fn new(insights: &Insights, created: DateTime<Local>) -> Self {
+        Self {
+            format: MANIFEST_VERSION,
+            created_at: created.to_rfc3339(),
+            updatehauler_version: env!("CARGO_PKG_VERSION").to_string(),
+            hostname: insights.hostname.clone(),
+            os: insights.os.clone(),
+            arch: insights.arch.clone(),
+            distro: insights.distro.clone(),
+            pkg_mgr: insights.pkg_mgr.clone(),
+            plugins: Vec::new(),
+        }
+    }
+
+    /// Record `path`, a file below `root`, as the save file of `plugin`
+    pub fn add_file(&mut self, root: &Path, plugin: &str, path: &Path) -> Result<()> {
+        let relative = path
+            .strip_prefix(root)
+            .with_context(|| format!("{} is outside {}", path.display(), root.display()))?;
+        let size = std::fs::metadata(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .len();
+        self.plugins.push(SnapshotFile {
+            plugin: plugin.to_string(),
+            file: relative.to_string_lossy().replace('\\', "/"),
+            sha256: sha256_file(path)?,
+            size,
+        });
+        Ok(())
+    }
+}
+
+/// Hex SHA-256 of the file at `path`
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Where the save file of `plugin` is staged in an archive:
+/// `files/<plugin>/<file name of save_file>`
+pub fn staged_path(plugin: &str, save_file: &Path) -> PathBuf {
+    Path::new("files").join(tracked_path(plugin, save_file))
+}
+
+/// `updatehauler-snapshot-<host>-<YYYYMMDD-HHMMSS>.tar.gz`
+pub fn default_archive_name(hostname: &str, created: DateTime<Local>) -> PathBuf {
+    let host = if hostname.is_empty() {
+        "localhost"
+    } else {
+        hostname
+    };
+    PathBuf::from(format!(
+        "updatehauler-snapshot-{}-{}.tar.gz",
+        host,
+        created.format("%Y%m%d-%H%M%S")
+    ))
+}
+
+/// Write `manifest` and every file it lists, read from below `root`, to a
+/// gzip-compressed tar archive at `out`
+pub fn write_archive(root: &Path, manifest: &Manifest, out: &Path) -> Result<()> {
+    if let Some(dir) = out.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let file = File::create(out).with_context(|| format!("Failed to create {}", out.display()))?;
+    let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    let json = serde_json::to_vec_pretty(manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(json.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    tar.append_data(&mut header, MANIFEST_NAME, json.as_slice())?;
+
+    for entry in &manifest.plugins {
+        tar.append_path_with_name(root.join(&entry.file), &entry.file)
+            .with_context(|| format!("Failed to add {} to the snapshot", entry.file))?;
+    }
+    tar.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Unpack the archive at `archive` into `dest` and check every file against
+/// the checksums in its manifest
+pub fn extract_archive(archive: &Path, dest: &Path) -> Result<Manifest> {
+    let file =
+        File::open(archive).with_context(|| format!("Failed to open {}", archive.display()))?;
+    tar::Archive::new(GzDecoder::new(file))
+        .unpack(dest)
+        .with_context(|| format!("Failed to extract {}", archive.display()))?;
+
+    let manifest_path = dest.join(MANIFEST_NAME);
+    let manifest: Manifest = serde_json::from_str(
+        &std::fs::read_to_string(&manifest_path)
+            .with_context(|| format!("{} has no {}", archive.display(), MANIFEST_NAME))?,
+    )
+    .with_context(|| format!("Invalid {} in {}", MANIFEST_NAME, archive.display()))?;
+    if manifest.format > MANIFEST_VERSION {
+        anyhow::bail!(
+            "Snapshot format {} is newer than this updatehauler supports ({})",
+            manifest.format,
+            MANIFEST_VERSION
+        );
+    }
+
+    for entry in &manifest.plugins {
+        let relative = Path::new(&entry.file);
+        if relative.is_absolute() || has_path_traversal(relative) {
+            anyhow::bail!("Snapshot file path is not allowed: {}", entry.file);
+        }
+        let path = dest.join(relative);
+        if !path.is_file() {
+            anyhow::bail!("Snapshot is missing {} ({})", entry.file, entry.plugin);
+        }
+        if sha256_file(&path)? != entry.sha256 {
+            anyhow::bail!(
+                "Checksum mismatch for {} ({}): the snapshot is corrupt",
+                entry.file,
+                entry.plugin
+            );
+        }
+    }
+    Ok(manifest)
+}
+
+/// A private directory under the system temp dir, removed with its
+/// contents when dropped
+pub struct WorkDir {
+    dir: tempfile::TempDir,
+}
+
+impl WorkDir {
+    pub fn new(label: &str) -> Result<Self> {
+        let prefix = format!("updatehauler-{}-", label);
+        let mut builder = tempfile::Builder::new();
+        builder.prefix(&prefix);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            builder.permissions(std::fs::Permissions::from_mode(0o700));
+        }
+        let dir = builder
+            .tempdir()
+            .with_context(|| format!("Failed to create a {} work directory", label))?;
+        Ok(Self { dir })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+/// State of `snapshot create|restore` kept across the run: the staging
+/// directory lives until the save or restore actions have finished
+pub enum SnapshotJob {
+    Create {
+        work: WorkDir,
+        output: PathBuf,
+        /// Plugin and the path its save file is staged at
+        staged: Vec<(String, PathBuf)>,
+    },
+    Restore {
+        _work: WorkDir,
+    },
+}
+
+impl SnapshotJob {
+    /// Plan `snapshot create`: point the save file of each of `plugins` that
+    /// has a save action into a staging directory. Returns the save actions
+    /// to run; the registry must be built again for custom plugins to pick
+    /// up their staged save file.
+    pub fn create(
+        config: &mut Config,
+        registry: &PluginRegistry,
+        plugins: Vec<String>,
+        output: PathBuf,
+    ) -> Result<(Self, Vec<String>)> {
+        let work = WorkDir::new("snapshot")?;
+        let mut staged = Vec::new();
+        let mut actions = Vec::new();
+        for plugin in plugins {
+            let save = format!("{}-save", plugin);
+            let Some(save_file) = config.save_file(&plugin) else {
+                continue;
+            };
+            if registry.get_action_by_name(&save).is_none() {
+                continue;
+            }
+            let path = work.path().join(staged_path(&plugin, &save_file));
+            config.set_save_file(&plugin, path.clone());
+            staged.push((plugin, path));
+            actions.push(save);
+        }
+        if staged.is_empty() {
+            anyhow::bail!("No enabled and available plugin has a save file to snapshot");
+        }
+        let job = Self::Create {
+            work,
+            output,
+            staged,
+        };
+        Ok((job, actions))
+    }
+
+    /// Plan `snapshot restore`: extract `archive` and point the save file of
+    /// every plugin it holds at the extracted copy. Returns the restore
+    /// actions of the plugins usable here; the registry must be built again
+    /// as for [`SnapshotJob::create`].
+    pub async fn restore(
+        config: &mut Config,
+        insights: &Insights,
+        registry: &PluginRegistry<'_>,
+        logger: &mut Logger,
+        archive: &Path,
+    ) -> Result<(Self, Vec<String>)> {
+        let work = WorkDir::new("restore")?;
+        let manifest = extract_archive(archive, work.path())?;
+        logger.log(&format!(
+            "Restoring snapshot of {} taken {} ({} plugins)",
+            manifest.hostname,
+            manifest.created_at,
+            manifest.plugins.len()
+        ));
+        if manifest.os != insights.os || manifest.arch != insights.arch {
+            logger.warn(&format!(
+                "Snapshot was taken on {} {}; this machine is {} {}",
+                manifest.os, manifest.arch, insights.os, insights.arch
+            ));
+        }
+        // Map what the other platform's save files list to packages usable here
+        config.translate_restore = manifest.os != insights.os;
+        let mut actions = Vec::new();
+        for entry in &manifest.plugins {
+            let restore = format!("{}-restore", entry.plugin);
+            let usable = registry.get_action_by_name(&restore).is_some()
+                && config.plugin_enabled(&entry.plugin)
+                && match registry.get_plugin(&entry.plugin) {
+                    Some(plugin) => plugin.check_available(config, insights).await,
+                    None => false,
+                };
+            if !usable || !config.set_save_file(&entry.plugin, work.path().join(&entry.file)) {
+                logger.warn(&format!(
+                    "Skipping {}: plugin is unknown, disabled or not available",
+                    entry.plugin
+                ));
+                continue;
+            }
+            actions.push(restore);
+        }
+        Ok((Self::Restore { _work: work }, actions))
+    }
+
+    /// Bundle the save files written by a `snapshot create` run into its
+    /// output archive, reported as a `snapshot-create` action. Plugins whose
+    /// save failed or wrote nothing are left out with a warning.
+    pub fn finish(
+        &self,
+        config: &Config,
+        insights: &Insights,
+        logger: &mut Logger,
+        results: &[ActionResult],
+    ) -> Option<ActionResult> {
+        let Self::Create {
+            work,
+            output,
+            staged,
+        } = self
+        else {
+            return None;
+        };
+        let started = std::time::Instant::now();
+        let mut result = ActionResult::new("snapshot-create", ActionStatus::Ok);
+        if let Err(e) = write_snapshot(config, insights, logger, results, work, output, staged) {
+            logger.error(&format!("Failed to write snapshot: {:#}", e));
+            result.status = ActionStatus::Failed;
+            result.error = Some(format!("{:#}", e));
+        }
+        result.duration = started.elapsed();
+        Some(result)
+    }
+}
+
+fn write_snapshot(
+    config: &Config,
+    insights: &Insights,
+    logger: &mut Logger,
+    results: &[ActionResult],
+    work: &WorkDir,
+    output: &Path,
+    staged: &[(String, PathBuf)],
+) -> Result<()> {
+    if config.dry_run {
+        logger.log(&format!("Would write snapshot to {}", output.display()));
+        return Ok(());
+    }
+    let mut manifest = Manifest::new(insights, Local::now());
+    for (plugin, path) in staged {
+        let save = format!("{}-save", plugin);
+        let saved = results.iter().any(|r| r.action == save && r.ok());
+        if !saved || !path.is_file() {
+            logger.warn(&format!(
+                "Leaving {} out of the snapshot: save failed",
+                plugin
+            ));
+            continue;
+        }
+        manifest.add_file(work.path(), plugin, path)?;
+    }
+    if manifest.plugins.is_empty() {
+        anyhow::bail!("No plugin saved a file");
+    }
+    write_archive(work.path(), &manifest, output)?;
+    logger.log(&format!(
+        "Wrote snapshot of {} plugins to {}",
+        manifest.plugins.len(),
+        output.display()
+    ));
+    Ok(())
+}
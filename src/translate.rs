@@ -0,0 +1,255 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::insights::Insights;
+
+/// Mapping table shipped with updatehauler
+const BUILTIN_PACKAGE_MAP: &str = include_str!("../data/package-map.yaml");
+
+/// Package names by manager (`flatpak`, `apt`, `dnf`, `pacman`, `apk`)
+pub type Alternatives = BTreeMap<String, String>;
+
+/// Alternatives for Brewfile entries, keyed by formula, cask or App Store
+/// app name
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PackageMap {
+    #[serde(default)]
+    pub brew: BTreeMap<String, Alternatives>,
+    #[serde(default)]
+    pub cask: BTreeMap<String, Alternatives>,
+    #[serde(default)]
+    pub mas: BTreeMap<String, Alternatives>,
+}
+
+impl PackageMap {
+    pub fn builtin() -> Self {
+        serde_saphyr::from_str(BUILTIN_PACKAGE_MAP).expect("built-in package map is valid")
+    }
+
+    /// The built-in table with the entries of `overrides` added; an entry
+    /// replaces the alternatives of the same manager
+    pub fn with_overrides(mut self, overrides: &PackageMap) -> Self {
+        for (table, extra) in [
+            (&mut self.brew, &overrides.brew),
+            (&mut self.cask, &overrides.cask),
+            (&mut self.mas, &overrides.mas),
+        ] {
+            for (name, alternatives) in extra {
+                table
+                    .entry(name.clone())
+                    .or_default()
+                    .extend(alternatives.clone());
+            }
+        }
+        self
+    }
+}
+
+/// Kind of a Brewfile entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    Tap,
+    Brew,
+    Cask,
+    /// Mac App Store app, named by its title
+    Mas,
+    /// Any other entry (`vscode`, `whalebrew`, ...)
+    Other,
+}
+
+/// One line of a Brewfile
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BrewfileEntry {
+    pub kind: EntryKind,
+    pub name: String,
+    /// The line as written, options included
+    pub line: String,
+}
+
+/// First double-quoted string of `s`
+fn quoted(s: &str) -> Option<&str> {
+    let start = s.find('"')? + 1;
+    let len = s[start..].find('"')?;
+    Some(&s[start..start + len])
+}
+
+/// Parse the entries of a Brewfile, skipping blank lines and comments
+pub fn parse_brewfile(content: &str) -> Vec<BrewfileEntry> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let kind = match keyword {
+                "tap" => EntryKind::Tap,
+                "brew" => EntryKind::Brew,
+                "cask" => EntryKind::Cask,
+                "mas" => EntryKind::Mas,
+                _ => EntryKind::Other,
+            };
+            let (kind, name) = match quoted(rest) {
+                Some(name) => (kind, name.to_string()),
+                None => (EntryKind::Other, String::new()),
+            };
+            BrewfileEntry {
+                kind,
+                name,
+                line: line.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// What the machine a Brewfile is restored on can install
+#[derive(Clone, Debug)]
+pub struct RestoreTarget {
+    pub is_darwin: bool,
+    pub has_brew: bool,
+    pub has_flatpak: bool,
+    /// Key of the system package manager in the package map
+    pub system: Option<String>,
+}
+
+impl RestoreTarget {
+    pub fn new(insights: &Insights) -> Self {
+        Self {
+            is_darwin: insights.is_darwin,
+            has_brew: insights.has_brew,
+            has_flatpak: insights.has_flatpak,
+            system: insights
+                .pkg_mgr
+                .as_deref()
+                .and_then(map_key)
+                .map(str::to_string),
+        }
+    }
+}
+
+/// Package map key of an `Insights::pkg_mgr` value
+fn map_key(pkg_mgr: &str) -> Option<&'static str> {
+    match pkg_mgr {
+        "apt-get" => Some("apt"),
+        "dnf" => Some("dnf"),
+        "pacman" | "arch" => Some("pacman"),
+        "apk" => Some("apk"),
+        _ => None,
+    }
+}
+
+/// A Brewfile split into what brew installs here, what other managers
+/// install instead and what has no alternative
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Translation {
+    /// Brewfile lines left to `brew bundle`
+    pub brewfile: Vec<String>,
+    /// Packages to install by manager (`flatpak`, `apt`, `dnf`, ...)
+    pub installs: BTreeMap<String, Vec<String>>,
+    /// Entries without a usable alternative, as written in the Brewfile
+    pub unmapped: Vec<String>,
+}
+
+impl Translation {
+    /// Lines of the Brewfile for `brew bundle`
+    pub fn brewfile_content(&self) -> String {
+        self.brewfile
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+}
+
+/// Translate a Brewfile for `target`. On macOS every entry stays with brew.
+/// Elsewhere casks and App Store apps become flatpaks or system packages,
+/// and formulas become system packages when brew is not installed.
+pub fn translate_brewfile(
+    entries: &[BrewfileEntry],
+    target: &RestoreTarget,
+    map: &PackageMap,
+) -> Translation {
+    let mut translation = Translation::default();
+    for entry in entries {
+        let table = match entry.kind {
+            EntryKind::Cask => &map.cask,
+            EntryKind::Mas => &map.mas,
+            EntryKind::Brew if !target.has_brew => &map.brew,
+            // Taps only matter to brew
+            EntryKind::Tap if !target.has_brew => continue,
+            EntryKind::Other if !target.has_brew => {
+                translation.unmapped.push(entry.line.clone());
+                continue;
+            }
+            _ => {
+                translation.brewfile.push(entry.line.clone());
+                continue;
+            }
+        };
+        if target.is_darwin {
+            translation.brewfile.push(entry.line.clone());
+            continue;
+        }
+        // Apps prefer flatpak, everything else the system package manager
+        let apps = entry.kind != EntryKind::Brew && target.has_flatpak;
+        let alternatives = table
+            .get(&entry.name)
+            .or_else(|| table.get(entry.name.rsplit('/').next().unwrap_or_default()));
+        let found = alternatives.and_then(|alternatives| {
+            apps.then_some("flatpak")
+                .into_iter()
+                .chain(target.system.as_deref())
+                .find_map(|manager| Some((manager, alternatives.get(manager)?)))
+        });
+        match found {
+            Some((manager, package)) => translation
+                .installs
+                .entry(manager.to_string())
+                .or_default()
+                .push(package.clone()),
+            // Name the alternatives this machine lacks the manager for
+            None => translation.unmapped.push(match alternatives {
+                Some(alternatives) if !alternatives.is_empty() => format!(
+                    "{} (alternatives: {})",
+                    entry.line,
+                    alternatives
+                        .iter()
+                        .map(|(manager, package)| format!("{} {}", manager, package))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                _ => entry.line.clone(),
+            }),
+        }
+    }
+    translation
+}
+
+/// Read and translate the Brewfile at `path`
+pub fn translate_brewfile_at(
+    path: &std::path::Path,
+    target: &RestoreTarget,
+    map: &PackageMap,
+) -> Result<Translation> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(translate_brewfile(&parse_brewfile(&content), target, map))
+}
+
+/// Command installing `packages` with `manager`, without sudo
+pub fn install_command(manager: &str, packages: &[String]) -> Option<Vec<String>> {
+    let base: &[&str] = match manager {
+        "flatpak" => &["flatpak", "install", "-y", "--noninteractive", "flathub"],
+        "apt" => &["apt-get", "install", "-y"],
+        "dnf" => &["dnf", "install", "-y"],
+        "pacman" => &["pacman", "-S", "--needed", "--noconfirm"],
+        "apk" => &["apk", "add"],
+        _ => return None,
+    };
+    Some(
+        base.iter()
+            .map(|s| s.to_string())
+            .chain(packages.iter().cloned())
+            .collect(),
+    )
+}
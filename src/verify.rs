@@ -0,0 +1,91 @@
+use anyhow::Result;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::insights::Insights;
+use crate::inventory::{Inventory, diff, snapshot};
+use crate::logger::Logger;
+use crate::plugins::{ErrorPolicy, PluginRegistry, cancellation, run_cmd};
+use crate::rollback::{apply_rollback, previous_versions, render_rollback, revertible};
+use crate::runner::{ActionResult, ActionStatus};
+
+/// A plugin's update finished but one of its `verify:` commands failed
+#[derive(Debug)]
+pub struct VerifyFailed(pub String);
+
+impl std::fmt::Display for VerifyFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for VerifyFailed {}
+
+/// Run the `verify:` commands of `plugin` with `sh -c`, stopping at the
+/// first one that fails. Its error is a [`VerifyFailed`] unless the run was
+/// cancelled.
+pub async fn run_verify(config: &Config, logger: &mut Logger, plugin: &str) -> Result<()> {
+    for command in config.verify.commands(plugin) {
+        if let Err(e) = run_cmd(config, logger, ErrorPolicy::Fail, "sh", &["-c", command]).await {
+            if cancellation().is_some() {
+                return Err(e);
+            }
+            return Err(VerifyFailed(format!("verify command '{}' failed: {}", command, e)).into());
+        }
+    }
+    Ok(())
+}
+
+/// Plugins whose update failed verification
+pub fn unverified_plugins(registry: &PluginRegistry, results: &[ActionResult]) -> Vec<String> {
+    results
+        .iter()
+        .filter(|r| r.status == ActionStatus::VerifyFailed)
+        .filter_map(|r| registry.plugin_name_for_action(&r.action))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Reinstall the previous versions of what the updates of `plugins` changed
+/// since `before`. Plugins that cannot list their packages have nothing to
+/// roll back to and are only logged.
+pub async fn rollback_unverified(
+    registry: Arc<PluginRegistry<'static>>,
+    config: Arc<Config>,
+    insights: Arc<Insights>,
+    logger: &Logger,
+    before: &Inventory,
+    plugins: &[String],
+) -> Vec<ActionResult> {
+    let listed: Vec<String> = plugins
+        .iter()
+        .filter(|p| before.contains_key(*p))
+        .cloned()
+        .collect();
+    for plugin in plugins.iter().filter(|p| !listed.contains(p)) {
+        logger
+            .with_prefix(plugin)
+            .error("Cannot roll back: the packages installed before the update are not known");
+    }
+    let after = snapshot(
+        Arc::clone(&registry),
+        Arc::clone(&config),
+        Arc::clone(&insights),
+        logger,
+        &listed,
+    )
+    .await;
+    let changes = diff(before, &after);
+    let changes: Vec<_> = changes.iter().filter(|c| revertible(c)).collect();
+    for line in render_rollback(&changes) {
+        logger.clone().log(&format!("Rolling back {}", line));
+    }
+    apply_rollback(
+        &registry,
+        &config,
+        &insights,
+        logger,
+        &previous_versions(&changes),
+    )
+    .await
+}
@@ -0,0 +1,251 @@
+use std::io::Write;
+use tempfile::TempDir;
+use updatehauler::audit::{AdvisoryDb, parse_osv, parse_rustsec, render_findings};
+use updatehauler::plugins::InstalledPackage;
+
+fn osv(id: &str, ecosystem: &str, name: &str, events: &str) -> String {
+    format!(
+        r#"{{
+  "id": "{id}",
+  "aliases": ["CVE-2024-0001"],
+  "summary": "Prototype pollution in {name}",
+  "affected": [{{
+    "package": {{"ecosystem": "{ecosystem}", "name": "{name}"}},
+    "ranges": [{{"type": "SEMVER", "events": {events}}}]
+  }}]
+}}"#
+    )
+}
+
+const RUSTSEC: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2024-0001"
+package = "hyper"
+date = "2024-01-01"
+aliases = ["GHSA-aaaa-bbbb-cccc"]
+
+[versions]
+patched = [">= 0.14.10", "^0.13.10"]
+unaffected = ["< 0.12.0"]
+```
+
+# Request smuggling in hyper
+
+Details.
+"#;
+
+#[test]
+fn test_parse_osv_ranges() {
+    let json = osv(
+        "GHSA-1",
+        "npm",
+        "lodash",
+        r#"[{"introduced": "0"}, {"fixed": "4.17.21"}, {"introduced": "5.0.0"}, {"last_affected": "5.0.2"}]"#,
+    );
+    let advisory = parse_osv(&json).expect("valid").expect("not withdrawn");
+    assert_eq!(advisory.id, "GHSA-1");
+    assert_eq!(advisory.aliases, ["CVE-2024-0001"]);
+    assert_eq!(advisory.summary, "Prototype pollution in lodash");
+
+    let versions = &advisory.affected[0].versions;
+    assert!(versions.contains("4.17.20"));
+    assert!(!versions.contains("4.17.21"));
+    assert!(versions.contains("5.0.2"));
+    assert!(!versions.contains("5.0.3"));
+    assert_eq!(versions.fixed_after("4.17.20").as_deref(), Some("4.17.21"));
+    assert_eq!(versions.fixed_after("5.0.0"), None);
+
+    let withdrawn = r#"{"id": "GHSA-2", "withdrawn": "2024-02-01T00:00:00Z", "affected": []}"#;
+    assert!(parse_osv(withdrawn).expect("valid").is_none());
+    assert!(parse_osv("not json").is_err());
+}
+
+#[test]
+fn test_parse_rustsec() {
+    let advisory = parse_rustsec(RUSTSEC)
+        .expect("valid")
+        .expect("not informational");
+    assert_eq!(advisory.id, "RUSTSEC-2024-0001");
+    assert_eq!(advisory.summary, "Request smuggling in hyper");
+    let affected = &advisory.affected[0];
+    assert_eq!(affected.ecosystem, "crates.io");
+    assert_eq!(affected.name, "hyper");
+    assert!(affected.versions.contains("0.14.9"));
+    assert!(!affected.versions.contains("0.14.10"));
+    assert!(!affected.versions.contains("0.13.12"));
+    assert!(affected.versions.contains("0.13.2"));
+    assert!(!affected.versions.contains("0.11.0"));
+    assert_eq!(
+        affected.versions.fixed_after("0.13.2").as_deref(),
+        Some("0.13.10")
+    );
+    assert_eq!(
+        affected.versions.fixed_after("0.14.1").as_deref(),
+        Some("0.14.10")
+    );
+
+    let informational =
+        RUSTSEC.replace("[versions]", "informational = \"unmaintained\"\n[versions]");
+    assert!(parse_rustsec(&informational).expect("valid").is_none());
+}
+
+#[test]
+fn test_audit_and_secure_versions() {
+    let advisories = [
+        osv(
+            "GHSA-1",
+            "PyPI",
+            "Jinja2",
+            r#"[{"introduced": "0"}, {"fixed": "3.1.3"}]"#,
+        ),
+        osv(
+            "GHSA-2",
+            "PyPI",
+            "jinja2",
+            r#"[{"introduced": "3.1.0"}, {"fixed": "3.1.4"}]"#,
+        ),
+        osv(
+            "GHSA-3",
+            "crates.io",
+            "ripgrep",
+            r#"[{"introduced": "0"}, {"fixed": "14.0.0"}]"#,
+        ),
+        osv("GHSA-4", "npm", "left-pad", r#"[{"introduced": "0"}]"#),
+    ];
+    let db = AdvisoryDb::new(
+        advisories
+            .iter()
+            .map(|json| parse_osv(json).expect("valid").expect("not withdrawn"))
+            .collect(),
+    );
+    assert_eq!(db.len(), 4);
+
+    let packages = [
+        InstalledPackage::new("pip", "jinja2", "3.1.2", "pip"),
+        InstalledPackage::new("cargo", "ripgrep", "13.0.0", "crates.io"),
+        InstalledPackage::new(
+            "cargo",
+            "ripgrep",
+            "13.0.0",
+            "git+https://github.com/x/ripgrep",
+        ),
+        InstalledPackage::new("npm", "left-pad", "1.3.0", "npm"),
+        InstalledPackage::new("npm", "typescript", "5.4.5", "npm"),
+        InstalledPackage::new("brew", "jinja2", "3.1.2", "formula"),
+    ];
+    let findings = db.audit(&packages);
+    let summary: Vec<(&str, &str, Option<&str>)> = findings
+        .iter()
+        .map(|f| (f.name.as_str(), f.id.as_str(), f.fixed.as_deref()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("jinja2", "GHSA-1", Some("3.1.3")),
+            ("jinja2", "GHSA-2", Some("3.1.4")),
+            ("ripgrep", "GHSA-3", Some("14.0.0")),
+            ("left-pad", "GHSA-4", None),
+        ]
+    );
+
+    // 3.1.3 fixes the first advisory but is still affected by the second
+    let (upgrades, unfixed) = db.upgrades(&packages);
+    assert_eq!(
+        upgrades,
+        [
+            (
+                "pip".to_string(),
+                vec![InstalledPackage::new("pip", "jinja2", "3.1.4", "pip")]
+            ),
+            (
+                "cargo".to_string(),
+                vec![InstalledPackage::new(
+                    "cargo",
+                    "ripgrep",
+                    "14.0.0",
+                    "crates.io"
+                )]
+            ),
+        ]
+    );
+    assert_eq!(unfixed, [&packages[3]]);
+}
+
+#[test]
+fn test_load_directory_and_zip() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let dir = temp_dir.path().join("db");
+    std::fs::create_dir_all(dir.join("crates/hyper")).expect("create dirs");
+    std::fs::write(dir.join("crates/hyper/RUSTSEC-2024-0001.md"), RUSTSEC).expect("write");
+    std::fs::write(dir.join("README.md"), "# Advisory DB\n").expect("write");
+    std::fs::write(
+        dir.join("GHSA-1.json"),
+        osv(
+            "GHSA-1",
+            "npm",
+            "lodash",
+            r#"[{"introduced": "0"}, {"fixed": "4.17.21"}]"#,
+        ),
+    )
+    .expect("write");
+    std::fs::write(dir.join("broken.json"), "{").expect("write");
+
+    let db = AdvisoryDb::load(&dir).expect("load directory");
+    assert_eq!(db.len(), 2);
+    assert_eq!(db.unreadable.len(), 1);
+    assert!(db.unreadable[0].0.ends_with("broken.json"));
+
+    let zip_path = temp_dir.path().join("all.zip");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).expect("create zip"));
+    for id in ["GHSA-1", "GHSA-2", "GHSA-3"] {
+        zip.start_file(
+            format!("{}.json", id),
+            zip::write::SimpleFileOptions::default(),
+        )
+        .expect("start file");
+        zip.write_all(
+            osv(
+                id,
+                "npm",
+                "lodash",
+                r#"[{"introduced": "0"}, {"fixed": "4.17.21"}]"#,
+            )
+            .as_bytes(),
+        )
+        .expect("write entry");
+    }
+    zip.finish().expect("finish zip");
+
+    let db = AdvisoryDb::load(&zip_path).expect("load zip");
+    assert_eq!(db.len(), 3);
+    let findings = db.audit(&[InstalledPackage::new("npm", "lodash", "4.17.20", "npm")]);
+    assert_eq!(findings.len(), 3);
+
+    assert!(AdvisoryDb::load(&temp_dir.path().join("missing")).is_err());
+    let empty = temp_dir.path().join("empty");
+    std::fs::create_dir(&empty).expect("create dir");
+    assert!(AdvisoryDb::load(&empty).is_err());
+}
+
+#[test]
+fn test_render_findings() {
+    let json = osv(
+        "GHSA-1",
+        "npm",
+        "lodash",
+        r#"[{"introduced": "0"}, {"fixed": "4.17.21"}]"#,
+    );
+    let db = AdvisoryDb::new(vec![parse_osv(&json).expect("valid").expect("advisory")]);
+    let findings = db.audit(&[InstalledPackage::new("npm", "lodash", "4.17.20", "npm")]);
+    let table = render_findings(&findings, 1);
+    let lines: Vec<&str> = table.lines().collect();
+    assert!(lines[0].starts_with("Plugin"));
+    assert!(lines.iter().any(|l| l.contains("lodash")
+        && l.contains("4.17.20")
+        && l.contains("4.17.21")
+        && l.contains("GHSA-1")));
+    assert_eq!(
+        render_findings(&[], 12),
+        "No known vulnerabilities in 12 packages\n"
+    );
+}
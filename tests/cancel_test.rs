@@ -0,0 +1,42 @@
+// Cancellation is process-wide, so these tests live in their own binary.
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::logger::Logger;
+use updatehauler::plugins::{
+    CommandFailed, ErrorPolicy, Interruption, cancel_commands, cancellation, run_cmd,
+};
+
+#[tokio::test]
+async fn test_cancel_stops_running_and_pending_commands() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    let mut logger = Logger::new(&config);
+
+    assert!(cancellation().is_none());
+
+    let canceller = tokio::spawn(async {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        cancel_commands(nix::sys::signal::Signal::SIGINT);
+    });
+
+    let started = Instant::now();
+    let err = run_cmd(&config, &mut logger, ErrorPolicy::Warn, "sleep", &["30"])
+        .await
+        .expect_err("cancelled command must be an error");
+    canceller.await.expect("canceller panicked");
+
+    assert!(started.elapsed() < Duration::from_secs(10));
+    let failed = err
+        .downcast_ref::<CommandFailed>()
+        .expect("error should be CommandFailed");
+    assert_eq!(failed.0.interrupted, Some(Interruption::Cancelled));
+
+    // Nothing new starts once the run is cancelled
+    let err = run_cmd(&config, &mut logger, ErrorPolicy::Fail, "true", &[])
+        .await
+        .expect_err("commands after cancellation must not run");
+    assert!(err.to_string().contains("cancelled"));
+}
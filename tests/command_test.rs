@@ -0,0 +1,289 @@
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::logger::Logger;
+use updatehauler::plugins::{
+    CommandFailed, CommandOutcome, ErrorPolicy, Interruption, descendants, is_transient, run_cmd,
+    scope_plugin,
+};
+
+fn test_config(temp_dir: &TempDir) -> Config {
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    config
+}
+
+#[tokio::test]
+async fn test_run_cmd_captures_output() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = test_config(&temp_dir);
+    let mut logger = Logger::new(&config);
+
+    let outcome = run_cmd(&config, &mut logger, ErrorPolicy::Fail, "echo", &["hello"])
+        .await
+        .expect("echo should succeed");
+
+    assert!(outcome.success());
+    assert_eq!(outcome.exit_code, Some(0));
+    assert_eq!(outcome.stdout.trim(), "hello");
+    assert!(!outcome.dry_run);
+
+    let log = std::fs::read_to_string(&config.log).expect("Failed to read log");
+    assert!(log.contains("echo → hello"));
+}
+
+#[tokio::test]
+async fn test_run_cmd_fail_policy_returns_error() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = test_config(&temp_dir);
+    let mut logger = Logger::new(&config);
+
+    let err = run_cmd(&config, &mut logger, ErrorPolicy::Fail, "false", &[])
+        .await
+        .expect_err("non-zero exit must be an error");
+
+    let failed = err
+        .downcast_ref::<CommandFailed>()
+        .expect("error should be CommandFailed");
+    assert_eq!(failed.0.exit_code, Some(1));
+    assert!(err.to_string().contains("return code 1"));
+}
+
+#[tokio::test]
+async fn test_run_cmd_warn_and_ignore_policies_keep_going() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = test_config(&temp_dir);
+    let mut logger = Logger::new(&config);
+
+    for policy in [ErrorPolicy::Warn, ErrorPolicy::Ignore] {
+        let outcome = run_cmd(&config, &mut logger, policy, "false", &[])
+            .await
+            .expect("tolerated failure should be Ok");
+        assert!(!outcome.success());
+        assert_eq!(outcome.exit_code, Some(1));
+    }
+
+    let log = std::fs::read_to_string(&config.log).expect("Failed to read log");
+    assert!(log.contains("WARN"));
+}
+
+#[tokio::test]
+async fn test_run_cmd_missing_program() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = test_config(&temp_dir);
+    let mut logger = Logger::new(&config);
+
+    let err = run_cmd(
+        &config,
+        &mut logger,
+        ErrorPolicy::Fail,
+        "updatehauler-no-such-program",
+        &[],
+    )
+    .await
+    .expect_err("missing program must be an error");
+
+    let failed = err
+        .downcast_ref::<CommandFailed>()
+        .expect("error should be CommandFailed");
+    assert_eq!(failed.0.exit_code, None);
+}
+
+#[tokio::test]
+async fn test_run_cmd_dry_run() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = test_config(&temp_dir);
+    config.dry_run = true;
+    let mut logger = Logger::new(&config);
+
+    let outcome = run_cmd(&config, &mut logger, ErrorPolicy::Fail, "false", &[])
+        .await
+        .expect("dry run never fails");
+
+    assert!(outcome.dry_run);
+    assert!(outcome.success());
+    assert_eq!(outcome.command.trim(), "false");
+}
+
+#[tokio::test]
+async fn test_run_cmd_tags_stderr_lines() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = test_config(&temp_dir);
+    let mut logger = Logger::new(&config);
+
+    let outcome = run_cmd(
+        &config,
+        &mut logger,
+        ErrorPolicy::Fail,
+        "sh",
+        &["-c", "echo out; echo err >&2"],
+    )
+    .await
+    .expect("sh should succeed");
+
+    assert_eq!(outcome.stdout, "out\n");
+    assert_eq!(outcome.stderr, "err\n");
+
+    let log = std::fs::read_to_string(&config.log).expect("Failed to read log");
+    assert!(log.contains("sh → out"));
+    assert!(log.contains("sh [stderr] → err"));
+}
+
+#[tokio::test]
+async fn test_run_cmd_timeout_kills_process_group() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = test_config(&temp_dir);
+    config.timeouts.plugins.insert("slow".to_string(), 1);
+    let mut logger = Logger::new(&config);
+    let marker = temp_dir.path().join("survived");
+    let script = format!("(sleep 3; touch {}) & sleep 30", marker.display());
+
+    let started = Instant::now();
+    let err = scope_plugin(
+        "slow",
+        run_cmd(
+            &config,
+            &mut logger,
+            ErrorPolicy::Ignore,
+            "sh",
+            &["-c", &script],
+        ),
+    )
+    .await
+    .expect_err("timed out command must be an error under any policy");
+
+    assert!(started.elapsed() < Duration::from_secs(10));
+    let failed = err
+        .downcast_ref::<CommandFailed>()
+        .expect("error should be CommandFailed");
+    assert_eq!(
+        failed.0.interrupted,
+        Some(Interruption::TimedOut(Duration::from_secs(1)))
+    );
+    assert!(err.to_string().contains("timed out after 1s"));
+
+    // The backgrounded grandchild was in the same process group and died too
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert!(!marker.exists());
+}
+
+#[tokio::test]
+async fn test_run_cmd_retries_transient_failures() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = test_config(&temp_dir);
+    config.retry.max_attempts = 3;
+    config.retry.backoff = 0;
+    let mut logger = Logger::new(&config);
+    let marker = temp_dir.path().join("attempted");
+    let script = format!(
+        "if [ -f {0} ]; then echo ok; else touch {0}; echo 'Could not resolve host: example.com' >&2; exit 6; fi",
+        marker.display()
+    );
+
+    let outcome = run_cmd(
+        &config,
+        &mut logger,
+        ErrorPolicy::Fail,
+        "sh",
+        &["-c", &script],
+    )
+    .await
+    .expect("second attempt should succeed");
+
+    assert!(outcome.success());
+    assert_eq!(outcome.attempts, 2);
+
+    let log = std::fs::read_to_string(&config.log).expect("Failed to read log");
+    assert!(log.contains("transient failure (attempt 1/3)"));
+    assert!(log.contains("Start (attempt 2/3)"));
+}
+
+#[tokio::test]
+async fn test_run_cmd_does_not_retry_permanent_failures() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = test_config(&temp_dir);
+    config.retry.max_attempts = 3;
+    config.retry.backoff = 0;
+    let mut logger = Logger::new(&config);
+
+    let err = run_cmd(
+        &config,
+        &mut logger,
+        ErrorPolicy::Fail,
+        "sh",
+        &["-c", "echo 'error: unknown option --bogus' >&2; exit 2"],
+    )
+    .await
+    .expect_err("bad argument must fail");
+
+    let failed = err
+        .downcast_ref::<CommandFailed>()
+        .expect("error should be CommandFailed");
+    assert_eq!(failed.0.attempts, 1);
+    assert_eq!(failed.0.exit_code, Some(2));
+}
+
+#[test]
+fn test_is_transient_classification() {
+    let outcome = |exit_code, stderr: &str| CommandOutcome {
+        command: "apt-get update".to_string(),
+        exit_code,
+        duration: Duration::ZERO,
+        stdout: String::new(),
+        stderr: stderr.to_string(),
+        dry_run: false,
+        interrupted: None,
+        attempts: 1,
+    };
+
+    assert!(is_transient(&outcome(
+        Some(100),
+        "E: Could not get lock /var/lib/dpkg/lock-frontend"
+    )));
+    assert!(is_transient(&outcome(Some(1), "npm ERR! code ECONNRESET")));
+    assert!(!is_transient(&outcome(Some(0), "Connection refused")));
+    assert!(!is_transient(&outcome(Some(2), "invalid argument")));
+    assert!(!is_transient(&outcome(None, "No such file or directory")));
+}
+
+#[test]
+fn test_is_transient_ignores_permanent_failures() {
+    let outcome = |stderr: &str| CommandOutcome {
+        command: "apt-get install".to_string(),
+        exit_code: Some(100),
+        duration: Duration::ZERO,
+        stdout: String::new(),
+        stderr: stderr.to_string(),
+        dry_run: false,
+        interrupted: None,
+        attempts: 1,
+    };
+
+    for permanent in [
+        "E: Failed to fetch http://deb.example.com/pool/main/w/widget_1.0.deb  404  Not Found",
+        "Cannot connect to the Docker daemon at unix:///var/run/docker.sock. connect: connection refused",
+        "error: another instance is already running",
+        "npm ERR! Failed to download the package: E404",
+    ] {
+        assert!(!is_transient(&outcome(permanent)), "{}", permanent);
+    }
+}
+
+#[test]
+fn test_descendants_follow_the_whole_tree() {
+    // sudo (10) → apt (11) → dpkg (12) → postinst (14); 13 belongs elsewhere
+    let table = [
+        (1, 0),
+        (10, 1),
+        (11, 10),
+        (12, 11),
+        (13, 1),
+        (14, 12),
+        (15, 11),
+    ];
+    let mut found = descendants(&table, 10);
+    found.sort();
+    assert_eq!(found, [11, 12, 14, 15]);
+    assert!(descendants(&table, 13).is_empty());
+}
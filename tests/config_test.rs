@@ -1,8 +1,9 @@
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
+    use std::time::Duration;
     use tempfile::TempDir;
-    use updatehauler::config::Config;
+    use updatehauler::config::{Config, OsUpdateMode};
 
     #[test]
     fn test_config_new() {
@@ -166,6 +167,119 @@ plugins:
         assert_eq!(config.plugins_enabled.os, Some(false));
     }
 
+    #[test]
+    fn test_config_load_from_yaml_timeouts() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let config_path = temp_dir.path().join("config.yaml");
+
+        std::fs::write(
+            &config_path,
+            r#"
+timeouts:
+  default: 3600
+  plugins:
+    nvim: 300
+    os: 0
+  commands:
+    "snap refresh": 1800
+    "nvim --headless -c MasonUpdate": 60
+"#,
+        )
+        .expect("Failed to write config file");
+
+        let config = Config::load_from_yaml("/home/test", Some(&config_path))
+            .expect("Failed to load config");
+        let timeouts = &config.timeouts;
+
+        assert_eq!(
+            timeouts.resolve(Some("brew"), "brew upgrade"),
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            timeouts.resolve(Some("nvim"), "nvim --headless -c PackerSync"),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(
+            timeouts.resolve(Some("nvim"), "nvim --headless -c MasonUpdate -c qa"),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            timeouts.resolve(Some("snap"), "snap refresh"),
+            Some(Duration::from_secs(1800))
+        );
+        // Prefixes only match whole words
+        assert_eq!(
+            timeouts.resolve(None, "snap refreshed"),
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(timeouts.resolve(Some("os"), "apt-get -y upgrade"), None);
+    }
+
+    #[test]
+    fn test_config_load_from_yaml_retry() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let config_path = temp_dir.path().join("config.yaml");
+
+        std::fs::write(
+            &config_path,
+            r#"
+retry:
+  max_attempts: 4
+  backoff: 5
+  max_backoff: 15
+  plugins: [brew]
+  commands: ["apt-get update"]
+"#,
+        )
+        .expect("Failed to write config file");
+
+        let config = Config::load_from_yaml("/home/test", Some(&config_path))
+            .expect("Failed to load config");
+        let retry = &config.retry;
+
+        assert_eq!(retry.max_attempts, 4);
+        assert!(retry.applies_to(Some("brew"), "brew upgrade"));
+        assert!(retry.applies_to(Some("os"), "apt-get update"));
+        assert!(!retry.applies_to(Some("os"), "apt-get -y upgrade"));
+        assert!(!retry.applies_to(None, "npm update -g"));
+        assert_eq!(retry.delay(1), Duration::from_secs(5));
+        assert_eq!(retry.delay(2), Duration::from_secs(10));
+        assert_eq!(retry.delay(3), Duration::from_secs(15));
+    }
+
+    #[test]
+    fn test_config_retry_disabled_by_default() {
+        let config = Config::new("/home/test");
+        assert_eq!(config.retry.max_attempts, 1);
+        assert!(config.retry.applies_to(Some("brew"), "brew upgrade"));
+    }
+
+    #[test]
+    fn test_config_load_from_yaml_os_mode() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let config_path = temp_dir.path().join("config.yaml");
+
+        std::fs::write(&config_path, "os:\n  mode: security\n")
+            .expect("Failed to write config file");
+        let config = Config::load_from_yaml("/home/test", Some(&config_path))
+            .expect("Failed to load config");
+        assert_eq!(config.os_mode, OsUpdateMode::Security);
+        assert_eq!(Config::new("/home/test").os_mode, OsUpdateMode::Full);
+
+        std::fs::write(&config_path, "os:\n  mode: everything\n")
+            .expect("Failed to write config file");
+        assert!(Config::load_from_yaml("/home/test", Some(&config_path)).is_err());
+    }
+
+    #[test]
+    fn test_config_timeouts_default_unlimited() {
+        let config = Config::new("/home/test");
+        assert_eq!(
+            config.timeouts.resolve(Some("nvim"), "nvim --headless"),
+            None
+        );
+    }
+
     #[test]
     fn test_config_load_from_yaml_paths() {
         let temp_dir = TempDir::new().expect("Failed to create temp dir");
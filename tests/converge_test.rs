@@ -0,0 +1,304 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::converge::{ConvergeStep, apply_steps, converge_steps, render_steps};
+use updatehauler::insights::Insights;
+use updatehauler::logger::Logger;
+use updatehauler::plugins::cargo::parse_cargo_backup;
+use updatehauler::plugins::pip::{uv_pip_args, uv_pip_install_args};
+use updatehauler::plugins::{
+    InstallScope, InstalledPackage, PipPlugin, Plugin, PluginMetadata, PluginRegistry,
+};
+use updatehauler::restore_plan::{PlanReport, plan};
+use updatehauler::runner::ActionStatus;
+
+fn pkg(manager: &str, name: &str, version: &str) -> InstalledPackage {
+    InstalledPackage::new(manager, name, version, manager)
+}
+
+fn report(
+    plugin: &str,
+    saved: Vec<InstalledPackage>,
+    installed: Vec<InstalledPackage>,
+) -> PlanReport {
+    PlanReport {
+        plugin: plugin.to_string(),
+        entries: plan(plugin, &saved, &installed),
+        error: None,
+        saved,
+        installed,
+    }
+}
+
+#[test]
+fn test_converge_steps() {
+    let reports = [
+        report(
+            "npm",
+            vec![
+                pkg("npm", "prettier", "3.2.0"),
+                pkg("npm", "eslint", "8.57.0"),
+            ],
+            vec![
+                pkg("npm", "eslint", "9.0.0"),
+                pkg("npm", "left-pad", "1.3.0"),
+            ],
+        ),
+        report(
+            "uv",
+            vec![pkg("uv", "ruff", "0.4.0")],
+            vec![pkg("uv", "ruff", "0.4.0")],
+        ),
+    ];
+
+    assert_eq!(
+        converge_steps(&reports, false),
+        [ConvergeStep {
+            plugin: "npm".to_string(),
+            install: vec![pkg("npm", "prettier", "3.2.0")],
+            remove: vec![pkg("npm", "left-pad", "1.3.0")],
+        }]
+    );
+    // Pinned also reinstalls the saved version of a mismatched package
+    assert_eq!(
+        converge_steps(&reports, true)[0].install,
+        [
+            pkg("npm", "eslint", "8.57.0"),
+            pkg("npm", "prettier", "3.2.0")
+        ]
+    );
+}
+
+#[test]
+fn test_render_steps() {
+    let steps = [
+        ConvergeStep {
+            plugin: "npm".to_string(),
+            install: vec![pkg("npm", "prettier", "3.2.0"), pkg("npm", "tsx", "")],
+            remove: vec![pkg("npm", "left-pad", "1.3.0")],
+        },
+        ConvergeStep {
+            plugin: "snap".to_string(),
+            install: Vec::new(),
+            remove: vec![pkg("snap", "hello", "2.10")],
+        },
+    ];
+    assert_eq!(
+        render_steps(&steps),
+        [
+            "npm: install prettier 3.2.0, tsx; remove left-pad 1.3.0",
+            "snap: remove hello 2.10"
+        ]
+    );
+}
+
+#[test]
+fn test_parse_cargo_backup() {
+    let json = r#"{"packages": [
+        {"name": "ripgrep", "version": "14.1.0"},
+        {"name": "bat", "version": "0.24.0"}
+    ]}"#;
+    let packages = parse_cargo_backup(json).expect("valid backup");
+    assert_eq!(packages.len(), 2);
+    assert_eq!(packages[0].name, "ripgrep");
+    assert_eq!(packages[0].version, "14.1.0");
+    assert_eq!(packages[1].name, "bat");
+    assert!(parse_cargo_backup("not json").is_err());
+}
+
+struct FakePlugin {
+    name: &'static str,
+    supported: bool,
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl Plugin for FakePlugin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: self.name.to_string(),
+            description: String::new(),
+            actions: Vec::new(),
+            run_after: Vec::new(),
+        }
+    }
+
+    async fn check_available(&self, _config: &Config, _insights: &Insights) -> bool {
+        true
+    }
+
+    async fn update(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn install_packages(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        for p in packages {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("install {}", p.name));
+        }
+        Ok(self.supported)
+    }
+
+    async fn remove_packages(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        for p in packages {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("remove {}", p.name));
+        }
+        Ok(self.supported)
+    }
+}
+
+#[test]
+fn test_uv_pip_args_target_the_system_interpreter() {
+    assert_eq!(
+        uv_pip_install_args(&["ruff==0.4.0", "requests"]),
+        [
+            "pip",
+            "install",
+            "--system",
+            "--break-system-packages",
+            "ruff==0.4.0",
+            "requests"
+        ]
+    );
+    assert_eq!(
+        uv_pip_args("uninstall", &["left-pad"]),
+        ["pip", "uninstall", "--system", "left-pad"]
+    );
+}
+
+#[tokio::test]
+async fn test_pip_removes_only_user_packages() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    config.dry_run = true;
+    let mut logger = Logger::new(&config);
+    let insights = Insights::new().expect("Failed to create Insights");
+    let packages = [
+        pkg("pip", "httpie", "3.2.2").with_scope(InstallScope::User),
+        pkg("pip", "python-apt", "2.4.0"),
+    ];
+
+    PipPlugin
+        .remove_packages(&config, &insights, &mut logger, &packages)
+        .await
+        .expect("dry run never fails");
+
+    let log = std::fs::read_to_string(&config.log).expect("Failed to read log");
+    assert!(log.contains("Keeping 1 packages not installed in the home directory: python-apt"));
+    let command = log
+        .lines()
+        .find(|l| l.contains("Would execute:"))
+        .expect("uninstall command logged");
+    assert!(command.contains("httpie"));
+    assert!(!command.contains("python-apt"));
+    assert!(!command.contains("--break-system-packages"));
+}
+
+#[tokio::test]
+async fn test_apply_steps_installs_then_removes() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    let logger = Logger::new(&config);
+    let insights = Insights::new().expect("Failed to create Insights");
+
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let mut registry = PluginRegistry::new();
+    registry.register(Box::new(FakePlugin {
+        name: "alpha",
+        supported: true,
+        calls: Arc::clone(&calls),
+    }));
+    registry.register(Box::new(FakePlugin {
+        name: "beta",
+        supported: false,
+        calls: Arc::clone(&calls),
+    }));
+    let steps = [
+        ConvergeStep {
+            plugin: "alpha".to_string(),
+            install: vec![pkg("alpha", "a", "1")],
+            remove: vec![pkg("alpha", "b", "2")],
+        },
+        ConvergeStep {
+            plugin: "beta".to_string(),
+            install: vec![pkg("beta", "c", "1")],
+            remove: vec![pkg("beta", "d", "1")],
+        },
+    ];
+
+    let results = apply_steps(&registry, &config, &insights, &logger, &steps).await;
+
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].action, "alpha-converge");
+    assert_eq!(results[0].status, ActionStatus::Ok);
+    assert_eq!(results[1].action, "beta-converge");
+    assert_eq!(results[1].status, ActionStatus::Failed);
+    assert_eq!(
+        results[1].error.as_deref(),
+        Some("beta cannot install single packages")
+    );
+    // An unsupported install stops the plugin before anything is removed
+    assert_eq!(
+        *calls.lock().unwrap(),
+        ["install a", "remove b", "install c"]
+    );
+}
+
+#[tokio::test]
+async fn test_pip_restore_through_uv_targets_the_system_interpreter() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    config.dry_run = true;
+    config.restore_pinned = true;
+    config.pip_file = temp_dir.path().join("pip-requirements.txt");
+    std::fs::write(&config.pip_file, "requests==2.31.0\n").expect("Failed to write save file");
+    let mut logger = Logger::new(&config);
+    let mut insights = Insights::new().expect("Failed to create Insights");
+    insights.has_uv = true;
+
+    PipPlugin
+        .restore(&config, &insights, &mut logger)
+        .await
+        .expect("dry run never fails");
+
+    let log = std::fs::read_to_string(&config.log).expect("Failed to read log");
+    let expected = format!(
+        "Would execute: uv pip install --system --break-system-packages --no-deps -r {}",
+        config.pip_file.display()
+    );
+    assert!(log.contains(&expected), "{}", log);
+}
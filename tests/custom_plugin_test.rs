@@ -0,0 +1,213 @@
+use std::path::Path;
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::insights::Insights;
+use updatehauler::logger::Logger;
+use updatehauler::plugins::{
+    BrewPlugin, PluginActionType, PluginRegistry, register_custom_plugins,
+};
+
+fn load(dir: &Path, yaml: &str) -> anyhow::Result<Config> {
+    let config_path = dir.join("config.yaml");
+    std::fs::write(&config_path, yaml).expect("Failed to write config file");
+    let mut config = Config::load_from_yaml("/home/test", Some(&config_path))?;
+    config.log = dir.join("test.log");
+    config.use_log = true;
+    Ok(config)
+}
+
+fn acme_yaml(dir: &Path) -> String {
+    format!(
+        r#"
+custom_plugins:
+  - name: acme
+    description: Update ACME internal tools
+    detect: sh
+    run_after: [os]
+    save_file: {}/acme.txt
+    update:
+      - echo upgrading "all tools"
+    save:
+      - printf "tool-a\ntool-b\n"
+    restore:
+      - cat {{save_file}}
+    actions:
+      doctor:
+        - echo healthy
+plugins:
+  acme: false
+"#,
+        dir.display()
+    )
+}
+
+#[test]
+fn test_custom_plugin_metadata() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = load(temp_dir.path(), &acme_yaml(temp_dir.path())).expect("Failed to load");
+
+    assert_eq!(config.custom_plugins.len(), 1);
+    assert_eq!(config.plugins_enabled.custom.get("acme"), Some(&false));
+
+    let mut registry = PluginRegistry::new();
+    register_custom_plugins(&mut registry, &config).expect("Failed to register");
+
+    let metadata = registry.get_metadata("acme").expect("acme not registered");
+    assert_eq!(metadata.description, "Update ACME internal tools");
+    assert_eq!(metadata.run_after, vec!["os".to_string()]);
+    let names: Vec<&str> = metadata.actions.iter().map(|a| a.name.as_str()).collect();
+    assert_eq!(names, ["acme", "acme-save", "acme-restore", "acme-doctor"]);
+    assert_eq!(
+        registry
+            .get_action_by_name("acme-save")
+            .unwrap()
+            .action_type,
+        Some(PluginActionType::Save)
+    );
+    assert_eq!(
+        registry
+            .get_action_by_name("acme-doctor")
+            .unwrap()
+            .action_type,
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_custom_plugin_actions_run_commands() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = load(temp_dir.path(), &acme_yaml(temp_dir.path())).expect("Failed to load");
+    let insights = Insights::new().expect("Failed to create Insights");
+    let mut logger = Logger::new(&config);
+
+    let mut registry = PluginRegistry::new();
+    register_custom_plugins(&mut registry, &config).expect("Failed to register");
+
+    for action in ["acme", "acme-save", "acme-restore", "acme-doctor"] {
+        registry
+            .execute_action(action, &config, &insights, &mut logger)
+            .await
+            .unwrap_or_else(|e| panic!("{} failed: {}", action, e));
+    }
+
+    let saved = std::fs::read_to_string(temp_dir.path().join("acme.txt"))
+        .expect("save_file was not written");
+    assert_eq!(saved, "tool-a\ntool-b\n");
+
+    let log = std::fs::read_to_string(&config.log).expect("Failed to read log");
+    assert!(log.contains("echo → upgrading all tools"));
+    assert!(log.contains("cat → tool-b"));
+    assert!(log.contains("echo → healthy"));
+}
+
+#[tokio::test]
+async fn test_custom_plugin_failing_command_fails_action() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = load(
+        temp_dir.path(),
+        r#"
+custom_plugins:
+  - name: broken
+    update:
+      - "false"
+      - echo never
+"#,
+    )
+    .expect("Failed to load");
+    let insights = Insights::new().expect("Failed to create Insights");
+    let mut logger = Logger::new(&config);
+
+    let mut registry = PluginRegistry::new();
+    register_custom_plugins(&mut registry, &config).expect("Failed to register");
+
+    let result = registry
+        .execute_action("broken", &config, &insights, &mut logger)
+        .await;
+    assert!(result.is_err());
+
+    let log = std::fs::read_to_string(&config.log).expect("Failed to read log");
+    assert!(!log.contains("echo never"));
+}
+
+#[test]
+fn test_custom_plugin_validation() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let invalid = [
+        ("name: Acme-Tools\n    update: [acme up]", "lowercase"),
+        ("name: schedule\n    update: [acme up]", "reserved"),
+        ("name: audit\n    update: [acme up]", "reserved"),
+        ("name: acme", "at least one update command"),
+        (
+            "name: acme\n    update: [acme up]\n    actions:\n      save: [acme save]",
+            "clashes",
+        ),
+        ("name: acme\n    update: ['acme \"up']", "not valid"),
+    ];
+
+    for (plugin, expected) in invalid {
+        let yaml = format!("custom_plugins:\n  - {}\n", plugin);
+        let err = load(temp_dir.path(), &yaml)
+            .err()
+            .unwrap_or_else(|| panic!("expected error for {}", plugin));
+        assert!(
+            format!("{:#}", err).contains(expected),
+            "error for {:?} was {:#}",
+            plugin,
+            err
+        );
+    }
+
+    let duplicate =
+        "custom_plugins:\n  - name: acme\n    update: [a]\n  - name: acme\n    update: [b]\n";
+    assert!(load(temp_dir.path(), duplicate).is_err());
+}
+
+#[test]
+fn test_custom_plugin_cannot_shadow_builtin() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = load(
+        temp_dir.path(),
+        "custom_plugins:\n  - name: brew\n    update: [brew upgrade]\n",
+    )
+    .expect("Failed to load");
+
+    let mut registry = PluginRegistry::new();
+    registry.register(Box::new(BrewPlugin));
+    let err = register_custom_plugins(&mut registry, &config).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("conflicts with the built-in plugin")
+    );
+}
+
+#[test]
+fn test_custom_plugin_enable_override() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = load(temp_dir.path(), &acme_yaml(temp_dir.path())).expect("Failed to load");
+
+    config.apply_plugin_enabled("acme", true);
+    assert_eq!(config.plugins_enabled.custom.get("acme"), Some(&true));
+}
+
+#[test]
+fn test_unknown_plugin_keys() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let yaml = format!(
+        "{}  acmee: true\n  run: false\n",
+        acme_yaml(temp_dir.path())
+    );
+    let config = load(temp_dir.path(), &yaml).expect("Failed to load");
+    assert_eq!(config.unknown_plugin_keys(|_| false), ["acmee"]);
+    assert!(
+        config
+            .unknown_plugin_keys(|name| name == "acmee")
+            .is_empty()
+    );
+
+    // Without external plugins nothing else can claim the key
+    let yaml = format!("external_plugins: false\n{}", yaml);
+    let err = load(temp_dir.path(), &yaml)
+        .err()
+        .expect("unknown plugin key rejected");
+    assert!(err.to_string().contains("plugins.acmee"), "{:#}", err);
+}
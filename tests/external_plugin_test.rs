@@ -0,0 +1,238 @@
+use std::ffi::OsString;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::insights::Insights;
+use updatehauler::logger::Logger;
+use updatehauler::plugins::{
+    ExternalPlugin, Plugin, PluginActionType, PluginRegistry, discover_external_plugins,
+};
+
+const ACME: &str = r#"#!/bin/sh
+read -r request
+case "$1" in
+  metadata)
+    echo '{"type":"result","protocol":1,"name":"acme","description":"ACME tools","run_after":["os"],"actions":[{"name":"save","type":"save"},{"name":"export","type":"save"},{"name":"doctor","description":"Check ACME"},{"name":"idle"}]}' ;;
+  check_available)
+    echo '{"type":"result","available":true}' ;;
+  update)
+    echo '{"type":"log","level":"info","message":"upgrading widgets"}'
+    echo 'plain output'
+    echo '{"type":"result","ok":true}' ;;
+  save)
+    echo "$request" > "$(dirname "$0")/request.json"
+    echo '{"type":"result","ok":true}' ;;
+  custom)
+    case "$request" in
+      *'"idle"'*)
+        echo '{"type":"result","ok":true,"handled":false}' ;;
+      *)
+        echo '{"type":"log","level":"warn","message":"widget loose"}'
+        echo '{"type":"result","ok":false,"error":"broken widget"}' ;;
+    esac ;;
+esac
+"#;
+
+fn write_plugin(dir: &Path, name: &str, script: &str) -> PathBuf {
+    let path = dir.join(format!("updatehauler-plugin-{}", name));
+    std::fs::write(&path, script).expect("Failed to write plugin");
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+        .expect("Failed to chmod plugin");
+    path
+}
+
+fn test_config(temp_dir: &TempDir) -> Config {
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    config
+}
+
+#[test]
+fn test_discover_external_plugins() {
+    let first = TempDir::new().expect("Failed to create temp dir");
+    let second = TempDir::new().expect("Failed to create temp dir");
+    let acme = write_plugin(first.path(), "acme", ACME);
+    write_plugin(second.path(), "acme", ACME);
+    write_plugin(second.path(), "zeta", ACME);
+    write_plugin(second.path(), "bad-name", ACME);
+    std::fs::write(second.path().join("updatehauler-plugin-noexec"), ACME)
+        .expect("Failed to write file");
+
+    let search_path =
+        std::env::join_paths([first.path(), second.path()]).expect("Failed to join paths");
+    let found = discover_external_plugins(&search_path);
+
+    let names: Vec<&str> = found.iter().map(|(n, _)| n.as_str()).collect();
+    assert_eq!(names, ["acme", "zeta"]);
+    assert_eq!(found[0].1, acme);
+    assert!(discover_external_plugins(&OsString::new()).is_empty());
+}
+
+#[tokio::test]
+async fn test_external_plugin_metadata_and_availability() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = test_config(&temp_dir);
+    let insights = Insights::new().expect("Failed to create Insights");
+    let mut logger = Logger::new(&config);
+    let path = write_plugin(temp_dir.path(), "acme", ACME);
+
+    let plugin = ExternalPlugin::load("acme", &path, &config, &mut logger)
+        .await
+        .expect("Failed to load plugin");
+    assert!(plugin.check_available(&config, &insights).await);
+
+    let metadata = plugin.get_metadata();
+    assert_eq!(metadata.description, "ACME tools");
+    assert_eq!(metadata.run_after, vec!["os".to_string()]);
+    let names: Vec<&str> = metadata.actions.iter().map(|a| a.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "acme",
+            "acme-save",
+            "acme-export",
+            "acme-doctor",
+            "acme-idle"
+        ]
+    );
+    assert_eq!(
+        metadata.actions[1].action_type,
+        Some(PluginActionType::Save)
+    );
+    assert_eq!(
+        metadata.actions[2].action_type,
+        Some(PluginActionType::Save)
+    );
+    assert_eq!(metadata.actions[3].action_type, None);
+    assert_eq!(metadata.actions[3].description, "Check ACME");
+}
+
+#[tokio::test]
+async fn test_external_plugin_actions() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = test_config(&temp_dir);
+    let insights = Insights::new().expect("Failed to create Insights");
+    let mut logger = Logger::new(&config);
+    let path = write_plugin(temp_dir.path(), "acme", ACME);
+
+    let mut registry = PluginRegistry::new();
+    registry.register(Box::new(
+        ExternalPlugin::load("acme", &path, &config, &mut logger)
+            .await
+            .expect("Failed to load plugin"),
+    ));
+
+    registry
+        .execute_action("acme", &config, &insights, &mut logger)
+        .await
+        .expect("update should succeed");
+    registry
+        .execute_action("acme-save", &config, &insights, &mut logger)
+        .await
+        .expect("save should succeed");
+    let err = registry
+        .execute_action("acme-doctor", &config, &insights, &mut logger)
+        .await
+        .expect_err("plugin reported a failure");
+    assert!(
+        err.to_string()
+            .contains("acme doctor failed: broken widget")
+    );
+    let err = registry
+        .execute_action("acme-idle", &config, &insights, &mut logger)
+        .await
+        .expect_err("an unhandled action is an error");
+    assert!(err.to_string().contains("did not handle its action 'idle'"));
+
+    let request: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(temp_dir.path().join("request.json"))
+            .expect("save request not received"),
+    )
+    .expect("request should be JSON");
+    assert_eq!(request["protocol"], 1);
+    assert_eq!(request["request"], "save");
+    assert_eq!(request["action"], "save");
+
+    registry
+        .execute_action("acme-export", &config, &insights, &mut logger)
+        .await
+        .expect("export should succeed");
+    let request: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(temp_dir.path().join("request.json"))
+            .expect("export request not received"),
+    )
+    .expect("request should be JSON");
+    assert_eq!(request["request"], "save");
+    assert_eq!(request["action"], "export");
+
+    let log = std::fs::read_to_string(&config.log).expect("Failed to read log");
+    // Only the update action upgraded anything
+    assert_eq!(log.matches("upgrading widgets").count(), 1);
+    assert!(log.contains("→ plain output"));
+    assert!(log.contains("WARN"));
+    assert!(!log.contains("\"type\":\"result\""));
+}
+
+#[tokio::test]
+async fn test_external_plugin_dry_run_does_not_execute() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = test_config(&temp_dir);
+    config.dry_run = true;
+    let insights = Insights::new().expect("Failed to create Insights");
+    let mut logger = Logger::new(&config);
+    let path = write_plugin(temp_dir.path(), "acme", ACME);
+
+    let plugin = ExternalPlugin::load("acme", &path, &config, &mut logger)
+        .await
+        .expect("Failed to load plugin");
+    plugin
+        .save(&config, &insights, &mut logger)
+        .await
+        .expect("dry run never fails");
+
+    assert!(!temp_dir.path().join("request.json").exists());
+    let log = std::fs::read_to_string(&config.log).expect("Failed to read log");
+    assert!(log.contains("Would execute:"));
+}
+
+#[tokio::test]
+async fn test_external_plugin_rejects_bad_metadata() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = test_config(&temp_dir);
+    let mut logger = Logger::new(&config);
+    let cases = [
+        (
+            "newer",
+            r#"{"type":"result","protocol":2}"#,
+            "protocol version 2",
+        ),
+        (
+            "renamed",
+            r#"{"type":"result","protocol":1,"name":"other"}"#,
+            "reports the name 'other'",
+        ),
+        (
+            "badaction",
+            r#"{"type":"result","protocol":1,"actions":[{"name":"update"}]}"#,
+            "invalid action name",
+        ),
+        ("silent", r#"not json"#, "no result line"),
+    ];
+
+    for (name, answer, expected) in cases {
+        let script = format!("#!/bin/sh\necho '{}'\n", answer);
+        let path = write_plugin(temp_dir.path(), name, &script);
+        let err = ExternalPlugin::load(name, &path, &config, &mut logger)
+            .await
+            .err()
+            .unwrap_or_else(|| panic!("{} should not load", name));
+        assert!(
+            format!("{:#}", err).contains(expected),
+            "error for {} was {:#}",
+            name,
+            err
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use chrono::{Local, TimeZone};
+use std::path::Path;
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::history::{
+    History, HistoryEntry, HistoryFilter, parse_since, render_entry, render_list,
+};
+use updatehauler::insights::Insights;
+use updatehauler::inventory::{ChangeKind, PackageChange};
+use updatehauler::plugins::{PluginRegistry, register_custom_plugins};
+use updatehauler::report::RunReport;
+use updatehauler::runner::{ActionResult, ActionStatus};
+
+fn config(dir: &Path, yaml: &str) -> Config {
+    let config_path = dir.join("config.yaml");
+    std::fs::write(&config_path, yaml).expect("Failed to write config file");
+    let mut config =
+        Config::load_from_yaml("/home/test", Some(&config_path)).expect("Failed to load");
+    config.state_dir = dir.join("state");
+    config
+}
+
+const PLUGINS: &str = r#"
+custom_plugins:
+  - name: acme
+    update: [echo acme]
+  - name: beta
+    update: ["false"]
+"#;
+
+/// A run started at `hour`:00 on 2025-01-31 with the given action statuses
+fn entry(config: &Config, hour: u32, results: &[(&str, ActionStatus)]) -> HistoryEntry {
+    let mut registry = PluginRegistry::new();
+    register_custom_plugins(&mut registry, config).expect("Failed to register");
+    let started = Local
+        .with_ymd_and_hms(2025, 1, 31, hour, 0, 0)
+        .earliest()
+        .expect("valid time");
+    let results: Vec<ActionResult> = results
+        .iter()
+        .map(|(action, status)| ActionResult::new(action, *status))
+        .collect();
+    let report = RunReport::new(
+        &registry,
+        &Insights::new().expect("Failed to create Insights"),
+        started,
+        started + chrono::Duration::seconds(90),
+        false,
+        &results,
+    );
+    HistoryEntry::new(
+        report,
+        None,
+        vec![
+            "updatehauler".to_string(),
+            "--jobs".to_string(),
+            "2".to_string(),
+        ],
+        None,
+    )
+}
+
+#[test]
+fn test_history_config() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = config(
+        temp_dir.path(),
+        "statedir: /var/lib/hauler\nmax_history_runs: 5\n",
+    );
+    assert_eq!(config.max_history_runs, 5);
+
+    let mut loaded =
+        Config::load_from_yaml("/home/test", Some(&temp_dir.path().join("config.yaml")))
+            .expect("Failed to load");
+    assert_eq!(loaded.state_dir, Path::new("/var/lib/hauler"));
+    loaded.state_dir = temp_dir.path().to_path_buf();
+    assert_eq!(
+        History::new(&loaded).path(),
+        temp_dir.path().join("history.jsonl")
+    );
+
+    std::fs::write(temp_dir.path().join("config.yaml"), "statedir: ../state\n")
+        .expect("Failed to write config file");
+    assert!(
+        Config::load_from_yaml("/home/test", Some(&temp_dir.path().join("config.yaml"))).is_err()
+    );
+}
+
+#[test]
+fn test_history_record_load_and_prune() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = config(temp_dir.path(), PLUGINS);
+    config.max_history_runs = 2;
+    let history = History::new(&config);
+    assert!(history.load().expect("missing file is empty").is_empty());
+
+    for hour in [1, 2, 3] {
+        let mut run = entry(&config, hour, &[("acme", ActionStatus::Ok)]);
+        history.record(&mut run).expect("Failed to record");
+    }
+    // A line cut short by a crash is skipped
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(history.path())
+        .and_then(|mut f| std::io::Write::write_all(&mut f, b"{\"id\": \"2025"))
+        .expect("Failed to append");
+
+    let entries = history.load().expect("Failed to load");
+    let ids: Vec<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(ids, ["20250131-020000", "20250131-030000"]);
+    assert_eq!(entries[1].report.actions[0].plugin.as_deref(), Some("acme"));
+    assert_eq!(entries[1].args, ["updatehauler", "--jobs", "2"]);
+
+    assert_eq!(
+        history.find("20250131-03").expect("prefix").id,
+        "20250131-030000"
+    );
+    assert!(
+        history
+            .find("20250131")
+            .unwrap_err()
+            .to_string()
+            .contains("ambiguous")
+    );
+    assert!(history.find("1999").is_err());
+
+    config.max_history_runs = 0;
+    let disabled = History::new(&config);
+    disabled
+        .record(&mut entry(&config, 4, &[]))
+        .expect("recording disabled");
+    assert_eq!(disabled.load().expect("Failed to load").len(), 2);
+}
+
+#[test]
+fn test_history_ids_stay_unique_within_a_second() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = config(temp_dir.path(), PLUGINS);
+    let history = History::new(&config);
+
+    let mut ids = Vec::new();
+    for _ in 0..3 {
+        let mut run = entry(&config, 2, &[]);
+        history.record(&mut run).expect("Failed to record");
+        ids.push(run.id);
+    }
+    assert_eq!(
+        ids,
+        ["20250131-020000", "20250131-020000-2", "20250131-020000-3"]
+    );
+    assert_eq!(
+        history.find("20250131-020000").expect("exact id").id,
+        "20250131-020000"
+    );
+    let loaded: Vec<String> = history
+        .load()
+        .expect("Failed to load")
+        .into_iter()
+        .map(|e| e.id)
+        .collect();
+    assert_eq!(loaded, ids);
+}
+
+#[test]
+fn test_history_filter() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = config(temp_dir.path(), PLUGINS);
+    let ok = entry(
+        &config,
+        1,
+        &[("acme", ActionStatus::Ok), ("beta", ActionStatus::Ok)],
+    );
+    let beta_failed = entry(
+        &config,
+        5,
+        &[
+            ("pre_acme", ActionStatus::Failed),
+            ("beta", ActionStatus::Failed),
+        ],
+    );
+
+    let filter = |plugin: Option<&str>, failed: bool| HistoryFilter {
+        plugin: plugin.map(str::to_string),
+        failed,
+        since: None,
+    };
+    assert!(filter(None, false).matches(&ok));
+    assert!(!filter(None, true).matches(&ok));
+    assert!(filter(None, true).matches(&beta_failed));
+    assert!(filter(Some("beta"), true).matches(&beta_failed));
+    // Only the hook of acme ran in the second run
+    assert!(!filter(Some("acme"), false).matches(&beta_failed));
+    assert!(!filter(Some("acme"), true).matches(&ok));
+
+    let since = HistoryFilter {
+        since: Some(parse_since("2025-01-31 03:00", Local::now()).expect("valid date")),
+        ..HistoryFilter::default()
+    };
+    assert!(!since.matches(&ok));
+    assert!(since.matches(&beta_failed));
+}
+
+#[test]
+fn test_parse_since() {
+    let now = Local
+        .with_ymd_and_hms(2025, 1, 31, 12, 0, 0)
+        .earliest()
+        .expect("valid time");
+    let at = |y, m, d, h| {
+        Local
+            .with_ymd_and_hms(y, m, d, h, 0, 0)
+            .earliest()
+            .expect("valid time")
+            .fixed_offset()
+    };
+
+    assert_eq!(parse_since("7d", now).expect("age"), at(2025, 1, 24, 12));
+    assert_eq!(parse_since("12h", now).expect("age"), at(2025, 1, 31, 0));
+    assert_eq!(parse_since("2w", now).expect("age"), at(2025, 1, 17, 12));
+    assert_eq!(
+        parse_since("2025-01-02", now).expect("date"),
+        at(2025, 1, 2, 0)
+    );
+    assert_eq!(
+        parse_since("2025-01-02 08:00", now).expect("date and time"),
+        at(2025, 1, 2, 8)
+    );
+    assert_eq!(
+        parse_since("2025-01-02T08:00:00Z", now)
+            .expect("RFC 3339")
+            .timestamp(),
+        1735804800
+    );
+    for bad in ["", "7x", "yesterday", "2025-13-01"] {
+        assert!(
+            parse_since(bad, now).is_err(),
+            "{:?} should be rejected",
+            bad
+        );
+    }
+}
+
+#[test]
+fn test_render_history() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = config(temp_dir.path(), PLUGINS);
+    let run = entry(
+        &config,
+        1,
+        &[("acme", ActionStatus::Ok), ("beta", ActionStatus::Failed)],
+    );
+
+    let list = render_list(&[&run], None);
+    assert!(list.starts_with("Run              Started           Duration  Status  Actions\n"));
+    assert!(list.contains("20250131-010000  2025-01-31 01:00  1m 30s    FAIL    1 ok, 1 failed\n"));
+    assert!(render_list(&[&run], Some("beta")).contains("  beta FAIL\n"));
+    assert_eq!(render_list(&[], None), "No matching runs in the history\n");
+
+    let details = render_entry(&run);
+    assert!(details.starts_with("Run 20250131-010000\n"));
+    assert!(details.contains("  Command:  updatehauler --jobs 2\n"));
+    assert!(details.contains("beta    update  FAIL    0.0s\n"));
+    assert!(!details.contains("Package changes:"));
+
+    let mut run = run;
+    run.report.changes = vec![PackageChange {
+        plugin: "acme".to_string(),
+        name: "tool".to_string(),
+        source: "acme".to_string(),
+        kind: ChangeKind::Upgraded,
+        from: Some("1.2".to_string()),
+        to: Some("1.3".to_string()),
+    }];
+    assert!(render_entry(&run).contains("\nPackage changes:\n  acme: upgraded tool 1.2 → 1.3\n"));
+}
@@ -0,0 +1,201 @@
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+use updatehauler::config::{Config, PreHookFailure};
+use updatehauler::hooks::run_finish_hooks;
+use updatehauler::insights::Insights;
+use updatehauler::logger::Logger;
+use updatehauler::plugins::{PluginRegistry, register_custom_plugins};
+use updatehauler::runner::{ActionResult, ActionStatus, ExecutionGraph};
+
+fn load(dir: &Path, yaml: &str) -> anyhow::Result<Config> {
+    let config_path = dir.join("config.yaml");
+    std::fs::write(&config_path, yaml).expect("Failed to write config file");
+    let mut config = Config::load_from_yaml("/home/test", Some(&config_path))?;
+    config.log = dir.join("test.log");
+    config.use_log = true;
+    Ok(config)
+}
+
+fn summary(results: &[ActionResult]) -> Vec<(&str, ActionStatus)> {
+    results
+        .iter()
+        .map(|r| (r.action.as_str(), r.status))
+        .collect()
+}
+
+async fn execute(config: Config, actions: &[&str]) -> Vec<ActionResult> {
+    let mut registry = PluginRegistry::new();
+    register_custom_plugins(&mut registry, &config).expect("Failed to register");
+    let actions: Vec<String> = actions.iter().map(|s| s.to_string()).collect();
+    let graph = ExecutionGraph::build(&registry, &actions).expect("graph should build");
+    let logger = Logger::new(&config);
+    graph
+        .execute(
+            Arc::new(registry),
+            Arc::new(config),
+            Arc::new(Insights::new().expect("Failed to create Insights")),
+            &logger,
+            1,
+        )
+        .await
+}
+
+const PLUGINS: &str = r#"
+custom_plugins:
+  - name: acme
+    update: [echo acme]
+  - name: beta
+    update: ["false"]
+"#;
+
+#[test]
+fn test_hooks_load_from_yaml() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = load(
+        temp_dir.path(),
+        r#"
+hooks:
+  pre_run: [echo start]
+  post_run: [echo end]
+  on_failure: [echo failed]
+  pre_os: [multipass stop devvm]
+  post_brew: [relink-dotfiles, echo linked]
+  pre_failure: continue
+"#,
+    )
+    .expect("Failed to load");
+
+    assert_eq!(config.hooks.pre_run, vec!["echo start"]);
+    assert_eq!(config.hooks.post_run, vec!["echo end"]);
+    assert_eq!(config.hooks.on_failure, vec!["echo failed"]);
+    assert_eq!(config.hooks.pre("os"), ["multipass stop devvm"]);
+    assert_eq!(
+        config.hooks.post("brew"),
+        ["relink-dotfiles", "echo linked"]
+    );
+    assert!(config.hooks.pre("brew").is_empty());
+    assert_eq!(config.hooks.pre_failure, PreHookFailure::Continue);
+
+    assert_eq!(Config::new("/tmp").hooks.pre_failure, PreHookFailure::Skip);
+}
+
+#[test]
+fn test_hooks_reject_unknown_entries() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    for (hook, expected) in [
+        ("after_os", "unknown hook 'after_os'"),
+        ("pre_Bad-Name", "does not name a valid plugin"),
+    ] {
+        let yaml = format!("hooks:\n  {}: [echo hi]\n", hook);
+        let err = load(temp_dir.path(), &yaml)
+            .err()
+            .expect("hook should be rejected");
+        assert!(
+            err.to_string().contains(expected),
+            "error for {} was {}",
+            hook,
+            err
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_failing_pre_hook_skips_plugin() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let yaml = format!(
+        "{}hooks:\n  pre_acme: [\"exit 3\"]\n  post_acme: [echo never]\n",
+        PLUGINS
+    );
+    let config = load(temp_dir.path(), &yaml).expect("Failed to load");
+    let log = config.log.clone();
+
+    let results = execute(config, &["acme"]).await;
+
+    assert_eq!(
+        summary(&results),
+        [
+            ("pre_acme", ActionStatus::Failed),
+            ("acme", ActionStatus::Skipped)
+        ]
+    );
+    let log = std::fs::read_to_string(log).expect("Failed to read log");
+    assert!(!log.contains("acme → Start"));
+    assert!(!log.contains("never"));
+}
+
+#[tokio::test]
+async fn test_failing_pre_hook_can_continue() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let yaml = format!(
+        "{}hooks:\n  pre_acme: [\"exit 3\"]\n  pre_failure: continue\n",
+        PLUGINS
+    );
+    let config = load(temp_dir.path(), &yaml).expect("Failed to load");
+
+    let results = execute(config, &["acme"]).await;
+
+    assert_eq!(
+        summary(&results),
+        [
+            ("pre_acme", ActionStatus::Failed),
+            ("acme", ActionStatus::Ok)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_post_hook_sees_plugin_result() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let out = temp_dir.path().join("post.txt");
+    let yaml = format!(
+        "{}hooks:\n  post_beta: ['echo \"$UPDATEHAULER_HOOK $UPDATEHAULER_PLUGIN $UPDATEHAULER_STATUS $UPDATEHAULER_FAILED\" > {}']\n",
+        PLUGINS,
+        out.display()
+    );
+    let config = load(temp_dir.path(), &yaml).expect("Failed to load");
+
+    let results = execute(config, &["acme", "beta"]).await;
+
+    assert_eq!(
+        summary(&results),
+        [
+            ("acme", ActionStatus::Ok),
+            ("beta", ActionStatus::Failed),
+            ("post_beta", ActionStatus::Ok)
+        ]
+    );
+    let written = std::fs::read_to_string(out).expect("post hook did not run");
+    assert_eq!(written, "post_beta beta FAIL beta\n");
+}
+
+#[tokio::test]
+async fn test_finish_hooks_run_on_failure_only_when_needed() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = load(
+        temp_dir.path(),
+        "hooks:\n  on_failure: ['echo \"failed: $UPDATEHAULER_FAILED\"']\n  post_run: ['echo \"status: $UPDATEHAULER_STATUS\"']\n",
+    )
+    .expect("Failed to load");
+    let mut logger = Logger::new(&config);
+    let actions = vec!["acme".to_string(), "beta".to_string()];
+
+    let ok = [ActionResult::new("acme", ActionStatus::Ok)];
+    let hooks = run_finish_hooks(&config, &mut logger, &actions, &ok).await;
+    assert_eq!(summary(&hooks), [("post_run", ActionStatus::Ok)]);
+
+    let failed = [ActionResult::new("beta", ActionStatus::TimedOut)];
+    let hooks = run_finish_hooks(&config, &mut logger, &actions, &failed).await;
+    assert_eq!(
+        summary(&hooks),
+        [
+            ("on_failure", ActionStatus::Ok),
+            ("post_run", ActionStatus::Ok)
+        ]
+    );
+
+    let log = std::fs::read_to_string(&config.log).expect("Failed to read log");
+    assert!(log.contains("sh → status: OK"));
+    assert!(log.contains("sh → failed: beta"));
+    assert!(log.contains("sh → status: TIMEOUT"));
+}
@@ -0,0 +1,404 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::cmp::Ordering;
+use std::sync::Arc;
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::insights::Insights;
+use updatehauler::inventory::{
+    ChangeKind, Inventory, InventoryFormat, InventoryReport, compare_versions, diff,
+    list_installed, plugins_to_track, render_changes, render_inventory, snapshot,
+};
+use updatehauler::logger::Logger;
+use updatehauler::plugins::{
+    BrewPlugin, CargoPlugin, InstallScope, InstalledPackage, Plugin, PluginMetadata,
+    PluginRegistry, brew, cargo, flatpak, gem, npm, os, pip, snap, uv, vscode,
+};
+
+fn pkg(manager: &str, name: &str, version: &str, source: &str) -> InstalledPackage {
+    InstalledPackage::new(manager, name, version, source)
+}
+
+#[test]
+fn test_parse_package_lists() {
+    assert_eq!(
+        brew::parse_brew_list_versions("git 2.43.0 2.44.0\nfirefox 125.0\n", "formula"),
+        [
+            pkg("brew", "git", "2.44.0", "formula"),
+            pkg("brew", "firefox", "125.0", "formula")
+        ]
+    );
+    assert_eq!(
+        cargo::parse_cargo_install_list(
+            "ripgrep v14.1.0:\n    rg\nmytool v0.1.0 (/home/me/mytool):\n    mytool\n    helper\n",
+            "/opt/cargo/bin"
+        ),
+        [
+            pkg("cargo", "ripgrep", "14.1.0", "crates.io").with_path("/opt/cargo/bin/rg"),
+            pkg("cargo", "mytool", "0.1.0", "/home/me/mytool").with_path("/opt/cargo/bin/mytool")
+        ]
+    );
+    assert_eq!(
+        npm::parse_npm_list(
+            r#"{"name": "lib", "dependencies": {"typescript": {"version": "5.4.0"}}}"#
+        )
+        .expect("valid JSON"),
+        [pkg("npm", "typescript", "5.4.0", "npm")]
+    );
+    assert!(
+        npm::parse_npm_list("{}")
+            .expect("no dependencies")
+            .is_empty()
+    );
+    assert_eq!(
+        pip::parse_pip_list(
+            r#"[{"name": "requests", "version": "2.31.0", "location": "/usr/lib/python3/site-packages"},
+                {"name": "ruff", "version": "0.5.0"}]"#
+        )
+        .expect("valid JSON"),
+        [
+            pkg("pip", "requests", "2.31.0", "pip").with_path("/usr/lib/python3/site-packages"),
+            pkg("pip", "ruff", "0.5.0", "pip")
+        ]
+    );
+    assert_eq!(
+        uv::parse_uv_tool_list("ruff v0.5.0\n- ruff\nblack v24.1.0\n- black\n- blackd\n"),
+        [
+            pkg("uv", "ruff", "0.5.0", "uv"),
+            pkg("uv", "black", "24.1.0", "uv")
+        ]
+    );
+    assert_eq!(
+        gem::parse_gem_list("rake (13.1.0, 13.0.6)\nbundler (default: 2.4.0)\n"),
+        [
+            pkg("gem", "rake", "13.1.0", "gem"),
+            pkg("gem", "bundler", "2.4.0", "gem")
+        ]
+    );
+    assert_eq!(
+        flatpak::parse_flatpak_list(
+            "org.gimp.GIMP\t2.10.38\tstable\tflathub\tuser\norg.gnome.Platform\t\t46\tflathub\tsystem\n"
+        ),
+        [
+            pkg("flatpak", "org.gimp.GIMP", "2.10.38", "flathub").with_scope(InstallScope::User),
+            pkg("flatpak", "org.gnome.Platform", "46", "flathub")
+        ]
+    );
+    assert_eq!(
+        snap::parse_snap_list(
+            "Name     Version  Rev   Tracking       Publisher  Notes\nfirefox  125.0    4173  latest/stable  mozilla    -\n"
+        ),
+        [pkg("snap", "firefox", "125.0", "snap").with_path("/snap/firefox/current")]
+    );
+    assert_eq!(
+        vscode::parse_vscode_extensions(
+            "ms-python.python@2024.1.0\nrust-lang.rust-analyzer@0.3.1\n",
+            "code"
+        ),
+        [
+            pkg("code", "ms-python.python", "2024.1.0", "code").with_scope(InstallScope::User),
+            pkg("code", "rust-lang.rust-analyzer", "0.3.1", "code").with_scope(InstallScope::User)
+        ]
+    );
+}
+
+#[test]
+fn test_parse_os_package_lists() {
+    assert_eq!(
+        os::parse_dpkg_query("ii \tcurl\t7.81.0-1ubuntu1.16\nrc \told-lib\t1.0\n"),
+        [pkg("apt", "curl", "7.81.0-1ubuntu1.16", "apt")]
+    );
+    assert_eq!(
+        os::parse_rpm_query("curl\t7.76.1-26.el9\n"),
+        [pkg("dnf", "curl", "7.76.1-26.el9", "dnf")]
+    );
+    assert_eq!(
+        os::parse_apk_info("ca-certificates-bundle-20240226-r0\n"),
+        [pkg("apk", "ca-certificates-bundle", "20240226-r0", "apk")]
+    );
+    assert_eq!(
+        os::parse_pacman_query("linux 6.1.1-1\n"),
+        [pkg("pacman", "linux", "6.1.1-1", "pacman")]
+    );
+    assert_eq!(
+        os::parse_mas_list("497799835  Xcode  (15.1)\n"),
+        [pkg("mas", "Xcode", "15.1", "mas").with_path("/Applications/Xcode.app")]
+    );
+}
+
+#[test]
+fn test_compare_versions() {
+    assert_eq!(compare_versions("1.9", "1.10"), Ordering::Less);
+    assert_eq!(compare_versions("2.44.0", "2.43.1"), Ordering::Greater);
+    assert_eq!(compare_versions("1.0", "1.0"), Ordering::Equal);
+    assert_eq!(compare_versions("1.0", "1.0.1"), Ordering::Less);
+    assert_eq!(
+        compare_versions("7.81.0-1ubuntu1.15", "7.81.0-1ubuntu1.16"),
+        Ordering::Less
+    );
+}
+
+#[test]
+fn test_compare_versions_orders_pre_releases_below_releases() {
+    assert_eq!(compare_versions("1.0.0-rc1", "1.0.0"), Ordering::Less);
+    assert_eq!(compare_versions("1.0.0", "1.0.0-rc1"), Ordering::Greater);
+    assert_eq!(compare_versions("1.0.0-rc.1", "1.0.0-rc.2"), Ordering::Less);
+    assert_eq!(compare_versions("2.1-beta2", "2.1"), Ordering::Less);
+    assert_eq!(compare_versions("2.1~rc1", "2.1"), Ordering::Less);
+    assert_eq!(compare_versions("2.1~rc1", "2.1~rc2"), Ordering::Less);
+    assert_eq!(compare_versions("2.0", "2.1~rc1"), Ordering::Less);
+    // Packaging revisions are not pre-releases
+    assert_eq!(
+        compare_versions("6.8.0-45-generic", "6.8.0-47-generic"),
+        Ordering::Less
+    );
+    assert_eq!(compare_versions("1.2-1", "1.2"), Ordering::Greater);
+
+    // Moving from a release candidate to the release is an upgrade
+    let mut before = Inventory::new();
+    before.insert(
+        "cargo".to_string(),
+        vec![pkg("cargo", "ripgrep", "15.0.0-rc1", "cargo")],
+    );
+    let mut after = Inventory::new();
+    after.insert(
+        "cargo".to_string(),
+        vec![pkg("cargo", "ripgrep", "15.0.0", "cargo")],
+    );
+    let changes = diff(&before, &after);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].kind, ChangeKind::Upgraded);
+}
+
+#[test]
+fn test_diff_and_render_changes() {
+    let mut before = Inventory::new();
+    before.insert(
+        "brew".to_string(),
+        vec![
+            pkg("brew", "git", "2.43.0", "formula"),
+            pkg("brew", "wget", "1.21", "formula"),
+            pkg("brew", "node", "22.1.0", "formula"),
+            pkg("brew", "firefox", "125.0", "cask"),
+        ],
+    );
+    before.insert(
+        "npm".to_string(),
+        vec![pkg("npm", "typescript", "5.0.0", "npm")],
+    );
+    let mut after = Inventory::new();
+    after.insert(
+        "brew".to_string(),
+        vec![
+            pkg("brew", "git", "2.44.0", "formula"),
+            pkg("brew", "jq", "1.7", "formula"),
+            pkg("brew", "node", "20.12.0", "formula"),
+            pkg("brew", "firefox", "125.0", "cask"),
+        ],
+    );
+    // Only listed after the run: not compared
+    after.insert(
+        "pip".to_string(),
+        vec![pkg("pip", "requests", "2.31.0", "pip")],
+    );
+
+    let changes = diff(&before, &after);
+    let kinds: Vec<(&str, ChangeKind)> =
+        changes.iter().map(|c| (c.name.as_str(), c.kind)).collect();
+    assert_eq!(
+        kinds,
+        [
+            ("git", ChangeKind::Upgraded),
+            ("jq", ChangeKind::Added),
+            ("node", ChangeKind::Downgraded),
+            ("wget", ChangeKind::Removed),
+        ]
+    );
+    assert_eq!(changes[0].from.as_deref(), Some("2.43.0"));
+    assert_eq!(changes[0].to.as_deref(), Some("2.44.0"));
+
+    assert_eq!(
+        render_changes(&changes),
+        [
+            "brew: upgraded git 2.43.0 → 2.44.0, added jq 1.7, downgraded node 22.1.0 → 20.12.0, removed wget"
+        ]
+    );
+    let json = serde_json::to_value(&changes[3]).expect("change serializes");
+    assert_eq!(json["kind"], "removed");
+    assert!(json.get("to").is_none());
+}
+
+#[test]
+fn test_plugins_to_track() {
+    let mut registry = PluginRegistry::new();
+    registry.register(Box::new(BrewPlugin));
+    registry.register(Box::new(CargoPlugin));
+    let actions: Vec<String> = [
+        "brew-save",
+        "brew-list",
+        "cargo-restore",
+        "brew",
+        "trim-logfile",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    assert_eq!(plugins_to_track(&registry, &actions), ["cargo", "brew"]);
+}
+
+struct FakePlugin {
+    name: &'static str,
+    packages: Option<Result<Vec<InstalledPackage>, &'static str>>,
+}
+
+#[async_trait]
+impl Plugin for FakePlugin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: self.name.to_string(),
+            description: String::new(),
+            actions: Vec::new(),
+            run_after: Vec::new(),
+        }
+    }
+
+    async fn check_available(&self, _config: &Config, _insights: &Insights) -> bool {
+        true
+    }
+
+    async fn update(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn list_installed(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        match &self.packages {
+            None => Ok(None),
+            Some(Ok(packages)) => Ok(Some(packages.clone())),
+            Some(Err(e)) => anyhow::bail!("{}", e),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_snapshot_skips_unsupported_and_failing_plugins() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    let logger = Logger::new(&config);
+
+    let mut registry = PluginRegistry::new();
+    registry.register(Box::new(FakePlugin {
+        name: "alpha",
+        packages: Some(Ok(vec![pkg("alpha", "a", "1", "alpha")])),
+    }));
+    registry.register(Box::new(FakePlugin {
+        name: "beta",
+        packages: None,
+    }));
+    registry.register(Box::new(FakePlugin {
+        name: "gamma",
+        packages: Some(Err("locked")),
+    }));
+    let plugins: Vec<String> = ["alpha", "beta", "gamma", "missing"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    let registry = Arc::new(registry);
+    let config = Arc::new(config);
+    let insights = Arc::new(Insights::new().expect("Failed to create Insights"));
+
+    let inventory = snapshot(
+        Arc::clone(&registry),
+        Arc::clone(&config),
+        Arc::clone(&insights),
+        &logger,
+        &plugins,
+    )
+    .await;
+    assert_eq!(inventory.keys().collect::<Vec<_>>(), ["alpha"]);
+    assert_eq!(inventory["alpha"], [pkg("alpha", "a", "1", "alpha")]);
+
+    let reports = list_installed(registry, config, insights, &logger, &plugins).await;
+    let listed: Vec<(&str, Option<&str>)> = reports
+        .iter()
+        .map(|r| (r.plugin.as_str(), r.error.as_deref()))
+        .collect();
+    assert_eq!(listed, [("alpha", None), ("gamma", Some("locked"))]);
+}
+
+#[test]
+fn test_render_inventory() {
+    assert_eq!("csv".parse(), Ok(InventoryFormat::Csv));
+    assert!(
+        "xml"
+            .parse::<InventoryFormat>()
+            .unwrap_err()
+            .contains("expected table, json or csv")
+    );
+
+    let reports = [
+        InventoryReport {
+            plugin: "npm".to_string(),
+            packages: vec![
+                pkg("npm", "typescript", "5.4.0", "npm")
+                    .with_path("/usr/lib/node_modules/typescript"),
+                pkg("npm", "@scope/a,b", "1.0", "npm"),
+            ],
+            error: None,
+        },
+        InventoryReport {
+            plugin: "brew".to_string(),
+            packages: vec![pkg("brew", "git", "2.44.0", "formula").with_scope(InstallScope::User)],
+            error: None,
+        },
+        InventoryReport {
+            plugin: "gem".to_string(),
+            packages: Vec::new(),
+            error: Some("gem failed".to_string()),
+        },
+    ];
+
+    let table = render_inventory(&reports, InventoryFormat::Table).expect("table renders");
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(
+        lines[0],
+        "Manager  Package     Version  Scope   Source   Path"
+    );
+    assert_eq!(lines[2], "brew     git         2.44.0   user    formula");
+    assert_eq!(lines[5], "gem: listing failed: gem failed");
+
+    let csv = render_inventory(&reports, InventoryFormat::Csv).expect("csv renders");
+    assert_eq!(
+        csv,
+        "manager,name,version,scope,source,path\n\
+         brew,git,2.44.0,user,formula,\n\
+         npm,\"@scope/a,b\",1.0,system,npm,\n\
+         npm,typescript,5.4.0,system,npm,/usr/lib/node_modules/typescript\n"
+    );
+
+    let json: serde_json::Value = serde_json::from_str(
+        &render_inventory(&reports, InventoryFormat::Json).expect("json renders"),
+    )
+    .expect("valid JSON");
+    assert_eq!(json.as_array().map(Vec::len), Some(3));
+    assert_eq!(json[0]["manager"], "brew");
+    assert_eq!(json[0]["scope"], "user");
+    assert!(json[0].get("path").is_none());
+    assert_eq!(json[2]["path"], "/usr/lib/node_modules/typescript");
+}
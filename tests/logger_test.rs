@@ -117,4 +117,25 @@ mod tests {
         assert!(log_path.exists());
         assert!(log_dir.exists());
     }
+
+    #[test]
+    fn test_log_with_prefix_shares_sink() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let log_path = temp_dir.path().join("test.log");
+
+        let mut config = Config::new("/tmp/test");
+        config.log = log_path.clone();
+        config.use_log = true;
+        config.datetime = false;
+
+        let mut logger = Logger::new(&config);
+        let mut brew_logger = logger.with_prefix("brew");
+        logger.log("Main message");
+        brew_logger.log("Plugin message");
+
+        assert_eq!(brew_logger.prefix(), Some("brew"));
+        let content = fs::read_to_string(&log_path).expect("Failed to read log file");
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines, vec!["Main message", "[brew] Plugin message"]);
+    }
 }
@@ -0,0 +1,317 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::insights::Insights;
+use updatehauler::logger::Logger;
+use updatehauler::outdated::{OutdatedReport, check_outdated, render_table};
+use updatehauler::plugins::{
+    OutdatedPackage, Plugin, PluginMetadata, PluginRegistry, brew, cargo, flatpak, gem, npm, os,
+    pip, snap, uv,
+};
+
+fn pkg(name: &str, installed: Option<&str>, available: &str) -> OutdatedPackage {
+    OutdatedPackage::new(name, installed, available)
+}
+
+#[test]
+fn test_parse_brew_outdated() {
+    let json = r#"{
+      "formulae": [{"name": "git", "installed_versions": ["2.43.0", "2.43.1"], "current_version": "2.44.0", "pinned": false}],
+      "casks": [{"name": "firefox", "installed_versions": "124.0", "current_version": "125.0"}]
+    }"#;
+    assert_eq!(
+        brew::parse_brew_outdated(json).expect("valid JSON"),
+        [
+            pkg("git", Some("2.43.1"), "2.44.0"),
+            pkg("firefox", Some("124.0"), "125.0")
+        ]
+    );
+    assert!(brew::parse_brew_outdated("").expect("empty").is_empty());
+    assert!(brew::parse_brew_outdated("not json").is_err());
+}
+
+#[test]
+fn test_parse_cargo_install_update() {
+    let table = "    Updating registry 'https://github.com/rust-lang/crates.io-index'
+
+Package       Installed  Latest   Needs update
+ripgrep       v13.0.0    v14.1.0  Yes
+bat           v0.24.0    v0.24.0  No
+";
+    assert_eq!(
+        cargo::parse_cargo_install_update(table),
+        [pkg("ripgrep", Some("13.0.0"), "14.1.0")]
+    );
+}
+
+#[test]
+fn test_parse_npm_and_pip_outdated() {
+    let npm_json = r#"{"typescript": {"current": "5.0.0", "wanted": "5.4.0", "latest": "5.4.0"},
+                       "eslint": {"wanted": "9.0.0", "latest": "9.0.0"}}"#;
+    assert_eq!(
+        npm::parse_npm_outdated(npm_json).expect("valid JSON"),
+        [
+            pkg("eslint", None, "9.0.0"),
+            pkg("typescript", Some("5.0.0"), "5.4.0")
+        ]
+    );
+
+    let pip_json = r#"[{"name": "requests", "version": "2.30.0", "latest_version": "2.31.0", "latest_filetype": "wheel"}]"#;
+    assert_eq!(
+        pip::parse_pip_outdated(pip_json).expect("valid JSON"),
+        [pkg("requests", Some("2.30.0"), "2.31.0")]
+    );
+}
+
+#[test]
+fn test_parse_line_based_managers() {
+    assert_eq!(
+        uv::parse_uv_tool_outdated("ruff v0.4.0 [latest: 0.5.0]\n- ruff\n"),
+        [pkg("ruff", Some("0.4.0"), "0.5.0")]
+    );
+    assert_eq!(
+        gem::parse_gem_outdated("rake (13.0.6 < 13.1.0)\nbundler (2.3.1, 2.4.0 < 2.5.0)\n"),
+        [
+            pkg("rake", Some("13.0.6"), "13.1.0"),
+            pkg("bundler", Some("2.4.0"), "2.5.0")
+        ]
+    );
+    assert_eq!(
+        flatpak::parse_flatpak_updates(
+            "org.gimp.GIMP\t2.10.38\tstable\norg.gnome.Platform\t\t46\n",
+            "org.gimp.GIMP\t2.10.36\norg.gnome.Platform\t\n",
+        ),
+        [
+            pkg("org.gimp.GIMP", Some("2.10.36"), "2.10.38"),
+            pkg("org.gnome.Platform", None, "46")
+        ]
+    );
+    assert_eq!(
+        snap::parse_snap_refresh_list(
+            "Name     Version  Rev   Size   Publisher  Notes\nfirefox  125.0    4173  260MB  mozilla    -\n",
+            "Name     Version  Rev   Tracking       Publisher  Notes\nfirefox  124.0    4100  latest/stable  mozilla    -\n",
+        ),
+        [pkg("firefox", Some("124.0"), "125.0")]
+    );
+}
+
+#[test]
+fn test_parse_os_managers() {
+    assert_eq!(
+        os::parse_apt_upgradable(
+            "Listing...\ncurl/jammy-updates 7.81.0-1ubuntu1.16 amd64 [upgradable from: 7.81.0-1ubuntu1.15]\n"
+        ),
+        [pkg(
+            "curl",
+            Some("7.81.0-1ubuntu1.15"),
+            "7.81.0-1ubuntu1.16"
+        )]
+    );
+    assert_eq!(
+        os::parse_dnf_check_update(
+            "\ncurl.x86_64   7.76.1-26.el9   baseos\nObsoleting Packages\ngrub2.x86_64  1:2.06-70.el9  baseos\n"
+        ),
+        [pkg("curl", None, "7.76.1-26.el9")]
+    );
+    assert_eq!(
+        os::parse_apk_version(
+            "Installed:                Available:\nca-certificates-bundle-20240226-r0 < 20240705-r0\n"
+        ),
+        [pkg(
+            "ca-certificates-bundle",
+            Some("20240226-r0"),
+            "20240705-r0"
+        )]
+    );
+    assert_eq!(
+        os::parse_pacman_updates("linux 6.1.1-1 -> 6.1.2-1\nvim 9.0-1 -> 9.1-1 [ignored]\n"),
+        [
+            pkg("linux", Some("6.1.1-1"), "6.1.2-1"),
+            pkg("vim", Some("9.0-1"), "9.1-1")
+        ]
+    );
+    assert_eq!(
+        os::parse_softwareupdate_list(
+            "Software Update found the following new or updated software:\n* Label: macOS Sonoma 14.2-23C64\n\tTitle: macOS Sonoma 14.2, Version: 14.2, Size: 1234K, Recommended: YES, Action: restart,\n"
+        ),
+        [pkg("macOS Sonoma 14.2-23C64", None, "14.2")]
+    );
+    assert_eq!(
+        os::parse_mas_outdated("497799835 Xcode (15.0 -> 15.1)\n"),
+        [pkg("Xcode", Some("15.0"), "15.1")]
+    );
+}
+
+#[test]
+fn test_render_table() {
+    let reports = [
+        OutdatedReport {
+            plugin: "brew".to_string(),
+            packages: vec![pkg("git", Some("2.43.0"), "2.44.0")],
+            error: None,
+        },
+        OutdatedReport {
+            plugin: "os".to_string(),
+            packages: vec![pkg("curl", None, "7.76.1")],
+            error: None,
+        },
+        OutdatedReport {
+            plugin: "npm".to_string(),
+            packages: Vec::new(),
+            error: Some("npm failed".to_string()),
+        },
+    ];
+    assert_eq!(
+        render_table(&reports),
+        "Plugin  Package  Installed  Available
+------  -------  ---------  ---------
+brew    git      2.43.0     2.44.0
+os      curl     ?          7.76.1
+npm: check failed: npm failed
+"
+    );
+    assert_eq!(render_table(&[]), "Everything is up to date\n");
+}
+
+struct FakePlugin {
+    name: &'static str,
+    outdated: Option<Result<Vec<OutdatedPackage>, &'static str>>,
+}
+
+#[async_trait]
+impl Plugin for FakePlugin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: self.name.to_string(),
+            description: String::new(),
+            actions: Vec::new(),
+            run_after: Vec::new(),
+        }
+    }
+
+    async fn check_available(&self, _config: &Config, _insights: &Insights) -> bool {
+        true
+    }
+
+    async fn update(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn check_outdated(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> Result<Option<Vec<OutdatedPackage>>> {
+        match &self.outdated {
+            None => Ok(None),
+            Some(Ok(packages)) => Ok(Some(packages.clone())),
+            Some(Err(e)) => anyhow::bail!("{}", e),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_check_outdated_collects_reports_in_order() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    let logger = Logger::new(&config);
+
+    let mut registry = PluginRegistry::new();
+    registry.register(Box::new(FakePlugin {
+        name: "alpha",
+        outdated: Some(Ok(vec![pkg("a", Some("1"), "2")])),
+    }));
+    registry.register(Box::new(FakePlugin {
+        name: "beta",
+        outdated: None,
+    }));
+    registry.register(Box::new(FakePlugin {
+        name: "gamma",
+        outdated: Some(Err("offline")),
+    }));
+    let plugins: Vec<String> = ["gamma", "beta", "alpha"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    let reports = check_outdated(
+        Arc::new(registry),
+        Arc::new(config),
+        Arc::new(Insights::new().expect("Failed to create Insights")),
+        &logger,
+        &plugins,
+    )
+    .await;
+
+    let names: Vec<&str> = reports.iter().map(|r| r.plugin.as_str()).collect();
+    assert_eq!(names, ["gamma", "alpha"]);
+    assert_eq!(reports[0].error.as_deref(), Some("offline"));
+    assert_eq!(reports[1].packages, [pkg("a", Some("1"), "2")]);
+
+    let json = serde_json::to_value(&reports).expect("reports serialize");
+    assert_eq!(json[1]["packages"][0]["available"], "2");
+    assert!(json[1].get("error").is_none());
+}
+
+#[test]
+fn test_parse_os_update_kinds() {
+    use os::{ClassifiedUpdate, UpdateKind};
+    use updatehauler::config::OsUpdateMode;
+
+    let apt = os::parse_apt_update_kinds(
+        "Listing...\n\
+         curl/jammy-updates,jammy-security 7.81.0-1ubuntu1.16 amd64 [upgradable from: 7.81.0-1ubuntu1.15]\n\
+         tzdata/jammy-updates 2024a-0ubuntu0.22.04 all [upgradable from: 2023c-0ubuntu0.22.04]\n\
+         code/stable 1.89.0-1754 amd64 [upgradable from: 1.88.1-1752]\n",
+    );
+    assert_eq!(
+        apt,
+        [
+            ClassifiedUpdate::new("curl", UpdateKind::Security),
+            ClassifiedUpdate::new("tzdata", UpdateKind::Bugfix),
+            ClassifiedUpdate::new("code", UpdateKind::Other),
+        ]
+    );
+    assert_eq!(
+        os::update_note(&apt, OsUpdateMode::Full),
+        "3 updates, 1 security"
+    );
+    assert_eq!(
+        os::update_note(&apt, OsUpdateMode::Minimal),
+        "2 updates, 1 security"
+    );
+    assert_eq!(
+        os::update_note(&apt, OsUpdateMode::Security),
+        "1 update, 1 security"
+    );
+
+    let check_update = "\ncurl.x86_64  8.6.0-1.fc40  updates\nglibc.x86_64  2.39-5.fc40  updates\ncode.x86_64  1.89.0-1754  code\n";
+    // dnf 4 and dnf 5 layouts; curl has both a bug fix and a security advisory
+    let dnf4 = "FEDORA-2024-a bugfix        curl-8.6.0-1.fc40.x86_64\n\
+                FEDORA-2024-b Moderate/Sec. curl-8.6.0-1.fc40.x86_64\n\
+                FEDORA-2024-c bugfix        glibc-2.39-5.fc40.x86_64\n";
+    let dnf5 = "Name          Type     Severity Package                   Issued\n\
+                FEDORA-2024-b security Moderate curl-8.6.0-1.fc40.x86_64 2024-02-01 00:00:00\n\
+                FEDORA-2024-c bugfix   None     glibc-2.39-5.fc40.x86_64 2024-02-02 00:00:00\n";
+    let expected = [
+        ClassifiedUpdate::new("curl", UpdateKind::Security),
+        ClassifiedUpdate::new("glibc", UpdateKind::Bugfix),
+        ClassifiedUpdate::new("code", UpdateKind::Other),
+    ];
+    assert_eq!(os::parse_dnf_update_kinds(check_update, dnf4), expected);
+    assert_eq!(os::parse_dnf_update_kinds(check_update, dnf5), expected);
+}
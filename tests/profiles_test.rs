@@ -0,0 +1,128 @@
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use updatehauler::config::Config;
+
+fn load(dir: &Path, yaml: &str) -> anyhow::Result<Config> {
+    let config_path = dir.join("config.yaml");
+    std::fs::write(&config_path, yaml).expect("Failed to write config file");
+    Config::load_from_yaml("/home/test", Some(&config_path))
+}
+
+const PROFILES: &str = r#"
+jobs: 6
+schedule:
+  hour: "2"
+profiles:
+  quick: [brew, npm]
+  weekend:
+    actions: [os, brew, brew-save, docker, nvim]
+    jobs: 1
+    timeout: 3600
+    debug: true
+    schedule:
+      hour: "9"
+      day_of_week: "6"
+"#;
+
+#[test]
+fn test_profiles_load_from_yaml() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = load(temp_dir.path(), PROFILES).expect("Failed to load");
+
+    assert_eq!(config.profiles.len(), 2);
+    assert_eq!(config.profiles["quick"].actions, ["brew", "npm"]);
+    assert_eq!(config.profiles["quick"].jobs, None);
+    assert_eq!(
+        config.profiles["weekend"].actions,
+        ["os", "brew", "brew-save", "docker", "nvim"]
+    );
+    assert_eq!(config.profiles["weekend"].jobs, Some(1));
+}
+
+#[test]
+fn test_apply_profile_overrides_config() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = load(temp_dir.path(), PROFILES).expect("Failed to load");
+
+    let actions = config.apply_profile("weekend").expect("profile exists");
+
+    assert_eq!(actions, ["os", "brew", "brew-save", "docker", "nvim"]);
+    assert_eq!(config.jobs, 1);
+    assert_eq!(config.timeouts.default, 3600);
+    assert!(config.debug);
+    assert_eq!(config.crontab_timing(), "0 9 * * 6");
+}
+
+#[test]
+fn test_apply_bare_profile_keeps_config() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = load(temp_dir.path(), PROFILES).expect("Failed to load");
+
+    let actions = config.apply_profile("quick").expect("profile exists");
+
+    assert_eq!(actions, ["brew", "npm"]);
+    assert_eq!(config.jobs, 6);
+    assert_eq!(config.crontab_timing(), "0 2 * * *");
+}
+
+#[test]
+fn test_apply_unknown_profile() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = load(temp_dir.path(), PROFILES).expect("Failed to load");
+    let err = config
+        .apply_profile("daily")
+        .expect_err("profile does not exist");
+    assert_eq!(
+        err.to_string(),
+        "Unknown profile: daily (valid: quick, weekend)"
+    );
+
+    let err = Config::new("/tmp")
+        .apply_profile("daily")
+        .expect_err("no profiles configured");
+    assert!(err.to_string().contains("no profiles configured"));
+}
+
+#[test]
+fn test_profiles_reject_invalid_entries() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    for (yaml, expected) in [
+        (
+            "profiles:\n  Quick: [brew]\n",
+            "invalid profile name 'Quick'",
+        ),
+        ("profiles:\n  quick: []\n", "profile 'quick' has no actions"),
+        (
+            "profiles:\n  quick:\n    actions: [brew]\n    jobs: 0\n",
+            "jobs must be at least 1",
+        ),
+        (
+            "profiles:\n  quick:\n    actions: [brew]\n    schedule:\n      hour: \"9;\"\n",
+            "invalid characters",
+        ),
+    ] {
+        let err = load(temp_dir.path(), yaml)
+            .err()
+            .expect("profile should be rejected");
+        assert!(
+            format!("{:#}", err).contains(expected),
+            "error for {:?} was {:#}",
+            yaml,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_crontab_entry_for_profile() {
+    let config = Config::new("/tmp");
+    let app = PathBuf::from("/usr/local/bin/updatehauler");
+
+    let entry = config.crontab_entry_for(&app, Some("weekend"));
+    assert!(entry.starts_with("0 2 * * * PATH="));
+    assert!(entry.ends_with("/usr/local/bin/updatehauler --profile weekend --logfile-only 2>&1"));
+    assert_eq!(
+        config.crontab_entry_for(&app, None),
+        config.crontab_entry(&app)
+    );
+}
@@ -0,0 +1,145 @@
+use std::path::Path;
+use tempfile::TempDir;
+use updatehauler::config::{AutoReboot, Config};
+use updatehauler::insights::Insights;
+use updatehauler::logger::Logger;
+use updatehauler::reboot::{
+    check_reboot, debian_reboot_required, installed_kernels, newer_kernel, parse_needs_restarting,
+    shutdown_args,
+};
+use updatehauler::runner::{ActionResult, ActionStatus};
+
+fn load(dir: &Path, yaml: &str) -> anyhow::Result<Config> {
+    let config_path = dir.join("config.yaml");
+    std::fs::write(&config_path, yaml).expect("Failed to write config file");
+    Config::load_from_yaml("/home/test", Some(&config_path))
+}
+
+#[test]
+fn test_debian_reboot_required() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let run_dir = temp_dir.path();
+    assert_eq!(debian_reboot_required(run_dir), None);
+
+    std::fs::write(
+        run_dir.join("reboot-required"),
+        "*** System restart required ***\n",
+    )
+    .expect("write");
+    assert_eq!(
+        debian_reboot_required(run_dir).as_deref(),
+        Some("reboot-required flag set")
+    );
+
+    std::fs::write(
+        run_dir.join("reboot-required.pkgs"),
+        "linux-image-6.1.0-18-amd64\nlibc6\nlinux-image-6.1.0-18-amd64\n",
+    )
+    .expect("write");
+    assert_eq!(
+        debian_reboot_required(run_dir).as_deref(),
+        Some("libc6 linux-image-6.1.0-18-amd64 updated")
+    );
+}
+
+#[test]
+fn test_parse_needs_restarting() {
+    assert!(parse_needs_restarting(
+        "Core libraries or services have been updated since boot-up:\n  * kernel\n\nReboot is required to fully utilize these updates.\n"
+    ));
+    assert!(!parse_needs_restarting(
+        "No core libraries or services have been updated since boot-up.\nReboot should not be necessary.\n"
+    ));
+}
+
+#[test]
+fn test_newer_kernel() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let modules = temp_dir.path();
+    for kernel in ["6.1.0-9-amd64", "6.1.0-18-amd64", "6.1.0-17-amd64"] {
+        std::fs::create_dir(modules.join(kernel)).expect("create dir");
+        std::fs::write(modules.join(kernel).join("modules.dep"), "").expect("write");
+    }
+    // Left behind by a removed kernel
+    std::fs::create_dir_all(modules.join("6.1.0-20-amd64/updates")).expect("create dir");
+
+    let mut installed = installed_kernels(modules);
+    installed.sort();
+    assert_eq!(
+        installed,
+        ["6.1.0-17-amd64", "6.1.0-18-amd64", "6.1.0-9-amd64"]
+    );
+    assert_eq!(
+        newer_kernel("6.1.0-17-amd64", &installed),
+        Some("6.1.0-18-amd64")
+    );
+    assert_eq!(newer_kernel("6.1.0-18-amd64", &installed), None);
+    let mainline = ["6.10.0-rc7".to_string(), "6.10.0".to_string()];
+    assert_eq!(newer_kernel("6.10.0-rc7", &mainline), Some("6.10.0"));
+    assert_eq!(newer_kernel("6.10.0", &mainline), None);
+    assert!(installed_kernels(&modules.join("missing")).is_empty());
+}
+
+#[test]
+fn test_auto_reboot_config() {
+    assert_eq!(AutoReboot::parse("never").unwrap(), AutoReboot::Never);
+    assert_eq!(
+        AutoReboot::parse("if_required").unwrap(),
+        AutoReboot::IfRequired
+    );
+    assert_eq!(
+        AutoReboot::parse("at 03:30").unwrap(),
+        AutoReboot::At("03:30".to_string())
+    );
+    for invalid in ["always", "at 3:30", "at 24:00", "at 03:60", "at"] {
+        assert!(AutoReboot::parse(invalid).is_err(), "{} accepted", invalid);
+    }
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/home/test");
+    assert_eq!(config.auto_reboot, AutoReboot::Never);
+    assert_eq!(shutdown_args(&config), None);
+
+    config = load(
+        temp_dir.path(),
+        "auto_reboot: if_required\nreboot_grace: 10\n",
+    )
+    .expect("Failed to load");
+    assert_eq!(
+        shutdown_args(&config).expect("reboots"),
+        [
+            "-r",
+            "+10",
+            "updatehauler: rebooting to finish installing updates"
+        ]
+    );
+
+    config = load(temp_dir.path(), "auto_reboot: at 03:30\n").expect("Failed to load");
+    assert_eq!(shutdown_args(&config).expect("reboots")[1], "03:30");
+
+    let err = load(
+        temp_dir.path(),
+        "auto_reboot: if_required\nreboot_grace: 0\n",
+    )
+    .err()
+    .expect("zero grace should be rejected");
+    assert!(err.to_string().contains("at least 1 minute"));
+}
+
+#[tokio::test]
+async fn test_check_reboot_needs_os_update() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    let logger = Logger::new(&config);
+    let insights = Insights::new().expect("Failed to create Insights");
+
+    let mut results = vec![
+        ActionResult::new("brew", ActionStatus::Ok),
+        ActionResult::new("os", ActionStatus::Skipped),
+    ];
+    assert!(!check_reboot(&config, &insights, &logger, &mut results).await);
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(|r| r.notes.is_empty()));
+}
@@ -0,0 +1,208 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::insights::Insights;
+use updatehauler::logger::Logger;
+use updatehauler::plugins::{
+    ErrorPolicy, PluginRegistry, note_action, record_commands, register_custom_plugins, run_cmd,
+};
+use updatehauler::report::{OutputFormat, RunReport};
+use updatehauler::runner::{ActionResult, ActionStatus, ExecutionGraph, record_action};
+
+fn load(dir: &Path, yaml: &str) -> Config {
+    let config_path = dir.join("config.yaml");
+    std::fs::write(&config_path, yaml).expect("Failed to write config file");
+    let mut config =
+        Config::load_from_yaml("/home/test", Some(&config_path)).expect("Failed to load");
+    config.log = dir.join("test.log");
+    config.use_log = true;
+    config
+}
+
+const PLUGINS: &str = r#"
+custom_plugins:
+  - name: acme
+    update: [echo acme, "true"]
+    save: [echo saved]
+    actions:
+      clean: [echo clean]
+  - name: beta
+    update: ["false"]
+"#;
+
+fn registry(config: &Config) -> PluginRegistry<'static> {
+    let mut registry = PluginRegistry::new();
+    register_custom_plugins(&mut registry, config).expect("Failed to register");
+    registry
+}
+
+#[test]
+fn test_output_format_from_str() {
+    assert_eq!("json".parse(), Ok(OutputFormat::Json));
+    assert_eq!("yaml".parse(), Ok(OutputFormat::Yaml));
+    assert!(
+        "xml"
+            .parse::<OutputFormat>()
+            .unwrap_err()
+            .contains("expected json or yaml")
+    );
+}
+
+#[tokio::test]
+async fn test_record_commands_captures_exit_codes() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    let mut logger = Logger::new(&config);
+
+    let ((), records) = record_commands(async {
+        let _ = run_cmd(&config, &mut logger, ErrorPolicy::Ignore, "echo", &["ok"]).await;
+        let _ = run_cmd(
+            &config,
+            &mut logger,
+            ErrorPolicy::Ignore,
+            "sh",
+            &["-c", "exit 3"],
+        )
+        .await;
+    })
+    .await;
+
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].command, "echo ok");
+    assert_eq!(records[0].exit_code, Some(0));
+    assert_eq!(records[1].exit_code, Some(3));
+    assert!(!records[0].dry_run);
+
+    config.dry_run = true;
+    let (_, records) = record_commands(run_cmd(
+        &config,
+        &mut logger,
+        ErrorPolicy::Fail,
+        "echo",
+        &["hi"],
+    ))
+    .await;
+    assert_eq!(records.len(), 1);
+    assert!(records[0].dry_run);
+    assert_eq!(records[0].command, "echo hi");
+}
+
+#[tokio::test]
+async fn test_record_action_keeps_notes() {
+    let result = record_action("os", "os", async {
+        note_action("3 updates, 1 security");
+        Ok(())
+    })
+    .await;
+    assert_eq!(result.status, ActionStatus::Ok);
+    assert_eq!(result.notes, ["3 updates, 1 security"]);
+
+    // Outside a recorded action notes are dropped
+    note_action("ignored");
+}
+
+#[tokio::test]
+async fn test_execute_records_commands_per_action() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = load(temp_dir.path(), PLUGINS);
+    let registry = registry(&config);
+    let actions: Vec<String> = ["acme", "beta"].iter().map(|s| s.to_string()).collect();
+    let graph = ExecutionGraph::build(&registry, &actions).expect("graph should build");
+    let logger = Logger::new(&config);
+
+    let results = graph
+        .execute(
+            Arc::new(registry),
+            Arc::new(config),
+            Arc::new(Insights::new().expect("Failed to create Insights")),
+            &logger,
+            1,
+        )
+        .await;
+
+    let acme = results
+        .iter()
+        .find(|r| r.action == "acme")
+        .expect("acme ran");
+    assert_eq!(acme.status, ActionStatus::Ok);
+    assert_eq!(acme.commands.len(), 2);
+    assert_eq!(acme.commands[1].exit_code, Some(0));
+    assert!(acme.error.is_none());
+
+    let beta = results
+        .iter()
+        .find(|r| r.action == "beta")
+        .expect("beta ran");
+    assert_eq!(beta.status, ActionStatus::Failed);
+    assert_eq!(beta.commands.len(), 1);
+    assert_eq!(beta.commands[0].exit_code, Some(1));
+    assert!(beta.error.is_some());
+}
+
+#[test]
+fn test_run_report_classifies_actions() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = load(temp_dir.path(), PLUGINS);
+    let registry = registry(&config);
+    let insights = Insights::new().expect("Failed to create Insights");
+
+    let mut updated = ActionResult::new("acme", ActionStatus::Ok);
+    updated.notes = vec!["2 updates, 1 security".to_string()];
+    let mut failed = ActionResult::new("beta", ActionStatus::Failed);
+    failed.error = Some("false failed".to_string());
+    failed.duration = Duration::from_millis(1500);
+    let results = [
+        ActionResult::new("pre_run", ActionStatus::Ok),
+        ActionResult::new("pre_acme", ActionStatus::Ok),
+        updated,
+        ActionResult::new("acme-save", ActionStatus::Ok),
+        ActionResult::new("acme-clean", ActionStatus::Ok),
+        failed,
+        ActionResult::new("trim-logfile", ActionStatus::Ok),
+    ];
+
+    let started = chrono::Local::now();
+    let finished = started + chrono::Duration::seconds(3);
+    let report = RunReport::new(&registry, &insights, started, finished, false, &results);
+
+    let kinds: Vec<(Option<&str>, &str)> = report
+        .actions
+        .iter()
+        .map(|a| (a.plugin.as_deref(), a.action_type.as_str()))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (None, "hook"),
+            (Some("acme"), "hook"),
+            (Some("acme"), "update"),
+            (Some("acme"), "save"),
+            (Some("acme"), "custom"),
+            (Some("beta"), "update"),
+            (None, "builtin"),
+        ]
+    );
+    assert_eq!(report.status, "FAIL");
+    assert_eq!((report.succeeded, report.failed), (6, 1));
+    assert_eq!(report.duration_secs, 3.0);
+    assert_eq!(report.host.hostname, insights.hostname);
+
+    let json: serde_json::Value =
+        serde_json::from_str(&report.render(OutputFormat::Json).expect("json renders"))
+            .expect("valid JSON");
+    assert_eq!(json["actions"][5]["type"], "update");
+    assert_eq!(json["actions"][5]["error"], "false failed");
+    assert_eq!(json["actions"][5]["duration_secs"], 1.5);
+    assert!(json["actions"][0].get("error").is_none());
+    assert_eq!(json["actions"][2]["notes"][0], "2 updates, 1 security");
+    assert!(json["actions"][0].get("notes").is_none());
+    assert_eq!(json["host"]["os"], insights.os.as_str());
+
+    let yaml = report.render(OutputFormat::Yaml).expect("yaml renders");
+    assert!(yaml.contains("status: FAIL\n"));
+    assert!(yaml.contains("- action: acme-clean\n"));
+}
@@ -0,0 +1,280 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::insights::Insights;
+use updatehauler::logger::Logger;
+use updatehauler::plugins::{InstalledPackage, Plugin, PluginMetadata, PluginRegistry, brew, pip};
+use updatehauler::restore_plan::{
+    PlanEntry, PlanReport, PlanStatus, plan, plan_restore, render_plan,
+};
+
+fn pkg(manager: &str, name: &str, version: &str) -> InstalledPackage {
+    InstalledPackage::new(manager, name, version, manager)
+}
+
+#[test]
+fn test_parse_pip_freeze() {
+    let freeze = "# generated by pip freeze
+requests==2.31.0
+Flask[async]==3.0.0 ; python_version >= \"3.8\"
+--index-url https://pypi.org/simple
+-e git+https://example.com/repo.git#egg=local
+mypkg @ file:///tmp/mypkg
+urllib3>=2
+";
+    assert_eq!(
+        pip::parse_pip_freeze(freeze),
+        [
+            pkg("pip", "requests", "2.31.0"),
+            pkg("pip", "Flask", "3.0.0")
+        ]
+    );
+    assert_eq!(
+        pip::unpinned_requirements(freeze),
+        [
+            "-e git+https://example.com/repo.git#egg=local",
+            "mypkg @ file:///tmp/mypkg",
+            "urllib3>=2"
+        ]
+    );
+}
+
+#[test]
+fn test_pip_plan_keeps_tooling_and_unpinned_requirements() {
+    let freeze = "requests==2.31.0
+-e git+https://example.com/repo.git@abc123#egg=local
+mypkg @ file:///tmp/mypkg
+urllib3>=2
+";
+    let saved = pip::parse_pip_requirements(freeze);
+    assert_eq!(
+        saved,
+        [
+            pkg("pip", "requests", "2.31.0"),
+            InstalledPackage::new(
+                "pip",
+                "local",
+                "",
+                "-e git+https://example.com/repo.git@abc123#egg=local"
+            ),
+            InstalledPackage::new("pip", "mypkg", "", "mypkg @ file:///tmp/mypkg"),
+            InstalledPackage::new("pip", "urllib3", "", "urllib3>=2"),
+        ]
+    );
+
+    let installed = [
+        pkg("pip", "requests", "2.31.0"),
+        pkg("pip", "local", "0.1.0"),
+        pkg("pip", "mypkg", "1.0"),
+        pkg("pip", "urllib3", "2.2.1"),
+        pkg("pip", "pip", "24.0"),
+        pkg("pip", "setuptools", "69.0.0"),
+        pkg("pip", "wheel", "0.43.0"),
+        pkg("pip", "left-pad", "1.0"),
+    ];
+    let entries = plan("pip", &saved, &installed);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name, "left-pad");
+    assert_eq!(entries[0].status, PlanStatus::Extra);
+}
+
+#[test]
+fn test_parse_brewfile_packages_and_subset() {
+    let brewfile = r#"tap "owner/tools"
+brew "git"
+brew "owner/tools/widget", args: ["with-extras"]
+cask "firefox"
+mas "Xcode", id: 497799835
+"#;
+    let formula = |name: &str| InstalledPackage::new("brew", name, "", "formula");
+    assert_eq!(
+        brew::parse_brewfile_packages(brewfile),
+        [
+            formula("git"),
+            formula("widget"),
+            InstalledPackage::new("brew", "firefox", "", "cask")
+        ]
+    );
+    assert_eq!(
+        brew::brewfile_subset(brewfile, &[formula("widget"), formula("firefox")]),
+        "tap \"owner/tools\"\nbrew \"owner/tools/widget\", args: [\"with-extras\"]\n"
+    );
+}
+
+#[test]
+fn test_plan_reports_missing_extra_and_mismatched() {
+    let saved = [
+        pkg("npm", "typescript", "5.4.0"),
+        pkg("npm", "prettier", "3.2.0"),
+        pkg("npm", "eslint", "8.57.0"),
+    ];
+    let installed = [
+        pkg("npm", "typescript", "5.4.0"),
+        pkg("npm", "eslint", "9.0.0"),
+        pkg("npm", "npm", "10.5.0"),
+    ];
+    let entry = |name: &str, status, saved: Option<&str>, installed: Option<&str>| PlanEntry {
+        plugin: "npm".to_string(),
+        name: name.to_string(),
+        status,
+        saved: saved.map(str::to_string),
+        installed: installed.map(str::to_string),
+    };
+    assert_eq!(
+        plan("npm", &saved, &installed),
+        [
+            entry(
+                "eslint",
+                PlanStatus::Mismatch,
+                Some("8.57.0"),
+                Some("9.0.0")
+            ),
+            entry("npm", PlanStatus::Extra, None, Some("10.5.0")),
+            entry("prettier", PlanStatus::Missing, Some("3.2.0"), None),
+        ]
+    );
+    assert!(plan("npm", &saved, &saved).is_empty());
+}
+
+#[test]
+fn test_plan_normalizes_pip_names_and_ignores_unversioned_entries() {
+    let saved = [pkg("pip", "typing_extensions", "4.9.0")];
+    let installed = [pkg("pip", "Typing-Extensions", "4.9.0")];
+    assert!(plan("pip", &saved, &installed).is_empty());
+
+    // Without a saved version only a missing package is a difference
+    let saved = [pkg("brew", "git", "")];
+    let installed = [pkg("brew", "git", "2.44.0")];
+    assert!(plan("brew", &saved, &installed).is_empty());
+}
+
+#[test]
+fn test_render_plan() {
+    let reports = [
+        PlanReport {
+            plugin: "uv".to_string(),
+            entries: plan("uv", &[pkg("uv", "ruff", "0.4.0")], &[]),
+            error: None,
+            saved: Vec::new(),
+            installed: Vec::new(),
+        },
+        PlanReport {
+            plugin: "pip".to_string(),
+            entries: Vec::new(),
+            error: Some("pip not found".to_string()),
+            saved: Vec::new(),
+            installed: Vec::new(),
+        },
+    ];
+    assert_eq!(
+        render_plan(&reports),
+        "Plugin  Package  Status   Saved  Installed
+------  -------  -------  -----  ---------
+uv      ruff     missing  0.4.0  -
+pip: plan failed: pip not found
+"
+    );
+    assert_eq!(
+        render_plan(&[]),
+        "Installed packages match the save files\n"
+    );
+}
+
+struct FakePlugin {
+    name: &'static str,
+    saved: Option<Result<Vec<InstalledPackage>, &'static str>>,
+    installed: Vec<InstalledPackage>,
+}
+
+#[async_trait]
+impl Plugin for FakePlugin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: self.name.to_string(),
+            description: String::new(),
+            actions: Vec::new(),
+            run_after: Vec::new(),
+        }
+    }
+
+    async fn check_available(&self, _config: &Config, _insights: &Insights) -> bool {
+        true
+    }
+
+    async fn update(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn saved_packages(&self, _config: &Config) -> Result<Option<Vec<InstalledPackage>>> {
+        match &self.saved {
+            None => Ok(None),
+            Some(Ok(packages)) => Ok(Some(packages.clone())),
+            Some(Err(e)) => anyhow::bail!("{}", e),
+        }
+    }
+
+    async fn list_installed(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        Ok(Some(self.installed.clone()))
+    }
+}
+
+#[tokio::test]
+async fn test_plan_restore_collects_reports_in_order() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    let logger = Logger::new(&config);
+
+    let mut registry = PluginRegistry::new();
+    registry.register(Box::new(FakePlugin {
+        name: "alpha",
+        saved: Some(Ok(vec![pkg("alpha", "a", "1")])),
+        installed: vec![pkg("alpha", "a", "2")],
+    }));
+    registry.register(Box::new(FakePlugin {
+        name: "beta",
+        saved: None,
+        installed: Vec::new(),
+    }));
+    registry.register(Box::new(FakePlugin {
+        name: "gamma",
+        saved: Some(Err("no save file")),
+        installed: Vec::new(),
+    }));
+    let plugins: Vec<String> = ["gamma", "beta", "alpha"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    let reports = plan_restore(
+        Arc::new(registry),
+        Arc::new(config),
+        Arc::new(Insights::new().expect("Failed to create Insights")),
+        &logger,
+        &plugins,
+    )
+    .await;
+
+    let names: Vec<&str> = reports.iter().map(|r| r.plugin.as_str()).collect();
+    assert_eq!(names, ["gamma", "alpha"]);
+    assert_eq!(reports[0].error.as_deref(), Some("no save file"));
+    assert_eq!(reports[1].entries.len(), 1);
+    assert_eq!(reports[1].entries[0].status, PlanStatus::Mismatch);
+}
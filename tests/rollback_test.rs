@@ -0,0 +1,283 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{Local, TimeZone};
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::history::{History, HistoryEntry};
+use updatehauler::insights::Insights;
+use updatehauler::inventory::{ChangeKind, PackageChange};
+use updatehauler::logger::Logger;
+use updatehauler::plugins::{InstalledPackage, Plugin, PluginMetadata, PluginRegistry};
+use updatehauler::report::RunReport;
+use updatehauler::rollback::{
+    apply_rollback, last_run, plan_rollback, previous_versions, render_rollback, rollback_changes,
+};
+use updatehauler::runner::{ActionResult, ActionStatus};
+
+fn change(
+    plugin: &str,
+    name: &str,
+    kind: ChangeKind,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> PackageChange {
+    PackageChange {
+        plugin: plugin.to_string(),
+        name: name.to_string(),
+        source: plugin.to_string(),
+        kind,
+        from: from.map(str::to_string),
+        to: to.map(str::to_string),
+    }
+}
+
+/// A run started at `hour`:00 with the given actions and package changes
+fn entry(hour: u32, dry_run: bool, actions: &[&str], changes: Vec<PackageChange>) -> HistoryEntry {
+    let started = Local
+        .with_ymd_and_hms(2025, 1, 31, hour, 0, 0)
+        .earliest()
+        .expect("valid time");
+    let results: Vec<ActionResult> = actions
+        .iter()
+        .map(|a| ActionResult::new(a, ActionStatus::Ok))
+        .collect();
+    let mut report = RunReport::new(
+        &PluginRegistry::new(),
+        &Insights::new().expect("Failed to create Insights"),
+        started,
+        started + chrono::Duration::seconds(90),
+        dry_run,
+        &results,
+    );
+    report.changes = changes;
+    HistoryEntry::new(report, None, vec!["updatehauler".to_string()], None)
+}
+
+fn npm_upgrade() -> Vec<PackageChange> {
+    vec![
+        change(
+            "npm",
+            "typescript",
+            ChangeKind::Upgraded,
+            Some("5.4.5"),
+            Some("5.5.2"),
+        ),
+        change("npm", "tsx", ChangeKind::Added, None, Some("4.0.0")),
+        change("npm", "left-pad", ChangeKind::Removed, Some("1.3.0"), None),
+        change(
+            "uv",
+            "ruff",
+            ChangeKind::Upgraded,
+            Some("0.4.0"),
+            Some("0.5.0"),
+        ),
+    ]
+}
+
+#[test]
+fn test_rollback_changes_skip_added_packages() {
+    let run = entry(2, false, &["npm", "uv"], npm_upgrade());
+    let names: Vec<&str> = rollback_changes(&run, &[])
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+    assert_eq!(names, ["typescript", "left-pad", "ruff"]);
+
+    let names: Vec<&str> = rollback_changes(&run, &["uv".to_string()])
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+    assert_eq!(names, ["ruff"]);
+}
+
+#[test]
+fn test_last_run_skips_dry_runs_and_rollbacks() {
+    let entries = [
+        entry(1, false, &["npm", "uv"], npm_upgrade()),
+        entry(2, true, &["npm"], npm_upgrade()),
+        entry(
+            3,
+            false,
+            &["npm-rollback"],
+            vec![change(
+                "npm",
+                "typescript",
+                ChangeKind::Downgraded,
+                Some("5.5.2"),
+                Some("5.4.5"),
+            )],
+        ),
+        entry(
+            4,
+            false,
+            &["npm"],
+            vec![change("npm", "tsx", ChangeKind::Added, None, Some("4.0.0"))],
+        ),
+    ];
+    let run = last_run(&entries, &[]).expect("a run to roll back");
+    assert_eq!(run.report.started_at, entries[0].report.started_at);
+    assert!(last_run(&entries, &["cargo".to_string()]).is_none());
+}
+
+#[test]
+fn test_previous_versions_and_render() {
+    let run = entry(2, false, &["npm", "uv"], npm_upgrade());
+    let changes = rollback_changes(&run, &[]);
+    let versions = previous_versions(&changes);
+    assert_eq!(
+        versions,
+        [
+            (
+                "npm".to_string(),
+                vec![
+                    InstalledPackage::new("npm", "typescript", "5.4.5", "npm"),
+                    InstalledPackage::new("npm", "left-pad", "1.3.0", "npm"),
+                ]
+            ),
+            (
+                "uv".to_string(),
+                vec![InstalledPackage::new("uv", "ruff", "0.4.0", "uv")]
+            ),
+        ]
+    );
+    assert_eq!(
+        render_rollback(&changes),
+        [
+            "npm: typescript 5.5.2 → 5.4.5, left-pad (removed) → 1.3.0",
+            "uv: ruff 0.5.0 → 0.4.0"
+        ]
+    );
+}
+
+#[test]
+fn test_plan_rollback_reads_the_history() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    config.state_dir = temp_dir.path().join("state");
+    let mut logger = Logger::new(&config);
+    let history = History::new(&config);
+
+    assert!(plan_rollback(&config, &mut logger, None, &[]).is_err());
+
+    let mut upgrade = entry(1, false, &["npm", "uv"], npm_upgrade());
+    let mut added = entry(
+        2,
+        false,
+        &["npm"],
+        vec![change("npm", "tsx", ChangeKind::Added, None, Some("4.0.0"))],
+    );
+    history.record(&mut upgrade).expect("record run");
+    history.record(&mut added).expect("record run");
+
+    let versions =
+        plan_rollback(&config, &mut logger, None, &["uv".to_string()]).expect("rollback plan");
+    assert_eq!(
+        versions,
+        [(
+            "uv".to_string(),
+            vec![InstalledPackage::new("uv", "ruff", "0.4.0", "uv")]
+        )]
+    );
+    let nothing = plan_rollback(&config, &mut logger, Some(&added.id), &[]).expect("found run");
+    assert!(nothing.is_empty());
+
+    let log = std::fs::read_to_string(&config.log).expect("Failed to read log");
+    assert!(log.contains(&format!("Rolling back run {}", upgrade.id)));
+    assert!(log.contains("nothing to roll back"));
+}
+
+struct FakePlugin {
+    name: &'static str,
+    installs_versions: bool,
+}
+
+#[async_trait]
+impl Plugin for FakePlugin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: self.name.to_string(),
+            description: String::new(),
+            actions: Vec::new(),
+            run_after: Vec::new(),
+        }
+    }
+
+    async fn check_available(&self, _config: &Config, _insights: &Insights) -> bool {
+        true
+    }
+
+    async fn update(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn install_versions(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+        _packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        Ok(self.installs_versions)
+    }
+}
+
+#[tokio::test]
+async fn test_apply_rollback_reports_plugins_that_cannot_downgrade() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    let logger = Logger::new(&config);
+
+    let mut registry = PluginRegistry::new();
+    registry.register(Box::new(FakePlugin {
+        name: "alpha",
+        installs_versions: true,
+    }));
+    registry.register(Box::new(FakePlugin {
+        name: "beta",
+        installs_versions: false,
+    }));
+    let versions = vec![
+        (
+            "alpha".to_string(),
+            vec![InstalledPackage::new("alpha", "a", "1.0", "alpha")],
+        ),
+        (
+            "beta".to_string(),
+            vec![
+                InstalledPackage::new("beta", "b", "2.0", "beta"),
+                InstalledPackage::new("beta", "c", "3.1", "beta"),
+            ],
+        ),
+    ];
+
+    let results = apply_rollback(
+        &registry,
+        &config,
+        &Insights::new().expect("Failed to create Insights"),
+        &logger,
+        &versions,
+    )
+    .await;
+
+    assert_eq!(results[0].action, "alpha-rollback");
+    assert_eq!(results[0].status, ActionStatus::Ok);
+    assert_eq!(results[1].action, "beta-rollback");
+    assert_eq!(results[1].status, ActionStatus::Failed);
+    assert_eq!(
+        results[1].error.as_deref(),
+        Some("beta cannot install previous versions; reinstall b 2.0, c 3.1 by hand")
+    );
+}
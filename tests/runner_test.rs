@@ -0,0 +1,305 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::insights::Insights;
+use updatehauler::logger::Logger;
+use updatehauler::plugins::{
+    BrewPlugin, CargoPlugin, NpmPlugin, OsPlugin, Plugin, PluginAction, PluginActionType,
+    PluginMetadata, PluginRegistry, RunPlugin, RustupPlugin,
+};
+use updatehauler::runner::{ActionStatus, ExecutionGraph};
+
+fn actions(names: &[&str]) -> Vec<String> {
+    names.iter().map(|s| s.to_string()).collect()
+}
+
+fn create_registry() -> PluginRegistry<'static> {
+    let mut registry = PluginRegistry::new();
+    registry.register(Box::new(BrewPlugin));
+    registry.register(Box::new(CargoPlugin));
+    registry.register(Box::new(NpmPlugin));
+    registry.register(Box::new(OsPlugin));
+    registry.register(Box::new(RunPlugin));
+    registry.register(Box::new(RustupPlugin));
+    registry
+}
+
+struct CyclicPlugin {
+    name: &'static str,
+    after: &'static str,
+}
+
+#[async_trait::async_trait]
+impl Plugin for CyclicPlugin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: self.name.to_string(),
+            description: "test".to_string(),
+            actions: vec![PluginAction {
+                name: self.name.to_string(),
+                description: "test".to_string(),
+                action_type: Some(PluginActionType::Update),
+            }],
+            run_after: vec![self.after.to_string()],
+        }
+    }
+
+    async fn check_available(&self, _config: &Config, _insights: &Insights) -> bool {
+        true
+    }
+
+    async fn update(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Records when its update starts and ends
+struct TimedPlugin {
+    name: &'static str,
+    exclusive: bool,
+    events: Arc<Mutex<Vec<(&'static str, bool)>>>,
+}
+
+#[async_trait::async_trait]
+impl Plugin for TimedPlugin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: self.name.to_string(),
+            description: "test".to_string(),
+            actions: vec![PluginAction {
+                name: self.name.to_string(),
+                description: "test".to_string(),
+                action_type: Some(PluginActionType::Update),
+            }],
+            run_after: Vec::new(),
+        }
+    }
+
+    async fn check_available(&self, _config: &Config, _insights: &Insights) -> bool {
+        true
+    }
+
+    fn exclusive(&self) -> bool {
+        self.exclusive
+    }
+
+    async fn update(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> anyhow::Result<()> {
+        self.events.lock().unwrap().push((self.name, true));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        self.events.lock().unwrap().push((self.name, false));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_graph_groups_actions_by_plugin() {
+    let registry = create_registry();
+    let graph = ExecutionGraph::build(&registry, &actions(&["brew", "npm", "brew-save"]))
+        .expect("graph should build");
+
+    let nodes = graph.nodes();
+    assert_eq!(nodes.len(), 2);
+    assert_eq!(nodes[0].name, "brew");
+    assert_eq!(nodes[0].actions, vec!["brew", "brew-save"]);
+    assert_eq!(nodes[1].name, "npm");
+}
+
+#[test]
+fn test_graph_orders_declared_dependencies() {
+    let registry = create_registry();
+    let graph = ExecutionGraph::build(
+        &registry,
+        &actions(&["cargo", "cargo-save", "brew", "rustup", "os"]),
+    )
+    .expect("graph should build");
+
+    let order = graph.topological_order().unwrap();
+    let pos = |name: &str| order.iter().position(|n| *n == name).unwrap();
+    assert!(pos("os") < pos("brew"));
+    assert!(pos("rustup") < pos("cargo"));
+}
+
+#[test]
+fn test_graph_ignores_dependencies_not_in_run() {
+    let registry = create_registry();
+    let graph =
+        ExecutionGraph::build(&registry, &actions(&["brew", "cargo"])).expect("graph should build");
+
+    assert!(graph.nodes().iter().all(|n| n.depends_on.is_empty()));
+}
+
+#[test]
+fn test_graph_detects_cycles() {
+    let mut registry = PluginRegistry::new();
+    registry.register(Box::new(CyclicPlugin {
+        name: "alpha",
+        after: "beta",
+    }));
+    registry.register(Box::new(CyclicPlugin {
+        name: "beta",
+        after: "alpha",
+    }));
+
+    let result = ExecutionGraph::build(&registry, &actions(&["alpha", "beta"]));
+    assert!(result.is_err());
+    assert!(
+        result
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("Dependency cycle")
+    );
+}
+
+#[tokio::test]
+async fn test_execute_returns_results_in_request_order() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.dry_run = true;
+    config.use_log = true;
+    config.log = temp_dir.path().join("test.log");
+
+    let registry = Arc::new(create_registry());
+    let requested = actions(&["npm", "not-a-plugin", "run"]);
+    let graph = ExecutionGraph::build(&registry, &requested).expect("graph should build");
+
+    let logger = Logger::new(&config);
+    let results = graph
+        .execute(
+            registry,
+            Arc::new(config),
+            Arc::new(Insights::new().expect("Failed to create Insights")),
+            &logger,
+            4,
+        )
+        .await;
+
+    let names: Vec<&str> = results.iter().map(|r| r.action.as_str()).collect();
+    assert_eq!(names, vec!["npm", "not-a-plugin", "run"]);
+    assert_eq!(results[0].status, ActionStatus::Ok);
+    assert_eq!(results[1].status, ActionStatus::Failed);
+    assert_eq!(results[2].status, ActionStatus::Ok);
+}
+
+#[tokio::test]
+async fn test_execute_runs_exclusive_plugins_alone() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.use_log = true;
+    config.log = temp_dir.path().join("test.log");
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut registry = PluginRegistry::new();
+    for (name, exclusive) in [
+        ("alpha", false),
+        ("os", true),
+        ("beta", false),
+        ("snap", true),
+    ] {
+        registry.register(Box::new(TimedPlugin {
+            name,
+            exclusive,
+            events: Arc::clone(&events),
+        }));
+    }
+    let registry = Arc::new(registry);
+    let graph = ExecutionGraph::build(&registry, &actions(&["alpha", "os", "beta", "snap"]))
+        .expect("graph should build");
+    assert_eq!(
+        graph
+            .nodes()
+            .iter()
+            .map(|n| n.exclusive)
+            .collect::<Vec<_>>(),
+        [false, true, false, true]
+    );
+
+    let logger = Logger::new(&config);
+    let results = graph
+        .execute(
+            registry,
+            Arc::new(config),
+            Arc::new(Insights::new().expect("Failed to create Insights")),
+            &logger,
+            4,
+        )
+        .await;
+    assert!(results.iter().all(|r| r.status == ActionStatus::Ok));
+
+    // An exclusive plugin starts with nothing running and ends before anything
+    // else starts
+    let events = events.lock().unwrap();
+    let mut running = Vec::new();
+    for &(name, started) in events.iter() {
+        if started {
+            if name == "os" || name == "snap" {
+                assert!(running.is_empty(), "{} started next to {:?}", name, running);
+            } else {
+                assert!(
+                    !running.contains(&"os") && !running.contains(&"snap"),
+                    "{} started next to {:?}",
+                    name,
+                    running
+                );
+            }
+            running.push(name);
+        } else {
+            running.retain(|n| *n != name);
+        }
+    }
+    assert_eq!(events.len(), 8);
+}
+
+#[test]
+fn test_action_status_from_error() {
+    use anyhow::Context;
+    use std::time::Duration;
+    use updatehauler::plugins::{CommandFailed, CommandOutcome, Interruption};
+
+    let outcome = |interrupted| CommandOutcome {
+        command: "snap refresh".to_string(),
+        exit_code: None,
+        duration: Duration::from_secs(1),
+        stdout: String::new(),
+        stderr: String::new(),
+        dry_run: false,
+        interrupted,
+        attempts: 1,
+    };
+
+    let timed_out: anyhow::Result<()> = Err(CommandFailed(outcome(Some(Interruption::TimedOut(
+        Duration::from_secs(1),
+    ))))
+    .into());
+    let timed_out = timed_out.context("snap update failed").unwrap_err();
+    assert_eq!(ActionStatus::from_error(&timed_out), ActionStatus::TimedOut);
+
+    let cancelled = CommandFailed(outcome(Some(Interruption::Cancelled))).into();
+    assert_eq!(
+        ActionStatus::from_error(&cancelled),
+        ActionStatus::Cancelled
+    );
+
+    let failed = anyhow::anyhow!("plain failure");
+    assert_eq!(ActionStatus::from_error(&failed), ActionStatus::Failed);
+    assert_eq!(ActionStatus::TimedOut.as_str(), "TIMEOUT");
+}
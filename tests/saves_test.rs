@@ -0,0 +1,148 @@
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use updatehauler::config::{Config, SaveRepo};
+use updatehauler::logger::Logger;
+use updatehauler::saves::{
+    ChangedFile, checkout_saves, commit_message, open_save_repo, parse_name_status, record_saves,
+    saves_at, tracked_path,
+};
+
+fn test_config(temp_dir: &TempDir) -> Config {
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    config
+}
+
+#[test]
+fn test_save_repo_config() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config_path = temp_dir.path().join("config.yaml");
+    let load = |yaml: &str| {
+        std::fs::write(&config_path, yaml).expect("Failed to write config file");
+        Config::load_from_yaml("/home/test", Some(&config_path))
+    };
+
+    assert_eq!(load("jobs: 2\n").expect("valid").save_repo, None);
+    assert_eq!(
+        load("save_repo: {}\n").expect("valid").save_repo,
+        Some(SaveRepo::new(Path::new("/home/test")))
+    );
+    let repo = load("save_repo:\n  path: ~/dotfiles/saves\n  push: true\n  remote: backup\n")
+        .expect("valid")
+        .save_repo
+        .expect("configured");
+    assert_eq!(repo.path, Path::new("/home/test/dotfiles/saves"));
+    assert!(repo.push);
+    assert_eq!(repo.remote, "backup");
+
+    assert!(load("save_repo:\n  path: /srv/../etc\n").is_err());
+    assert!(load("save_repo:\n  remote: --upload-pack=x\n").is_err());
+}
+
+#[test]
+fn test_commit_message() {
+    assert_eq!(
+        tracked_path("brew", Path::new("/home/me/.config/brew/Linux-Brewfile")),
+        Path::new("brew/Linux-Brewfile")
+    );
+
+    let changed = parse_name_status(
+        "M\tbrew/Linux-Brewfile\nA\tcargo/cargo.json\nR100\tnpm/a.json\tnpm/b.json\n",
+    );
+    assert_eq!(
+        changed[2],
+        ChangedFile {
+            change: "renamed",
+            path: "npm/b.json".to_string()
+        }
+    );
+    assert_eq!(
+        commit_message("laptop-42", &changed),
+        "Update brew, cargo, npm saves on laptop-42\n\n\
+         - brew/Linux-Brewfile: modified\n\
+         - cargo/cargo.json: added\n\
+         - npm/b.json: renamed\n"
+    );
+    assert!(parse_name_status("").is_empty());
+}
+
+#[tokio::test]
+async fn test_record_saves() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = test_config(&temp_dir);
+    let save_file = temp_dir.path().join("Brewfile");
+    config.brew_file = save_file.clone();
+    let mut logger = Logger::new(&config);
+    let repo = SaveRepo {
+        path: temp_dir.path().join("repo"),
+        ..SaveRepo::new(temp_dir.path())
+    };
+    let saved: Vec<(String, PathBuf)> = vec![("brew".to_string(), save_file.clone())];
+
+    std::fs::write(&save_file, "brew \"git\"\n").expect("Failed to write save file");
+    let first = record_saves(&config, &mut logger, &repo, "host", &saved)
+        .await
+        .expect("first commit");
+    assert!(first.expect("committed").contains("- brew/Brewfile: added"));
+
+    let unchanged = record_saves(&config, &mut logger, &repo, "host", &saved)
+        .await
+        .expect("nothing to commit");
+    assert_eq!(unchanged, None);
+
+    std::fs::write(&save_file, "brew \"git\"\nbrew \"jq\"\n").expect("Failed to write save file");
+    // Files next to a save file in the repository are not save files
+    std::fs::write(repo.path.join("brew").join("NOTES.md"), "notes\n")
+        .expect("Failed to write notes");
+    let second = record_saves(&config, &mut logger, &repo, "host", &saved)
+        .await
+        .expect("second commit");
+    assert!(
+        second
+            .expect("committed")
+            .contains("- brew/Brewfile: modified")
+    );
+
+    let previous = saves_at(&repo.path, "HEAD~1", &config).expect("files at HEAD~1");
+    assert_eq!(previous, [("brew".to_string(), b"brew \"git\"\n".to_vec())]);
+    let current = saves_at(&repo.path, "HEAD", &config).expect("files at HEAD");
+    assert_eq!(
+        current,
+        [("brew".to_string(), b"brew \"git\"\nbrew \"jq\"\n".to_vec())]
+    );
+    assert!(saves_at(&repo.path, "--all", &config).is_err());
+}
+
+#[tokio::test]
+async fn test_checkout_saves() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = test_config(&temp_dir);
+    config.brew_file = temp_dir.path().join("Brewfile");
+    config.save_repo = Some(SaveRepo {
+        path: temp_dir.path().join("repo"),
+        ..SaveRepo::new(temp_dir.path())
+    });
+    let mut logger = Logger::new(&config);
+    assert!(open_save_repo(&config).is_err());
+
+    let repo = config.save_repo.clone().expect("configured");
+    let saved = vec![("brew".to_string(), config.brew_file.clone())];
+    std::fs::write(&config.brew_file, "brew \"git\"\n").expect("Failed to write save file");
+    record_saves(&config, &mut logger, &repo, "host", &saved)
+        .await
+        .expect("first commit");
+    std::fs::write(&config.brew_file, "brew \"jq\"\n").expect("Failed to write save file");
+    record_saves(&config, &mut logger, &repo, "host", &saved)
+        .await
+        .expect("second commit");
+
+    let repo = open_save_repo(&config).expect("repository exists");
+    assert!(checkout_saves(&config, &mut logger, &repo, "HEAD~1", &["os".to_string()]).is_err());
+    let checked_out = checkout_saves(&config, &mut logger, &repo, "HEAD~1", &[]).expect("checkout");
+    assert_eq!(checked_out, ["brew"]);
+    assert_eq!(
+        std::fs::read_to_string(&config.brew_file).expect("Failed to read save file"),
+        "brew \"git\"\n"
+    );
+}
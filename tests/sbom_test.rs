@@ -0,0 +1,161 @@
+use chrono::{TimeZone, Utc};
+use updatehauler::plugins::{InstallScope, InstalledPackage};
+use updatehauler::sbom::{SbomFormat, SbomInfo, purl, render_sbom};
+
+fn pkg(manager: &str, name: &str, version: &str, source: &str) -> InstalledPackage {
+    InstalledPackage::new(manager, name, version, source)
+}
+
+fn info() -> SbomInfo {
+    SbomInfo {
+        hostname: "laptop-42".to_string(),
+        distro: Some("ubuntu".to_string()),
+        created: Utc
+            .with_ymd_and_hms(2025, 1, 31, 8, 0, 0)
+            .single()
+            .expect("valid time"),
+        serial: "3f2b8c1e-5d4a-4b6f-9e7d-0a1b2c3d4e5f".to_string(),
+    }
+}
+
+#[test]
+fn test_sbom_format_from_str() {
+    assert_eq!("cyclonedx-json".parse(), Ok(SbomFormat::CycloneDxJson));
+    assert_eq!("spdx-json".parse(), Ok(SbomFormat::SpdxJson));
+    assert!(
+        "spdx"
+            .parse::<SbomFormat>()
+            .unwrap_err()
+            .contains("expected cyclonedx-json or spdx-json")
+    );
+}
+
+#[test]
+fn test_purl() {
+    let cases = [
+        (
+            pkg("cargo", "ripgrep", "14.1.0", "crates.io"),
+            "pkg:cargo/ripgrep@14.1.0",
+        ),
+        (
+            pkg("npm", "typescript", "5.4.0", "npm"),
+            "pkg:npm/typescript@5.4.0",
+        ),
+        (
+            pkg("npm", "@angular/cli", "17.0.0", "npm"),
+            "pkg:npm/%40angular/cli@17.0.0",
+        ),
+        (
+            pkg("pip", "Typing_Extensions", "4.9.0", "pip"),
+            "pkg:pypi/typing-extensions@4.9.0",
+        ),
+        (pkg("uv", "ruff", "0.5.0", "uv"), "pkg:pypi/ruff@0.5.0"),
+        (pkg("gem", "rake", "13.1.0", "gem"), "pkg:gem/rake@13.1.0"),
+        (
+            pkg("brew", "git", "2.44.0", "formula"),
+            "pkg:brew/git@2.44.0",
+        ),
+        (
+            pkg("apt", "libc6", "2.35-0ubuntu3.6", "apt"),
+            "pkg:deb/ubuntu/libc6@2.35-0ubuntu3.6",
+        ),
+        (
+            pkg("flatpak", "org.gimp.GIMP", "2.10.38", "flathub"),
+            "pkg:generic/flatpak/org.gimp.GIMP@2.10.38",
+        ),
+    ];
+    for (package, expected) in cases {
+        assert_eq!(purl(&package, Some("ubuntu")), expected);
+    }
+    // Epochs and `+` in Debian versions are percent-encoded
+    assert_eq!(
+        purl(&pkg("apt", "vim", "2:9.0+dfsg-1", "apt"), None),
+        "pkg:deb/debian/vim@2%3A9.0%2Bdfsg-1"
+    );
+    assert_eq!(
+        purl(&pkg("dnf", "curl", "7.76.1-26.el9", "dnf"), Some("fedora")),
+        "pkg:rpm/fedora/curl@7.76.1-26.el9"
+    );
+    assert_eq!(
+        purl(&pkg("dnf", "curl", "7.76.1", "dnf"), None),
+        "pkg:rpm/curl@7.76.1"
+    );
+}
+
+#[test]
+fn test_render_cyclonedx() {
+    let cask =
+        pkg("brew", "firefox", "125.0", "cask").with_path("/opt/homebrew/Caskroom/firefox/125.0");
+    let tool = pkg("uv", "ruff", "0.5.0", "uv").with_scope(InstallScope::User);
+    let lib = pkg("pip", "ruff", "0.5.0", "pip");
+    let json: serde_json::Value = serde_json::from_str(
+        &render_sbom(&[&cask, &tool, &lib], SbomFormat::CycloneDxJson, &info())
+            .expect("CycloneDX renders"),
+    )
+    .expect("valid JSON");
+
+    assert_eq!(json["bomFormat"], "CycloneDX");
+    assert_eq!(json["specVersion"], "1.5");
+    assert_eq!(
+        json["serialNumber"],
+        "urn:uuid:3f2b8c1e-5d4a-4b6f-9e7d-0a1b2c3d4e5f"
+    );
+    assert_eq!(json["metadata"]["timestamp"], "2025-01-31T08:00:00Z");
+    assert_eq!(json["metadata"]["component"]["name"], "laptop-42");
+    assert_eq!(
+        json["metadata"]["tools"]["components"][0]["name"],
+        "updatehauler"
+    );
+
+    let components = json["components"].as_array().expect("components");
+    assert_eq!(components.len(), 3);
+    assert_eq!(components[0]["type"], "application");
+    assert_eq!(components[0]["purl"], "pkg:brew/firefox@125.0");
+    assert_eq!(components[0]["properties"][3]["name"], "updatehauler:path");
+    assert_eq!(components[1]["type"], "library");
+    assert_eq!(components[1]["properties"][2]["value"], "user");
+    // Both installs of ruff are kept, with distinct bom-refs
+    assert_eq!(components[1]["bom-ref"], "pkg:pypi/ruff@0.5.0");
+    assert_eq!(components[2]["bom-ref"], "pkg:pypi/ruff@0.5.0#3");
+}
+
+#[test]
+fn test_render_spdx() {
+    let packages = [
+        pkg("apt", "curl", "7.81.0-1ubuntu1.16", "apt"),
+        pkg("code", "ms-python.python", "2024.1.0", "code").with_scope(InstallScope::User),
+    ];
+    let refs: Vec<&InstalledPackage> = packages.iter().collect();
+    let json: serde_json::Value = serde_json::from_str(
+        &render_sbom(&refs, SbomFormat::SpdxJson, &info()).expect("SPDX renders"),
+    )
+    .expect("valid JSON");
+
+    assert_eq!(json["spdxVersion"], "SPDX-2.3");
+    assert_eq!(json["SPDXID"], "SPDXRef-DOCUMENT");
+    assert_eq!(json["name"], "updatehauler-laptop-42");
+    assert_eq!(
+        json["documentNamespace"],
+        "https://spdx.org/spdxdocs/updatehauler-laptop-42-3f2b8c1e-5d4a-4b6f-9e7d-0a1b2c3d4e5f"
+    );
+    assert_eq!(json["creationInfo"]["created"], "2025-01-31T08:00:00Z");
+
+    let curl = &json["packages"][0];
+    assert_eq!(curl["SPDXID"], "SPDXRef-Package-apt-1");
+    assert_eq!(curl["versionInfo"], "7.81.0-1ubuntu1.16");
+    assert_eq!(curl["downloadLocation"], "NOASSERTION");
+    assert_eq!(curl["externalRefs"][0]["referenceType"], "purl");
+    assert_eq!(
+        curl["externalRefs"][0]["referenceLocator"],
+        "pkg:deb/ubuntu/curl@7.81.0-1ubuntu1.16"
+    );
+    assert_eq!(
+        json["packages"][1]["externalRefs"][0]["referenceLocator"],
+        "pkg:generic/code/ms-python.python@2024.1.0"
+    );
+    assert_eq!(json["relationships"][1]["relationshipType"], "DESCRIBES");
+    assert_eq!(
+        json["relationships"][1]["relatedSpdxElement"],
+        "SPDXRef-Package-code-2"
+    );
+}
@@ -0,0 +1,165 @@
+use chrono::{Local, TimeZone};
+use std::path::Path;
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::insights::Insights;
+use updatehauler::snapshot::{
+    MANIFEST_NAME, Manifest, WorkDir, default_archive_name, extract_archive, sha256_file,
+    staged_path, write_archive,
+};
+
+fn created() -> chrono::DateTime<Local> {
+    Local
+        .with_ymd_and_hms(2025, 1, 31, 8, 30, 0)
+        .earliest()
+        .expect("valid time")
+}
+
+/// A manifest over two save files staged below `root`
+fn staged_manifest(root: &Path) -> Manifest {
+    let insights = Insights::new().expect("Failed to create Insights");
+    let mut manifest = Manifest::new(&insights, created());
+    for (plugin, file, contents) in [
+        ("brew", "Linux-Brewfile", "brew \"git\"\n"),
+        ("acme", "acme.txt", "tool-a 1.0\n"),
+    ] {
+        let path = root.join(staged_path(plugin, Path::new(file)));
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create dir");
+        std::fs::write(&path, contents).expect("Failed to write save file");
+        manifest
+            .add_file(root, plugin, &path)
+            .expect("Failed to add file");
+    }
+    manifest
+}
+
+#[test]
+fn test_staged_path_and_archive_name() {
+    assert_eq!(
+        staged_path("brew", Path::new("/home/me/.config/brew/Linux-Brewfile")),
+        Path::new("files/brew/Linux-Brewfile")
+    );
+    assert_eq!(
+        staged_path("go", Path::new("")),
+        Path::new("files/go/go-save")
+    );
+    assert_eq!(
+        default_archive_name("laptop-42", created()),
+        Path::new("updatehauler-snapshot-laptop-42-20250131-083000.tar.gz")
+    );
+    assert_eq!(
+        default_archive_name("", created()),
+        Path::new("updatehauler-snapshot-localhost-20250131-083000.tar.gz")
+    );
+}
+
+#[test]
+fn test_snapshot_round_trip() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let stage = temp_dir.path().join("stage");
+    let manifest = staged_manifest(&stage);
+    assert_eq!(manifest.plugins[0].file, "files/brew/Linux-Brewfile");
+    assert_eq!(manifest.plugins[1].size, 11);
+    assert_eq!(
+        manifest.plugins[1].sha256,
+        "f7be15f8c4239bb9bc8faddf0852484bea7dcb0b4f83001013258efc131d91fc"
+    );
+
+    let archive = temp_dir.path().join("out/snap.tar.gz");
+    write_archive(&stage, &manifest, &archive).expect("Failed to write archive");
+
+    let dest = temp_dir.path().join("dest");
+    let restored = extract_archive(&archive, &dest).expect("Failed to extract archive");
+    assert!(dest.join(MANIFEST_NAME).is_file());
+    assert_eq!(restored.plugins, manifest.plugins);
+    assert_eq!(restored.created_at, created().to_rfc3339());
+    assert_eq!(
+        std::fs::read_to_string(dest.join("files/acme/acme.txt")).expect("restored file"),
+        "tool-a 1.0\n"
+    );
+}
+
+#[test]
+fn test_snapshot_rejects_bad_archives() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let stage = temp_dir.path().join("stage");
+    let mut manifest = staged_manifest(&stage);
+
+    // A file changed after its checksum was taken
+    std::fs::write(stage.join("files/acme/acme.txt"), "tool-a 2.0\n").expect("Failed to write");
+    let archive = temp_dir.path().join("tampered.tar.gz");
+    write_archive(&stage, &manifest, &archive).expect("Failed to write archive");
+    let err = extract_archive(&archive, &temp_dir.path().join("a")).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Checksum mismatch for files/acme/acme.txt")
+    );
+
+    manifest.plugins[1].sha256 = sha256_file(&stage.join("files/acme/acme.txt")).expect("hash");
+    manifest.format = 99;
+    write_archive(&stage, &manifest, &archive).expect("Failed to write archive");
+    let err = extract_archive(&archive, &temp_dir.path().join("b")).unwrap_err();
+    assert!(err.to_string().contains("Snapshot format 99 is newer"));
+
+    assert!(extract_archive(&temp_dir.path().join("missing.tar.gz"), temp_dir.path()).is_err());
+}
+
+#[test]
+fn test_config_save_file() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config_path = temp_dir.path().join("config.yaml");
+    std::fs::write(
+        &config_path,
+        r#"
+custom_plugins:
+  - name: acme
+    update: [echo acme]
+    save: [echo tool-a]
+    save_file: ~/.config/acme/tools.txt
+  - name: beta
+    update: [echo beta]
+"#,
+    )
+    .expect("Failed to write config file");
+    let mut config =
+        Config::load_from_yaml("/home/test", Some(&config_path)).expect("Failed to load");
+
+    assert!(config.save_file("brew").is_some());
+    assert_eq!(config.save_file("beta"), None);
+    assert_eq!(config.save_file("os"), None);
+    assert_eq!(
+        config.save_file("acme").as_deref(),
+        Some(Path::new("~/.config/acme/tools.txt"))
+    );
+
+    assert!(config.set_save_file("acme", "/tmp/stage/acme.txt".into()));
+    assert!(config.set_save_file("cargo", "/tmp/stage/cargo.json".into()));
+    assert!(!config.set_save_file("beta", "/tmp/stage/beta.txt".into()));
+    assert!(!config.set_save_file("os", "/tmp/stage/os.txt".into()));
+    assert_eq!(
+        config.save_file("acme").as_deref(),
+        Some(Path::new("/tmp/stage/acme.txt"))
+    );
+    assert_eq!(config.cargo_file, Path::new("/tmp/stage/cargo.json"));
+}
+
+#[test]
+fn test_work_dirs_are_unique_private_and_removed_on_drop() {
+    let first = WorkDir::new("snapshot").expect("Failed to create work dir");
+    let second = WorkDir::new("snapshot").expect("Failed to create work dir");
+    assert_ne!(first.path(), second.path());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(first.path())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+
+    let path = first.path().to_path_buf();
+    drop(first);
+    assert!(!path.exists());
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::translate::{
+    BrewfileEntry, EntryKind, PackageMap, RestoreTarget, install_command, parse_brewfile,
+    translate_brewfile,
+};
+
+const BREWFILE: &str = r#"
+# Written on a Mac
+tap "homebrew/bundle"
+brew "ripgrep"
+brew "fd", args: ["HEAD"]
+brew "acme/tools/acmectl"
+cask "firefox"
+cask "karabiner-elements"
+mas "Slack for Desktop", id: 803453959
+vscode "rust-lang.rust-analyzer"
+"#;
+
+fn linux(has_brew: bool, has_flatpak: bool) -> RestoreTarget {
+    RestoreTarget {
+        is_darwin: false,
+        has_brew,
+        has_flatpak,
+        system: Some("apt".to_string()),
+    }
+}
+
+fn installs(pairs: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+    pairs
+        .iter()
+        .map(|(manager, packages)| {
+            (
+                manager.to_string(),
+                packages.iter().map(|p| p.to_string()).collect(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_brewfile() {
+    let entries = parse_brewfile(BREWFILE);
+    assert_eq!(entries.len(), 8);
+    assert_eq!(
+        entries[2],
+        BrewfileEntry {
+            kind: EntryKind::Brew,
+            name: "fd".to_string(),
+            line: r#"brew "fd", args: ["HEAD"]"#.to_string(),
+        }
+    );
+    assert_eq!(entries[6].kind, EntryKind::Mas);
+    assert_eq!(entries[6].name, "Slack for Desktop");
+    assert_eq!(entries[7].kind, EntryKind::Other);
+    assert_eq!(parse_brewfile("cask\n")[0].kind, EntryKind::Other);
+}
+
+#[test]
+fn test_builtin_package_map() {
+    let map = PackageMap::builtin();
+    assert_eq!(map.brew["fd"]["apt"], "fd-find");
+    assert_eq!(map.cask["firefox"]["flatpak"], "org.mozilla.firefox");
+    assert!(map.mas.contains_key("Slack for Desktop"));
+}
+
+#[test]
+fn test_translate_for_linux() {
+    let entries = parse_brewfile(BREWFILE);
+    let map = PackageMap::builtin();
+
+    // Without brew, formulas become apt packages and apps flatpaks
+    let translation = translate_brewfile(&entries, &linux(false, true), &map);
+    assert!(translation.brewfile.is_empty());
+    assert_eq!(
+        translation.installs,
+        installs(&[
+            ("apt", &["ripgrep", "fd-find"]),
+            ("flatpak", &["org.mozilla.firefox", "com.slack.Slack"]),
+        ])
+    );
+    assert_eq!(
+        translation.unmapped,
+        [
+            r#"brew "acme/tools/acmectl""#,
+            r#"cask "karabiner-elements""#,
+            r#"vscode "rust-lang.rust-analyzer""#,
+        ]
+    );
+
+    // With brew, only the apps need an alternative; without flatpak the
+    // unused alternatives are named
+    let translation = translate_brewfile(&entries, &linux(true, false), &map);
+    assert_eq!(
+        translation.brewfile_content(),
+        "tap \"homebrew/bundle\"\nbrew \"ripgrep\"\nbrew \"fd\", args: [\"HEAD\"]\n\
+         brew \"acme/tools/acmectl\"\nvscode \"rust-lang.rust-analyzer\"\n"
+    );
+    assert!(translation.installs.is_empty());
+    assert_eq!(
+        translation.unmapped[0],
+        r#"cask "firefox" (alternatives: flatpak org.mozilla.firefox)"#
+    );
+}
+
+#[test]
+fn test_translate_on_macos_keeps_everything() {
+    let entries = parse_brewfile(BREWFILE);
+    let target = RestoreTarget {
+        is_darwin: true,
+        has_brew: true,
+        has_flatpak: false,
+        system: None,
+    };
+    let translation = translate_brewfile(&entries, &target, &PackageMap::builtin());
+    assert_eq!(translation.brewfile.len(), entries.len());
+    assert!(translation.installs.is_empty() && translation.unmapped.is_empty());
+}
+
+#[test]
+fn test_package_map_overrides() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config_path = temp_dir.path().join("config.yaml");
+    std::fs::write(
+        &config_path,
+        r#"
+package_map:
+  brew:
+    acme/tools/acmectl: {apt: acmectl}
+    fd: {apt: fd}
+  cask:
+    karabiner-elements: {apt: keyd}
+"#,
+    )
+    .expect("Failed to write config file");
+    let config = Config::load_from_yaml("/home/test", Some(&config_path)).expect("Failed to load");
+    let map = PackageMap::builtin().with_overrides(&config.package_map);
+    assert_eq!(map.brew["fd"]["apt"], "fd");
+    assert_eq!(map.brew["fd"]["dnf"], "fd-find");
+
+    let translation = translate_brewfile(&parse_brewfile(BREWFILE), &linux(false, false), &map);
+    assert_eq!(
+        translation.installs["apt"],
+        ["ripgrep", "fd", "acmectl", "keyd"]
+    );
+
+    std::fs::write(&config_path, "package_map:\n  formula: {}\n").expect("Failed to write");
+    assert!(Config::load_from_yaml("/home/test", Some(&config_path)).is_err());
+}
+
+#[test]
+fn test_install_command() {
+    let packages = vec!["jq".to_string(), "fd-find".to_string()];
+    assert_eq!(
+        install_command("apt", &packages).expect("apt"),
+        ["apt-get", "install", "-y", "jq", "fd-find"]
+    );
+    assert_eq!(
+        install_command("flatpak", &packages[..1]).expect("flatpak"),
+        [
+            "flatpak",
+            "install",
+            "-y",
+            "--noninteractive",
+            "flathub",
+            "jq"
+        ]
+    );
+    assert_eq!(install_command("nix", &packages), None);
+}
@@ -0,0 +1,228 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use updatehauler::config::Config;
+use updatehauler::insights::Insights;
+use updatehauler::inventory::Inventory;
+use updatehauler::logger::Logger;
+use updatehauler::plugins::{
+    InstalledPackage, Plugin, PluginMetadata, PluginRegistry, register_custom_plugins,
+};
+use updatehauler::runner::{ActionResult, ActionStatus, ExecutionGraph};
+use updatehauler::verify::{rollback_unverified, unverified_plugins};
+
+fn load(dir: &Path, yaml: &str) -> anyhow::Result<Config> {
+    let config_path = dir.join("config.yaml");
+    std::fs::write(&config_path, yaml).expect("Failed to write config file");
+    let mut config = Config::load_from_yaml("/home/test", Some(&config_path))?;
+    config.log = dir.join("test.log");
+    config.use_log = true;
+    Ok(config)
+}
+
+const PLUGINS: &str = r#"
+custom_plugins:
+  - name: acme
+    update: [echo acme]
+    actions:
+      doctor: [echo doctor]
+"#;
+
+async fn execute(config: Config, actions: &[&str]) -> Vec<ActionResult> {
+    let mut registry = PluginRegistry::new();
+    register_custom_plugins(&mut registry, &config).expect("Failed to register");
+    let actions: Vec<String> = actions.iter().map(|s| s.to_string()).collect();
+    let graph = ExecutionGraph::build(&registry, &actions).expect("graph should build");
+    let logger = Logger::new(&config);
+    graph
+        .execute(
+            Arc::new(registry),
+            Arc::new(config),
+            Arc::new(Insights::new().expect("Failed to create Insights")),
+            &logger,
+            1,
+        )
+        .await
+}
+
+#[test]
+fn test_verify_loads_from_yaml() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = load(
+        temp_dir.path(),
+        "verify:\n  nvim: [nvim --headless +qa]\n  npm: [node -e 1, npm --version]\n  rollback: true\n",
+    )
+    .expect("Failed to load");
+    assert_eq!(config.verify.commands("nvim"), ["nvim --headless +qa"]);
+    assert_eq!(
+        config.verify.commands("npm"),
+        ["node -e 1", "npm --version"]
+    );
+    assert!(config.verify.commands("brew").is_empty());
+    assert!(config.verify.rollback);
+    assert!(!Config::new("/tmp").verify.rollback);
+
+    let err = load(temp_dir.path(), "verify:\n  Bad-Name: [echo hi]\n")
+        .err()
+        .expect("invalid plugin name should be rejected");
+    assert!(err.to_string().contains("does not name a valid plugin"));
+}
+
+#[tokio::test]
+async fn test_failing_verify_marks_update() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let yaml = format!("{}verify:\n  acme: [\"true\", \"exit 3\"]\n", PLUGINS);
+    let config = load(temp_dir.path(), &yaml).expect("Failed to load");
+
+    let results = execute(config, &["acme", "acme-doctor"]).await;
+
+    assert_eq!(results[0].action, "acme");
+    assert_eq!(results[0].status, ActionStatus::VerifyFailed);
+    assert_eq!(
+        results[0].error.as_deref(),
+        Some("verify command 'exit 3' failed: sh -c exit 3 exited with return code 3")
+    );
+    // The commands of the verification are recorded with the update
+    let commands: Vec<&str> = results[0]
+        .commands
+        .iter()
+        .map(|c| c.command.as_str())
+        .collect();
+    assert_eq!(commands, ["echo acme", "sh -c true", "sh -c exit 3"]);
+    // Only updates are verified
+    assert_eq!(results[1].status, ActionStatus::Ok);
+    assert_eq!(ActionStatus::VerifyFailed.as_str(), "VERIFY_FAIL");
+}
+
+#[tokio::test]
+async fn test_passing_verify_keeps_update_ok() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let yaml = format!("{}verify:\n  acme: [\"true\"]\n", PLUGINS);
+    let config = load(temp_dir.path(), &yaml).expect("Failed to load");
+
+    let results = execute(config, &["acme"]).await;
+    assert_eq!(results[0].status, ActionStatus::Ok);
+}
+
+/// Lists `typescript` 5.4.5 on its first listing and 5.5.2 afterwards
+struct UpgradedPlugin {
+    listings: AtomicUsize,
+    installed: Arc<Mutex<Vec<InstalledPackage>>>,
+}
+
+#[async_trait]
+impl Plugin for UpgradedPlugin {
+    fn name(&self) -> &str {
+        "alpha"
+    }
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: "alpha".to_string(),
+            description: String::new(),
+            actions: Vec::new(),
+            run_after: Vec::new(),
+        }
+    }
+
+    async fn check_available(&self, _config: &Config, _insights: &Insights) -> bool {
+        true
+    }
+
+    async fn update(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn list_installed(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+    ) -> Result<Option<Vec<InstalledPackage>>> {
+        let version = match self.listings.fetch_add(1, Ordering::SeqCst) {
+            0 => "5.4.5",
+            _ => "5.5.2",
+        };
+        Ok(Some(vec![
+            InstalledPackage::new("alpha", "typescript", version, "alpha"),
+            InstalledPackage::new("alpha", "prettier", "3.2.0", "alpha"),
+        ]))
+    }
+
+    async fn install_versions(
+        &self,
+        _config: &Config,
+        _insights: &Insights,
+        _logger: &mut Logger,
+        packages: &[InstalledPackage],
+    ) -> Result<bool> {
+        self.installed.lock().unwrap().extend_from_slice(packages);
+        Ok(true)
+    }
+}
+
+#[tokio::test]
+async fn test_rollback_unverified_reinstalls_previous_versions() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut config = Config::new("/tmp/test");
+    config.log = temp_dir.path().join("test.log");
+    config.use_log = true;
+    let logger = Logger::new(&config);
+    let insights = Insights::new().expect("Failed to create Insights");
+
+    let installed = Arc::new(Mutex::new(Vec::new()));
+    let plugin = UpgradedPlugin {
+        listings: AtomicUsize::new(0),
+        installed: Arc::clone(&installed),
+    };
+    let before: Inventory = [(
+        "alpha".to_string(),
+        plugin
+            .list_installed(&config, &insights, &mut logger.clone())
+            .await
+            .expect("listing")
+            .expect("packages"),
+    )]
+    .into();
+    let mut registry = PluginRegistry::new();
+    registry.register(Box::new(plugin));
+    let registry = Arc::new(registry);
+
+    let results = [
+        ActionResult::new("alpha", ActionStatus::VerifyFailed),
+        ActionResult::new("beta", ActionStatus::Failed),
+    ];
+    let unverified = unverified_plugins(&registry, &results);
+    assert_eq!(unverified, ["alpha"]);
+
+    let rollback = rollback_unverified(
+        registry,
+        Arc::new(config),
+        Arc::new(insights),
+        &logger,
+        &before,
+        &unverified,
+    )
+    .await;
+
+    assert_eq!(rollback.len(), 1);
+    assert_eq!(rollback[0].action, "alpha-rollback");
+    assert_eq!(rollback[0].status, ActionStatus::Ok);
+    assert_eq!(
+        *installed.lock().unwrap(),
+        [InstalledPackage::new(
+            "alpha",
+            "typescript",
+            "5.4.5",
+            "alpha"
+        )]
+    );
+}